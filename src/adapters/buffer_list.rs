@@ -0,0 +1,107 @@
+use crate::ports::buffer_list::{Buffer, BufferListPort};
+use crate::ports::cursor::CursorControllerPort;
+use crate::ports::editor_rows::EditorRowsPort;
+use crate::ports::undo::UndoHistoryPort;
+use std::{fs, path};
+
+pub struct BufferList {
+    buffers: Vec<Buffer>,
+    current: usize,
+    next_id: usize,
+}
+
+impl BufferList {
+    fn same_file(a: &path::Path, b: &path::Path) -> bool {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        }
+    }
+}
+
+impl BufferListPort for BufferList {
+    fn new() -> Self {
+        Self {
+            buffers: Vec::new(),
+            current: 0,
+            next_id: 1,
+        }
+    }
+
+    fn add(
+        &mut self,
+        editor_rows: Box<dyn EditorRowsPort>,
+        cursor_controller: Box<dyn CursorControllerPort>,
+        undo_history: Box<dyn UndoHistoryPort>,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.buffers.push(Buffer {
+            id,
+            editor_rows,
+            cursor_controller,
+            undo_history,
+        });
+        self.current = self.buffers.len() - 1;
+        id
+    }
+
+    fn remove(&mut self, id: usize) -> Option<Buffer> {
+        let index = self.buffers.iter().position(|buffer| buffer.id == id)?;
+        let buffer = self.buffers.remove(index);
+        if index < self.current || self.current >= self.buffers.len() {
+            self.current = self.current.saturating_sub(1);
+        }
+        Some(buffer)
+    }
+
+    fn find_by_path(&self, path: &path::Path) -> Option<usize> {
+        self.buffers
+            .iter()
+            .find(|buffer| {
+                buffer
+                    .editor_rows
+                    .get_file_name()
+                    .is_some_and(|file_name| Self::same_file(file_name, path))
+            })
+            .map(|buffer| buffer.id)
+    }
+
+    fn switch_to(&mut self, id: usize) -> bool {
+        match self.buffers.iter().position(|buffer| buffer.id == id) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn next(&mut self) {
+        if !self.buffers.is_empty() {
+            self.current = (self.current + 1) % self.buffers.len();
+        }
+    }
+
+    fn previous(&mut self) {
+        if !self.buffers.is_empty() {
+            self.current = (self.current + self.buffers.len() - 1) % self.buffers.len();
+        }
+    }
+
+    fn get(&self, id: usize) -> Option<&Buffer> {
+        self.buffers.iter().find(|buffer| buffer.id == id)
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Buffer> {
+        self.buffers.iter_mut().find(|buffer| buffer.id == id)
+    }
+
+    fn current(&self) -> &Buffer {
+        &self.buffers[self.current]
+    }
+
+    fn buffers(&self) -> &[Buffer] {
+        &self.buffers
+    }
+}
@@ -0,0 +1,143 @@
+use crate::ports::completion::{CompletionItem, CompletionRequest, CompletionSourcePort};
+use std::collections::HashSet;
+use std::{env, fs, path};
+
+/// Words shorter than this are not worth completing.
+const MIN_WORD_LENGTH: usize = 3;
+
+/// Returns whether `ch` is part of a word, like an identifier.
+pub fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Returns the byte offset where the word ending `line` starts.
+pub fn word_start(line: &str) -> usize {
+    line.char_indices()
+        .rev()
+        .take_while(|(_, ch)| is_word_char(*ch))
+        .last()
+        .map_or(line.len(), |(index, _)| index)
+}
+
+/// Returns the byte offset where the path ending `line` starts.
+pub fn path_start(line: &str) -> usize {
+    let is_path_char = |ch: char| !ch.is_whitespace() && !"\"'`()[]{}<>,;=".contains(ch);
+    line.char_indices()
+        .rev()
+        .take_while(|(_, ch)| is_path_char(*ch))
+        .last()
+        .map_or(line.len(), |(index, _)| index)
+}
+
+/// Returns every completion source barn has, for `[completion] sources`
+/// to pick from by name.
+pub fn built_in() -> Vec<Box<dyn CompletionSourcePort>> {
+    vec![
+        Box::new(BufferWords::new()),
+        Box::new(PathCompletion::new()),
+    ]
+}
+
+/// Completes the words of the open buffers, those of the current buffer
+/// first.
+pub struct BufferWords;
+
+impl CompletionSourcePort for BufferWords {
+    fn new() -> Self {
+        Self
+    }
+
+    fn name(&self) -> &str {
+        "buffer"
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> Vec<CompletionItem> {
+        let start = word_start(request.line);
+        let typed = &request.line[start..];
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for rows in &request.buffers {
+            for row in rows.get_rows() {
+                for word in row.split(|ch: char| !is_word_char(ch)) {
+                    let long_enough = word.chars().count() >= MIN_WORD_LENGTH;
+                    // Numbers and the word being typed itself are left out
+                    if !long_enough
+                        || word == typed
+                        || word.starts_with(|ch: char| ch.is_ascii_digit())
+                        || !seen.insert(word.to_string())
+                    {
+                        continue;
+                    }
+                    items.push(CompletionItem {
+                        label: word.to_string(),
+                        insert: word.to_string(),
+                        start,
+                        detail: None,
+                        documentation: None,
+                        source: self.name().to_string(),
+                    });
+                }
+            }
+        }
+        items
+    }
+}
+
+/// Completes the path being typed with the entries of the directory it
+/// is in, once it has a `/` in it.
+pub struct PathCompletion;
+
+impl CompletionSourcePort for PathCompletion {
+    fn new() -> Self {
+        Self
+    }
+
+    fn name(&self) -> &str {
+        "path"
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> Vec<CompletionItem> {
+        let start = path_start(request.line);
+        let typed = &request.line[start..];
+        // Comments such as `//` and URLs are not paths to complete
+        let Some(slash) = typed.rfind('/').filter(|_| !typed.contains("//")) else {
+            return Vec::new();
+        };
+        let (directory, name) = typed.split_at(slash + 1);
+        let listed = match directory.strip_prefix("~/") {
+            Some(rest) => match env::var_os("HOME") {
+                Some(home) => path::Path::new(&home).join(rest),
+                None => return Vec::new(),
+            },
+            None => request.directory.join(directory),
+        };
+        let Ok(entries) = fs::read_dir(listed) else {
+            return Vec::new();
+        };
+
+        let mut items: Vec<CompletionItem> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let mut file_name = entry.file_name().into_string().ok()?;
+                // Hidden entries only once their dot is typed
+                if file_name.starts_with('.') && !name.starts_with('.') {
+                    return None;
+                }
+                if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                    file_name.push('/');
+                }
+                let path = format!("{}{}", directory, file_name);
+                Some(CompletionItem {
+                    label: path.clone(),
+                    insert: path,
+                    start,
+                    detail: None,
+                    documentation: None,
+                    source: self.name().to_string(),
+                })
+            })
+            .collect();
+        items.sort_by(|a, b| a.label.cmp(&b.label));
+        items
+    }
+}
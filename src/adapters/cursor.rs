@@ -88,6 +88,10 @@ impl CursorControllerPort for CursorController {
         )
     }
 
+    fn get_file_position(&self) -> (usize, usize) {
+        (self.cursor_x, self.cursor_y)
+    }
+
     fn get_render_x(&self, row: &dyn RowPort) -> usize {
         row.get_content()[..self.cursor_x]
             .chars()
@@ -113,20 +117,45 @@ impl CursorControllerPort for CursorController {
         self.col_offset
     }
 
+    fn set_offsets(&mut self, row_offset: usize, col_offset: usize) {
+        self.row_offset = row_offset;
+        self.col_offset = col_offset;
+    }
+
+    fn set_screen_size(&mut self, window_size: (usize, usize)) {
+        self.screen_cols = window_size.0;
+        self.screen_rows = window_size.1;
+    }
+
     fn scroll(&mut self, editor_rows: &dyn EditorRowsPort) {
+        // The rows may have changed under the cursor (e.g. edited through
+        // another window), so keep it inside the text first
+        self.cursor_y = cmp::min(self.cursor_y, editor_rows.number_of_rows());
+        let row_len = if self.cursor_y < editor_rows.number_of_rows() {
+            editor_rows.get_row(self.cursor_y).len()
+        } else {
+            0
+        };
+        self.cursor_x = cmp::min(self.cursor_x, row_len);
+
         self.render_x = 0;
         if self.cursor_y < editor_rows.number_of_rows() {
             self.render_x = self.get_render_x(editor_rows.get_editor_row(self.cursor_y))
         }
 
+        // A viewport squeezed to nothing by a small terminal still scrolls as
+        // if it had one cell, so the offsets stay within the text
+        let screen_rows = cmp::max(self.screen_rows, 1);
+        let screen_cols = cmp::max(self.screen_cols, 1);
+
         self.row_offset = cmp::min(self.row_offset, self.cursor_y);
-        if self.cursor_y >= self.row_offset + self.screen_rows {
-            self.row_offset = self.cursor_y - self.screen_rows + 1;
+        if self.cursor_y >= self.row_offset + screen_rows {
+            self.row_offset = self.cursor_y - screen_rows + 1;
         }
 
         self.col_offset = cmp::min(self.col_offset, self.render_x);
-        if self.render_x >= self.col_offset + self.screen_cols {
-            self.col_offset = self.render_x - self.screen_cols + 1;
+        if self.render_x >= self.col_offset + screen_cols {
+            self.col_offset = self.render_x - screen_cols + 1;
         }
     }
 }
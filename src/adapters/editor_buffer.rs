@@ -40,6 +40,10 @@ impl EditorBufferPort for EditorBuffer {
     fn set_style(&mut self, style: style::Attribute) -> io::Result<()> {
         queue!(self, style::SetAttribute(style))
     }
+
+    fn set_color(&mut self, color: style::Color) -> io::Result<()> {
+        queue!(self, style::SetForegroundColor(color))
+    }
 }
 
 impl io::Write for EditorBuffer {
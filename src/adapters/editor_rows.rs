@@ -1,6 +1,6 @@
 use crate::ports::editor_rows::EditorRowsPort;
 use crate::ports::editor_rows::RowPort;
-use std::{env, fs, path, rc::Rc};
+use std::{fs, io, path, rc::Rc};
 
 pub const TAB_STOP: usize = 8;
 
@@ -25,9 +25,33 @@ impl RowPort for Row {
     fn get_render(&self) -> &String {
         &self.render
     }
+
+    fn render_x(&self, index: usize) -> usize {
+        self.row_content[..index].chars().fold(0, Self::advance)
+    }
+
+    fn index_at_render_x(&self, render_x: usize) -> usize {
+        let mut current = 0;
+        for (index, c) in self.row_content.char_indices() {
+            current = Self::advance(current, c);
+            if current > render_x {
+                return index;
+            }
+        }
+        self.row_content.len()
+    }
 }
 
 impl Row {
+    /// Returns the column following character `c` drawn at `render_x`.
+    fn advance(render_x: usize, c: char) -> usize {
+        if c == '\t' {
+            render_x + (TAB_STOP - 1) - (render_x % TAB_STOP) + 1
+        } else {
+            render_x + 1
+        }
+    }
+
     fn update_render(&mut self) {
         let mut index = 0;
         self.render.clear();
@@ -49,42 +73,55 @@ impl Row {
 pub struct EditorRows {
     row_content: Vec<Rc<dyn RowPort>>,
     file_name: Option<path::PathBuf>,
+    dirty: bool,
+    read_only: bool,
 }
 
 impl EditorRows {
-    pub fn from_file(file: path::PathBuf) -> Self {
-        let file_content = fs::read_to_string(&file).expect("Unable to read file");
-
-        let row_content = file_content
-            .lines()
-            .map(|line| {
-                let row = Row::new(line.into(), String::new());
-                Rc::new(row) as Rc<dyn RowPort>
-            })
-            .collect();
+    fn make_row(content: &str) -> Rc<dyn RowPort> {
+        Rc::new(Row::new(content.into(), String::new())) as Rc<dyn RowPort>
+    }
+}
 
+impl EditorRowsPort for EditorRows {
+    fn new() -> Self {
         Self {
+            row_content: Vec::new(),
+            file_name: None,
+            dirty: false,
+            read_only: false,
+        }
+    }
+
+    fn from_file(file: path::PathBuf) -> io::Result<Self> {
+        // A missing file opens as an empty buffer that will be created on save
+        let file_content = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let row_content = file_content.lines().map(Self::make_row).collect();
+
+        Ok(Self {
             row_content,
             file_name: Some(file),
-        }
+            dirty: false,
+            read_only: false,
+        })
     }
-}
 
-impl EditorRowsPort for EditorRows {
-    fn new() -> Self {
-        let mut arg = env::args();
-
-        match arg.nth(1) {
-            None => Self {
-                row_content: Vec::new(),
-                file_name: None,
-            },
-            Some(file) => Self::from_file(file.into()),
+    fn read_only(name: path::PathBuf, text: &str) -> Self {
+        Self {
+            row_content: text.lines().map(Self::make_row).collect(),
+            file_name: Some(name),
+            dirty: false,
+            read_only: true,
         }
     }
 
-    fn render_row(&self, row: &mut Row) {
-        row.update_render();
+    fn is_read_only(&self) -> bool {
+        self.read_only
     }
 
     fn number_of_rows(&self) -> usize {
@@ -92,11 +129,11 @@ impl EditorRowsPort for EditorRows {
     }
 
     fn get_row(&self, at: usize) -> &str {
-        &self.row_content[at].get_content()
+        self.row_content[at].get_content()
     }
 
     fn get_render(&self, at: usize) -> &String {
-        &self.row_content[at].get_render()
+        self.row_content[at].get_render()
     }
 
     fn get_editor_row(&self, at: usize) -> &dyn RowPort {
@@ -106,4 +143,84 @@ impl EditorRowsPort for EditorRows {
     fn get_file_name(&self) -> Option<&path::PathBuf> {
         self.file_name.as_ref()
     }
+
+    fn set_file_name(&mut self, file_name: path::PathBuf) {
+        self.file_name = Some(file_name);
+    }
+
+    fn insert_char(&mut self, at_x: usize, at_y: usize, ch: char) {
+        if at_y == self.number_of_rows() {
+            self.row_content.push(Self::make_row(""));
+        }
+        let mut content = self.get_row(at_y).to_string();
+        content.insert(at_x, ch);
+        self.row_content[at_y] = Self::make_row(&content);
+        self.dirty = true;
+    }
+
+    fn insert_newline(&mut self, at_x: usize, at_y: usize) {
+        if at_y >= self.number_of_rows() {
+            self.row_content.push(Self::make_row(""));
+        } else {
+            let content = self.get_row(at_y).to_string();
+            let (head, tail) = content.split_at(at_x);
+            self.row_content[at_y] = Self::make_row(head);
+            self.row_content.insert(at_y + 1, Self::make_row(tail));
+        }
+        self.dirty = true;
+    }
+
+    fn delete_char(&mut self, at_x: usize, at_y: usize) {
+        if at_y >= self.number_of_rows() || (at_x == 0 && at_y == 0) {
+            return;
+        }
+        if at_x == 0 {
+            let joined = format!("{}{}", self.get_row(at_y - 1), self.get_row(at_y));
+            self.row_content[at_y - 1] = Self::make_row(&joined);
+            self.row_content.remove(at_y);
+        } else {
+            let mut content = self.get_row(at_y).to_string();
+            let previous = content[..at_x]
+                .chars()
+                .next_back()
+                .map_or(0, char::len_utf8);
+            content.remove(at_x - previous);
+            self.row_content[at_y] = Self::make_row(&content);
+        }
+        self.dirty = true;
+    }
+
+    fn get_rows(&self) -> Vec<String> {
+        self.row_content
+            .iter()
+            .map(|row| row.get_content().to_string())
+            .collect()
+    }
+
+    fn set_rows(&mut self, rows: Vec<String>) {
+        self.row_content = rows.iter().map(|row| Self::make_row(row)).collect();
+        self.dirty = true;
+    }
+
+    fn save(&mut self) -> io::Result<usize> {
+        if self.read_only {
+            return Err(io::Error::other("Buffer is read-only"));
+        }
+        let file_name = self
+            .file_name
+            .as_ref()
+            .ok_or_else(|| io::Error::other("No file name"))?;
+        let content: String = self
+            .row_content
+            .iter()
+            .map(|row| format!("{}\n", row.get_content()))
+            .collect();
+        fs::write(file_name, &content)?;
+        self.dirty = false;
+        Ok(content.len())
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
 }
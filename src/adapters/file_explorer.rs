@@ -0,0 +1,254 @@
+use crate::ports::file_explorer::{ExplorerEntry, FileExplorerPort};
+use std::{fs, io, path};
+
+struct Node {
+    path: path::PathBuf,
+    is_dir: bool,
+    expanded: bool,
+    /// The directory's entries, read the first time it is expanded.
+    children: Option<Vec<Node>>,
+}
+
+impl Node {
+    fn new(path: path::PathBuf, is_dir: bool) -> Self {
+        Self {
+            path,
+            is_dir,
+            expanded: false,
+            children: None,
+        }
+    }
+
+    /// Reads the directory's entries, directories first, then by name.
+    fn read_children(&self, show_hidden: bool) -> io::Result<Vec<Node>> {
+        let mut children = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if !show_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            // Follow symbolic links so linked directories can be expanded
+            let is_dir = entry.path().is_dir();
+            children.push(Node::new(entry.path(), is_dir));
+        }
+        children.sort_by_cached_key(|node| {
+            let name = node.path.file_name().unwrap_or_default();
+            (!node.is_dir, name.to_string_lossy().to_lowercase())
+        });
+        Ok(children)
+    }
+
+    fn expand(&mut self, show_hidden: bool) -> io::Result<()> {
+        if self.children.is_none() {
+            self.children = Some(self.read_children(show_hidden)?);
+        }
+        self.expanded = true;
+        Ok(())
+    }
+
+    /// Reads the directory again, keeping the expanded subdirectories that
+    /// still exist expanded.
+    fn reload(&mut self, show_hidden: bool) -> io::Result<()> {
+        let mut old = self.children.take().unwrap_or_default();
+        let mut children = self.read_children(show_hidden)?;
+        for child in children.iter_mut() {
+            let Some(index) = old.iter().position(|node| node.path == child.path) else {
+                continue;
+            };
+            let previous = old.swap_remove(index);
+            if previous.expanded && child.is_dir {
+                child.children = previous.children;
+                // A subdirectory that can no longer be read is shown collapsed
+                child.expanded = child.reload(show_hidden).is_ok();
+            }
+        }
+        self.children = Some(children);
+        Ok(())
+    }
+
+    fn flatten(&self, depth: usize, out: &mut Vec<ExplorerEntry>) {
+        for child in self.children.iter().flatten() {
+            out.push(ExplorerEntry {
+                path: child.path.clone(),
+                depth,
+                is_dir: child.is_dir,
+                expanded: child.expanded,
+            });
+            if child.expanded {
+                child.flatten(depth + 1, out);
+            }
+        }
+    }
+
+    fn find_mut(&mut self, path: &path::Path) -> Option<&mut Node> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .flatten()
+            .filter(|child| path.starts_with(&child.path))
+            .find_map(|child| child.find_mut(path))
+    }
+}
+
+pub struct FileExplorer {
+    root: Node,
+    show_hidden: bool,
+    selected: usize,
+}
+
+impl FileExplorer {
+    fn select_path(&mut self, path: &path::Path) {
+        if let Some(index) = self.entries().iter().position(|entry| entry.path == path) {
+            self.selected = index;
+        }
+    }
+
+    /// Returns the selected directory, or the one holding the selected file.
+    fn target_directory(&self) -> path::PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => entry.path,
+            Some(entry) => entry
+                .path
+                .parent()
+                .map_or_else(|| self.root.path.clone(), path::Path::to_path_buf),
+            None => self.root.path.clone(),
+        }
+    }
+
+    fn check_name(name: &str) -> io::Result<&str> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "No name given"));
+        }
+        Ok(name)
+    }
+}
+
+impl FileExplorerPort for FileExplorer {
+    fn new(root: path::PathBuf, show_hidden: bool) -> io::Result<Self> {
+        let mut root = Node::new(root, true);
+        root.expand(show_hidden)?;
+        Ok(Self {
+            root,
+            show_hidden,
+            selected: 0,
+        })
+    }
+
+    fn root(&self) -> &path::Path {
+        &self.root.path
+    }
+
+    fn entries(&self) -> Vec<ExplorerEntry> {
+        let mut entries = Vec::new();
+        self.root.flatten(0, &mut entries);
+        entries
+    }
+
+    fn selected(&self) -> usize {
+        self.selected
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries().len().saturating_sub(1));
+    }
+
+    fn selected_entry(&self) -> Option<ExplorerEntry> {
+        self.entries().into_iter().nth(self.selected)
+    }
+
+    fn toggle(&mut self) -> io::Result<()> {
+        let Some(entry) = self.selected_entry().filter(|entry| entry.is_dir) else {
+            return Ok(());
+        };
+        let show_hidden = self.show_hidden;
+        match self.root.find_mut(&entry.path) {
+            Some(node) if node.expanded => node.expanded = false,
+            Some(node) => node.expand(show_hidden)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn collapse(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            return;
+        };
+        if entry.expanded {
+            if let Some(node) = self.root.find_mut(&entry.path) {
+                node.expanded = false;
+            }
+        } else if let Some(parent) = entry.path.parent() {
+            self.select_path(parent);
+        }
+    }
+
+    fn refresh(&mut self) -> io::Result<()> {
+        let selected = self.selected_entry().map(|entry| entry.path);
+        self.root.reload(self.show_hidden)?;
+        if let Some(path) = selected {
+            self.select_path(&path);
+        }
+        // The selected entry may be gone, leaving the index past the end
+        self.select(self.selected);
+        Ok(())
+    }
+
+    fn create(&mut self, name: &str) -> io::Result<path::PathBuf> {
+        let name = Self::check_name(name)?;
+        let directory = self.target_directory();
+        let path = directory.join(name.trim_end_matches('/'));
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::File::create_new(&path)?;
+        }
+
+        // Show the new entry inside its directory
+        let show_hidden = self.show_hidden;
+        if let Some(node) = self.root.find_mut(&directory) {
+            node.expand(show_hidden)?;
+        }
+        self.refresh()?;
+        self.select_path(&path);
+        Ok(path)
+    }
+
+    fn rename(&mut self, name: &str) -> io::Result<path::PathBuf> {
+        let name = Self::check_name(name)?;
+        let Some(entry) = self.selected_entry() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Nothing selected"));
+        };
+        let path = entry
+            .path
+            .parent()
+            .map_or_else(|| path::PathBuf::from(name), |parent| parent.join(name));
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        fs::rename(&entry.path, &path)?;
+        self.refresh()?;
+        self.select_path(&path);
+        Ok(path)
+    }
+
+    fn delete(&mut self) -> io::Result<()> {
+        let Some(entry) = self.selected_entry() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Nothing selected"));
+        };
+        if entry.is_dir {
+            fs::remove_dir_all(&entry.path)?;
+        } else {
+            fs::remove_file(&entry.path)?;
+        }
+        self.refresh()
+    }
+}
@@ -0,0 +1,53 @@
+use crate::ports::file_finder::{FileFinderPort, FinderMatch};
+use crate::toolshed::fuzzy::fuzzy_match;
+use ignore::WalkBuilder;
+use std::path;
+
+pub struct FileFinder {
+    root: path::PathBuf,
+    /// Every indexed file, relative to the root.
+    files: Vec<String>,
+}
+
+impl FileFinderPort for FileFinder {
+    fn new(root: path::PathBuf, show_hidden: bool) -> Self {
+        let files = WalkBuilder::new(&root)
+            .hidden(!show_hidden)
+            // Honor .gitignore files even outside of a git repository
+            .require_git(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&root).ok()?;
+                Some(relative.to_string_lossy().into_owned())
+            })
+            .collect();
+        Self { root, files }
+    }
+
+    fn find(&self, query: &str) -> Vec<FinderMatch> {
+        let mut matches: Vec<FinderMatch> = self
+            .files
+            .iter()
+            .filter_map(|file| {
+                let (score, positions) = fuzzy_match(query, file)?;
+                Some(FinderMatch {
+                    path: self.root.join(file),
+                    label: file.clone(),
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+        // Shorter paths win ties, as they are more likely what was meant
+        matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.label.len().cmp(&b.label.len()))
+                .then(a.label.cmp(&b.label))
+        });
+        matches
+    }
+}
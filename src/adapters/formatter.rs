@@ -0,0 +1,113 @@
+use crate::ports::config::CodeFormatterConfig;
+use crate::ports::formatter::FormatterPort;
+use std::collections::HashMap;
+use std::io::Write;
+use std::{io, path, process, thread};
+
+/// The languages formatters are known for: their name, the extensions of
+/// their files and the command formatting them, reading the source from
+/// standard input and writing the result to standard output.
+const LANGUAGES: &[(&str, &[&str], &str)] = &[
+    ("rust", &["rs"], "rustfmt --edition 2021"),
+    (
+        "javascript",
+        &["js", "jsx", "mjs", "cjs"],
+        "prettier --stdin-filepath {file}",
+    ),
+    (
+        "typescript",
+        &["ts", "tsx"],
+        "prettier --stdin-filepath {file}",
+    ),
+    ("python", &["py"], "black --quiet -"),
+    ("go", &["go"], "gofmt"),
+    ("c", &["c", "h"], "clang-format --assume-filename={file}"),
+    (
+        "cpp",
+        &["cc", "cpp", "hpp"],
+        "clang-format --assume-filename={file}",
+    ),
+];
+
+/// Formats files by piping them through external formatter commands.
+pub struct ExternalFormatter {
+    /// The command of each formatted language, by file extension.
+    commands: HashMap<String, String>,
+}
+
+impl ExternalFormatter {
+    fn command(&self, file: &path::Path) -> Option<&String> {
+        let extension = file.extension()?.to_str()?;
+        self.commands.get(extension)
+    }
+}
+
+impl FormatterPort for ExternalFormatter {
+    fn new(config: &CodeFormatterConfig) -> Self {
+        let mut commands = HashMap::new();
+        for (language, extensions, command) in LANGUAGES {
+            if !config.languages.iter().any(|enabled| enabled == language) {
+                continue;
+            }
+            let command = config
+                .formatters
+                .get(*language)
+                .map_or(*command, String::as_str);
+            for extension in *extensions {
+                commands.insert(extension.to_string(), command.to_string());
+            }
+        }
+        Self { commands }
+    }
+
+    fn formats(&self, file: &path::Path) -> bool {
+        self.command(file).is_some()
+    }
+
+    fn format(&self, file: &path::Path, text: &str) -> io::Result<String> {
+        let command = self
+            .command(file)
+            .ok_or_else(|| io::Error::other("No formatter for this file type"))?;
+        let file_name = file.to_string_lossy();
+        let mut words = command
+            .split_whitespace()
+            .map(|word| word.replace("{file}", &file_name));
+        let program = words.next().unwrap_or_default();
+
+        // Formatters look for their configuration next to the file
+        let directory = file
+            .parent()
+            .filter(|directory| directory.is_dir())
+            .unwrap_or(path::Path::new("."));
+        let mut child = process::Command::new(&program)
+            .args(words)
+            .current_dir(directory)
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)))?;
+        // Written from a thread, so that a formatter writing out as it reads
+        // cannot fill its pipe and stall, and one exiting early still has
+        // its errors read rather than a broken pipe reported
+        let writer = child.stdin.take().map(|mut stdin| {
+            let text = text.to_owned();
+            thread::spawn(move || stdin.write_all(text.as_bytes()))
+        });
+        let output = child.wait_with_output()?;
+        let written = writer.map_or(Ok(()), |writer| writer.join().unwrap_or(Ok(())));
+        if !output.status.success() {
+            // The first line of the errors says what went wrong
+            let error = String::from_utf8_lossy(&output.stderr);
+            let reason = error
+                .trim()
+                .lines()
+                .next()
+                .map_or_else(|| format!("{} failed", program), String::from);
+            return Err(io::Error::other(reason));
+        }
+        // Output for part of the text would replace all of it
+        written?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
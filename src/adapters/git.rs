@@ -0,0 +1,255 @@
+use crate::ports::git::{BlameLine, FileStatus, GitPort, PatchHunk};
+use crate::toolshed::date::format_date;
+use std::collections::HashMap;
+use std::io::Write;
+use std::{io, path, process, thread};
+
+/// The length commit hashes are abbreviated to.
+const HASH_LENGTH: usize = 8;
+
+/// What `git blame --porcelain` says about a commit, the first time the
+/// commit comes up.
+#[derive(Default)]
+struct CommitInfo {
+    author: String,
+    time: i64,
+    /// The author's distance from UTC, in seconds.
+    offset: i64,
+}
+
+/// Talks to git by running the `git` command line tool.
+pub struct GitCli;
+
+impl GitCli {
+    /// Runs git with `args` in `directory`, returning what it printed.
+    fn run(directory: &path::Path, args: &[&str]) -> io::Result<String> {
+        Self::run_with_input(directory, args, None)
+    }
+
+    /// Runs git like `run`, writing `input` to its standard input.
+    fn run_with_input(
+        directory: &path::Path,
+        args: &[&str],
+        input: Option<&str>,
+    ) -> io::Result<String> {
+        let mut child = process::Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args(args)
+            .stdin(if input.is_some() {
+                process::Stdio::piped()
+            } else {
+                process::Stdio::null()
+            })
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()?;
+        // Written from a thread, so that git exiting early still has its
+        // errors read rather than a broken pipe reported
+        let writer = match (input, child.stdin.take()) {
+            (Some(input), Some(mut stdin)) => {
+                let input = input.to_owned();
+                Some(thread::spawn(move || stdin.write_all(input.as_bytes())))
+            }
+            _ => None,
+        };
+        let output = child.wait_with_output()?;
+        let written = writer.map_or(Ok(()), |writer| writer.join().unwrap_or(Ok(())));
+        if !output.status.success() {
+            // The first line of git's errors says what went wrong
+            let error = String::from_utf8_lossy(&output.stderr);
+            let reason = error.trim().lines().next().unwrap_or_default();
+            return Err(io::Error::other(reason.to_string()));
+        }
+        written?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Returns `file` as an argument for git.
+    fn path_argument(file: &path::Path) -> String {
+        file.to_string_lossy().into_owned()
+    }
+
+    /// Parses the output of `git status --porcelain -z`.
+    fn parse_status(output: &str) -> Vec<FileStatus> {
+        let mut statuses = Vec::new();
+        let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+        while let Some(entry) = entries.next() {
+            let mut codes = entry.chars();
+            let (Some(staged), Some(unstaged)) = (codes.next(), codes.next()) else {
+                continue;
+            };
+            // Renames and copies are followed by the path they came from
+            if matches!(staged, 'R' | 'C') {
+                entries.next();
+            }
+            statuses.push(FileStatus {
+                path: entry.get(3..).unwrap_or_default().into(),
+                staged,
+                unstaged,
+            });
+        }
+        statuses
+    }
+
+    /// Splits the output of `git diff` for one file into its hunks.
+    fn parse_diff(output: &str) -> Vec<PatchHunk> {
+        let mut header = String::new();
+        let mut hunks: Vec<PatchHunk> = Vec::new();
+        for line in output.lines() {
+            if line.starts_with("@@") {
+                hunks.push(PatchHunk {
+                    header: header.clone(),
+                    text: String::new(),
+                });
+            }
+            let text = match hunks.last_mut() {
+                Some(hunk) => &mut hunk.text,
+                None => &mut header,
+            };
+            text.push_str(line);
+            text.push('\n');
+        }
+        hunks
+    }
+
+    /// Splits `file` into the directory to run git in and its name there.
+    fn locate(file: &path::Path) -> Option<(&path::Path, String)> {
+        let name = file.file_name()?.to_str()?;
+        let directory = match file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => path::Path::new("."),
+        };
+        Some((directory, name.to_string()))
+    }
+
+    /// Parses a timezone such as `+0130` into seconds east of UTC.
+    fn parse_offset(timezone: &str) -> i64 {
+        let (sign, digits) = match timezone.strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, timezone.trim_start_matches('+')),
+        };
+        let value: i64 = digits.parse().unwrap_or(0);
+        sign * (value / 100 * 3600 + value % 100 * 60)
+    }
+
+    /// Parses the output of `git blame --porcelain` into one entry per line.
+    fn parse_blame(output: &str) -> Vec<BlameLine> {
+        let mut commits: HashMap<&str, CommitInfo> = HashMap::new();
+        let mut lines = Vec::new();
+        let mut current = "";
+        for line in output.lines() {
+            // The content of a line ends the headers about it
+            if line.starts_with('\t') {
+                let info = commits.get(current);
+                let committed = current.bytes().any(|byte| byte != b'0');
+                lines.push(BlameLine {
+                    commit: committed.then(|| current[..HASH_LENGTH].to_string()),
+                    author: info.map(|info| info.author.clone()).unwrap_or_default(),
+                    date: info
+                        .map(|info| format_date(info.time, info.offset))
+                        .unwrap_or_default(),
+                });
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "author" => commits.entry(current).or_default().author = value.into(),
+                "author-time" => {
+                    commits.entry(current).or_default().time = value.parse().unwrap_or(0)
+                }
+                "author-tz" => {
+                    commits.entry(current).or_default().offset = Self::parse_offset(value)
+                }
+                // Each line starts with the hash of its commit
+                hash if hash.len() == 40 && hash.bytes().all(|byte| byte.is_ascii_hexdigit()) => {
+                    current = hash
+                }
+                _ => {}
+            }
+        }
+        lines
+    }
+}
+
+impl GitPort for GitCli {
+    fn new() -> Self {
+        Self
+    }
+
+    fn head_content(&self, file: &path::Path) -> Option<String> {
+        self.show(file, "HEAD").ok()
+    }
+
+    fn show(&self, file: &path::Path, revision: &str) -> io::Result<String> {
+        let (directory, name) =
+            Self::locate(file).ok_or_else(|| io::Error::other("No file name"))?;
+        // `./` makes the path relative to `directory` rather than the repository root
+        Self::run(directory, &["show", &format!("{}:./{}", revision, name)])
+    }
+
+    fn blame(&self, file: &path::Path) -> io::Result<Vec<BlameLine>> {
+        let (directory, name) =
+            Self::locate(file).ok_or_else(|| io::Error::other("No file name"))?;
+        let output = Self::run(directory, &["blame", "--porcelain", "--", &name])?;
+        Ok(Self::parse_blame(&output))
+    }
+
+    fn repository_root(&self, directory: &path::Path) -> io::Result<path::PathBuf> {
+        let root = Self::run(directory, &["rev-parse", "--show-toplevel"])?;
+        Ok(root.trim_end().into())
+    }
+
+    fn commit_message_file(&self, root: &path::Path) -> io::Result<path::PathBuf> {
+        let git_directory = Self::run(root, &["rev-parse", "--absolute-git-dir"])?;
+        Ok(path::Path::new(git_directory.trim_end()).join("COMMIT_EDITMSG"))
+    }
+
+    fn status(&self, root: &path::Path) -> io::Result<Vec<FileStatus>> {
+        let output = Self::run(root, &["status", "--porcelain", "-z"])?;
+        Ok(Self::parse_status(&output))
+    }
+
+    fn diff_hunks(
+        &self,
+        root: &path::Path,
+        file: &path::Path,
+        staged: bool,
+    ) -> io::Result<Vec<PatchHunk>> {
+        let file = Self::path_argument(file);
+        let mut args = vec!["diff", "--no-color", "--no-ext-diff"];
+        if staged {
+            args.push("--cached");
+        }
+        args.extend(["--", &file]);
+        Ok(Self::parse_diff(&Self::run(root, &args)?))
+    }
+
+    fn stage(&self, root: &path::Path, file: &path::Path) -> io::Result<()> {
+        Self::run(root, &["add", "--all", "--", &Self::path_argument(file)]).map(drop)
+    }
+
+    fn unstage(&self, root: &path::Path, file: &path::Path) -> io::Result<()> {
+        Self::run(
+            root,
+            &["reset", "--quiet", "--", &Self::path_argument(file)],
+        )
+        .map(drop)
+    }
+
+    fn apply_to_index(&self, root: &path::Path, hunk: &PatchHunk, reverse: bool) -> io::Result<()> {
+        let mut args = vec!["apply", "--cached"];
+        if reverse {
+            args.push("--reverse");
+        }
+        args.push("-");
+        let patch = format!("{}{}", hunk.header, hunk.text);
+        Self::run_with_input(root, &args, Some(&patch)).map(drop)
+    }
+
+    fn commit(&self, root: &path::Path, message: &str) -> io::Result<String> {
+        Self::run_with_input(root, &["commit", "--quiet", "--file", "-"], Some(message))?;
+        let hash = Self::run(root, &["rev-parse", "--short", "HEAD"])?;
+        Ok(hash.trim_end().to_string())
+    }
+}
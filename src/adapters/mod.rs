@@ -1,5 +1,18 @@
+pub mod buffer_list;
+pub mod completion_sources;
 pub mod cursor;
 pub mod editor_buffer;
 pub mod editor_rows;
+pub mod file_explorer;
+pub mod file_finder;
+pub mod formatter;
+pub mod git;
+pub mod overlay_stack;
+pub mod project_search;
+pub mod rpc;
+pub mod script_engine;
+pub mod spell_checker;
 pub mod status_message;
 pub mod terminal_io;
+pub mod undo;
+pub mod window_layout;
@@ -0,0 +1,55 @@
+use crate::ports::overlay::{Overlay, OverlayKind, OverlayStackPort};
+use crate::ports::window::Rect;
+
+pub struct OverlayStack {
+    overlays: Vec<Overlay>,
+}
+
+impl OverlayStackPort for OverlayStack {
+    fn new() -> Self {
+        Self {
+            overlays: Vec::new(),
+        }
+    }
+
+    fn open(&mut self, kind: OverlayKind) {
+        if !self.overlays.iter().any(|overlay| overlay.kind == kind) {
+            self.overlays.push(Overlay {
+                kind,
+                rect: Rect::default(),
+            });
+        }
+    }
+
+    fn close(&mut self, kind: OverlayKind) {
+        self.overlays.retain(|overlay| overlay.kind != kind);
+    }
+
+    fn overlays(&self) -> &[Overlay] {
+        &self.overlays
+    }
+
+    fn set_rect(&mut self, kind: OverlayKind, rect: Rect) {
+        for overlay in &mut self.overlays {
+            if overlay.kind == kind {
+                overlay.rect = rect;
+            }
+        }
+    }
+
+    fn focused(&self) -> Option<OverlayKind> {
+        self.overlays
+            .iter()
+            .rev()
+            .map(|overlay| overlay.kind)
+            .find(|kind| kind.takes_keys())
+    }
+
+    fn at(&self, x: usize, y: usize) -> Option<OverlayKind> {
+        self.overlays
+            .iter()
+            .rev()
+            .find(|overlay| overlay.rect.contains(x, y))
+            .map(|overlay| overlay.kind)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::ports::project_search::{ProjectSearchPort, SearchMatch, SearchOptions};
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::{fs, io, path, thread};
+
+/// Searching stops after this many matches, to keep the results usable.
+const MAX_MATCHES: usize = 10_000;
+
+pub struct ProjectSearch {
+    receiver: mpsc::Receiver<SearchMatch>,
+    /// Tells the search thread to stop early.
+    cancelled: Arc<AtomicBool>,
+    done: bool,
+}
+
+impl ProjectSearch {
+    fn search(
+        root: path::PathBuf,
+        regex: Regex,
+        show_hidden: bool,
+        sender: mpsc::Sender<SearchMatch>,
+        cancelled: Arc<AtomicBool>,
+    ) {
+        let files = WalkBuilder::new(&root)
+            .hidden(!show_hidden)
+            .require_git(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()));
+
+        let mut found = 0;
+        for entry in files {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            // Files that are not text are skipped
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let label = entry
+                .path()
+                .strip_prefix(&root)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .into_owned();
+            for (line, text) in content.lines().enumerate() {
+                let Some(found_at) = regex.find(text) else {
+                    continue;
+                };
+                let result = SearchMatch {
+                    path: entry.path().to_path_buf(),
+                    label: label.clone(),
+                    line,
+                    start: found_at.start(),
+                    end: found_at.end(),
+                    text: text.to_string(),
+                };
+                found += 1;
+                if cancelled.load(Ordering::Relaxed)
+                    || sender.send(result).is_err()
+                    || found >= MAX_MATCHES
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl ProjectSearchPort for ProjectSearch {
+    fn start(root: path::PathBuf, pattern: &str, options: SearchOptions) -> io::Result<Self> {
+        let pattern = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .map_err(|e| {
+                // The last line of a regex error explains what is wrong with it
+                let reason = e.to_string().lines().last().unwrap_or_default().to_string();
+                io::Error::new(io::ErrorKind::InvalidInput, reason)
+            })?;
+
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancelled);
+        thread::spawn(move || Self::search(root, regex, options.show_hidden, sender, flag));
+
+        Ok(Self {
+            receiver,
+            cancelled,
+            done: false,
+        })
+    }
+
+    fn poll(&mut self) -> Vec<SearchMatch> {
+        let mut matches = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(found) => matches.push(found),
+                Err(mpsc::TryRecvError::Empty) => break,
+                // The search thread hangs up once it has gone through every file
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        matches
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::ports::rpc::{RpcError, RpcMessage, RpcPort};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::sync::mpsc;
+use std::{io, path, process, thread};
+
+/// The longest message body read, in bytes. A longer one is taken as the
+/// process having gone wrong, rather than allocated.
+const MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
+
+/// How many messages may wait to be written before the process is taken as
+/// no longer reading them, and more are refused.
+const OUTGOING_LIMIT: usize = 1024;
+
+/// Talks JSON-RPC to a child process, with each message preceded by a
+/// `Content-Length` header as in the Language Server Protocol.
+pub struct RpcProcess {
+    child: process::Child,
+    /// Drained by a thread writing to the input of the process, so that a
+    /// process that stops reading cannot block the editor.
+    outgoing: mpsc::SyncSender<String>,
+    /// Fed by a thread reading the output of the process until it ends.
+    incoming: mpsc::Receiver<RpcMessage>,
+    next_id: u64,
+}
+
+impl RpcProcess {
+    /// Reads the body of the next message, or `None` at the end of the
+    /// output.
+    fn read_frame(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+        let length = length.ok_or_else(|| io::Error::other("Message without a length"))?;
+        if length > MAX_FRAME_LENGTH {
+            return Err(io::Error::other(format!("Message of {} bytes", length)));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        Ok(Some(body))
+    }
+
+    fn parse_message(message: Value) -> Option<RpcMessage> {
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        match (
+            message.get("method").and_then(Value::as_str),
+            message.get("id"),
+        ) {
+            (Some(method), Some(id)) => Some(RpcMessage::Request {
+                id: id.clone(),
+                method: method.to_string(),
+                params,
+            }),
+            (Some(method), None) => Some(RpcMessage::Notification {
+                method: method.to_string(),
+                params,
+            }),
+            (None, Some(id)) => {
+                let result = match message.get("error") {
+                    Some(error) => Err(RpcError::new(
+                        error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                        error
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default(),
+                    )),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                Some(RpcMessage::Response {
+                    id: id.as_u64()?,
+                    result,
+                })
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["jsonrpc"] = json!("2.0");
+        let body = message.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        self.outgoing.try_send(frame).map_err(|e| match e {
+            mpsc::TrySendError::Full(_) => io::Error::other("Not reading its input"),
+            mpsc::TrySendError::Disconnected(_) => {
+                io::Error::new(io::ErrorKind::BrokenPipe, "Stopped reading its input")
+            }
+        })
+    }
+}
+
+impl RpcPort for RpcProcess {
+    fn spawn(program: &str, args: &[String], directory: &path::Path) -> io::Result<Self> {
+        let mut child = process::Command::new(program)
+            .args(args)
+            .current_dir(directory)
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            // Anything written there would end up over the editor's screen
+            .stderr(process::Stdio::null())
+            .spawn()?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("No input"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("No output"))?;
+
+        let (outgoing, frames) = mpsc::sync_channel::<String>(OUTGOING_LIMIT);
+        thread::spawn(move || {
+            for frame in frames {
+                let written = stdin.write_all(frame.as_bytes());
+                if written.and_then(|()| stdin.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = io::BufReader::new(stdout);
+            while let Ok(Some(body)) = Self::read_frame(&mut reader) {
+                // Messages that are not JSON-RPC are skipped
+                let Some(message) = serde_json::from_slice(&body)
+                    .ok()
+                    .and_then(Self::parse_message)
+                else {
+                    continue;
+                };
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            outgoing,
+            incoming,
+            next_id: 1,
+        })
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> io::Result<u64> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "id": id, "method": method, "params": params }))?;
+        Ok(id)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(json!({ "method": method, "params": params }))
+    }
+
+    fn respond(&mut self, id: Value, result: Result<Value, RpcError>) -> io::Result<()> {
+        match result {
+            Ok(result) => self.send(json!({ "id": id, "result": result })),
+            Err(error) => self.send(json!({
+                "id": id,
+                "error": { "code": error.code, "message": error.message },
+            })),
+        }
+    }
+
+    fn receive(&mut self) -> Vec<RpcMessage> {
+        self.incoming.try_iter().collect()
+    }
+
+    fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for RpcProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_frame(input: &str) -> io::Result<Option<Vec<u8>>> {
+        RpcProcess::read_frame(&mut input.as_bytes())
+    }
+
+    #[test]
+    fn reads_frames_until_the_end_of_the_output() {
+        let input = "Content-Length: 2\r\n\r\n{}content-length:3\r\nContent-Type: x\r\n\r\n[1]";
+        let mut reader = input.as_bytes();
+        let first = RpcProcess::read_frame(&mut reader).unwrap();
+        assert_eq!(first.as_deref(), Some(&b"{}"[..]));
+        let second = RpcProcess::read_frame(&mut reader).unwrap();
+        assert_eq!(second.as_deref(), Some(&b"[1]"[..]));
+        assert!(RpcProcess::read_frame(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_frames_without_a_usable_length() {
+        assert!(first_frame("Content-Type: x\r\n\r\n{}").is_err());
+        let too_long = format!("Content-Length: {}\r\n\r\n", MAX_FRAME_LENGTH + 1);
+        assert!(first_frame(&too_long).is_err());
+        // A body cut short
+        assert!(first_frame("Content-Length: 10\r\n\r\n{}").is_err());
+    }
+
+    #[test]
+    fn parses_requests_notifications_and_responses() {
+        let request = json!({ "id": "a", "method": "ask", "params": [1] });
+        assert!(matches!(
+            RpcProcess::parse_message(request),
+            Some(RpcMessage::Request { id, method, params })
+                if id == "a" && method == "ask" && params == json!([1])
+        ));
+        let notification = json!({ "method": "tell" });
+        assert!(matches!(
+            RpcProcess::parse_message(notification),
+            Some(RpcMessage::Notification { method, params })
+                if method == "tell" && params.is_null()
+        ));
+        let response = json!({ "id": 7, "result": { "ok": true } });
+        assert!(matches!(
+            RpcProcess::parse_message(response),
+            Some(RpcMessage::Response { id: 7, result: Ok(result) }) if result["ok"] == true
+        ));
+        let error = json!({ "id": 8, "error": { "code": -32601, "message": "No such method" } });
+        assert!(matches!(
+            RpcProcess::parse_message(error),
+            Some(RpcMessage::Response { id: 8, result: Err(e) })
+                if e.code == -32601 && e.message == "No such method"
+        ));
+    }
+
+    #[test]
+    fn ignores_messages_it_cannot_make_sense_of() {
+        assert!(RpcProcess::parse_message(json!({ "result": 1 })).is_none());
+        // The ids of the requests sent are numbers
+        assert!(RpcProcess::parse_message(json!({ "id": "x", "result": 1 })).is_none());
+    }
+}
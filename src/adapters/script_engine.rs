@@ -0,0 +1,317 @@
+use crate::ports::config::ScriptingConfig;
+use crate::ports::script_engine::{ScriptContext, ScriptEnginePort};
+use crate::toolshed::keys;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FnPtr, FuncArgs, AST, INT};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::{io, path};
+
+/// The events scripts can handle.
+const EVENTS: &[&str] = &["open", "change", "save"];
+const MODES: &[&str] = &["normal", "insert"];
+
+/// The deepest scripts may call functions, so that runaway recursion is
+/// stopped rather than overflowing the stack.
+const MAX_CALL_LEVELS: usize = 64;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// What the scripts defined.
+#[derive(Default)]
+struct Definitions {
+    commands: HashMap<String, FnPtr>,
+    /// Functions bound to keys, by mode and key name.
+    bindings: HashMap<(String, String), FnPtr>,
+    handlers: HashMap<String, Vec<FnPtr>>,
+}
+
+/// Runs Rhai scripts. Besides the language itself, scripts only get the
+/// functions registered here, which act on the context of the call.
+pub struct RhaiEngine {
+    engine: Engine,
+    /// The init script, in which the functions scripts define live.
+    ast: AST,
+    definitions: Rc<RefCell<Definitions>>,
+    context: Rc<RefCell<ScriptContext>>,
+}
+
+impl RhaiEngine {
+    /// Checks that `index` is below `len`, for a script to use it.
+    fn index(index: INT, len: usize, what: &str) -> ScriptResult<usize> {
+        usize::try_from(index)
+            .ok()
+            .filter(|index| *index < len)
+            .ok_or_else(|| format!("No {} {}", what, index).into())
+    }
+
+    fn register_definitions(&mut self) {
+        let definitions = self.definitions.clone();
+        self.engine
+            .register_fn("command", move |name: &str, function: FnPtr| {
+                definitions
+                    .borrow_mut()
+                    .commands
+                    .insert(name.to_string(), function);
+            });
+        let definitions = self.definitions.clone();
+        self.engine.register_fn(
+            "bind",
+            move |mode: &str, key: &str, function: FnPtr| -> ScriptResult<()> {
+                if !MODES.contains(&mode) {
+                    return Err(format!("No mode {}", mode).into());
+                }
+                let key = keys::parse_key(key)
+                    .as_ref()
+                    .and_then(keys::key_name)
+                    .ok_or_else(|| format!("No key {}", key))?;
+                definitions
+                    .borrow_mut()
+                    .bindings
+                    .insert((mode.to_string(), key), function);
+                Ok(())
+            },
+        );
+        let definitions = self.definitions.clone();
+        self.engine.register_fn(
+            "on",
+            move |event: &str, function: FnPtr| -> ScriptResult<()> {
+                if !EVENTS.contains(&event) {
+                    return Err(format!("No event {}", event).into());
+                }
+                definitions
+                    .borrow_mut()
+                    .handlers
+                    .entry(event.to_string())
+                    .or_default()
+                    .push(function);
+                Ok(())
+            },
+        );
+    }
+
+    fn register_buffer_api(&mut self) {
+        let context = self.context.clone();
+        self.engine.register_fn("file_name", move || {
+            context.borrow().file_name.clone().unwrap_or_default()
+        });
+        let context = self.context.clone();
+        self.engine
+            .register_fn("row_count", move || context.borrow().rows.len() as INT);
+        let context = self.context.clone();
+        self.engine
+            .register_fn("row", move |row: INT| -> ScriptResult<String> {
+                let context = context.borrow();
+                let row = Self::index(row, context.rows.len(), "row")?;
+                Ok(context.rows[row].clone())
+            });
+        let context = self.context.clone();
+        self.engine.register_fn("rows", move || -> Array {
+            context
+                .borrow()
+                .rows
+                .iter()
+                .cloned()
+                .map(Dynamic::from)
+                .collect()
+        });
+        let context = self.context.clone();
+        self.engine
+            .register_fn("set_row", move |row: INT, text: &str| -> ScriptResult<()> {
+                let mut context = context.borrow_mut();
+                let row = Self::index(row, context.rows.len(), "row")?;
+                context.rows[row] = text.replace('\n', " ");
+                context.edited = true;
+                Ok(())
+            });
+        let context = self.context.clone();
+        self.engine.register_fn(
+            "insert_row",
+            move |row: INT, text: &str| -> ScriptResult<()> {
+                let mut context = context.borrow_mut();
+                let row = Self::index(row, context.rows.len() + 1, "row")?;
+                context.rows.insert(row, text.replace('\n', " "));
+                context.edited = true;
+                Ok(())
+            },
+        );
+        let context = self.context.clone();
+        self.engine
+            .register_fn("delete_row", move |row: INT| -> ScriptResult<()> {
+                let mut context = context.borrow_mut();
+                let row = Self::index(row, context.rows.len(), "row")?;
+                context.rows.remove(row);
+                context.edited = true;
+                Ok(())
+            });
+        let context = self.context.clone();
+        self.engine.register_fn("set_rows", move |rows: Array| {
+            let mut context = context.borrow_mut();
+            context.rows = rows.into_iter().map(|row| row.to_string()).collect();
+            context.edited = true;
+        });
+        let context = self.context.clone();
+        self.engine
+            .register_fn("insert_text", move |text: &str| -> ScriptResult<()> {
+                let mut context = context.borrow_mut();
+                let (x, y) = context.cursor;
+                if y == context.rows.len() {
+                    context.rows.push(String::new());
+                }
+                let row = context.rows.get(y).ok_or("The cursor is past the end")?;
+                if !row.is_char_boundary(x) {
+                    return Err("The cursor is inside a char".into());
+                }
+                let tail = row[x..].to_string();
+                let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+                lines[0].insert_str(0, &row[..x]);
+                let last = lines.len() - 1;
+                let cursor = (lines[last].len(), y + last);
+                lines[last].push_str(&tail);
+                context.rows.splice(y..=y, lines);
+                context.cursor = cursor;
+                context.edited = true;
+                context.moved = true;
+                Ok(())
+            });
+    }
+
+    fn register_editor_api(&mut self) {
+        let context = self.context.clone();
+        self.engine
+            .register_fn("cursor_column", move || context.borrow().cursor.0 as INT);
+        let context = self.context.clone();
+        self.engine
+            .register_fn("cursor_row", move || context.borrow().cursor.1 as INT);
+        let context = self.context.clone();
+        self.engine.register_fn(
+            "set_cursor",
+            move |column: INT, row: INT| -> ScriptResult<()> {
+                let mut context = context.borrow_mut();
+                // The editor keeps the cursor within the rows
+                let column = usize::try_from(column).map_err(|_| "Negative column")?;
+                let row = usize::try_from(row).map_err(|_| "Negative row")?;
+                context.cursor = (column, row);
+                context.moved = true;
+                Ok(())
+            },
+        );
+        let context = self.context.clone();
+        self.engine.register_fn("message", move |text: &str| {
+            context.borrow_mut().messages.push(text.to_string());
+        });
+        let context = self.context.clone();
+        self.engine.on_print(move |text| {
+            context.borrow_mut().messages.push(text.to_string());
+        });
+        let context = self.context.clone();
+        self.engine.register_fn("execute", move |line: &str| {
+            let line = line.strip_prefix(':').unwrap_or(line);
+            context.borrow_mut().commands.push(line.to_string());
+        });
+    }
+
+    /// Calls `function` with `args`, on `context`.
+    fn call(
+        &mut self,
+        context: &mut ScriptContext,
+        function: &FnPtr,
+        args: impl FuncArgs,
+    ) -> io::Result<()> {
+        *self.context.borrow_mut() = std::mem::take(context);
+        let result = function.call::<Dynamic>(&self.engine, &self.ast, args);
+        *context = std::mem::take(&mut *self.context.borrow_mut());
+        result
+            .map(|_| ())
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+impl ScriptEnginePort for RhaiEngine {
+    fn new(config: &ScriptingConfig) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(config.max_operations);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        let mut scripting = Self {
+            engine,
+            ast: AST::empty(),
+            definitions: Rc::default(),
+            context: Rc::default(),
+        };
+        scripting.register_definitions();
+        scripting.register_buffer_api();
+        scripting.register_editor_api();
+        scripting
+    }
+
+    fn load(&mut self, file: &path::Path) -> io::Result<()> {
+        let ast = self
+            .engine
+            .compile_file(file.into())
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.engine
+            .run_ast(&ast)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.ast = ast;
+        Ok(())
+    }
+
+    fn commands(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.definitions.borrow().commands.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn run_command(
+        &mut self,
+        context: &mut ScriptContext,
+        name: &str,
+        argument: Option<&str>,
+    ) -> Option<io::Result<()>> {
+        let function = self.definitions.borrow().commands.get(name).cloned()?;
+        let argument = argument.unwrap_or_default().to_string();
+        Some(self.call(context, &function, (argument,)))
+    }
+
+    fn key_bindings(&self, mode: &str) -> Vec<String> {
+        let definitions = self.definitions.borrow();
+        let mut keys: Vec<String> = definitions
+            .bindings
+            .keys()
+            .filter(|(bound_in, _)| bound_in == mode)
+            .map(|(_, key)| key.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    fn run_key_binding(
+        &mut self,
+        context: &mut ScriptContext,
+        mode: &str,
+        key: &str,
+    ) -> Option<io::Result<()>> {
+        let function = self
+            .definitions
+            .borrow()
+            .bindings
+            .get(&(mode.to_string(), key.to_string()))
+            .cloned()?;
+        Some(self.call(context, &function, ()))
+    }
+
+    fn emit(&mut self, context: &mut ScriptContext, event: &str) -> io::Result<()> {
+        let handlers = self
+            .definitions
+            .borrow()
+            .handlers
+            .get(event)
+            .cloned()
+            .unwrap_or_default();
+        let file_name = context.file_name.clone().unwrap_or_default();
+        for handler in handlers {
+            self.call(context, &handler, (file_name.clone(),))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+use crate::ports::config::SpellCheckerConfig;
+use crate::ports::spell_checker::SpellCheckerPort;
+use crate::toolshed::edit_distance::edit_distance;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::{env, fs, io, path};
+
+/// Where Hunspell dictionaries are installed, after those in `DICPATH`.
+const DICTIONARY_DIRECTORIES: &[&str] = &[
+    "~/.local/share/hunspell",
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/Library/Spelling",
+];
+
+/// Suggestions are at most this many edits away from the misspelled word.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// A char of an affix condition, such as `a`, `.` or `[^aeiou]`.
+enum ConditionChar {
+    Any,
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+impl ConditionChar {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            ConditionChar::Any => true,
+            ConditionChar::OneOf(chars) => chars.contains(&ch),
+            ConditionChar::NoneOf(chars) => !chars.contains(&ch),
+        }
+    }
+}
+
+/// One way of adding a prefix or suffix to a word.
+struct AffixRule {
+    /// Removed from the word before adding `add`.
+    strip: String,
+    add: String,
+    /// What the start (or end, for a suffix) of the word has to look like.
+    condition: Vec<ConditionChar>,
+}
+
+/// The rules of a prefix or suffix flag.
+struct Affix {
+    /// Whether the affix combines with affixes of the other kind.
+    cross_product: bool,
+    rules: Vec<AffixRule>,
+}
+
+/// The affixes of a Hunspell `.aff` file, by flag.
+#[derive(Default)]
+struct Affixes {
+    /// How flags are written: one char each (the default), two chars each
+    /// (`long`) or as comma separated numbers (`num`).
+    flag_type: String,
+    prefixes: HashMap<String, Affix>,
+    suffixes: HashMap<String, Affix>,
+}
+
+impl Affixes {
+    fn parse(content: &str) -> Self {
+        let mut affixes = Self::default();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", flag_type, ..] => affixes.flag_type = flag_type.to_string(),
+                [kind @ ("PFX" | "SFX"), flag, cross, count] if count.parse::<usize>().is_ok() => {
+                    let affix = Affix {
+                        cross_product: *cross == "Y",
+                        rules: Vec::new(),
+                    };
+                    affixes.table(kind).insert(flag.to_string(), affix);
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let condition = rest.first().copied().unwrap_or(".");
+                    let rule = AffixRule {
+                        strip: Self::affix_text(strip),
+                        // Flags on the affix itself, for affixing twice, are left out
+                        add: Self::affix_text(add.split('/').next().unwrap_or_default()),
+                        condition: Self::parse_condition(condition),
+                    };
+                    if let Some(affix) = affixes.table(kind).get_mut(*flag) {
+                        affix.rules.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+        affixes
+    }
+
+    fn table(&mut self, kind: &str) -> &mut HashMap<String, Affix> {
+        if kind == "PFX" {
+            &mut self.prefixes
+        } else {
+            &mut self.suffixes
+        }
+    }
+
+    /// `0` stands for nothing in the strip and add fields.
+    fn affix_text(field: &str) -> String {
+        if field == "0" {
+            String::new()
+        } else {
+            field.to_string()
+        }
+    }
+
+    fn parse_condition(condition: &str) -> Vec<ConditionChar> {
+        let mut chars = condition.chars();
+        let mut parsed = Vec::new();
+        while let Some(ch) = chars.next() {
+            parsed.push(match ch {
+                '.' => ConditionChar::Any,
+                '[' => {
+                    let class: String = chars.by_ref().take_while(|ch| *ch != ']').collect();
+                    match class.strip_prefix('^') {
+                        Some(class) => ConditionChar::NoneOf(class.chars().collect()),
+                        None => ConditionChar::OneOf(class.chars().collect()),
+                    }
+                }
+                ch => ConditionChar::OneOf(vec![ch]),
+            });
+        }
+        parsed
+    }
+
+    /// Splits the flags of a dictionary word.
+    fn split_flags(&self, flags: &str) -> Vec<String> {
+        match self.flag_type.as_str() {
+            "long" => {
+                let chars: Vec<char> = flags.chars().collect();
+                chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+            }
+            "num" => flags.split(',').map(String::from).collect(),
+            _ => flags.chars().map(String::from).collect(),
+        }
+    }
+
+    fn add_suffix(word: &str, rule: &AffixRule) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let tail = chars.len().checked_sub(rule.condition.len())?;
+        let fits = rule
+            .condition
+            .iter()
+            .zip(&chars[tail..])
+            .all(|(condition, ch)| condition.matches(*ch));
+        let stem = word.strip_suffix(rule.strip.as_str())?;
+        fits.then(|| format!("{}{}", stem, rule.add))
+    }
+
+    fn add_prefix(word: &str, rule: &AffixRule) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let fits = chars.len() >= rule.condition.len()
+            && rule
+                .condition
+                .iter()
+                .zip(&chars)
+                .all(|(condition, ch)| condition.matches(*ch));
+        let stem = word.strip_prefix(rule.strip.as_str())?;
+        fits.then(|| format!("{}{}", rule.add, stem))
+    }
+
+    /// Adds `word` and every form its flags allow to `words`.
+    fn expand(&self, word: &str, flags: &[String], words: &mut HashSet<String>) {
+        words.insert(word.to_string());
+        let prefixes: Vec<&Affix> = flags
+            .iter()
+            .filter_map(|flag| self.prefixes.get(flag))
+            .collect();
+        for prefix in &prefixes {
+            for rule in &prefix.rules {
+                words.extend(Self::add_prefix(word, rule));
+            }
+        }
+        for suffix in flags.iter().filter_map(|flag| self.suffixes.get(flag)) {
+            for rule in &suffix.rules {
+                let Some(suffixed) = Self::add_suffix(word, rule) else {
+                    continue;
+                };
+                // A suffix and a prefix can both be added if both allow it
+                if suffix.cross_product {
+                    for prefix in prefixes.iter().filter(|prefix| prefix.cross_product) {
+                        for rule in &prefix.rules {
+                            words.extend(Self::add_prefix(&suffixed, rule));
+                        }
+                    }
+                }
+                words.insert(suffixed);
+            }
+        }
+    }
+}
+
+/// A spell checker using the word lists of Hunspell dictionaries.
+pub struct SpellChecker {
+    /// Every accepted form of every word.
+    words: HashSet<String>,
+    /// Words that are not reported, but not suggested either.
+    ignored: HashSet<String>,
+    /// Where the words added by the user are kept.
+    personal_dictionary: Option<path::PathBuf>,
+}
+
+impl SpellChecker {
+    fn expand_home(directory: &str) -> Option<path::PathBuf> {
+        match directory.strip_prefix("~/") {
+            Some(rest) => env::var_os("HOME").map(|home| path::Path::new(&home).join(rest)),
+            None => Some(directory.into()),
+        }
+    }
+
+    /// Finds the `.dic` and `.aff` files of `language`.
+    fn find_dictionary(language: &str) -> Option<(path::PathBuf, path::PathBuf)> {
+        let from_environment: Vec<path::PathBuf> = env::var_os("DICPATH")
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        let name = language.replace('-', "_");
+        from_environment
+            .into_iter()
+            .chain(
+                DICTIONARY_DIRECTORIES
+                    .iter()
+                    .filter_map(|directory| Self::expand_home(directory)),
+            )
+            .map(|directory| {
+                (
+                    directory.join(format!("{}.dic", name)),
+                    directory.join(format!("{}.aff", name)),
+                )
+            })
+            .find(|(words, _)| words.is_file())
+    }
+
+    /// Reads a dictionary file, which may not be UTF-8 encoded.
+    fn read_text(file: &path::Path) -> io::Result<String> {
+        let bytes = fs::read(file)?;
+        Ok(match String::from_utf8(bytes) {
+            Ok(text) => text,
+            // Older dictionaries are mostly Latin-1, whose bytes are chars
+            Err(e) => e.into_bytes().into_iter().map(char::from).collect(),
+        })
+    }
+
+    /// Matches `suggestion` to the capitalization of `word`.
+    fn match_case(word: &str, suggestion: &str) -> String {
+        let mut chars = word.chars();
+        let first_upper = chars.next().is_some_and(char::is_uppercase);
+        if first_upper && chars.clone().count() > 0 && chars.all(char::is_uppercase) {
+            return suggestion.to_uppercase();
+        }
+        if first_upper {
+            let mut suggestion_chars = suggestion.chars();
+            if let Some(first) = suggestion_chars.next() {
+                return first.to_uppercase().chain(suggestion_chars).collect();
+            }
+        }
+        suggestion.to_string()
+    }
+}
+
+impl SpellCheckerPort for SpellChecker {
+    fn new(config: &SpellCheckerConfig) -> io::Result<Self> {
+        let (dic, aff) = Self::find_dictionary(&config.language).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No dictionary found for {}", config.language),
+            )
+        })?;
+        let affixes = match Self::read_text(&aff) {
+            Ok(content) => Affixes::parse(&content),
+            Err(_) => Affixes::default(),
+        };
+
+        let mut words = HashSet::new();
+        // The first line holds the number of words
+        for line in Self::read_text(&dic)?.lines().skip(1) {
+            let entry = line.split_whitespace().next().unwrap_or_default();
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            if !word.is_empty() {
+                affixes.expand(word, &affixes.split_flags(flags), &mut words);
+            }
+        }
+        words.extend(config.custom_dictionary.iter().cloned());
+
+        let personal_dictionary = Self::expand_home(&format!(
+            "~/.hunspell_{}",
+            config.language.replace('-', "_")
+        ));
+        if let Some(Ok(content)) = personal_dictionary.as_deref().map(fs::read_to_string) {
+            words.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|word| !word.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        Ok(Self {
+            words,
+            ignored: config.ignore_words.iter().cloned().collect(),
+            personal_dictionary,
+        })
+    }
+
+    fn check(&self, word: &str) -> bool {
+        let known = |word: &str| self.words.contains(word) || self.ignored.contains(word);
+        let lowercase = word.to_lowercase();
+        known(word)
+            // Capitalized and all caps words are fine if the word is
+            || known(&lowercase)
+            // Possessives of known words are too
+            || word
+                .strip_suffix("'s")
+                .is_some_and(|stem| known(stem) || known(&stem.to_lowercase()))
+    }
+
+    fn suggest(&self, word: &str, limit: usize) -> Vec<String> {
+        let lowercase = word.to_lowercase();
+        let length = lowercase.chars().count();
+        let mut candidates: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .filter(|candidate| {
+                candidate.chars().count().abs_diff(length) <= MAX_SUGGESTION_DISTANCE
+            })
+            .map(|candidate| {
+                (
+                    edit_distance(&lowercase, &candidate.to_lowercase()),
+                    candidate,
+                )
+            })
+            .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+            .collect();
+        candidates.sort();
+
+        let mut suggestions: Vec<String> = Vec::new();
+        for (_, candidate) in candidates {
+            let suggestion = Self::match_case(word, candidate);
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+            if suggestions.len() == limit {
+                break;
+            }
+        }
+        suggestions
+    }
+
+    fn add_word(&mut self, word: &str) -> io::Result<()> {
+        let file = self
+            .personal_dictionary
+            .as_ref()
+            .ok_or_else(|| io::Error::other("No home directory to keep added words in"))?;
+        let mut personal = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file)?;
+        writeln!(personal, "{}", word)?;
+        self.words.insert(word.to_string());
+        Ok(())
+    }
+}
@@ -15,7 +15,6 @@ use crate::ports::editor_buffer::EditorBufferPort;
 use crate::ports::terminal_io::{CursorEventTypes, EventReader, ReaderPort, WriterPort};
 use crate::ports::terminal_io::{EventReaderError, WriterError};
 use crossterm::{event, terminal};
-use std::io;
 use std::time::Duration;
 
 /// `CrosstermEventReader` is an implementation of `EventReader` using `crossterm`.
@@ -46,17 +45,33 @@ impl<E: EventReader> ReaderAdapter<E> {
 }
 
 impl<E: EventReader> ReaderPort for ReaderAdapter<E> {
-    /// Reads a key event from the terminal.
-    /// Continuously polls for an event and returns the first key event detected.
-    fn read_key(&self) -> Result<event::KeyEvent, EventReaderError> {
+    /// Reads an input event from the terminal.
+    /// Continuously polls for an event and returns the first key, mouse or resize event detected.
+    fn read_event(&self) -> Result<event::Event, EventReaderError> {
         loop {
             if self.event_reader.poll_event(Duration::from_millis(500))? {
-                if let event::Event::Key(event) = self.event_reader.read_event()? {
+                if let event @ (event::Event::Key(_)
+                | event::Event::Mouse(_)
+                | event::Event::Resize(..)) = self.event_reader.read_event()?
+                {
                     return Ok(event);
                 }
             }
         }
     }
+
+    /// Reads an input event from the terminal if one arrives within `timeout`.
+    fn poll_event(&self, timeout: Duration) -> Result<Option<event::Event>, EventReaderError> {
+        if !self.event_reader.poll_event(timeout)? {
+            return Ok(None);
+        }
+        match self.event_reader.read_event()? {
+            event @ (event::Event::Key(_) | event::Event::Mouse(_) | event::Event::Resize(..)) => {
+                Ok(Some(event))
+            }
+            _ => Ok(None),
+        }
+    }
 }
 
 /// Macro to queue a series of cursor events into a given `EditorBufferPort`.
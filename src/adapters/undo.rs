@@ -0,0 +1,102 @@
+use crate::ports::undo::{Snapshot, UndoHistoryPort};
+use std::collections::VecDeque;
+
+pub struct UndoHistory {
+    undo_stack: VecDeque<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    limit: usize,
+    in_change: bool,
+}
+
+impl UndoHistoryPort for UndoHistory {
+    fn new(limit: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            limit,
+            in_change: false,
+        }
+    }
+
+    fn record(&mut self, snapshot: Snapshot) {
+        self.undo_stack.push_back(snapshot);
+        // Drop the oldest changes once the configured limit is reached
+        while self.undo_stack.len() > self.limit {
+            self.undo_stack.pop_front();
+        }
+        self.redo_stack.clear();
+        self.in_change = true;
+    }
+
+    fn in_change(&self) -> bool {
+        self.in_change
+    }
+
+    fn end_change(&mut self) {
+        self.in_change = false;
+    }
+
+    fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        self.in_change = false;
+        let previous = self.undo_stack.pop_back()?;
+        self.redo_stack.push(current);
+        Some(previous)
+    }
+
+    fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        self.in_change = false;
+        let next = self.redo_stack.pop()?;
+        self.undo_stack.push_back(current);
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &str) -> Snapshot {
+        Snapshot {
+            rows: vec![text.to_string()],
+            cursor: (text.len(), 0),
+        }
+    }
+
+    #[test]
+    fn undoes_and_redoes_changes_in_order() {
+        let mut history = UndoHistory::new(10);
+        assert_eq!(history.undo(snapshot("")), None);
+        history.record(snapshot(""));
+        assert!(history.in_change());
+        history.end_change();
+        assert!(!history.in_change());
+        history.record(snapshot("a"));
+
+        assert_eq!(history.undo(snapshot("ab")), Some(snapshot("a")));
+        assert!(!history.in_change());
+        assert_eq!(history.undo(snapshot("a")), Some(snapshot("")));
+        assert_eq!(history.undo(snapshot("")), None);
+        assert_eq!(history.redo(snapshot("")), Some(snapshot("a")));
+        assert_eq!(history.redo(snapshot("a")), Some(snapshot("ab")));
+        assert_eq!(history.redo(snapshot("ab")), None);
+
+        // A new change drops what could be redone
+        assert_eq!(history.undo(snapshot("ab")), Some(snapshot("a")));
+        history.record(snapshot("a"));
+        assert_eq!(history.redo(snapshot("ac")), None);
+    }
+
+    #[test]
+    fn keeps_no_more_changes_than_the_undo_limit() {
+        let mut history = UndoHistory::new(2);
+        for text in ["", "a", "ab"] {
+            history.record(snapshot(text));
+            history.end_change();
+        }
+        assert_eq!(history.undo(snapshot("abc")), Some(snapshot("ab")));
+        assert_eq!(history.undo(snapshot("ab")), Some(snapshot("a")));
+        assert_eq!(history.undo(snapshot("a")), None);
+        assert_eq!(history.redo(snapshot("a")), Some(snapshot("ab")));
+        assert_eq!(history.redo(snapshot("ab")), Some(snapshot("abc")));
+    }
+}
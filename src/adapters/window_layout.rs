@@ -0,0 +1,524 @@
+use crate::ports::window::{
+    FocusDirection, Rect, SplitDirection, Window, WindowLayoutPort, WindowPlacement,
+};
+
+/// Smallest height a window can shrink to: one text row plus its status line.
+const MIN_HEIGHT: usize = 2;
+/// Smallest width a window can shrink to.
+const MIN_WIDTH: usize = 1;
+
+enum Node {
+    Leaf(Window),
+    Split {
+        direction: SplitDirection,
+        /// Share of the split's area given to `first`.
+        ratio: f64,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn contains(&self, id: usize) -> bool {
+        match self {
+            Node::Leaf(window) => window.id == id,
+            Node::Split { first, second, .. } => first.contains(id) || second.contains(id),
+        }
+    }
+
+    fn leaves<'a>(&'a self, out: &mut Vec<&'a Window>) {
+        match self {
+            Node::Leaf(window) => out.push(window),
+            Node::Split { first, second, .. } => {
+                first.leaves(out);
+                second.leaves(out);
+            }
+        }
+    }
+
+    fn leaves_mut<'a>(&'a mut self, out: &mut Vec<&'a mut Window>) {
+        match self {
+            Node::Leaf(window) => out.push(window),
+            Node::Split { first, second, .. } => {
+                first.leaves_mut(out);
+                second.leaves_mut(out);
+            }
+        }
+    }
+}
+
+/// Divides `total` cells in two according to `ratio`, leaving at least `min`
+/// cells on each side whenever there is room for it.
+fn split_size(total: usize, ratio: f64, min: usize) -> usize {
+    if total < 2 * min {
+        return total / 2;
+    }
+    ((total as f64 * ratio).round() as usize).clamp(min, total - min)
+}
+
+/// Splits `area` between the two children of a split, returning the areas of
+/// the first and second child and, for vertical splits, the separator column.
+fn split_area(area: Rect, direction: SplitDirection, ratio: f64) -> (Rect, Rect, Option<Rect>) {
+    match direction {
+        SplitDirection::Horizontal => {
+            let first_height = split_size(area.height, ratio, MIN_HEIGHT);
+            (
+                Rect {
+                    height: first_height,
+                    ..area
+                },
+                Rect {
+                    y: area.y + first_height,
+                    height: area.height - first_height,
+                    ..area
+                },
+                None,
+            )
+        }
+        SplitDirection::Vertical => {
+            // One column is reserved for the separator
+            let usable = area.width.saturating_sub(1);
+            let first_width = split_size(usable, ratio, MIN_WIDTH);
+            (
+                Rect {
+                    width: first_width,
+                    ..area
+                },
+                Rect {
+                    x: area.x + first_width + 1,
+                    width: usable - first_width,
+                    ..area
+                },
+                Some(Rect {
+                    x: area.x + first_width,
+                    width: 1,
+                    ..area
+                }),
+            )
+        }
+    }
+}
+
+pub struct WindowLayout {
+    root: Option<Node>,
+    focused: usize,
+}
+
+impl WindowLayout {
+    fn root(&self) -> &Node {
+        self.root.as_ref().expect("window layout has no root")
+    }
+
+    fn split_node(
+        node: Node,
+        target: usize,
+        direction: SplitDirection,
+        window: &mut Option<Window>,
+    ) -> Node {
+        match node {
+            Node::Leaf(existing) if existing.id == target => match window.take() {
+                Some(new) => Node::Split {
+                    direction,
+                    ratio: 0.5,
+                    first: Box::new(Node::Leaf(new)),
+                    second: Box::new(Node::Leaf(existing)),
+                },
+                None => Node::Leaf(existing),
+            },
+            Node::Split {
+                direction: split_direction,
+                ratio,
+                first,
+                second,
+            } => Node::Split {
+                direction: split_direction,
+                ratio,
+                first: Box::new(Self::split_node(*first, target, direction, window)),
+                second: Box::new(Self::split_node(*second, target, direction, window)),
+            },
+            leaf => leaf,
+        }
+    }
+
+    /// Removes every leaf for which `remove` returns true, collapsing splits
+    /// that are left with a single child.
+    fn prune(
+        node: Node,
+        remove: &dyn Fn(&Window) -> bool,
+        removed: &mut Vec<Window>,
+    ) -> Option<Node> {
+        match node {
+            Node::Leaf(window) if remove(&window) => {
+                removed.push(window);
+                None
+            }
+            Node::Leaf(window) => Some(Node::Leaf(window)),
+            Node::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => match (
+                Self::prune(*first, remove, removed),
+                Self::prune(*second, remove, removed),
+            ) {
+                (Some(first), Some(second)) => Some(Node::Split {
+                    direction,
+                    ratio,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (Some(node), None) | (None, Some(node)) => Some(node),
+                (None, None) => None,
+            },
+        }
+    }
+
+    fn place_node(node: &Node, area: Rect, placement: &mut WindowPlacement) {
+        match node {
+            Node::Leaf(window) => placement.windows.push((window.id, area)),
+            Node::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, second_area, separator) = split_area(area, *direction, *ratio);
+                Self::place_node(first, first_area, placement);
+                Self::place_node(second, second_area, placement);
+                placement.separators.extend(separator);
+            }
+        }
+    }
+
+    /// Adjusts the innermost split along `direction` that contains `target`.
+    /// Returns `None` if `target` is not in `node`, otherwise whether a split
+    /// has been adjusted yet.
+    fn resize_node(
+        node: &mut Node,
+        target: usize,
+        direction: SplitDirection,
+        delta: isize,
+        area: Rect,
+    ) -> Option<bool> {
+        let Node::Split {
+            direction: split_direction,
+            ratio,
+            first,
+            second,
+        } = node
+        else {
+            return node.contains(target).then_some(false);
+        };
+
+        let (first_area, second_area, _) = split_area(area, *split_direction, *ratio);
+        let in_first = first.contains(target);
+        let adjusted = if in_first {
+            Self::resize_node(first, target, direction, delta, first_area)?
+        } else {
+            Self::resize_node(second, target, direction, delta, second_area)?
+        };
+        if adjusted || *split_direction != direction {
+            return Some(adjusted);
+        }
+
+        let total = match direction {
+            SplitDirection::Horizontal => area.height,
+            SplitDirection::Vertical => area.width.saturating_sub(1),
+        };
+        if total > 0 {
+            let step = delta as f64 / total as f64;
+            *ratio = (*ratio + if in_first { step } else { -step }).clamp(0.0, 1.0);
+        }
+        Some(true)
+    }
+}
+
+impl WindowLayoutPort for WindowLayout {
+    fn new(root: Window) -> Self {
+        Self {
+            focused: root.id,
+            root: Some(Node::Leaf(root)),
+        }
+    }
+
+    fn split(&mut self, direction: SplitDirection, window: Window) {
+        let id = window.id;
+        let root = self.root.take().expect("window layout has no root");
+        self.root = Some(Self::split_node(
+            root,
+            self.focused,
+            direction,
+            &mut Some(window),
+        ));
+        self.focused = id;
+    }
+
+    fn close(&mut self) -> Option<Window> {
+        if self.windows().len() < 2 {
+            return None;
+        }
+        let next_focus = {
+            let windows = self.windows();
+            let index = windows.iter().position(|w| w.id == self.focused)?;
+            // Focus moves to the following window, or the previous one at the end
+            windows
+                .get(index + 1)
+                .or_else(|| windows.get(index.wrapping_sub(1)))?
+                .id
+        };
+        let focused = self.focused;
+        let mut removed = Vec::new();
+        let root = self.root.take().expect("window layout has no root");
+        self.root = Self::prune(root, &|window| window.id == focused, &mut removed);
+        self.focused = next_focus;
+        removed.pop()
+    }
+
+    fn only(&mut self) -> Vec<Window> {
+        let focused = self.focused;
+        let mut removed = Vec::new();
+        let root = self.root.take().expect("window layout has no root");
+        self.root = Self::prune(root, &|window| window.id != focused, &mut removed);
+        removed
+    }
+
+    fn focus(&mut self, direction: FocusDirection, area: Rect) -> bool {
+        let placement = self.place(area);
+        let Some(&(_, current)) = placement.windows.iter().find(|(id, _)| *id == self.focused)
+        else {
+            return false;
+        };
+
+        let overlaps = |a: (usize, usize), b: (usize, usize)| a.0 < b.1 && b.0 < a.1;
+        let rows = |r: &Rect| (r.y, r.y + r.height);
+        let cols = |r: &Rect| (r.x, r.x + r.width);
+
+        let target = placement
+            .windows
+            .iter()
+            .filter(|(id, _)| *id != self.focused)
+            .filter_map(|(id, rect)| {
+                let distance = match direction {
+                    FocusDirection::Left if overlaps(rows(rect), rows(&current)) => {
+                        current.x.checked_sub(rect.x + rect.width)
+                    }
+                    FocusDirection::Right if overlaps(rows(rect), rows(&current)) => {
+                        rect.x.checked_sub(current.x + current.width)
+                    }
+                    FocusDirection::Up if overlaps(cols(rect), cols(&current)) => {
+                        current.y.checked_sub(rect.y + rect.height)
+                    }
+                    FocusDirection::Down if overlaps(cols(rect), cols(&current)) => {
+                        rect.y.checked_sub(current.y + current.height)
+                    }
+                    _ => None,
+                }?;
+                // Prefer the closest window, then the one best aligned with the current one
+                let alignment = rect.x.abs_diff(current.x) + rect.y.abs_diff(current.y);
+                Some((distance, alignment, *id))
+            })
+            .min();
+
+        match target {
+            Some((_, _, id)) => {
+                self.focused = id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn focus_next(&mut self) {
+        let windows = self.windows();
+        if let Some(index) = windows.iter().position(|w| w.id == self.focused) {
+            self.focused = windows[(index + 1) % windows.len()].id;
+        }
+    }
+
+    fn focus_id(&mut self, id: usize) -> bool {
+        if self.root().contains(id) {
+            self.focused = id;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn resize(&mut self, direction: SplitDirection, delta: isize, area: Rect) {
+        let focused = self.focused;
+        if let Some(root) = self.root.as_mut() {
+            Self::resize_node(root, focused, direction, delta, area);
+        }
+    }
+
+    fn place(&self, area: Rect) -> WindowPlacement {
+        let mut placement = WindowPlacement {
+            windows: Vec::new(),
+            separators: Vec::new(),
+        };
+        Self::place_node(self.root(), area, &mut placement);
+        placement
+    }
+
+    fn focused(&self) -> &Window {
+        self.windows()
+            .into_iter()
+            .find(|window| window.id == self.focused)
+            .expect("focused window is not in the layout")
+    }
+
+    fn focused_mut(&mut self) -> &mut Window {
+        let focused = self.focused;
+        self.windows_mut()
+            .into_iter()
+            .find(|window| window.id == focused)
+            .expect("focused window is not in the layout")
+    }
+
+    fn windows(&self) -> Vec<&Window> {
+        let mut windows = Vec::new();
+        self.root().leaves(&mut windows);
+        windows
+    }
+
+    fn windows_mut(&mut self) -> Vec<&mut Window> {
+        let mut windows = Vec::new();
+        if let Some(root) = self.root.as_mut() {
+            root.leaves_mut(&mut windows);
+        }
+        windows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::cursor::CursorController;
+    use crate::ports::cursor::CursorControllerPort;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 81,
+        height: 24,
+    };
+
+    fn window(id: usize) -> Window {
+        Window {
+            id,
+            buffer_id: 0,
+            cursor_controller: Box::new(CursorController::new((80, 22))),
+            selection_anchor: None,
+        }
+    }
+
+    fn rect(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns window 2 on the left of window 1, with window 3 above window 2,
+    /// and window 3 focused.
+    fn three_windows() -> WindowLayout {
+        let mut layout = WindowLayout::new(window(1));
+        layout.split(SplitDirection::Vertical, window(2));
+        layout.split(SplitDirection::Horizontal, window(3));
+        layout
+    }
+
+    fn ids(layout: &WindowLayout) -> Vec<usize> {
+        layout.windows().iter().map(|window| window.id).collect()
+    }
+
+    #[test]
+    fn splits_sizes_keeping_the_smallest_size_on_each_side() {
+        assert_eq!(split_size(24, 0.5, MIN_HEIGHT), 12);
+        assert_eq!(split_size(24, 0.0, MIN_HEIGHT), 2);
+        assert_eq!(split_size(24, 1.0, MIN_HEIGHT), 22);
+        assert_eq!(split_size(3, 0.9, MIN_HEIGHT), 1);
+    }
+
+    #[test]
+    fn splits_areas_with_a_separator_between_side_by_side_windows() {
+        let (top, bottom, separator) = split_area(AREA, SplitDirection::Horizontal, 0.5);
+        assert_eq!((top, bottom), (rect(0, 0, 81, 12), rect(0, 12, 81, 12)));
+        assert_eq!(separator, None);
+        let (left, right, separator) = split_area(AREA, SplitDirection::Vertical, 0.5);
+        assert_eq!((left, right), (rect(0, 0, 40, 24), rect(41, 0, 40, 24)));
+        assert_eq!(separator, Some(rect(40, 0, 1, 24)));
+    }
+
+    #[test]
+    fn places_nested_splits() {
+        let layout = three_windows();
+        assert_eq!(ids(&layout), vec![3, 2, 1]);
+        assert_eq!(layout.focused().id, 3);
+        let placement = layout.place(AREA);
+        assert_eq!(
+            placement.windows,
+            vec![
+                (3, rect(0, 0, 40, 12)),
+                (2, rect(0, 12, 40, 12)),
+                (1, rect(41, 0, 40, 24)),
+            ]
+        );
+        assert_eq!(placement.separators, vec![rect(40, 0, 1, 24)]);
+    }
+
+    #[test]
+    fn collapses_splits_left_with_one_window() {
+        let mut layout = three_windows();
+        assert_eq!(layout.close().map(|window| window.id), Some(3));
+        assert_eq!(layout.focused().id, 2);
+        let placement = layout.place(AREA);
+        assert_eq!(
+            placement.windows,
+            vec![(2, rect(0, 0, 40, 24)), (1, rect(41, 0, 40, 24))]
+        );
+
+        let mut layout = three_windows();
+        let closed: Vec<usize> = layout.only().iter().map(|window| window.id).collect();
+        assert_eq!(closed, vec![2, 1]);
+        let placement = layout.place(AREA);
+        assert_eq!(placement.windows, vec![(3, AREA)]);
+        assert!(placement.separators.is_empty());
+        assert!(layout.close().is_none());
+    }
+
+    #[test]
+    fn clamps_resizing_to_the_area_of_the_split() {
+        let mut layout = WindowLayout::new(window(1));
+        layout.split(SplitDirection::Vertical, window(2));
+        layout.resize(SplitDirection::Vertical, 100, AREA);
+        assert_eq!(layout.place(AREA).windows[0], (2, rect(0, 0, 79, 24)));
+        layout.resize(SplitDirection::Vertical, -1000, AREA);
+        assert_eq!(layout.place(AREA).windows[0], (2, rect(0, 0, 1, 24)));
+        // The split gives the window nothing, only shown as the smallest width
+        layout.resize(SplitDirection::Vertical, 3, AREA);
+        assert_eq!(layout.place(AREA).windows[0], (2, rect(0, 0, 3, 24)));
+        // There is no split along the other direction to resize
+        layout.resize(SplitDirection::Horizontal, 5, AREA);
+        assert_eq!(layout.place(AREA).windows[0], (2, rect(0, 0, 3, 24)));
+    }
+
+    #[test]
+    fn focuses_the_nearest_window_in_a_direction() {
+        let mut layout = three_windows();
+        assert!(!layout.focus(FocusDirection::Left, AREA));
+        assert!(!layout.focus(FocusDirection::Up, AREA));
+        assert!(layout.focus(FocusDirection::Down, AREA));
+        assert_eq!(layout.focused().id, 2);
+        assert!(!layout.focus(FocusDirection::Down, AREA));
+        assert!(layout.focus(FocusDirection::Right, AREA));
+        assert_eq!(layout.focused().id, 1);
+        assert!(!layout.focus(FocusDirection::Right, AREA));
+        // The best aligned of the windows as near is picked
+        assert!(layout.focus(FocusDirection::Left, AREA));
+        assert_eq!(layout.focused().id, 3);
+    }
+}
@@ -0,0 +1,500 @@
+//! `actions.rs`
+//!
+//! The actions of the editor and the keys bound to them in each mode: the
+//! one table keys are dispatched from, and the command palette, the help
+//! and the key hints list.
+
+use crate::ports::config::Config;
+use crate::ports::window::FocusDirection;
+use crate::toolshed::keys;
+use crossterm::event;
+
+/// The modes keys are bound in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMode {
+    Normal,
+    Insert,
+}
+
+impl KeyMode {
+    pub const ALL: [KeyMode; 2] = [KeyMode::Normal, KeyMode::Insert];
+
+    /// Returns the name of the mode, as scripts bind keys in it.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyMode::Normal => "normal",
+            KeyMode::Insert => "insert",
+        }
+    }
+}
+
+/// What a built-in action does when it runs no command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Moves the cursor, the way the domain takes a key such as `j`.
+    Move(event::KeyCode),
+    LineStart,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    PageUp,
+    PageDown,
+    InsertMode,
+    /// Leaves insert mode.
+    NormalMode,
+    /// Completes the word before the cursor, in insert mode.
+    Complete,
+    Undo,
+    Redo,
+    Paste,
+    /// Copies the selection, if any.
+    Copy,
+    /// Deletes the selection, if any.
+    Cut,
+    ClearSelection,
+    /// Starts typing a `:` command.
+    CommandLine,
+    /// Follows the link under the cursor on a help page.
+    FollowLink,
+    Exit,
+    NextWindow,
+    FocusWindow(FocusDirection),
+    /// Moves to the next (`true`) or previous changed rows.
+    Hunk(bool),
+    /// Moves to the next (`true`) or previous misspelled word.
+    Misspelling(bool),
+    SpellSuggest,
+    SpellAdd,
+    JumpBack,
+    /// Runs what a script bound to the keys of the action.
+    Script,
+}
+
+/// The built-in actions of normal mode besides moving the cursor: their
+/// name, keys, command and what they do otherwise. Commands ending in a
+/// space still need an argument. The actions `[cursor_keymaps]` has keys
+/// for get them from there.
+const ACTIONS: &[(&str, &str, &str, Option<Op>)] = &[
+    ("Start of line", "", "", Some(Op::LineStart)),
+    ("End of line", "", "", Some(Op::LineEnd)),
+    ("First line", "", "", Some(Op::FirstLine)),
+    ("Last line", "", "", Some(Op::LastLine)),
+    ("Page up", "", "", Some(Op::PageUp)),
+    ("Page down", "", "", Some(Op::PageDown)),
+    ("Insert mode", "", "", Some(Op::InsertMode)),
+    ("Undo", "u", "", Some(Op::Undo)),
+    ("Redo", "<C-r>", "", Some(Op::Redo)),
+    ("Copy selection", "", "", Some(Op::Copy)),
+    ("Cut selection", "", "", Some(Op::Cut)),
+    ("Paste", "", "", Some(Op::Paste)),
+    ("Clear selection", "<Esc>", "", Some(Op::ClearSelection)),
+    ("Command line", ":", "", Some(Op::CommandLine)),
+    ("Open file", "", "e ", None),
+    ("New buffer", "", "enew", None),
+    ("Save file", "", "w", None),
+    ("Save file as", "", "w ", None),
+    ("Save and quit", "", "wq", None),
+    ("Quit", "", "q", None),
+    ("Exit barn", "<C-q>", "", Some(Op::Exit)),
+    ("Next buffer", "", "bn", None),
+    ("Previous buffer", "", "bp", None),
+    ("List buffers", "", "ls", None),
+    ("Close buffer", "", "bd", None),
+    ("Split window", "<C-w>s", "split", None),
+    ("Split window vertically", "<C-w>v", "vsplit", None),
+    ("Next window", "<C-w>w", "", Some(Op::NextWindow)),
+    ("Close window", "<C-w>c", "close", None),
+    ("Close other windows", "<C-w>o", "only", None),
+    ("Taller window", "<C-w>+", "resize +1", None),
+    ("Shorter window", "<C-w>-", "resize -1", None),
+    ("Wider window", "<C-w>>", "vertical resize +1", None),
+    ("Narrower window", "<C-w><lt>", "vertical resize -1", None),
+    ("New tab", "", "tabnew", None),
+    ("Close tab", "", "tabclose", None),
+    ("Next tab", "gt", "tabnext", None),
+    ("Previous tab", "gT", "tabprevious", None),
+    ("Toggle file explorer", "<C-e>", "Lex", None),
+    ("Find file", "<C-p>", "Files", None),
+    ("Search in files", "<C-f>", "grep", None),
+    ("Git status", "", "Git", None),
+    ("Git commit", "", "Gcommit", None),
+    ("Toggle git blame", "", "blame", None),
+    ("Open file at revision", "", "Gedit ", None),
+    ("Next change", "]c", "", Some(Op::Hunk(true))),
+    ("Previous change", "[c", "", Some(Op::Hunk(false))),
+    (
+        "Next misspelled word",
+        "]s",
+        "",
+        Some(Op::Misspelling(true)),
+    ),
+    (
+        "Previous misspelled word",
+        "[s",
+        "",
+        Some(Op::Misspelling(false)),
+    ),
+    ("Suggest spelling", "z=", "", Some(Op::SpellSuggest)),
+    ("Add word to dictionary", "zg", "", Some(Op::SpellAdd)),
+    ("Toggle Markdown preview", "", "MarkdownPreview", None),
+    ("Format buffer", "", "Format", None),
+    ("Go to definition", "gd", "Definition", None),
+    ("Find references", "gr", "References", None),
+    ("Show hover", "K", "Hover", None),
+    ("Jump back", "<C-o>", "", Some(Op::JumpBack)),
+    ("Rename symbol", "", "Rename ", None),
+    ("Code actions", "ga", "CodeAction", None),
+    ("Command palette", "<C-k>", "Commands", None),
+    ("Follow help link", "<CR>", "", Some(Op::FollowLink)),
+    ("Help", "", "help", None),
+    ("Help on a topic", "", "help ", None),
+];
+
+/// The built-in actions of insert mode, as in `ACTIONS`.
+const INSERT_ACTIONS: &[(&str, &str, &str, Option<Op>)] = &[
+    ("Normal mode", "", "", Some(Op::NormalMode)),
+    ("Complete word", "<C-n>", "", Some(Op::Complete)),
+];
+
+/// More keys running the actions of normal mode, by the name of the
+/// action, besides the keys listed with it.
+const MORE_KEYS: &[(&str, &str)] = &[
+    ("<Left>", "Move left"),
+    ("<Down>", "Move down"),
+    ("<Up>", "Move up"),
+    ("<Right>", "Move right"),
+    ("<Home>", "Start of line"),
+    ("<End>", "End of line"),
+    ("<PageUp>", "Page up"),
+    ("<PageDown>", "Page down"),
+    ("<C-w><Left>", "Window to the left"),
+    ("<C-w><Down>", "Window below"),
+    ("<C-w><Up>", "Window above"),
+    ("<C-w><Right>", "Window to the right"),
+    ("<C-w>q", "Close window"),
+];
+
+/// Something the editor does, as listed in the command palette.
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub name: String,
+    /// The mode its keys are typed in.
+    pub mode: KeyMode,
+    /// The keys running it, if any.
+    pub keys: Vec<event::KeyEvent>,
+    /// The command line running it, without the `:`, if any.
+    pub command: Option<String>,
+    /// What it does when it has no command.
+    pub op: Option<Op>,
+}
+
+impl Action {
+    /// Returns the keys running the action, named as scripts bind them.
+    pub fn key_names(&self) -> String {
+        self.keys.iter().filter_map(keys::key_name).collect()
+    }
+
+    /// Returns the name, keys and command of the action, in columns.
+    pub fn label(&self) -> String {
+        let command = self
+            .command
+            .as_ref()
+            .map(|command| format!(":{}", command.trim_end()))
+            .unwrap_or_default();
+        format!("{:<28}{:<10}{}", self.name, self.key_names(), command)
+            .trim_end()
+            .to_string()
+    }
+
+    /// Returns whether the command still needs an argument, typed on the
+    /// command line before it runs.
+    pub fn needs_argument(&self) -> bool {
+        self.command
+            .as_ref()
+            .is_some_and(|command| command.ends_with(' '))
+    }
+}
+
+/// What the keys typed so far run.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// The action of that index.
+    Run(usize),
+    /// Nothing yet, as they start longer sequences.
+    Pending,
+    Unbound,
+}
+
+/// The actions of the editor, and every sequence of keys running one.
+pub struct Keymap {
+    pub actions: Vec<Action>,
+    /// The mode and keys of each sequence, and the index of the action it runs.
+    bindings: Vec<(KeyMode, Vec<event::KeyEvent>, usize)>,
+}
+
+impl Keymap {
+    /// Builds the built-in actions, with the keys of `config`, then one
+    /// action per extension command in `extension_commands`, and one per
+    /// key `script_keys` binds in a mode. Keys bound by scripts replace
+    /// the sequences they start.
+    pub fn new(
+        config: &Config,
+        extension_commands: Vec<String>,
+        script_keys: Vec<(KeyMode, String)>,
+    ) -> Self {
+        let keymaps = &config.cursor_keymaps;
+        let directions = [
+            ("Move left", "Window to the left", keymaps.left, 'h'),
+            ("Move down", "Window below", keymaps.down, 'j'),
+            ("Move up", "Window above", keymaps.up, 'k'),
+            ("Move right", "Window to the right", keymaps.right, 'l'),
+        ];
+        let char_key =
+            |ch| event::KeyEvent::new(event::KeyCode::Char(ch), event::KeyModifiers::NONE);
+        let ctrl_w = event::KeyEvent::new(event::KeyCode::Char('w'), event::KeyModifiers::CONTROL);
+
+        let movement = directions.iter().map(|&(name, _, ch, direction)| Action {
+            name: name.into(),
+            mode: KeyMode::Normal,
+            keys: vec![char_key(ch)],
+            command: None,
+            op: Some(Op::Move(event::KeyCode::Char(direction))),
+        });
+        let windows = directions.iter().map(|&(_, name, ch, direction)| Action {
+            name: name.into(),
+            mode: KeyMode::Normal,
+            keys: vec![ctrl_w, char_key(ch)],
+            command: None,
+            op: Some(Op::FocusWindow(match direction {
+                'h' => FocusDirection::Left,
+                'j' => FocusDirection::Down,
+                'k' => FocusDirection::Up,
+                _ => FocusDirection::Right,
+            })),
+        });
+        let built_in = [
+            (KeyMode::Normal, ACTIONS),
+            (KeyMode::Insert, INSERT_ACTIONS),
+        ]
+        .into_iter()
+        .flat_map(|(mode, actions)| {
+            actions.iter().map(move |(name, keys, command, op)| Action {
+                name: name.to_string(),
+                mode,
+                keys: keys::parse_keys(keys).unwrap_or_default(),
+                command: Some(command.to_string()).filter(|command| !command.is_empty()),
+                op: *op,
+            })
+        });
+        let extensions = extension_commands.into_iter().map(|name| Action {
+            name: format!("Extension: {}", name),
+            mode: KeyMode::Normal,
+            keys: Vec::new(),
+            command: Some(name),
+            op: None,
+        });
+        let mut actions: Vec<Action> = movement
+            .chain(windows)
+            .chain(built_in)
+            .chain(extensions)
+            .collect();
+
+        // A value starting with `:` is the command to run instead
+        let configured = [
+            ("Page up", &keymaps.page_up),
+            ("Page down", &keymaps.page_down),
+            ("Start of line", &keymaps.start_of_line),
+            ("End of line", &keymaps.end_of_line),
+            ("First line", &keymaps.first_line),
+            ("Last line", &keymaps.last_line),
+            ("Save file", &keymaps.save),
+            ("Save file as", &keymaps.save_as),
+            ("Quit", &keymaps.quit),
+            ("Insert mode", &keymaps.insert_mode),
+            ("Normal mode", &keymaps.exit_insert_mode),
+            ("Copy selection", &keymaps.copy),
+            ("Cut selection", &keymaps.cut),
+            ("Paste", &keymaps.paste),
+        ];
+        for (name, value) in configured {
+            let Some(action) = actions.iter_mut().find(|action| action.name == name) else {
+                continue;
+            };
+            match value.strip_prefix(':') {
+                Some(command) => action.command = Some(command.to_string()),
+                None => action.keys = keys::parse_config_keys(value).unwrap_or_default(),
+            }
+        }
+
+        let index_of = |name: &str| actions.iter().position(|action| action.name == name);
+        let more: Vec<_> = MORE_KEYS
+            .iter()
+            .filter_map(|(keys, name)| {
+                Some((KeyMode::Normal, keys::parse_keys(keys)?, index_of(name)?))
+            })
+            .collect();
+        let mut bindings: Vec<_> = actions
+            .iter()
+            .enumerate()
+            .filter(|(_, action)| !action.keys.is_empty())
+            .map(|(index, action)| (action.mode, action.keys.clone(), index))
+            .chain(more)
+            .collect();
+
+        for (mode, key) in script_keys {
+            let Some(parsed) = keys::parse_key(&key) else {
+                continue;
+            };
+            bindings.retain(|(bound_in, keys, _)| {
+                *bound_in != mode || keys::key_name(&keys[0]).as_ref() != Some(&key)
+            });
+            bindings.push((mode, vec![parsed], actions.len()));
+            actions.push(Action {
+                name: "Script binding".into(),
+                mode,
+                keys: vec![parsed],
+                command: None,
+                op: Some(Op::Script),
+            });
+        }
+        Self { actions, bindings }
+    }
+
+    /// Returns what the keys `typed` in `mode` run. A sequence bound to an
+    /// action runs it even if longer ones start with it.
+    pub fn lookup(&self, mode: KeyMode, typed: &[event::KeyEvent]) -> Lookup {
+        let typed = names(typed);
+        let mut lookup = Lookup::Unbound;
+        for (_, keys, index) in self.bindings.iter().filter(|binding| binding.0 == mode) {
+            let keys = names(keys);
+            if keys == typed {
+                return Lookup::Run(*index);
+            }
+            if keys.starts_with(&typed) {
+                lookup = Lookup::Pending;
+            }
+        }
+        lookup
+    }
+
+    /// Returns the sequences of `mode` going on after the keys `typed`: the
+    /// keys left to type, and the name of the action they run.
+    pub fn continuations(&self, mode: KeyMode, typed: &[event::KeyEvent]) -> Vec<(String, String)> {
+        let typed = names(typed);
+        self.bindings
+            .iter()
+            .filter(|binding| binding.0 == mode)
+            .map(|(_, keys, index)| (names(keys), *index))
+            .filter(|(keys, _)| keys.len() > typed.len() && keys.starts_with(&typed))
+            .map(|(keys, index)| {
+                (
+                    keys[typed.len()..].concat(),
+                    self.actions[index].name.clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Returns the names of `keys`, which tell apart the keys bindings do.
+fn names(keys: &[event::KeyEvent]) -> Vec<String> {
+    keys.iter()
+        .map(|key| keys::key_name(key).unwrap_or_default())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a keymap moving the cursor with `directions`, in the order
+    /// left, down, up and right, with `more` options of `[cursor_keymaps]`.
+    fn keymap(directions: &str, more: &str, script_keys: Vec<(KeyMode, String)>) -> Keymap {
+        let chars: Vec<char> = directions.chars().collect();
+        let text = format!(
+            "[cursor_keymaps]\nleft = '{}'\ndown = '{}'\nup = '{}'\nright = '{}'\n{}",
+            chars[0], chars[1], chars[2], chars[3], more
+        );
+        let config: Config = toml::from_str(&text).unwrap();
+        Keymap::new(&config, vec!["Rot13".into()], script_keys)
+    }
+
+    fn typed(sequence: &str) -> Vec<event::KeyEvent> {
+        keys::parse_keys(sequence).unwrap()
+    }
+
+    fn run<'a>(keymap: &'a Keymap, mode: KeyMode, sequence: &str) -> Option<&'a str> {
+        match keymap.lookup(mode, &typed(sequence)) {
+            Lookup::Run(index) => Some(&keymap.actions[index].name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn looks_up_sequences_from_the_bindings() {
+        let keymap = keymap("hjkl", "", Vec::new());
+        let normal = KeyMode::Normal;
+        assert_eq!(run(&keymap, normal, "j"), Some("Move down"));
+        assert_eq!(run(&keymap, normal, "<Down>"), Some("Move down"));
+        assert_eq!(run(&keymap, normal, "gd"), Some("Go to definition"));
+        assert_eq!(run(&keymap, normal, "gg"), Some("First line"));
+        assert_eq!(run(&keymap, normal, "<C-w>q"), Some("Close window"));
+        assert_eq!(keymap.lookup(normal, &typed("g")), Lookup::Pending);
+        assert_eq!(keymap.lookup(normal, &typed("gx")), Lookup::Unbound);
+        assert_eq!(run(&keymap, KeyMode::Insert, "<Esc>"), Some("Normal mode"));
+        assert_eq!(run(&keymap, KeyMode::Insert, "j"), None);
+        let rot13 = keymap
+            .actions
+            .iter()
+            .find(|action| action.name == "Extension: Rot13");
+        assert_eq!(rot13.unwrap().command.as_deref(), Some("Rot13"));
+    }
+
+    #[test]
+    fn binds_window_keys_to_the_configured_directions() {
+        let keymap = keymap("asdw", "", Vec::new());
+        assert_eq!(run(&keymap, KeyMode::Normal, "a"), Some("Move left"));
+        assert_eq!(run(&keymap, KeyMode::Normal, "h"), None);
+        assert_eq!(
+            run(&keymap, KeyMode::Normal, "<C-w>a"),
+            Some("Window to the left")
+        );
+        let hints = keymap.continuations(KeyMode::Normal, &typed("<C-w>"));
+        assert!(hints.contains(&("w".into(), "Window to the right".into())));
+        assert!(!hints.iter().any(|(keys, _)| keys == "l"));
+    }
+
+    #[test]
+    fn reads_the_keys_and_commands_of_the_config() {
+        let more = "page_up = 'ctrl-u'\nfirst_line = 'go'\nexit_insert_mode = 'jk'\nsave = '<C-s>'\nquit = ':q!'\n";
+        let keymap = keymap("hjkl", more, Vec::new());
+        assert_eq!(run(&keymap, KeyMode::Normal, "<C-u>"), Some("Page up"));
+        assert_eq!(run(&keymap, KeyMode::Normal, "<PageUp>"), Some("Page up"));
+        assert_eq!(run(&keymap, KeyMode::Normal, "gg"), None);
+        assert_eq!(run(&keymap, KeyMode::Normal, "<C-s>"), Some("Save file"));
+        let hints = keymap.continuations(KeyMode::Normal, &typed("g"));
+        assert!(hints.contains(&("o".into(), "First line".into())));
+        let hints = keymap.continuations(KeyMode::Insert, &typed("j"));
+        assert_eq!(hints, vec![("k".into(), "Normal mode".into())]);
+        let quit = keymap.actions.iter().find(|action| action.name == "Quit");
+        assert_eq!(quit.unwrap().command.as_deref(), Some("q!"));
+    }
+
+    #[test]
+    fn lets_keys_bound_by_scripts_replace_the_sequences_they_start() {
+        let script_keys = vec![
+            (KeyMode::Normal, "g".to_string()),
+            (KeyMode::Insert, "<C-t>".to_string()),
+        ];
+        let keymap = keymap("hjkl", "", script_keys);
+        assert_eq!(run(&keymap, KeyMode::Normal, "g"), Some("Script binding"));
+        assert!(keymap
+            .continuations(KeyMode::Normal, &typed("g"))
+            .is_empty());
+        assert_eq!(run(&keymap, KeyMode::Normal, "<C-t>"), None);
+        assert_eq!(
+            run(&keymap, KeyMode::Insert, "<C-t>"),
+            Some("Script binding")
+        );
+    }
+}
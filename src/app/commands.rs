@@ -0,0 +1,240 @@
+//! `commands.rs`
+//!
+//! Parsing of the ex-style commands typed after `:` in normal mode.
+
+use crate::ports::window::SplitDirection;
+use std::path;
+
+/// A command entered on the command line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// `:e <file>` - open a file, or switch to it if it is already open.
+    Edit(path::PathBuf),
+    /// `:enew` - open an empty, unnamed buffer.
+    EditNew,
+    /// `:bn` - switch to the next buffer.
+    BufferNext,
+    /// `:bp` - switch to the previous buffer.
+    BufferPrevious,
+    /// `:b <id>` - switch to the buffer with the given id.
+    Buffer(usize),
+    /// `:bd[!]` - close the current buffer.
+    BufferDelete { force: bool },
+    /// `:ls` - list the open buffers.
+    ListBuffers,
+    /// `:w [file]` - save the current buffer.
+    Write(Option<path::PathBuf>),
+    /// `:q[!]` - close the focused window, quitting with the last one.
+    Quit { force: bool },
+    /// `:wq` - save the current buffer, then behave like `:q`.
+    WriteQuit,
+    /// `:sp [file]` / `:vs [file]` - split the focused window.
+    Split {
+        direction: SplitDirection,
+        file: Option<path::PathBuf>,
+    },
+    /// `:close` - close the focused window.
+    Close,
+    /// `:only` - close every window but the focused one.
+    Only,
+    /// `:resize +N|-N` / `:vertical resize +N|-N` - grow or shrink the
+    /// focused window.
+    Resize {
+        direction: SplitDirection,
+        delta: isize,
+    },
+    /// `:tabnew [file]` - open a new tab page.
+    TabNew(Option<path::PathBuf>),
+    /// `:tabclose` - close the current tab page.
+    TabClose,
+    /// `:tabnext` - switch to the next tab page.
+    TabNext,
+    /// `:tabprevious` - switch to the previous tab page.
+    TabPrevious,
+    /// `:Ex [dir]` - open the file explorer, optionally on `dir`.
+    Explore(Option<path::PathBuf>),
+    /// `:Lex` - open the file explorer, or close it if it has focus.
+    ExploreToggle,
+    /// `:Files` - fuzzy find a file in the working directory.
+    Files,
+    /// `:grep [pattern]` - search the files in the working directory.
+    Grep(Option<String>),
+    /// `:blame` - show or hide who last changed each row.
+    Blame,
+    /// `:Gedit <revision>` - open the current file as of a git revision,
+    /// read-only.
+    Revision(String),
+    /// `:Git` - list the changes in the repository to stage them.
+    GitStatus,
+    /// `:Gcommit` - write a message and commit the staged changes.
+    Commit,
+    /// `:MarkdownPreview` - show or hide the rendered Markdown pane.
+    MarkdownPreview,
+    /// `:Format` - run the current buffer through its formatter.
+    Format,
+    /// `:Definition` - jump to where the symbol under the cursor is defined.
+    Definition,
+    /// `:References` - list the places the symbol under the cursor is used.
+    References,
+    /// `:Hover` - show the type and docs of the symbol under the cursor.
+    Hover,
+    /// `:Rename <name>` - rename the symbol under the cursor everywhere.
+    Rename(String),
+    /// `:CodeAction` - pick a code action, such as a fix, to run.
+    CodeAction,
+    /// `:Commands` - pick an action from the command palette to run.
+    Commands,
+    /// `:help [topic]` - open the help page on `topic`, or the index.
+    Help(Option<String>),
+    /// Any other command, run by the extension that has it.
+    Extension {
+        name: String,
+        argument: Option<String>,
+    },
+}
+
+impl Command {
+    /// Parses a command line (without the leading `:`).
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (line, None),
+        };
+
+        match (name, argument) {
+            ("e" | "edit", Some(file)) => Ok(Command::Edit(file.into())),
+            ("e" | "edit", None) => Err("No file name".into()),
+            ("enew", None) => Ok(Command::EditNew),
+            ("bn" | "bnext", None) => Ok(Command::BufferNext),
+            ("bp" | "bprevious", None) => Ok(Command::BufferPrevious),
+            ("b" | "buffer", Some(id)) => id
+                .parse()
+                .map(Command::Buffer)
+                .map_err(|_| format!("Invalid buffer number: {}", id)),
+            ("bd" | "bdelete", None) => Ok(Command::BufferDelete { force: false }),
+            ("bd!" | "bdelete!", None) => Ok(Command::BufferDelete { force: true }),
+            ("ls" | "buffers", None) => Ok(Command::ListBuffers),
+            ("w" | "write", file) => Ok(Command::Write(file.map(Into::into))),
+            ("q" | "quit", None) => Ok(Command::Quit { force: false }),
+            ("q!" | "quit!", None) => Ok(Command::Quit { force: true }),
+            ("wq" | "x", None) => Ok(Command::WriteQuit),
+            ("sp" | "split", file) => Ok(Command::Split {
+                direction: SplitDirection::Horizontal,
+                file: file.map(Into::into),
+            }),
+            ("vs" | "vsplit", file) => Ok(Command::Split {
+                direction: SplitDirection::Vertical,
+                file: file.map(Into::into),
+            }),
+            ("clo" | "close", None) => Ok(Command::Close),
+            ("on" | "only", None) => Ok(Command::Only),
+            ("res" | "resize", Some(delta)) => Ok(Command::Resize {
+                direction: SplitDirection::Horizontal,
+                delta: Self::parse_delta(delta)?,
+            }),
+            ("vert" | "vertical", Some(argument)) => match argument.split_once(' ') {
+                Some(("res" | "resize", delta)) => Ok(Command::Resize {
+                    direction: SplitDirection::Vertical,
+                    delta: Self::parse_delta(delta.trim())?,
+                }),
+                _ => Err(format!("Not an editor command: {}", line)),
+            },
+            ("tabnew" | "tabe" | "tabedit", file) => Ok(Command::TabNew(file.map(Into::into))),
+            ("tabc" | "tabclose", None) => Ok(Command::TabClose),
+            ("tabn" | "tabnext", None) => Ok(Command::TabNext),
+            ("tabp" | "tabprevious", None) => Ok(Command::TabPrevious),
+            ("Ex" | "Explore", directory) => Ok(Command::Explore(directory.map(Into::into))),
+            ("Lex" | "Lexplore", None) => Ok(Command::ExploreToggle),
+            ("Files", None) => Ok(Command::Files),
+            ("gr" | "grep", pattern) => Ok(Command::Grep(pattern.map(Into::into))),
+            ("blame" | "Gblame", None) => Ok(Command::Blame),
+            ("Gedit" | "revision", Some(revision)) => Ok(Command::Revision(revision.into())),
+            ("Gedit" | "revision", None) => Err("No revision".into()),
+            ("G" | "Git", None) => Ok(Command::GitStatus),
+            ("Gcommit", None) => Ok(Command::Commit),
+            ("MarkdownPreview" | "mdp", None) => Ok(Command::MarkdownPreview),
+            ("Format" | "fmt", None) => Ok(Command::Format),
+            ("Definition" | "def", None) => Ok(Command::Definition),
+            ("References" | "refs", None) => Ok(Command::References),
+            ("Hover", None) => Ok(Command::Hover),
+            ("Rename", Some(name)) => Ok(Command::Rename(name.into())),
+            ("Rename", None) => Err("No new name".into()),
+            ("CodeAction" | "ca", None) => Ok(Command::CodeAction),
+            ("Commands", None) => Ok(Command::Commands),
+            ("h" | "help", topic) => Ok(Command::Help(topic.map(Into::into))),
+            _ => Ok(Command::Extension {
+                name: name.into(),
+                argument: argument.map(Into::into),
+            }),
+        }
+    }
+
+    /// Parses a relative size such as `+3` or `-2`.
+    fn parse_delta(delta: &str) -> Result<isize, String> {
+        let invalid = || format!("Expected +N or -N, got: {}", delta);
+        if !delta.starts_with(['+', '-']) {
+            return Err(invalid());
+        }
+        delta.parse().map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_their_abbreviations() {
+        assert_eq!(
+            Command::parse("e src/main.rs"),
+            Ok(Command::Edit("src/main.rs".into()))
+        );
+        assert_eq!(Command::parse("  bnext  "), Ok(Command::BufferNext));
+        assert_eq!(Command::parse("b 3"), Ok(Command::Buffer(3)));
+        assert_eq!(
+            Command::parse("bd!"),
+            Ok(Command::BufferDelete { force: true })
+        );
+        assert_eq!(Command::parse("w"), Ok(Command::Write(None)));
+        assert_eq!(Command::parse("h"), Ok(Command::Help(None)));
+    }
+
+    #[test]
+    fn reports_missing_and_invalid_arguments() {
+        assert!(Command::parse("e").is_err());
+        assert!(Command::parse("b two").is_err());
+        assert!(Command::parse("Rename").is_err());
+    }
+
+    #[test]
+    fn parses_resize_deltas() {
+        assert_eq!(
+            Command::parse("res +3"),
+            Ok(Command::Resize {
+                direction: SplitDirection::Horizontal,
+                delta: 3,
+            })
+        );
+        assert_eq!(
+            Command::parse("vert resize -2"),
+            Ok(Command::Resize {
+                direction: SplitDirection::Vertical,
+                delta: -2,
+            })
+        );
+        assert!(Command::parse("res 3").is_err());
+        assert!(Command::parse("vert split").is_err());
+    }
+
+    #[test]
+    fn passes_unknown_commands_on_to_the_extensions() {
+        assert_eq!(
+            Command::parse("Spell fix all"),
+            Ok(Command::Extension {
+                name: "Spell".into(),
+                argument: Some("fix all".into()),
+            })
+        );
+    }
+}
@@ -1,9 +1,54 @@
+use crate::app::actions::{Action, KeyMode, Keymap, Lookup, Op};
+use crate::app::commands::Command;
+use crate::app::help;
 use crate::ports::config;
 use crate::ports::editor::EditorDomainPort;
+use crate::ports::overlay::OverlayKind;
 use crate::ports::terminal_io::{CursorEventTypes, ReaderPort, WriterPort};
+use crate::toolshed::keys;
 use crossterm::event;
 use crossterm::terminal;
-use std::{cmp, io};
+use std::{cmp, io, path, time};
+
+/// Number of rows the mouse wheel scrolls a window by.
+const MOUSE_SCROLL_LINES: isize = 3;
+/// How often the screen is updated while work runs in the background.
+const TASK_POLL_INTERVAL: time::Duration = time::Duration::from_millis(50);
+/// How long a prefix key waits for the rest of its sequence before the
+/// keys that may follow it are shown.
+const KEY_HINTS_DELAY: time::Duration = time::Duration::from_millis(500);
+
+/// The input modes of the editor.
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Insert,
+    /// A `:` command is being typed; holds the text entered so far.
+    Command(String),
+    /// An answer to a question from the file explorer is being typed.
+    Prompt(Prompt, String),
+    /// A popup list is open; holds the query typed to narrow it down.
+    Picker(String),
+}
+
+/// The questions the file explorer asks on the command line.
+#[derive(Debug, PartialEq, Eq)]
+enum Prompt {
+    Create,
+    Rename,
+    /// Confirms deleting the named entry.
+    Delete(String),
+}
+
+impl Prompt {
+    fn label(&self) -> String {
+        match self {
+            Prompt::Create => "New file (end with / for a directory): ".into(),
+            Prompt::Rename => "Rename to: ".into(),
+            Prompt::Delete(name) => format!("Delete {}? (y/n): ", name),
+        }
+    }
+}
 
 #[warn(dead_code)]
 pub struct EditorApp<R: ReaderPort, W: WriterPort, E: EditorDomainPort> {
@@ -11,85 +56,909 @@ pub struct EditorApp<R: ReaderPort, W: WriterPort, E: EditorDomainPort> {
     writer: W,
     domain: E,
     config: config::Config,
+    mode: Mode,
+    /// The keys typed so far of a sequence (such as `Ctrl-W`) waiting for
+    /// the rest of it.
+    pending_keys: Vec<event::KeyEvent>,
+    /// When to show the keys that may follow the pending keys, unless the
+    /// next key comes first.
+    key_hints_at: Option<time::Instant>,
+    /// The actions listed in the command palette when it was last opened.
+    palette: Vec<Action>,
 }
 
 impl<R: ReaderPort, W: WriterPort, E: EditorDomainPort> EditorApp<R, W, E> {
-    pub fn new(reader: R, writer: W, config: config::Config) -> Self {
+    pub fn new(reader: R, writer: W, config: config::Config, files: Vec<path::PathBuf>) -> Self {
         // Obtain the window size before initializing the domain
-        let window_size = terminal::size()
-            .map(|(w, h)| (w as usize, h as usize - 2))
-            .unwrap();
+        let window_size = terminal::size().map(Self::window_size).unwrap();
+        let mut domain = E::new(window_size, &config);
+
+        // Open every file given on the command line, falling back to an empty
+        // buffer. A directory is opened in the file explorer instead.
+        let mut first = None;
+        for file in files {
+            let result = if file.is_dir() {
+                domain.open_explorer(Some(file.clone()))
+            } else {
+                domain.open_file(Some(file.clone())).map(|id| {
+                    first.get_or_insert(id);
+                })
+            };
+            if let Err(e) = result {
+                domain.set_message(format!("Could not open {}: {}", file.display(), e));
+            }
+        }
+        if domain.list_buffers().is_empty() {
+            domain.open_file(None).unwrap();
+        }
+        // Start on the first file that opened rather than the last one
+        if let Some(id) = first {
+            let _ = domain.switch_buffer(id);
+        }
+
         Self {
             reader,
             writer,
-            domain: E::new(window_size),
+            domain,
             config,
+            mode: Mode::Normal,
+            pending_keys: Vec::new(),
+            key_hints_at: None,
+            palette: Vec::new(),
+        }
+    }
+
+    /// Computes the size of the text area for a terminal of `w` by `h`
+    /// cells: the tab line, a status line and the message bar take three rows.
+    fn window_size((w, h): (u16, u16)) -> (usize, usize) {
+        (w as usize, (h as usize).saturating_sub(3))
+    }
+
+    /// Handles the next event. While `busy`, gives up after a short wait so
+    /// the screen can show the progress of background work. Gives up as
+    /// well when the keys following a pending key are due to be shown.
+    fn process_event(&mut self, busy: bool) -> io::Result<bool> {
+        let until_hints = self
+            .key_hints_at
+            .map(|at| at.saturating_duration_since(time::Instant::now()));
+        let timeout = match (busy, until_hints) {
+            (true, Some(wait)) => Some(cmp::min(wait, TASK_POLL_INTERVAL)),
+            (true, None) => Some(TASK_POLL_INTERVAL),
+            (false, wait) => wait,
+        };
+        let event = match timeout {
+            Some(timeout) => match self.reader.poll_event(timeout)? {
+                Some(event) => event,
+                None => {
+                    self.show_key_hints();
+                    return Ok(true);
+                }
+            },
+            None => self.reader.read_event()?,
+        };
+        match event {
+            event::Event::Key(key) => self.process_keypress(key),
+            event::Event::Mouse(mouse) => {
+                self.process_mouse(mouse);
+                Ok(true)
+            }
+            event::Event::Resize(w, h) => {
+                self.domain.set_window_size(Self::window_size((w, h)));
+                Ok(true)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn process_mouse(&mut self, mouse: event::MouseEvent) {
+        let (x, y) = (mouse.column as usize, mouse.row as usize);
+        match mouse.kind {
+            // Clicking a label on the tab line switches to that tab page
+            event::MouseEventKind::Down(event::MouseButton::Left) if y == 0 => {
+                if let Some(index) = self.domain.tab_at(x) {
+                    if let Err(e) = self.domain.switch_tab(index) {
+                        self.domain.set_message(e.to_string());
+                    }
+                }
+            }
+            event::MouseEventKind::Down(event::MouseButton::Left) => {
+                self.domain.close_completion();
+                self.domain.click(x, y);
+            }
+            event::MouseEventKind::Drag(event::MouseButton::Left) => self.domain.drag(x, y),
+            event::MouseEventKind::ScrollUp => self.domain.scroll_view(x, y, -MOUSE_SCROLL_LINES),
+            event::MouseEventKind::ScrollDown => self.domain.scroll_view(x, y, MOUSE_SCROLL_LINES),
+            _ => {}
+        }
+    }
+
+    fn process_keypress(&mut self, key: event::KeyEvent) -> io::Result<bool> {
+        // The hover popup and key hints stay up until the next key
+        self.domain.close_hover();
+        self.domain.close_key_hints();
+        self.key_hints_at = None;
+        // The topmost popup taking keys gets them before the mode does
+        match self.domain.focused_overlay() {
+            Some(OverlayKind::Picker) => return self.process_picker_keypress(key),
+            Some(OverlayKind::GitPanel)
+                if self.mode == Mode::Normal && self.pending_keys.is_empty() =>
+            {
+                if let Some(result) = self.process_git_panel_keypress(key) {
+                    return result;
+                }
+            }
+            Some(OverlayKind::Completion)
+                if self.mode == Mode::Insert && self.process_completion_keypress(key) =>
+            {
+                return Ok(true);
+            }
+            _ => {}
+        }
+        match self.mode {
+            Mode::Normal => self.process_normal_keypress(key),
+            Mode::Insert => self.process_insert_keypress(key),
+            Mode::Command(_) | Mode::Prompt(..) => self.process_command_keypress(key),
+            // The picker was closed behind our back, such as by a script
+            Mode::Picker(_) => {
+                self.mode = Mode::Normal;
+                self.process_normal_keypress(key)
+            }
+        }
+    }
+
+    /// Maps a key to one of the cursor directions understood by the domain,
+    /// using the keys configured in `[cursor_keymaps]`.
+    fn cursor_direction(&self, code: event::KeyCode) -> Option<event::KeyCode> {
+        let keymaps = &self.config.cursor_keymaps;
+        match code {
+            event::KeyCode::Char(c) if c == keymaps.left => Some(event::KeyCode::Char('h')),
+            event::KeyCode::Char(c) if c == keymaps.down => Some(event::KeyCode::Char('j')),
+            event::KeyCode::Char(c) if c == keymaps.up => Some(event::KeyCode::Char('k')),
+            event::KeyCode::Char(c) if c == keymaps.right => Some(event::KeyCode::Char('l')),
+            event::KeyCode::Left => Some(event::KeyCode::Char('h')),
+            event::KeyCode::Down => Some(event::KeyCode::Char('j')),
+            event::KeyCode::Up => Some(event::KeyCode::Char('k')),
+            event::KeyCode::Right => Some(event::KeyCode::Char('l')),
+            event::KeyCode::Home | event::KeyCode::End => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Handles a key while the git panel is open, or returns `None` for keys
+    /// it leaves to normal mode.
+    fn process_git_panel_keypress(&mut self, key: event::KeyEvent) -> Option<io::Result<bool>> {
+        if key.modifiers.contains(event::KeyModifiers::CONTROL) {
+            return None;
+        }
+        let result = match self.cursor_direction(key.code).unwrap_or(key.code) {
+            event::KeyCode::Char('j') => {
+                self.domain.git_panel_move(1);
+                Ok(())
+            }
+            event::KeyCode::Char('k') => {
+                self.domain.git_panel_move(-1);
+                Ok(())
+            }
+            event::KeyCode::Char('s') => self.domain.git_panel_stage(true),
+            event::KeyCode::Char('u') => self.domain.git_panel_stage(false),
+            event::KeyCode::Char('=') => self.domain.git_panel_toggle_hunks(),
+            event::KeyCode::Enter => self.domain.git_panel_open_file(),
+            event::KeyCode::Char('c') => self.domain.start_commit(),
+            event::KeyCode::Char('R') => self.domain.git_panel_refresh(),
+            event::KeyCode::Char('q') | event::KeyCode::Esc => {
+                self.domain.close_git_panel();
+                Ok(())
+            }
+            event::KeyCode::Char(':') => return None,
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.domain.set_message(e.to_string());
+        }
+        Some(Ok(true))
+    }
+
+    /// Handles a key while the file explorer has focus. Control keys, `:` and
+    /// `g` fall through to normal mode, so commands and window keys keep working.
+    fn process_explorer_keypress(&mut self, key: event::KeyEvent) -> Option<io::Result<bool>> {
+        if key.modifiers.contains(event::KeyModifiers::CONTROL) {
+            return None;
+        }
+        let result = match self.cursor_direction(key.code).unwrap_or(key.code) {
+            event::KeyCode::Char('j') => {
+                self.domain.explorer_move(1);
+                Ok(())
+            }
+            event::KeyCode::Char('k') => {
+                self.domain.explorer_move(-1);
+                Ok(())
+            }
+            event::KeyCode::Char('l') | event::KeyCode::Enter => self.domain.explorer_open(),
+            event::KeyCode::Char('h') => {
+                self.domain.explorer_collapse();
+                Ok(())
+            }
+            event::KeyCode::Char('a') => {
+                self.prompt(Prompt::Create, String::new());
+                Ok(())
+            }
+            event::KeyCode::Char('r') => {
+                if let Some(name) = self.domain.explorer_selection() {
+                    self.prompt(Prompt::Rename, name);
+                }
+                Ok(())
+            }
+            event::KeyCode::Char('d') => {
+                if let Some(name) = self.domain.explorer_selection() {
+                    self.prompt(Prompt::Delete(name), String::new());
+                }
+                Ok(())
+            }
+            event::KeyCode::Char('R') => self.domain.explorer_refresh(),
+            event::KeyCode::Char('q') => {
+                self.domain.close_explorer();
+                Ok(())
+            }
+            event::KeyCode::Esc => {
+                self.domain.leave_explorer();
+                Ok(())
+            }
+            event::KeyCode::Char(':' | 'g') => return None,
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.domain.set_message(e.to_string());
         }
+        Some(Ok(true))
     }
 
-    fn process_keypress(&mut self) -> io::Result<bool> {
-        match self.reader.read_key()? {
-            // Quit
-            event::KeyEvent {
-                code: event::KeyCode::Char('q'),
-                modifiers: event::KeyModifiers::CONTROL,
-                ..
-            } => Ok(false),
-
-            // Move cursor
-            event::KeyEvent {
-                code:
-                    direction @ (event::KeyCode::Char('h')
-                    | event::KeyCode::Char('j')
-                    | event::KeyCode::Char('k')
-                    | event::KeyCode::Char('l')),
-                ..
-            } => {
+    /// Asks `prompt` on the command line, with `answer` already filled in.
+    fn prompt(&mut self, prompt: Prompt, answer: String) {
+        self.domain
+            .set_command_line(Some(format!("{}{}", prompt.label(), answer)));
+        self.mode = Mode::Prompt(prompt, answer);
+    }
+
+    fn answer_prompt(&mut self, prompt: Prompt, answer: &str) {
+        let result = match prompt {
+            Prompt::Create => self.domain.explorer_create(answer),
+            Prompt::Rename => self.domain.explorer_rename(answer),
+            Prompt::Delete(_) if answer.trim().eq_ignore_ascii_case("y") => {
+                self.domain.explorer_delete()
+            }
+            Prompt::Delete(_) => Ok(()),
+        };
+        if let Err(e) = result {
+            self.domain.set_message(e.to_string());
+        }
+    }
+
+    /// Shows the keys that may follow the pending keys in the current mode,
+    /// once they have waited long enough.
+    fn show_key_hints(&mut self) {
+        match self.key_hints_at {
+            Some(at) if at <= time::Instant::now() => self.key_hints_at = None,
+            _ => return,
+        }
+        let typed = &self.pending_keys;
+        let hints = self.keymap().continuations(self.key_mode(), typed);
+        let prefix = typed.iter().filter_map(keys::key_name).collect();
+        if !hints.is_empty() {
+            self.domain.show_key_hints(prefix, hints);
+        }
+    }
+
+    /// Returns the actions of the editor and the keys bound to them.
+    fn keymap(&self) -> Keymap {
+        let script_keys = KeyMode::ALL
+            .into_iter()
+            .flat_map(|mode| {
+                let keys = self.domain.key_bindings(mode.name());
+                keys.into_iter().map(move |key| (mode, key))
+            })
+            .collect();
+        Keymap::new(&self.config, self.domain.extension_commands(), script_keys)
+    }
+
+    /// Returns the mode keys are looked up in: insert mode, or normal mode
+    /// for the keys falling through to it from the others.
+    fn key_mode(&self) -> KeyMode {
+        match self.mode {
+            Mode::Insert => KeyMode::Insert,
+            _ => KeyMode::Normal,
+        }
+    }
+
+    fn process_normal_keypress(&mut self, key: event::KeyEvent) -> io::Result<bool> {
+        if self.pending_keys.is_empty() && self.domain.explorer_focused() {
+            if let Some(result) = self.process_explorer_keypress(key) {
+                return result;
+            }
+        }
+
+        self.pending_keys.push(key);
+        let keymap = self.keymap();
+        match keymap.lookup(KeyMode::Normal, &self.pending_keys) {
+            Lookup::Run(index) => {
+                self.pending_keys.clear();
+                self.run_action(&keymap.actions[index])
+            }
+            Lookup::Pending => {
+                self.key_hints_at = Some(time::Instant::now() + KEY_HINTS_DELAY);
+                Ok(true)
+            }
+            Lookup::Unbound => {
+                self.pending_keys.clear();
+                Ok(true)
+            }
+        }
+    }
+
+    /// Does what `op` stands for, returning `Ok(false)` when the editor
+    /// should exit.
+    fn run_op(&mut self, op: Op) -> io::Result<bool> {
+        let result = match op {
+            Op::Move(direction) => {
                 self.domain.move_cursor(direction);
+                Ok(())
+            }
+            Op::LineStart => {
+                self.domain.move_cursor(event::KeyCode::Home);
+                Ok(())
+            }
+            Op::LineEnd => {
+                self.domain.move_cursor(event::KeyCode::End);
+                Ok(())
+            }
+            Op::FirstLine => {
+                self.domain.set_cursor_position(0, 0);
+                Ok(())
+            }
+            Op::LastLine => {
+                let rows = self.domain.get_editor_rows().number_of_rows();
+                self.domain.set_cursor_position(0, rows.saturating_sub(1));
+                Ok(())
+            }
+            Op::PageUp | Op::PageDown => {
+                self.move_page(op == Op::PageUp);
+                Ok(())
+            }
+            Op::InsertMode => {
+                self.mode = Mode::Insert;
+                self.domain.set_message("-- INSERT --".into());
+                Ok(())
+            }
+            Op::NormalMode => {
+                self.domain.close_completion();
+                self.domain.end_change();
+                self.domain.set_message(String::new());
+                self.mode = Mode::Normal;
+                Ok(())
+            }
+            Op::Complete => self.domain.complete(),
+            Op::Undo => {
+                if !self.domain.undo() {
+                    self.domain.set_message("Already at oldest change".into());
+                }
+                Ok(())
+            }
+            Op::Redo => {
+                if !self.domain.redo() {
+                    self.domain.set_message("Already at newest change".into());
+                }
+                Ok(())
+            }
+            Op::Paste => {
+                self.domain.paste();
+                Ok(())
+            }
+            Op::Copy => {
+                if self.domain.has_selection() {
+                    self.domain.yank_selection();
+                }
+                Ok(())
+            }
+            Op::Cut => {
+                if self.domain.has_selection() {
+                    self.domain.delete_selection();
+                }
+                Ok(())
+            }
+            Op::ClearSelection => {
+                self.domain.clear_selection();
+                Ok(())
+            }
+            Op::CommandLine => {
+                self.mode = Mode::Command(String::new());
+                self.domain.set_command_line(Some(":".into()));
+                Ok(())
+            }
+            Op::FollowLink => match self.help_link() {
+                Some(topic) => self.open_help(Some(&topic)),
+                None => Ok(()),
+            },
+            Op::Exit => return Ok(false),
+            Op::NextWindow => {
+                self.domain.focus_next_window();
+                Ok(())
+            }
+            Op::FocusWindow(direction) => {
+                self.domain.focus_window(direction);
+                Ok(())
+            }
+            // Move between the rows changed since the last commit
+            Op::Hunk(forward) => {
+                if !self.domain.jump_to_hunk(forward) {
+                    self.domain.set_message("No more hunks".into());
+                }
+                Ok(())
+            }
+            Op::Misspelling(forward) => {
+                if !self.domain.jump_to_misspelling(forward) {
+                    self.domain.set_message("No more misspelled words".into());
+                }
+                Ok(())
+            }
+            Op::SpellSuggest => self.domain.spell_suggest().map(|()| {
+                self.mode = Mode::Picker(String::new());
+            }),
+            Op::SpellAdd => self.domain.spell_add_word().map(|word| {
+                self.domain
+                    .set_message(format!("\"{}\" added to the dictionary", word))
+            }),
+            // Go back to before the last jump to a definition
+            Op::JumpBack => {
+                if !self.domain.jump_back() {
+                    self.domain.set_message("No jump to go back from".into());
+                }
+                Ok(())
+            }
+            // Run by `run_action`, which has the keys the script bound
+            Op::Script => Ok(()),
+        };
+        if let Err(e) = result {
+            self.domain.set_message(e.to_string());
+        }
+        Ok(true)
+    }
+
+    /// Moves the cursor a page up or down.
+    fn move_page(&mut self, up: bool) {
+        let cursor_x = self.domain.get_cursor_controller().get_file_position().0;
+        if up {
+            let row_offset = self.domain.get_cursor_controller().get_row_offset();
+            self.domain.set_cursor_position(cursor_x, row_offset);
+        } else {
+            let cursor_y = cmp::min(
+                (self.domain.get_cursor_controller().get_row_offset()
+                    + self.domain.get_window_size().unwrap().1)
+                    .saturating_sub(1),
+                self.domain.get_editor_rows().number_of_rows(),
+            );
+            self.domain.set_cursor_position(cursor_x, cursor_y);
+        }
+        (0..self.domain.get_window_size().unwrap().1).for_each(|_| {
+            self.domain.move_cursor(if up {
+                event::KeyCode::Char('k')
+            } else {
+                event::KeyCode::Char('j')
+            });
+        });
+    }
+
+    /// Handles the keys of the completion popup while it is open. Returns
+    /// false for keys it leaves to insert mode.
+    fn process_completion_keypress(&mut self, key: event::KeyEvent) -> bool {
+        let control = key.modifiers.contains(event::KeyModifiers::CONTROL);
+        match key.code {
+            event::KeyCode::Up => self.domain.completion_move(-1),
+            event::KeyCode::Down => self.domain.completion_move(1),
+            event::KeyCode::Char('p') if control => self.domain.completion_move(-1),
+            event::KeyCode::Char('n') if control => self.domain.completion_move(1),
+            event::KeyCode::Tab | event::KeyCode::Enter => return self.domain.completion_accept(),
+            // Closes the popup without leaving insert mode
+            event::KeyCode::Esc => self.domain.close_completion(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn process_insert_keypress(&mut self, key: event::KeyEvent) -> io::Result<bool> {
+        self.pending_keys.push(key);
+        let keymap = self.keymap();
+        // The first of the pending keys is typed once no sequence starts
+        // with them, and the others are looked up again
+        loop {
+            match keymap.lookup(KeyMode::Insert, &self.pending_keys) {
+                Lookup::Run(index) => {
+                    self.pending_keys.clear();
+                    return self.run_action(&keymap.actions[index]);
+                }
+                Lookup::Pending => {
+                    self.key_hints_at = Some(time::Instant::now() + KEY_HINTS_DELAY);
+                    return Ok(true);
+                }
+                Lookup::Unbound => {
+                    let key = self.pending_keys.remove(0);
+                    self.type_key(key);
+                    if self.pending_keys.is_empty() {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles a key of insert mode bound to nothing, which mostly types.
+    fn type_key(&mut self, key: event::KeyEvent) {
+        self.domain.run_key_hooks(&key);
+        // Anything but typing closes the completion popup
+        if !matches!(
+            key.code,
+            event::KeyCode::Char(_) | event::KeyCode::Backspace
+        ) {
+            self.domain.close_completion();
+        }
+        let control = key.modifiers.contains(event::KeyModifiers::CONTROL);
+        match key.code {
+            event::KeyCode::Enter => self.domain.insert_newline(),
+            event::KeyCode::Backspace => {
+                self.domain.delete_char();
+                self.domain.update_completion(None);
+            }
+            event::KeyCode::Tab => self.domain.insert_char('\t'),
+            event::KeyCode::Char(ch) if !control => {
+                self.domain.insert_char(ch);
+                self.domain.update_completion(Some(ch));
+            }
+            code @ (event::KeyCode::Left
+            | event::KeyCode::Right
+            | event::KeyCode::Up
+            | event::KeyCode::Down
+            | event::KeyCode::Home
+            | event::KeyCode::End) => {
+                // Moving around starts a new undo step, like leaving insert mode would
+                self.domain.end_change();
+                if let Some(direction) = self.cursor_direction(code) {
+                    self.domain.move_cursor(direction);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn process_command_keypress(&mut self, key: event::KeyEvent) -> io::Result<bool> {
+        let (Mode::Command(line) | Mode::Prompt(_, line)) = &mut self.mode else {
+            return Ok(true);
+        };
+        match key.code {
+            event::KeyCode::Esc => self.mode = Mode::Normal,
+            event::KeyCode::Backspace if line.is_empty() => self.mode = Mode::Normal,
+            event::KeyCode::Backspace => {
+                line.pop();
+            }
+            event::KeyCode::Char(ch) => line.push(ch),
+            event::KeyCode::Enter => {
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                self.domain.set_command_line(None);
+                return match mode {
+                    Mode::Command(line) => match Command::parse(&line) {
+                        Ok(command) => self.execute_command(command),
+                        Err(message) => {
+                            self.domain.set_message(message);
+                            Ok(true)
+                        }
+                    },
+                    Mode::Prompt(prompt, answer) => {
+                        self.answer_prompt(prompt, &answer);
+                        Ok(true)
+                    }
+                    _ => Ok(true),
+                };
+            }
+            _ => {}
+        }
+
+        let command_line = match &self.mode {
+            Mode::Command(line) => Some(format!(":{}", line)),
+            Mode::Prompt(prompt, answer) => Some(format!("{}{}", prompt.label(), answer)),
+            _ => None,
+        };
+        self.domain.set_command_line(command_line);
+        Ok(true)
+    }
+
+    fn open_finder(&mut self) {
+        self.domain.open_finder();
+        self.mode = Mode::Picker(String::new());
+    }
+
+    fn open_grep(&mut self, pattern: String) {
+        self.domain.open_grep(&pattern);
+        self.mode = Mode::Picker(pattern);
+    }
+
+    /// Opens the help page tagged `topic`, or the closest match, or the
+    /// index without one.
+    fn open_help(&mut self, topic: Option<&str>) -> io::Result<()> {
+        let pages = help::pages(
+            &self.keymap().actions,
+            &self.domain.extension_summaries(),
+            &self.domain.extension_options(),
+        );
+        let (page, row) = match topic {
+            Some(topic) => help::find(&pages, topic)
+                .ok_or_else(|| io::Error::other(format!("No help for {}", topic)))?,
+            None => (0, 0),
+        };
+        let page = &pages[page];
+        self.domain
+            .open_read_only(page.buffer_name(), &page.text, row);
+        Ok(())
+    }
+
+    /// Returns the topic the link under the cursor leads to, on a help page.
+    fn help_link(&mut self) -> Option<String> {
+        let (x, y) = self.domain.get_cursor_controller().get_file_position();
+        let rows = self.domain.get_editor_rows();
+        if !help::is_help_buffer(rows.get_file_name()?) {
+            return None;
+        }
+        (y < rows.number_of_rows())
+            .then(|| help::link_at(rows.get_row(y), x))
+            .flatten()
+    }
+
+    fn open_palette(&mut self) {
+        self.palette = self.keymap().actions;
+        // Insert mode is left before the palette opens
+        self.palette.retain(|action| action.mode == KeyMode::Normal);
+        let labels = self.palette.iter().map(Action::label).collect();
+        self.domain.open_palette(labels);
+        self.mode = Mode::Picker(String::new());
+    }
+
+    /// Runs `action`: its command, or what it does otherwise. Commands
+    /// still needing an argument are left on the command line to finish.
+    fn run_action(&mut self, action: &Action) -> io::Result<bool> {
+        if let (Some(command), true) = (&action.command, action.needs_argument()) {
+            self.domain.set_command_line(Some(format!(":{}", command)));
+            self.mode = Mode::Command(command.clone());
+            return Ok(true);
+        }
+        if let Some(command) = &action.command {
+            return match Command::parse(command) {
+                Ok(command) => self.execute_command(command),
+                Err(message) => {
+                    self.domain.set_message(message);
+                    Ok(true)
+                }
+            };
+        }
+        match action.op {
+            Some(Op::Script) => {
+                for key in &action.keys {
+                    self.domain.run_key_binding(action.mode.name(), key);
+                }
                 Ok(true)
             }
+            Some(op) => self.run_op(op),
+            None => Ok(true),
+        }
+    }
+
+    fn process_picker_keypress(&mut self, key: event::KeyEvent) -> io::Result<bool> {
+        let Mode::Picker(query) = &mut self.mode else {
+            return Ok(true);
+        };
+        let control = key.modifiers.contains(event::KeyModifiers::CONTROL);
+        match key.code {
+            event::KeyCode::Esc => {
+                self.domain.close_picker();
+                self.mode = Mode::Normal;
+            }
+            event::KeyCode::Enter => {
+                self.mode = Mode::Normal;
+                if let Err(e) = self.domain.picker_accept() {
+                    self.domain.set_message(e.to_string());
+                }
+                let choice = self.domain.take_palette_choice();
+                if let Some(action) = choice.and_then(|index| self.palette.get(index).cloned()) {
+                    return self.run_action(&action);
+                }
+            }
+            event::KeyCode::Up => self.domain.picker_move(-1),
+            event::KeyCode::Down => self.domain.picker_move(1),
+            event::KeyCode::Char('p' | 'k') if control => self.domain.picker_move(-1),
+            event::KeyCode::Char('n' | 'j') if control => self.domain.picker_move(1),
+            event::KeyCode::Backspace if query.pop().is_some() => {
+                let query = query.clone();
+                self.domain.set_picker_query(&query);
+            }
+            event::KeyCode::Char(ch) if !control => {
+                query.push(ch);
+                let query = query.clone();
+                self.domain.set_picker_query(&query);
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Runs a parsed command, returning `Ok(false)` when the editor should exit.
+    fn execute_command(&mut self, command: Command) -> io::Result<bool> {
+        let result = match command {
+            Command::Edit(file) => self.domain.open_file(Some(file)).map(|_| ()),
+            Command::EditNew => self.domain.open_file(None).map(|_| ()),
+            Command::BufferNext => {
+                self.domain.next_buffer();
+                Ok(())
+            }
+            Command::BufferPrevious => {
+                self.domain.previous_buffer();
+                Ok(())
+            }
+            Command::Buffer(id) => self.domain.switch_buffer(id),
+            Command::BufferDelete { force } => self.domain.close_buffer(force),
+            Command::ListBuffers => {
+                let list = self.domain.list_buffers().join(" | ");
+                self.domain.set_message(list);
+                Ok(())
+            }
+            Command::Write(file) => self.write(file),
+            Command::Quit { force } => {
+                if self.domain.close_window() {
+                    return Ok(true);
+                }
+                if force || !self.domain.has_unsaved_changes() {
+                    return Ok(false);
+                }
+                self.domain
+                    .set_message("No write since last change (add ! to override)".into());
+                Ok(())
+            }
+            Command::WriteQuit => match self.write(None) {
+                Ok(()) => return self.execute_command(Command::Quit { force: false }),
+                Err(_) => Ok(()),
+            },
+            Command::Split { direction, file } => self.domain.split_window(direction, file),
+            Command::Close => {
+                if !self.domain.close_window() {
+                    self.domain.set_message("Cannot close last window".into());
+                }
+                Ok(())
+            }
+            Command::Only => {
+                self.domain.only_window();
+                Ok(())
+            }
+            Command::Resize { direction, delta } => {
+                self.domain.resize_window(direction, delta);
+                Ok(())
+            }
+            Command::TabNew(file) => self.domain.new_tab(file),
+            Command::TabClose => {
+                if !self.domain.close_tab() {
+                    self.domain.set_message("Cannot close last tab page".into());
+                }
+                Ok(())
+            }
+            Command::TabNext => {
+                self.domain.next_tab();
+                Ok(())
+            }
+            Command::TabPrevious => {
+                self.domain.previous_tab();
+                Ok(())
+            }
+            Command::Explore(directory) => self.domain.open_explorer(directory),
+            Command::ExploreToggle => self.domain.toggle_explorer(),
+            Command::Files => {
+                self.open_finder();
+                Ok(())
+            }
+            Command::Grep(pattern) => {
+                self.open_grep(pattern.unwrap_or_default());
+                Ok(())
+            }
+            Command::Blame => self.domain.toggle_blame(),
+            Command::Revision(revision) => self.domain.open_revision(&revision),
+            Command::GitStatus => self.domain.open_git_panel(),
+            Command::Commit => self.domain.start_commit(),
+            Command::MarkdownPreview => self.domain.toggle_markdown_preview(),
+            Command::Format => self.domain.format_buffer(),
+            Command::Definition => self.domain.goto_definition(),
+            Command::References => self.domain.find_references(),
+            Command::Hover => self.domain.show_hover(),
+            Command::Rename(name) => self.domain.rename_symbol(&name),
+            Command::CodeAction => self.domain.code_actions(),
+            Command::Commands => {
+                self.open_palette();
+                Ok(())
+            }
+            Command::Help(topic) => self.open_help(topic.as_deref()),
+            Command::Extension { name, argument } => self
+                .domain
+                .run_extension_command(&name, argument.as_deref()),
+        };
+
+        if let Err(e) = result {
+            self.domain.set_message(e.to_string());
+        }
+        Ok(true)
+    }
 
-            // Page up/down
-            event::KeyEvent {
-                code: val @ (event::KeyCode::PageUp | event::KeyCode::PageDown),
-                ..
-            } => {
-                if matches!(val, event::KeyCode::PageUp) {
-                    self.domain.get_cursor_controller().get_cursor_position().1 =
-                        self.domain.get_cursor_controller().get_row_offset()
-                } else {
-                    self.domain.get_cursor_controller().get_cursor_position().1 = cmp::min(
-                        self.domain.get_cursor_controller().get_row_offset()
-                            + self.domain.get_window_size().unwrap().1
-                            - 1,
-                        self.domain.get_editor_rows().number_of_rows(),
-                    );
-                }
-                (0..self.domain.get_window_size().unwrap().1).for_each(|_| {
-                    self.domain
-                        .move_cursor(if matches!(val, event::KeyCode::PageUp) {
-                            event::KeyCode::Char('k')
-                        } else {
-                            event::KeyCode::Char('j')
-                        });
+    fn write(&mut self, file: Option<path::PathBuf>) -> io::Result<()> {
+        // A buffer the extensions fail on, e.g. to format, is saved as it is
+        let prepared = self.domain.before_save();
+        match self.domain.save(file) {
+            Ok(bytes) => {
+                self.domain.set_message(match prepared {
+                    Ok(()) => format!("{} bytes written to disk", bytes),
+                    Err(e) => format!("{} bytes written, but {}", bytes, e),
                 });
-                Ok(true)
+                match self.domain.after_save() {
+                    Ok(Some(message)) => self.domain.set_message(message),
+                    Ok(None) => {}
+                    Err(e) => self.domain.set_message(e.to_string()),
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.domain
+                    .set_message(format!("Can't save! I/O error: {}", e));
+                Err(e)
             }
-            _ => Ok(true),
         }
     }
 
+    /// Carries out the command lines scripts ran. Returns false if one of
+    /// them quit the editor.
+    fn run_script_commands(&mut self, lines: Vec<String>) -> io::Result<bool> {
+        for line in lines {
+            let result = match Command::parse(&line) {
+                Ok(command) => self.execute_command(command),
+                Err(message) => {
+                    self.domain.set_message(message);
+                    Ok(true)
+                }
+            };
+            if !result? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     pub fn run(&mut self) -> io::Result<bool> {
-        let (cursor_x, cursor_y) = self.domain.get_cursor_position();
+        let mut busy = self.domain.poll_tasks();
+        let script_commands = self.domain.take_script_commands();
+        // What the commands have scripts do in turn is taken care of soon after
+        busy |= !script_commands.is_empty();
+        if !self.run_script_commands(script_commands)? {
+            return Ok(false);
+        }
+        // Pickers the domain opened on its own, such as a list of references
+        if self.domain.picker_open() && !matches!(self.mode, Mode::Picker(_)) {
+            match self.mode {
+                Mode::Normal => self.mode = Mode::Picker(String::new()),
+                _ => self.domain.close_picker(),
+            }
+        }
         self.domain.scroll();
+        let (cursor_x, cursor_y) = self.domain.get_cursor_position();
 
         self.writer
             .reset_screen(self.domain.get_buffer(), None)
             .unwrap();
 
+        self.domain.draw_tab_line();
         self.domain.draw_rows().unwrap();
         self.domain.draw_status_bar();
         self.domain.draw_message_bar();
+        self.domain.draw_popup();
 
         self.writer
             .cursor_event(
@@ -103,6 +972,6 @@ impl<R: ReaderPort, W: WriterPort, E: EditorDomainPort> EditorApp<R, W, E> {
 
         self.writer.flush(self.domain.get_buffer()).unwrap();
 
-        self.process_keypress()
+        self.process_event(busy)
     }
 }
@@ -0,0 +1,510 @@
+//! `help.rs`
+//!
+//! The pages of `:help`, generated from what they document so that they
+//! keep up with it: the commands and options from the doc comments of
+//! their types, the keys from the actions and the extensions from those
+//! enabled. Pages mark topics with `*tag*` and link to them with `|tag|`.
+
+use crate::app::actions::{Action, KeyMode};
+use crate::ports::config::Config;
+use crate::ports::extension::{ExtensionOptions, ExtensionSummary};
+use crate::toolshed::fuzzy::fuzzy_match;
+use crate::toolshed::schema::{self, Field};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path;
+
+/// The source of the commands, whose doc comments describe them.
+const COMMANDS_SOURCE: &str = include_str!("commands.rs");
+/// The source of the config types, whose doc comments describe the options.
+const CONFIG_SOURCE: &str = include_str!("../ports/config.rs");
+
+/// Where descriptions are wrapped, indent included.
+const TEXT_WIDTH: usize = 76;
+/// The column tags are lined up at, unless the text before them is longer.
+const TAG_COLUMN: usize = 48;
+
+/// The help buffers are named after their page, following this.
+const BUFFER_PREFIX: &str = "help:";
+
+const INDEX: &str = "\
+*index*  Barn help
+
+Move the cursor onto a link, such as |keys|, and press Enter to follow it.
+Ctrl-O goes back. `:help <topic>` opens the topic tagged *topic*, or the
+closest one found.
+
+|commands|      The commands typed after `:`
+|keys|          The keys of normal mode
+|options|       The options of config.toml
+|extensions|    The extensions enabled
+";
+
+/// A page of help, shown in a read-only buffer.
+pub struct Page {
+    pub name: &'static str,
+    pub text: String,
+}
+
+impl Page {
+    /// Returns the name of the buffer showing the page.
+    pub fn buffer_name(&self) -> path::PathBuf {
+        format!("{}{}", BUFFER_PREFIX, self.name).into()
+    }
+}
+
+/// Returns whether `file` is the name of a help buffer.
+pub fn is_help_buffer(file: &path::Path) -> bool {
+    file.to_str()
+        .is_some_and(|name| name.starts_with(BUFFER_PREFIX))
+}
+
+/// Returns every page, the index first. `options` holds the options of
+/// every extension, by the name of its table.
+pub fn pages(
+    actions: &[Action],
+    extensions: &[ExtensionSummary],
+    options: &[(String, ExtensionOptions)],
+) -> Vec<Page> {
+    vec![
+        Page {
+            name: "index",
+            text: INDEX.into(),
+        },
+        Page {
+            name: "commands",
+            text: commands_page(),
+        },
+        Page {
+            name: "keys",
+            text: keys_page(actions),
+        },
+        Page {
+            name: "options",
+            text: options_page(options),
+        },
+        Page {
+            name: "extensions",
+            text: extensions_page(extensions, options),
+        },
+    ]
+}
+
+/// Returns the page and row of the tag `topic` names: the one spelled
+/// like it, in any case, or the closest fuzzy match.
+pub fn find(pages: &[Page], topic: &str) -> Option<(usize, usize)> {
+    let mut tags = Vec::new();
+    for (page, text) in pages.iter().enumerate() {
+        for (row, line) in text.text.lines().enumerate() {
+            for range in marked(line, '*') {
+                tags.push((page, row, &line[range]));
+            }
+        }
+    }
+    let exact = tags.iter().find(|(_, _, tag)| *tag == topic);
+    let exact = exact.or_else(|| {
+        tags.iter()
+            .find(|(_, _, tag)| tag.eq_ignore_ascii_case(topic))
+    });
+    // The best match, the shortest tag among those as good
+    let closest = || {
+        tags.iter()
+            .filter_map(|entry| Some((fuzzy_match(topic, entry.2)?.0, entry)))
+            .min_by_key(|(score, (_, _, tag))| (-score, tag.len()))
+            .map(|(_, entry)| entry)
+    };
+    exact.or_else(closest).map(|(page, row, _)| (*page, *row))
+}
+
+/// Returns the tag of the link at byte column `x` of `line`, if any.
+pub fn link_at(line: &str, x: usize) -> Option<String> {
+    marked(line, '|')
+        .into_iter()
+        .find(|range| range.start - 1 <= x && x <= range.end)
+        .map(|range| line[range].to_string())
+}
+
+/// Returns the byte ranges of `line` between two `delimiter`s, with no
+/// whitespace in between.
+fn marked(line: &str, delimiter: char) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (at, ch) in line.char_indices() {
+        if ch == delimiter {
+            match start {
+                Some(start_at) if at > start_at => {
+                    ranges.push(start_at..at);
+                    start = None;
+                }
+                _ => start = Some(at + ch.len_utf8()),
+            }
+        } else if ch.is_whitespace() {
+            start = None;
+        }
+    }
+    ranges
+}
+
+/// Returns `text` followed by `tags`, lined up at the tag column.
+fn tagged(text: &str, tags: &[&str]) -> String {
+    let tags: Vec<String> = tags.iter().map(|tag| format!("*{}*", tag)).collect();
+    format!("{:<width$} {}", text, tags.join(" "), width = TAG_COLUMN)
+}
+
+/// Wraps `text` into indented lines, each ending with a newline.
+fn wrap(text: &str, indent: usize) -> String {
+    let mut wrapped = String::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && indent + line.len() + 1 + word.len() > TEXT_WIDTH {
+            wrapped.push_str(&format!("{}{}\n", " ".repeat(indent), line));
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        wrapped.push_str(&format!("{}{}\n", " ".repeat(indent), line));
+    }
+    wrapped
+}
+
+/// Returns the doc comments of the items in the body of `item` in
+/// `source`, such as the variants of an enum, each with the line after it.
+fn doc_comments<'a>(source: &'a str, item: &str) -> Vec<(String, &'a str)> {
+    let Some(start) = source.find(item) else {
+        return Vec::new();
+    };
+    let body = &source[start..];
+    let body = &body[..body.find("\n}").unwrap_or(body.len())];
+    let mut docs = Vec::new();
+    let mut doc: Vec<&str> = Vec::new();
+    for line in body.lines().skip(1).map(str::trim) {
+        match line.strip_prefix("///") {
+            Some(text) => doc.push(text.trim()),
+            // Attributes go between a doc comment and its item
+            None if line.starts_with("#[") => {}
+            None => {
+                if !doc.is_empty() {
+                    docs.push((doc.join(" "), line));
+                }
+                doc.clear();
+            }
+        }
+    }
+    docs
+}
+
+/// Generates the page of commands from the doc comments of `Command`,
+/// which start with how the command is typed, such as `` `:e <file>` - ``.
+fn commands_page() -> String {
+    let mut text = String::from("*commands*  The commands typed after `:`\n\n");
+    for (doc, _) in doc_comments(COMMANDS_SOURCE, "pub enum Command {") {
+        let Some((usage, description)) = doc.split_once(" - ") else {
+            continue;
+        };
+        let tags: Vec<&str> = usage
+            .split('`')
+            .filter(|part| part.starts_with(':'))
+            .filter_map(|part| part.split([' ', '[']).next())
+            .collect();
+        if tags.is_empty() {
+            continue;
+        }
+        let mut chars = description.chars();
+        let description: String = chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        text.push_str(&tagged(&usage.replace('`', ""), &tags));
+        text.push('\n');
+        text.push_str(&wrap(&description, 4));
+    }
+    text
+}
+
+/// Generates the page of keys from the actions having some, by mode.
+fn keys_page(actions: &[Action]) -> String {
+    let mut text = String::from("*keys*  The keys of each mode\n");
+    for mode in KeyMode::ALL {
+        text.push_str(&format!("\nIn {} mode:\n\n", mode.name()));
+        let keyed = actions
+            .iter()
+            .filter(|action| action.mode == mode && !action.keys.is_empty());
+        for action in keyed {
+            let keys = action.key_names();
+            let line = format!("{:<12}{}", keys, action.name);
+            // Tagged as Vim does, telling apart the same keys in each mode
+            let tag = match mode {
+                KeyMode::Normal => keys,
+                KeyMode::Insert => format!("i_{}", keys),
+            };
+            text.push_str(&tagged(&line, &[tag.as_str()]));
+            text.push('\n');
+        }
+    }
+    text.push_str("\nSee |commands| for what is run from the command line instead.\n");
+    text
+}
+
+/// A table of `config.toml`, with the options it holds.
+struct Table<'a> {
+    path: &'a str,
+    options: Vec<&'a Field>,
+}
+
+/// Returns the tables of `config.toml` holding options, found among the
+/// fields of `Config`: those read as structs.
+fn tables(fields: &[Field]) -> Vec<Table<'_>> {
+    let holds = |table: &Field, field: &Field| {
+        field.path.rsplit_once('.').map(|(parent, _)| parent) == Some(table.path.as_str())
+    };
+    let is_table = |table: &Field| fields.iter().any(|field| holds(table, field));
+    fields
+        .iter()
+        .filter(|table| is_table(table))
+        .map(|table| Table {
+            path: &table.path,
+            options: fields
+                .iter()
+                .filter(|field| holds(table, field) && !is_table(field))
+                .collect(),
+        })
+        .filter(|table| !table.options.is_empty())
+        .collect()
+}
+
+/// Returns the values of the fields of `value` and of the structs it holds,
+/// by path, as written in `config.toml`, read from its `Debug` form.
+fn debug_values(value: &impl std::fmt::Debug) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut path: Vec<&str> = Vec::new();
+    // A list or map spread over lines, by path, and its items so far
+    let mut open: Option<(String, Vec<&str>)> = None;
+    let text = format!("{:#?}", value);
+    for line in text.lines().map(str::trim) {
+        if let Some((at, items)) = open.as_mut() {
+            match line {
+                "]," => {
+                    values.insert(std::mem::take(at), format!("[{}]", items.join(", ")));
+                    open = None;
+                }
+                "}," => {
+                    let entries: Vec<String> = items
+                        .iter()
+                        .map(|item| item.replacen(": ", " = ", 1))
+                        .collect();
+                    let value = format!("{{ {} }}", entries.join(", "));
+                    values.insert(std::mem::take(at), value);
+                    open = None;
+                }
+                _ => items.push(line.strip_suffix(',').unwrap_or(line)),
+            }
+            continue;
+        }
+        let Some((name, value)) = line.split_once(": ") else {
+            if line.starts_with('}') {
+                path.pop();
+            }
+            continue;
+        };
+        let at = [path.as_slice(), &[name]].concat().join(".");
+        match value {
+            "[" | "{" => open = Some((at, Vec::new())),
+            // A struct, named by its type
+            _ if value.ends_with(" {") && value.starts_with(char::is_uppercase) => path.push(name),
+            _ => {
+                values.insert(at, value.strip_suffix(',').unwrap_or(value).to_string());
+            }
+        }
+    }
+    values
+}
+
+/// Returns the fields of `Config` followed by those of the tables of
+/// `extensions`, with the values they take by default, by path.
+fn option_fields(
+    extensions: &[(String, ExtensionOptions)],
+) -> (Vec<Field>, HashMap<String, String>) {
+    let mut fields = schema::fields::<Config>();
+    let mut defaults = schema::defaults::<Config>()
+        .map(|config| debug_values(&config))
+        .unwrap_or_default();
+    for (name, options) in extensions {
+        let table = format!("extensions.{}", name);
+        fields.push(Field {
+            path: table.clone(),
+            owner: "ExtensionsConfig",
+            kind: "table",
+            required: false,
+        });
+        fields.extend(options.fields.iter().map(|field| Field {
+            path: format!("{}.{}", table, field.path),
+            ..field.clone()
+        }));
+        let values = options
+            .defaults
+            .as_ref()
+            .map(debug_values)
+            .unwrap_or_default();
+        defaults.extend(
+            values
+                .into_iter()
+                .map(|(path, value)| (format!("{}.{}", table, path), value)),
+        );
+    }
+    (fields, defaults)
+}
+
+/// Generates the page of options from the fields of `Config` and of the
+/// tables of `extensions`, the values they take by default and their doc
+/// comments.
+fn options_page(extensions: &[(String, ExtensionOptions)]) -> String {
+    let mut text = String::from("*options*  The options of config.toml\n");
+    let (fields, defaults) = option_fields(extensions);
+    for table in tables(&fields) {
+        let header = format!("[{}]", table.path);
+        text.push('\n');
+        text.push_str(&tagged(&header, &[header.as_str()]));
+        text.push_str("\n\n");
+        for field in table.options {
+            let name = &field.path[table.path.len() + 1..];
+            let line = match defaults.get(&field.path) {
+                Some(value) if !field.required => format!("{} = {}", name, value),
+                _ => format!("{} (required)", name),
+            };
+            text.push_str(&tagged(&line, &[field.path.as_str()]));
+            text.push('\n');
+            let doc = doc_comments(CONFIG_SOURCE, &format!("pub struct {} {{", field.owner))
+                .into_iter()
+                .find(|(_, item)| item.starts_with(&format!("pub {}:", name)))
+                .map(|(doc, _)| format!(" {}", doc))
+                .unwrap_or_default();
+            let article = match field.kind.starts_with(['a', 'e', 'i', 'o', 'u']) {
+                true => "An",
+                false => "A",
+            };
+            text.push_str(&wrap(&format!("{} {}.{}", article, field.kind, doc), 4));
+        }
+    }
+    text
+}
+
+/// Generates the page of the extensions enabled, linking to their options.
+fn extensions_page(
+    extensions: &[ExtensionSummary],
+    options: &[(String, ExtensionOptions)],
+) -> String {
+    let mut text = String::from("*extensions*  The extensions enabled\n\n");
+    let (fields, _) = option_fields(options);
+    let tables = tables(&fields);
+    for extension in extensions {
+        text.push_str(&tagged(&extension.name, &[extension.name.as_str()]));
+        text.push('\n');
+        text.push_str(&wrap(&extension.description, 4));
+        let table = format!("extensions.{}", extension.name);
+        if tables.iter().any(|known| known.path == table) {
+            text.push_str(&format!("    Options: |[{}]|\n", table));
+        }
+        if !extension.commands.is_empty() {
+            let commands: Vec<String> = extension
+                .commands
+                .iter()
+                .map(|command| format!(":{}", command))
+                .collect();
+            text.push_str(&wrap(&format!("Commands: {}", commands.join(", ")), 4));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::config::{
+        CodeFormatterConfig, CursorKeyMapsConfig, ExtensionsConfig, SpellCheckerConfig,
+    };
+
+    fn marked_text(line: &str, delimiter: char) -> Vec<&str> {
+        marked(line, delimiter)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect()
+    }
+
+    #[test]
+    fn finds_text_between_delimiters_without_whitespace() {
+        assert_eq!(marked_text("*tag*  see |link|", '*'), ["tag"]);
+        assert_eq!(marked_text("*tag*  see |link|", '|'), ["link"]);
+        assert_eq!(marked_text("a * b * and **", '*'), Vec::<&str>::new());
+        assert_eq!(marked_text("|é| |ü|", '|'), ["é", "ü"]);
+    }
+
+    #[test]
+    fn finds_the_link_under_the_cursor() {
+        let line = "See |keys| or |commands|.";
+        assert_eq!(link_at(line, 4).as_deref(), Some("keys"));
+        assert_eq!(link_at(line, 7).as_deref(), Some("keys"));
+        assert_eq!(link_at(line, 9).as_deref(), Some("keys"));
+        assert_eq!(link_at(line, 11), None);
+        assert_eq!(link_at(line, 16).as_deref(), Some("commands"));
+    }
+
+    #[test]
+    fn finds_tags_exactly_then_ignoring_case_then_fuzzily() {
+        let page = |name, text: &str| Page {
+            name,
+            text: text.into(),
+        };
+        let pages = [
+            page("index", "*index*\n|keys|"),
+            page("keys", "*keys*\nj    Down    *j*\nJ    Join    *J*"),
+        ];
+        assert_eq!(find(&pages, "J"), Some((1, 2)));
+        assert_eq!(find(&pages, "KEYS"), Some((1, 0)));
+        assert_eq!(find(&pages, "idx"), Some((0, 0)));
+        assert_eq!(find(&pages, "zzz"), None);
+    }
+
+    #[test]
+    fn reads_values_from_their_debug_form() {
+        let values = debug_values(&ExtensionsConfig::default());
+        assert_eq!(values["file_explorer.enabled"], "true");
+        assert_eq!(values["file_explorer.default_directory"], r#"".""#);
+        let mut formatter = CodeFormatterConfig::default();
+        formatter.formatters.insert("rust".into(), "rustfmt".into());
+        let values = debug_values(&formatter);
+        assert_eq!(values["languages"], r#"["rust", "javascript", "python"]"#);
+        assert_eq!(values["formatters"], r#"{ "rust" = "rustfmt" }"#);
+        let keymaps: CursorKeyMapsConfig =
+            toml::from_str("down = 'j'\nup = 'k'\nleft = 'h'\nright = 'l'").unwrap();
+        assert_eq!(debug_values(&keymaps)["down"], "'j'");
+        assert_eq!(debug_values(&keymaps)["first_line"], r#""gg""#);
+    }
+
+    #[test]
+    fn lists_every_table_of_the_config_with_its_options() {
+        let spell_checker = ExtensionOptions {
+            fields: schema::fields::<SpellCheckerConfig>(),
+            defaults: Some(Box::new(SpellCheckerConfig::default())),
+        };
+        let text = options_page(&[("spell_checker".into(), spell_checker)]);
+        for header in [
+            "*[general]*",
+            "*[cursor_keymaps]*",
+            "*[extensions.file_explorer]*",
+            "*[extensions.spell_checker]*",
+        ] {
+            assert!(text.contains(header), "{} is missing", header);
+        }
+        assert!(!text.contains("*[extensions]*"));
+        assert!(text.contains("down (required)"));
+        assert!(text.contains("undo_limit = 1000"));
+        assert!(text.contains(r#"sources = ["lsp", "buffer", "path"]"#));
+        assert!(text.contains(r#"language = "en-US""#));
+        assert!(text.contains("Underlines misspelled words."));
+    }
+}
@@ -1 +1,4 @@
+pub mod actions;
+pub mod commands;
 pub mod editor_app;
+pub mod help;
@@ -0,0 +1,251 @@
+//! `completion.rs`
+//!
+//! The completion popup: the items the sources offered where completion
+//! was asked for, narrowed down to those matching what is typed, with the
+//! documentation of the selected one beside them.
+
+use super::markdown::{self, PreviewTheme, Renderer};
+use super::popup;
+use crate::ports::completion::CompletionItem;
+use crate::ports::editor_buffer::EditorBufferPort;
+use crate::ports::window::Rect;
+use crate::toolshed::fuzzy::fuzzy_match;
+use crossterm::style;
+use std::{cmp, io};
+
+/// The most items shown at once, and the widest the list gets.
+const LIST_HEIGHT: usize = 10;
+const LIST_WIDTH: usize = 60;
+
+/// Widest and tallest the documentation gets, inside its border.
+const DOCUMENTATION_WIDTH: usize = 60;
+const DOCUMENTATION_HEIGHT: usize = 12;
+
+pub struct Completion {
+    /// The buffer and row completion was asked for in. Moving off the row
+    /// closes the popup.
+    buffer_id: usize,
+    row: usize,
+    items: Vec<CompletionItem>,
+    /// The items matching what is typed, best first, as indices into
+    /// `items` with the char indices of their labels matched.
+    shown: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+    /// The first item shown, when the items do not fit the popup.
+    offset: usize,
+    /// The most items kept after filtering.
+    max_items: usize,
+    /// The names of the sources, the one whose items go first first.
+    sources: Vec<String>,
+    /// Whether more items are still to come from a language server.
+    pub waiting: bool,
+}
+
+impl Completion {
+    pub fn new(buffer_id: usize, row: usize, max_items: usize, sources: Vec<String>) -> Self {
+        Self {
+            buffer_id,
+            row,
+            items: Vec::new(),
+            shown: Vec::new(),
+            selected: 0,
+            offset: 0,
+            max_items,
+            sources,
+            waiting: false,
+        }
+    }
+
+    pub fn buffer_id(&self) -> usize {
+        self.buffer_id
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Adds items, leaving out those with the label of one already there.
+    /// Items from elsewhere replace buffer words though, as they know more
+    /// about them.
+    pub fn add_items(&mut self, items: Vec<CompletionItem>) {
+        for item in items {
+            match self
+                .items
+                .iter()
+                .position(|known| known.label == item.label)
+            {
+                Some(index) if self.items[index].source == "buffer" && item.source != "buffer" => {
+                    self.items[index] = item;
+                }
+                Some(_) => {}
+                None => self.items.push(item),
+            }
+        }
+    }
+
+    /// Narrows the items down to those matching what is typed, given the
+    /// row up to the cursor.
+    pub fn filter(&mut self, line: &str) {
+        let mut shown: Vec<(i64, usize, Vec<usize>)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let typed = line.get(item.start..)?;
+                let (score, highlights) = fuzzy_match(typed, &item.label)?;
+                Some((score, index, highlights))
+            })
+            .collect();
+        // Shorter labels win ties, then the order of the sources
+        let items = &self.items;
+        let rank = |index: usize| {
+            let source = &items[index].source;
+            self.sources.iter().position(|name| name == source)
+        };
+        shown.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then(items[a.1].label.len().cmp(&items[b.1].label.len()))
+                .then(rank(a.1).cmp(&rank(b.1)))
+                .then(a.1.cmp(&b.1))
+        });
+        shown.truncate(self.max_items);
+        self.shown = shown
+            .into_iter()
+            .map(|(_, index, highlights)| (index, highlights))
+            .collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+    }
+
+    /// Moves the selection, wrapping around at either end.
+    pub fn move_selection(&mut self, delta: isize) {
+        let count = self.shown.len() as isize;
+        if count > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(count) as usize;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&CompletionItem> {
+        let (index, _) = self.shown.get(self.selected)?;
+        self.items.get(*index)
+    }
+
+    /// Draws the list below or above the cursor at `cursor`, with the
+    /// documentation of the selected item beside it. Returns where the list
+    /// was drawn, if there was room.
+    pub fn draw(
+        &mut self,
+        out: &mut dyn EditorBufferPort,
+        area: Rect,
+        cursor: (usize, usize),
+        theme: PreviewTheme,
+    ) -> io::Result<Option<Rect>> {
+        let widest = self
+            .shown
+            .iter()
+            .map(|(index, _)| {
+                let item = &self.items[*index];
+                let detail = item
+                    .detail
+                    .as_ref()
+                    .map_or(0, |detail| detail.chars().count() + 2);
+                item.label.chars().count() + detail + 2
+            })
+            .max();
+        let Some(widest) = widest else {
+            return Ok(None);
+        };
+        let height = cmp::min(self.shown.len(), LIST_HEIGHT);
+        // The items line up with the cursor, inside the border
+        let anchor = (cursor.0.saturating_sub(1), cursor.1);
+        let Some(rect) = popup::place(area, anchor, cmp::min(widest, LIST_WIDTH) + 2, height + 2)
+        else {
+            return Ok(None);
+        };
+        let list = popup::draw_frame(out, rect, None)?;
+
+        // Keep the selected item in view
+        self.offset = self
+            .offset
+            .clamp(self.selected.saturating_sub(list.height - 1), self.selected);
+        for row in 0..list.height {
+            let Some((index, highlights)) = self.shown.get(row + self.offset) else {
+                break;
+            };
+            let item = &self.items[*index];
+            out.move_cursor_to(list.x as u16, (list.y + row) as u16)?;
+            if row + self.offset == self.selected {
+                out.set_style(style::Attribute::Reverse)?;
+            }
+            let mut left = list.width;
+            out.append_char(' ');
+            left -= 1;
+            for (at, ch) in item.label.chars().enumerate().take(left) {
+                let highlighted = highlights.contains(&at);
+                if highlighted {
+                    out.set_style(style::Attribute::Bold)?;
+                }
+                out.append_char(ch);
+                if highlighted {
+                    out.set_style(style::Attribute::NormalIntensity)?;
+                }
+                left -= 1;
+            }
+            if let Some(detail) = &item.detail {
+                let detail: String = format!("  {}", detail).chars().take(left).collect();
+                left -= detail.chars().count();
+                out.set_style(style::Attribute::Dim)?;
+                out.append_str(&detail);
+                out.set_style(style::Attribute::NormalIntensity)?;
+            }
+            out.append_str(&" ".repeat(left));
+            out.set_style(style::Attribute::Reset)?;
+        }
+
+        if let Some(documentation) = self.selected().and_then(|item| item.documentation.clone()) {
+            self.draw_documentation(out, area, rect, &documentation, theme)?;
+        }
+        Ok(Some(rect))
+    }
+
+    /// Draws `documentation` to the right of the list at `list`, or to its
+    /// left when there is more room there.
+    fn draw_documentation(
+        &self,
+        out: &mut dyn EditorBufferPort,
+        area: Rect,
+        list: Rect,
+        documentation: &str,
+        theme: PreviewTheme,
+    ) -> io::Result<()> {
+        let right = (area.x + area.width).saturating_sub(list.x + list.width);
+        let left = list.x.saturating_sub(area.x);
+        let widest = documentation.lines().map(|line| line.chars().count()).max();
+        let width = cmp::min(widest.unwrap_or(0), DOCUMENTATION_WIDTH) + 2;
+        let width = cmp::min(width, cmp::max(right, left));
+        if width < 4 {
+            return Ok(());
+        }
+        let rows: Vec<&str> = documentation.lines().collect();
+        let lines = Renderer::render(&rows, width - 2, theme);
+        let height = cmp::min(lines.len(), DOCUMENTATION_HEIGHT) + 2;
+        let rect = Rect {
+            x: match right >= left {
+                true => list.x + list.width,
+                false => list.x - width,
+            },
+            y: list.y,
+            width,
+            height: cmp::min(height, (area.y + area.height).saturating_sub(list.y)),
+        };
+        if rect.height < 3 {
+            return Ok(());
+        }
+        let inner = popup::draw_frame(out, rect, None)?;
+        markdown::draw(out, &lines, 0, inner)
+    }
+}
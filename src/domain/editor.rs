@@ -1,195 +1,3672 @@
+use crate::adapters::buffer_list::BufferList;
+use crate::adapters::completion_sources::{self, is_word_char, word_start};
 use crate::adapters::editor_buffer::EditorBuffer;
 use crate::adapters::editor_rows::EditorRows;
+use crate::adapters::file_explorer::FileExplorer;
+use crate::adapters::file_finder::FileFinder;
+use crate::adapters::overlay_stack::OverlayStack;
+use crate::adapters::project_search::ProjectSearch;
 use crate::adapters::status_message::StatusMessage;
+use crate::adapters::undo::UndoHistory;
+use crate::adapters::window_layout::WindowLayout;
+use crate::ports::buffer_list::{Buffer, BufferListPort};
+use crate::ports::completion::{CompletionRequest, CompletionSourcePort};
+use crate::ports::config::{
+    CompletionConfig, Config, FileExplorerConfig, MarkdownPreviewConfig, SearchConfig,
+};
 use crate::ports::cursor::CursorControllerPort;
 use crate::ports::editor::EditorDomainPort;
 use crate::ports::editor_buffer::EditorBufferPort;
-use crate::ports::editor_rows::EditorRowsPort;
+use crate::ports::editor_rows::{EditorRowsPort, RowPort};
+use crate::ports::extension::{
+    BufferEdit, BufferView, Decoration, ExtensionOptions, ExtensionSummary,
+};
+use crate::ports::file_explorer::FileExplorerPort;
+use crate::ports::file_finder::FileFinderPort;
+use crate::ports::overlay::{OverlayKind, OverlayStackPort};
+use crate::ports::project_search::{ProjectSearchPort, SearchMatch, SearchOptions};
+use crate::ports::rpc::{RpcError, RpcMessage, INVALID_PARAMS, METHOD_NOT_FOUND};
 use crate::ports::status_message::StatusMessagePort;
+use crate::ports::undo::{Snapshot, UndoHistoryPort};
+use crate::ports::window::{
+    FocusDirection, Rect, SplitDirection, Window, WindowLayoutPort, WindowPlacement,
+};
+use crate::toolshed::fuzzy::fuzzy_match;
+use crate::toolshed::prose;
 use crate::{adapters::cursor::CursorController, log_info};
 
+use super::completion::Completion;
+use super::extensions::code_formatter::CodeFormatter;
+use super::extensions::git::GitIntegration;
+use super::extensions::language_servers::{Answer, LanguageServers, Location, Query};
+use super::extensions::plugin::Plugin;
+use super::extensions::scripting::Scripting;
+use super::extensions::spell_check::SpellCheck;
+use super::extensions::{self, ExtensionRegistry};
+use super::git_panel::{GitPanel, GitPanelRow};
+use super::markdown::{self, PreviewTheme, Renderer};
+use super::picker::{Picker, PickerItem};
+use super::popup;
+use super::workspace_edit::WorkspaceEdit;
+
 use crossterm::{event, queue, style, terminal};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 
 use std::cmp;
-use std::io::{self, stdout, Write};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path;
+
+/// Widest the file explorer panel gets; it never takes more than half the screen.
+const EXPLORER_WIDTH: usize = 30;
+
+/// The most corrections offered for a misspelled word.
+const SUGGESTION_LIMIT: usize = 20;
+
+/// The most places remembered to jump back to.
+const JUMP_LIMIT: usize = 100;
+
+/// Widest and tallest the hover popup gets, inside its border.
+const HOVER_WIDTH: usize = 80;
+const HOVER_HEIGHT: usize = 12;
+
+/// Widest the popup of the keys following a pending prefix key gets,
+/// inside its border.
+const KEY_HINTS_WIDTH: usize = 40;
+
+/// Where the items of the open picker come from.
+enum PickerSource {
+    Files(Box<dyn FileFinderPort>),
+    /// A project search, started again whenever the query changes.
+    Grep(Option<Box<dyn ProjectSearchPort>>),
+    /// Corrections for the word at byte range `word` of row `row`.
+    Spelling {
+        row: usize,
+        word: Range<usize>,
+        suggestions: Vec<String>,
+    },
+    /// Places a language server pointed to, such as the references of a
+    /// symbol.
+    Locations(Vec<PickerItem>),
+    /// The titles of the code actions a language server offered.
+    CodeActions(Vec<String>),
+    /// The actions of the command palette, run by the app once picked.
+    Palette(Vec<String>),
+}
 
 pub struct EditorDomain {
     window_size: (usize, usize),
     buffer: Box<dyn EditorBufferPort>,
-    cursor_controller: Box<dyn CursorControllerPort>,
-    editor_rows: Box<dyn EditorRowsPort>,
+    buffers: Box<dyn BufferListPort>,
+    /// The tab pages, each with its own window layout. The first one is
+    /// created along with the first buffer, as every window shows one.
+    tabs: Vec<Box<dyn WindowLayoutPort>>,
+    current_tab: usize,
+    next_window_id: usize,
     status_message: Box<dyn StatusMessagePort>,
+    command_line: Option<String>,
+    undo_limit: usize,
+    /// Where the left mouse button went down, as the start of a drag selection.
+    drag_origin: Option<(usize, usize)>,
+    /// Text yanked or deleted from a selection, put back by `paste`.
+    register: String,
+    /// The file explorer side panel, while it is open.
+    explorer: Option<Box<dyn FileExplorerPort>>,
+    /// Whether keys go to the file explorer rather than the focused window.
+    explorer_focused: bool,
+    /// The first explorer entry shown, when the entries do not fit the panel.
+    explorer_offset: usize,
+    explorer_config: FileExplorerConfig,
+    /// The file selected in the explorer, shown in place of the focused
+    /// window's buffer while browsing.
+    preview: Option<Box<dyn EditorRowsPort>>,
+    /// The popups open over the windows, in the order they are drawn.
+    overlays: Box<dyn OverlayStackPort>,
+    /// The popup list shown over the windows, while one is open.
+    picker: Option<Picker>,
+    picker_source: Option<PickerSource>,
+    search_config: SearchConfig,
+    /// The features enabled under `[extensions]`, such as git integration.
+    extensions: ExtensionRegistry,
+    /// The buffers edited since the extensions were last told about it.
+    edited: HashSet<usize>,
+    /// The command lines scripts ran, for the app to carry out.
+    script_commands: Vec<String>,
+    /// The index of the action picked from the command palette, for the
+    /// app to run.
+    palette_choice: Option<usize>,
+    /// Where the cursor was before jumping to definitions, as buffer id
+    /// and file position, the last jump last.
+    jumps: Vec<(usize, (usize, usize))>,
+    /// The Markdown shown in a popup at the cursor, about the symbol
+    /// under it.
+    hover: Option<String>,
+    /// The pending prefix key and the keys that may follow it, with the
+    /// names of their actions, while their popup is open.
+    key_hints: Option<(String, Vec<(String, String)>)>,
+    /// The completion popup at the cursor, while it is open.
+    completion: Option<Completion>,
+    /// The sources named in `[completion] sources`, other than the
+    /// language servers, in order.
+    completion_sources: Vec<Box<dyn CompletionSourcePort>>,
+    completion_config: CompletionConfig,
+    /// The git status popup, while it is open.
+    git_panel: Option<GitPanel>,
+    /// The buffer a commit message is being written in, with the root of
+    /// the repository it is committed to when saved.
+    commit_buffer: Option<(usize, path::PathBuf)>,
+    /// The buffer rendered in the Markdown preview pane, while it is open.
+    markdown_preview: Option<usize>,
+    /// The rows the preview renders when it is not live, as of the last save.
+    markdown_rows: Vec<String>,
+    markdown_config: MarkdownPreviewConfig,
 }
 
-impl EditorDomainPort for EditorDomain {
-    fn new(window_size: (usize, usize)) -> Self {
-        log_info!(
-            "Initializing editor domain with window size: {:?}",
-            window_size
-        );
-        Self {
-            window_size,
-            buffer: Box::new(EditorBuffer::new()),
-            cursor_controller: Box::new(CursorController::new(window_size)),
-            editor_rows: Box::new(EditorRows::new()),
-            status_message: Box::new(StatusMessage::new("HELP: Ctrl-Q = Quit".into())),
+impl EditorDomain {
+    /// The window layout of the current tab page.
+    fn layout(&self) -> &dyn WindowLayoutPort {
+        &*self.tabs[self.current_tab]
+    }
+
+    fn layout_mut(&mut self) -> &mut dyn WindowLayoutPort {
+        &mut *self.tabs[self.current_tab]
+    }
+
+    /// The screen area between the tab line and the message bar.
+    fn screen_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 1,
+            width: self.window_size.0,
+            height: self.window_size.1 + 1,
         }
     }
 
-    fn draw_rows(&mut self) -> io::Result<()> {
-        // Define the screen dimensions
-        let screen_rows = self.window_size.1;
-        let screen_columns = self.window_size.0;
+    /// The screen area shared by the windows: everything but the tab line,
+    /// the message bar, the file explorer and the Markdown preview.
+    fn layout_area(&self) -> Rect {
+        let area = self.screen_area();
+        let area = match self.explorer_rect() {
+            // One column is left for the separator next to the explorer
+            Some(panel) => Rect {
+                x: panel.width + 1,
+                width: area.width.saturating_sub(panel.width + 1),
+                ..area
+            },
+            None => area,
+        };
+        match self.markdown_preview_rect() {
+            Some(pane) => Rect {
+                width: area.width.saturating_sub(pane.width + 1),
+                ..area
+            },
+            None => area,
+        }
+    }
 
-        // Iterate through each row on the screen
-        for i in 0..screen_rows {
-            // Calculate the corresponding row in the file based on the current scroll position
-            let file_row = i + self.cursor_controller.get_row_offset();
+    /// The area of the file explorer panel, including its status line.
+    fn explorer_rect(&self) -> Option<Rect> {
+        self.explorer.as_ref()?;
+        Some(Rect {
+            x: 0,
+            y: 1,
+            width: cmp::min(EXPLORER_WIDTH, self.window_size.0 / 2),
+            height: self.window_size.1 + 1,
+        })
+    }
 
-            // Check if the current screen row has a corresponding file row
-            if file_row >= self.editor_rows.number_of_rows() {
-                // If the file has no content and we're at the middle third of the screen
-                if self.editor_rows.number_of_rows() == 0 && i == screen_rows / 3 {
-                    // Prepare a welcome message
-                    let mut welcome =
-                        format!("BARN Editor --- Version {}", env!("CARGO_PKG_VERSION"));
+    /// The area of the Markdown preview pane, including its status line. It
+    /// takes the right half of the space the explorer leaves.
+    fn markdown_preview_rect(&self) -> Option<Rect> {
+        self.markdown_preview?;
+        let explorer = self.explorer_rect().map_or(0, |panel| panel.width + 1);
+        let width = self.window_size.0.saturating_sub(explorer) / 2;
+        Some(Rect {
+            x: self.window_size.0 - width,
+            y: 1,
+            width,
+            height: self.window_size.1 + 1,
+        })
+    }
 
-                    // Truncate the message if it exceeds screen width
-                    if welcome.len() > screen_columns {
-                        welcome.truncate(screen_columns);
-                    }
+    /// Resolves the configured default directory, falling back to the
+    /// current one if it does not exist.
+    fn default_explorer_root(&self) -> path::PathBuf {
+        let directory = &self.explorer_config.default_directory;
+        let root = match directory.strip_prefix('~') {
+            Some(rest) => env::var_os("HOME")
+                .map(|home| path::PathBuf::from(home).join(rest.trim_start_matches('/'))),
+            None => Some(directory.into()),
+        };
+        root.filter(|root| root.is_dir())
+            .unwrap_or_else(|| ".".into())
+    }
 
-                    // Calculate padding to center the message
-                    let mut padding = (screen_columns - welcome.len()) / 2;
-                    // Draw a tilde before the welcome message if there's space
-                    if padding != 0 {
-                        self.buffer.append_char('~');
-                        padding -= 1; // Reduce padding by one for the tilde
-                    }
+    fn explorer_mut(&mut self) -> io::Result<&mut dyn FileExplorerPort> {
+        match self.explorer.as_mut() {
+            Some(explorer) => Ok(&mut **explorer),
+            None => Err(io::Error::other("The file explorer is not open")),
+        }
+    }
 
-                    // Add spaces to pad out the message
-                    (0..padding).for_each(|_| self.buffer.append_char(' '));
+    /// Loads the file selected in the explorer for previewing, if previews
+    /// are enabled and the explorer has focus.
+    fn update_preview(&mut self) {
+        let selected = self
+            .explorer
+            .as_ref()
+            .filter(|_| self.explorer_focused && self.explorer_config.preview_files)
+            .and_then(|explorer| explorer.selected_entry())
+            .filter(|entry| !entry.is_dir);
+        let Some(entry) = selected else {
+            self.preview = None;
+            return;
+        };
+        let current = self
+            .preview
+            .as_ref()
+            .and_then(|preview| preview.get_file_name());
+        if current != Some(&entry.path) {
+            // Files that cannot be read as text are not previewed
+            self.preview = EditorRows::from_file(entry.path)
+                .ok()
+                .map(|rows| Box::new(rows) as Box<dyn EditorRowsPort>);
+        }
+    }
 
-                    // Append the welcome message
-                    self.buffer.append_str(&welcome);
-                } else {
-                    // For empty file rows, draw a tilde
-                    self.buffer.append_char('~');
+    /// Returns what the extensions are shown of buffer `buffer`, as shown
+    /// by `window`.
+    fn buffer_view<'a>(window: &Window, buffer: &'a Buffer) -> BufferView<'a> {
+        BufferView {
+            id: buffer.id,
+            rows: &*buffer.editor_rows,
+            cursor: window.cursor_controller.get_file_position(),
+        }
+    }
+
+    /// Returns the git integration, unless it is disabled.
+    fn git(&self) -> io::Result<&GitIntegration> {
+        self.extensions
+            .get::<GitIntegration>()
+            .ok_or_else(|| io::Error::other("Git integration is disabled"))
+    }
+
+    /// Returns the root of the repository holding the current buffer's
+    /// file, or the working directory if it has none.
+    fn repository_root(&self) -> io::Result<path::PathBuf> {
+        let file = self.focused_ref().1.editor_rows.get_file_name();
+        self.git()?
+            .repository_root(file.map(path::PathBuf::as_path))
+    }
+
+    /// Lists the repository's changes again in the git panel, if it is open.
+    fn refresh_git_panel(&mut self) -> io::Result<()> {
+        let Some(panel) = self.git_panel.as_mut() else {
+            return Ok(());
+        };
+        let git = self
+            .extensions
+            .get::<GitIntegration>()
+            .ok_or_else(|| io::Error::other("Git integration is disabled"))?
+            .git();
+        let statuses = git.status(&panel.root)?;
+        let root = panel.root.clone();
+        panel.set_statuses(&statuses, |file, staged| {
+            git.diff_hunks(&root, file, staged).unwrap_or_default()
+        });
+        Ok(())
+    }
+
+    /// Catches up with a new commit: change markers and blame are relative
+    /// to `HEAD`.
+    fn after_commit(&mut self) {
+        if let Some(git) = self.extensions.get_mut::<GitIntegration>() {
+            let buffers: Vec<BufferView> = self
+                .buffers
+                .buffers()
+                .iter()
+                .map(|buffer| BufferView {
+                    id: buffer.id,
+                    rows: &*buffer.editor_rows,
+                    cursor: (0, 0),
+                })
+                .collect();
+            git.after_commit(&buffers);
+        }
+        if let Err(e) = self.refresh_git_panel() {
+            self.set_message(e.to_string());
+        }
+    }
+
+    /// Commits the changes staged in the repository at `root` with the
+    /// message saved in the commit buffer, which is closed. Returns a
+    /// message saying what was committed.
+    fn commit_buffer_saved(&mut self, root: &path::Path) -> io::Result<String> {
+        let message: Vec<String> = self
+            .focused()
+            .1
+            .editor_rows
+            .get_rows()
+            .into_iter()
+            .filter(|row| !row.starts_with('#'))
+            .collect();
+        let message = message.join("\n").trim().to_string();
+        if message.is_empty() {
+            return Err(io::Error::other(
+                "Aborting commit due to empty commit message",
+            ));
+        }
+        let hash = self.git()?.git().commit(root, &format!("{}\n", message))?;
+        self.commit_buffer = None;
+        self.close_buffer(true)?;
+        self.after_commit();
+        let summary = message.lines().next().unwrap_or_default();
+        Ok(format!("Committed {}: {}", hash, summary))
+    }
+
+    /// Returns the widths of the columns the extensions draw in front of
+    /// the rows of buffer `buffer_id`, in a window `width` columns wide.
+    /// The columns take at most half of the window.
+    fn gutter_widths(&self, buffer_id: usize, width: usize) -> Vec<usize> {
+        let Some(buffer) = self.buffers.get(buffer_id) else {
+            return Vec::new();
+        };
+        let view = BufferView {
+            id: buffer_id,
+            rows: &*buffer.editor_rows,
+            cursor: (0, 0),
+        };
+        let mut left = width / 2;
+        self.extensions
+            .iter()
+            .map(|extension| {
+                let width = cmp::min(extension.gutter_width(&view), left);
+                left -= width;
+                width
+            })
+            .collect()
+    }
+
+    /// Returns the part of a window's `rect` left for text once the columns
+    /// in front of the rows are taken out.
+    fn text_rect(&self, buffer_id: usize, rect: Rect) -> Rect {
+        let gutter: usize = self.gutter_widths(buffer_id, rect.width).iter().sum();
+        Rect {
+            x: rect.x + gutter,
+            width: rect.width - gutter,
+            ..rect
+        }
+    }
+
+    /// Draws the gutter decorations of the rows shown by `window` into the
+    /// columns of `rect`, which leaves out the status line like the rows do.
+    fn draw_gutter(
+        out: &mut dyn EditorBufferPort,
+        window: &Window,
+        decorations: &[Decoration],
+        rect: Rect,
+    ) -> io::Result<()> {
+        if rect.width == 0 {
+            return Ok(());
+        }
+        let row_offset = window.cursor_controller.get_row_offset();
+        for i in 0..rect.height.saturating_sub(1) {
+            out.move_cursor_to(rect.x as u16, (rect.y + i) as u16)?;
+            out.append_str(&" ".repeat(rect.width));
+        }
+        for decoration in decorations {
+            let Decoration::Gutter {
+                row,
+                column,
+                text,
+                color,
+            } = decoration
+            else {
+                continue;
+            };
+            let Some(i) = row.checked_sub(row_offset) else {
+                continue;
+            };
+            if i + 1 >= rect.height || *column >= rect.width {
+                continue;
+            }
+            let text: String = text.chars().take(rect.width - column).collect();
+            out.move_cursor_to((rect.x + column) as u16, (rect.y + i) as u16)?;
+            out.set_color(*color)?;
+            out.append_str(&text);
+            out.set_color(style::Color::Reset)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the row and byte range of the word under or just before the
+    /// cursor.
+    fn word_at_cursor(&self) -> Option<(usize, Range<usize>)> {
+        let (window, buffer) = self.focused_ref();
+        let (x, y) = window.cursor_controller.get_file_position();
+        if y >= buffer.editor_rows.number_of_rows() {
+            return None;
+        }
+        let row = buffer.editor_rows.get_row(y);
+        prose::words(row, 0..row.len())
+            .into_iter()
+            .find(|word| word.start <= x && x <= word.end)
+            .map(|word| (y, word))
+    }
+
+    /// Replaces byte range `range` of row `row` with `text`, keeping the
+    /// cursor on the same char.
+    fn replace_text(&mut self, row: usize, range: Range<usize>, text: &str) {
+        // Check the edit lands in the buffer before recording an undo step
+        let (_, buffer) = self.focused();
+        let rows = &buffer.editor_rows;
+        if row >= rows.number_of_rows() || rows.get_row(row).get(range.clone()).is_none() {
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let (window, buffer) = self.focused();
+        let mut rows = buffer.editor_rows.get_rows();
+        rows[row].replace_range(range.clone(), text);
+        buffer.editor_rows.set_rows(rows);
+
+        let (x, y) = window.cursor_controller.get_file_position();
+        if y == row && x >= range.end {
+            window
+                .cursor_controller
+                .set_cursor_position(x - range.len() + text.len(), y);
+        }
+    }
+
+    /// Applies an edit an extension made to the focused buffer.
+    fn apply_edit(&mut self, edit: BufferEdit) {
+        match edit {
+            BufferEdit::Replace { row, range, text } => self.replace_text(row, range, &text),
+            BufferEdit::ReplaceAll(rows) => self.replace_rows(rows),
+        }
+    }
+
+    /// Replaces every row of the focused buffer as one change, keeping the
+    /// cursor where it was as far as the new rows reach.
+    fn replace_rows(&mut self, rows: Vec<String>) {
+        if !self.begin_edit() {
+            return;
+        }
+        let (window, buffer) = self.focused();
+        let (x, y) = window.cursor_controller.get_file_position();
+        buffer.editor_rows.set_rows(rows);
+        buffer.undo_history.end_change();
+        self.place_cursor(x, y);
+    }
+
+    /// Moves the cursor of the focused window as close to byte column `x`
+    /// of row `y` as the rows of its buffer reach.
+    fn place_cursor(&mut self, x: usize, y: usize) {
+        let id = self.layout().focused().buffer_id;
+        self.place_buffer_cursor(id, x, y);
+    }
+
+    /// Returns the cursor of buffer `id`: the focused window's if it shows
+    /// the buffer, or where the buffer was last left otherwise.
+    fn buffer_cursor(&mut self, id: usize) -> Option<&mut Box<dyn CursorControllerPort>> {
+        let window = self.tabs[self.current_tab].focused_mut();
+        if window.buffer_id == id {
+            return Some(&mut window.cursor_controller);
+        }
+        self.buffers
+            .get_mut(id)
+            .map(|buffer| &mut buffer.cursor_controller)
+    }
+
+    /// Moves the cursor of buffer `id`, as `buffer_cursor` finds it, as
+    /// close to byte column `x` of row `y` as the rows of the buffer reach.
+    fn place_buffer_cursor(&mut self, id: usize, x: usize, y: usize) {
+        let Some(buffer) = self.buffers.get(id) else {
+            return;
+        };
+        let rows = &buffer.editor_rows;
+        let y = cmp::min(y, rows.number_of_rows().saturating_sub(1));
+        let x = match rows.number_of_rows() {
+            0 => 0,
+            _ => {
+                let row = rows.get_row(y);
+                let mut x = cmp::min(x, row.len());
+                while !row.is_char_boundary(x) {
+                    x -= 1;
                 }
-            } else {
-                // Get the content of the file row and the column offset for horizontal scrolling
-                let row = self.editor_rows.get_render(file_row);
-                let col_offset = self.cursor_controller.get_col_offset();
-                // Determine the length of the text to be displayed on the screen
-                let len = cmp::min(row.len().saturating_sub(col_offset), screen_columns);
-                let start = if len == 0 { 0 } else { col_offset };
-                // Append the part of the row that fits on the screen
-                self.buffer.append_str(&row[start..start + len]);
+                x
+            }
+        };
+        if let Some(cursor) = self.buffer_cursor(id) {
+            cursor.set_cursor_position(x, y);
+        }
+    }
+
+    /// Applies what the scripts changed since the last time: their edits
+    /// of the focused buffer, as one change each, and cursor moves. Their
+    /// messages are shown and their commands left for the app to run.
+    fn apply_script_results(&mut self) {
+        let Some(scripting) = self.extensions.get_mut::<Scripting>() else {
+            return;
+        };
+        for result in scripting.take_results() {
+            let focused = self.layout().focused().buffer_id == result.buffer_id;
+            if result.edited && !focused {
+                self.set_message("Scripts can only edit the focused buffer".into());
+            } else if result.edited && self.focused().1.editor_rows.get_rows() != result.rows {
+                self.replace_rows(result.rows);
+            }
+            if result.moved && focused {
+                self.place_cursor(result.cursor.0, result.cursor.1);
+            }
+            for message in result.messages {
+                self.set_message(message);
             }
+            self.script_commands.extend(result.commands);
+        }
+    }
+
+    /// Calls `ask` with the language servers and the focused buffer, to
+    /// send a request about it.
+    fn ask_language_server(
+        &mut self,
+        ask: impl FnOnce(&mut LanguageServers, &BufferView) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let window = self.tabs[self.current_tab].focused();
+        let buffer = self
+            .buffers
+            .get(window.buffer_id)
+            .ok_or_else(|| io::Error::other("No buffer"))?;
+        let view = Self::buffer_view(window, buffer);
+        let servers = self
+            .extensions
+            .get_mut::<LanguageServers>()
+            .ok_or_else(|| io::Error::other("Language servers are disabled"))?;
+        ask(servers, &view)
+    }
 
-            // Clear to the end of the line to remove any previous content
-            queue!(
-                self.buffer,
-                terminal::Clear(terminal::ClearType::UntilNewLine)
-            )?;
+    /// Returns the selection of the focused window as a half-open range of
+    /// byte offsets and rows, or `None` if nothing is selected.
+    fn selected_range(&mut self) -> Option<((usize, usize), (usize, usize))> {
+        let (start, (x, y)) = self.clamped_selection()?;
+        let row = self.focused_ref().1.editor_rows.get_row(y);
+        Some((start, (Self::end_of_char(row, x), y)))
+    }
+
+    /// Replaces every row of buffer `id` as one change, whether or not a
+    /// window shows it.
+    fn replace_buffer_rows(&mut self, id: usize, rows: Vec<String>) {
+        if self.layout().focused().buffer_id == id {
+            self.replace_rows(rows);
+            return;
+        }
+        let Some(buffer) = self.buffers.get_mut(id) else {
+            return;
+        };
+        let snapshot = Snapshot {
+            rows: buffer.editor_rows.get_rows(),
+            cursor: buffer.cursor_controller.get_file_position(),
+        };
+        buffer.undo_history.end_change();
+        buffer.undo_history.record(snapshot);
+        buffer.editor_rows.set_rows(rows);
+        buffer.undo_history.end_change();
+        self.edited.insert(id);
+    }
 
-            // Move to the next line
-            self.buffer.append_str("\r\n");
-            stdout().flush()?; // Flush the buffer to apply changes to the terminal
+    /// Makes the edits a language server asked for, those of each file as
+    /// one change of its buffer. Files that are not open are opened, to be
+    /// looked over and saved. Nothing is changed if any edit fails.
+    fn apply_workspace_edit(&mut self, edit: WorkspaceEdit) -> io::Result<()> {
+        /// Where the rows of an edited file come from.
+        enum Source {
+            Buffer(usize),
+            File(EditorRows),
+        }
+
+        // Every file's new rows are worked out before any buffer changes
+        let mut changes: Vec<(path::PathBuf, Source, Vec<String>)> = Vec::new();
+        for file_edit in &edit.files {
+            // A file edited again is edited on top of its previous edits
+            if let Some((_, _, rows)) = changes
+                .iter_mut()
+                .find(|(file, ..)| *file == file_edit.file)
+            {
+                *rows = file_edit.apply(rows, edit.encoding)?;
+                continue;
+            }
+            let (source, rows) = match self.buffers.find_by_path(&file_edit.file) {
+                Some(id) => {
+                    let rows = &self
+                        .buffers
+                        .get(id)
+                        .ok_or_else(|| io::Error::other("No buffer"))?
+                        .editor_rows;
+                    if rows.is_read_only() {
+                        return Err(io::Error::other(format!(
+                            "{} is read-only",
+                            file_edit.file.display()
+                        )));
+                    }
+                    (Source::Buffer(id), rows.get_rows())
+                }
+                None => {
+                    let rows = EditorRows::from_file(file_edit.file.clone())?;
+                    let current = rows.get_rows();
+                    (Source::File(rows), current)
+                }
+            };
+            let edited = file_edit.apply(&rows, edit.encoding)?;
+            if edited != rows {
+                changes.push((file_edit.file.clone(), source, edited));
+            }
         }
 
+        let count = changes.len();
+        for (_, source, rows) in changes {
+            let id = match source {
+                Source::Buffer(id) => id,
+                Source::File(rows) => self.add_buffer(rows),
+            };
+            self.replace_buffer_rows(id, rows);
+        }
+        // Opening files made them the current buffer
+        self.sync_current_buffer();
+        if count > 1 {
+            self.set_message(format!("Changed {} files", count));
+        }
         Ok(())
     }
 
-    fn draw_status_bar(&mut self) {
-        // Set the style of the buffer to Reverse (inverts the foreground and background colors)
-        self.buffer.set_style(style::Attribute::Reverse).unwrap();
-        self.buffer.set_style(style::Attribute::Bold).unwrap();
+    /// Shows what the language servers answered since the last time.
+    fn apply_language_answers(&mut self) {
+        let Some(servers) = self.extensions.get_mut::<LanguageServers>() else {
+            return;
+        };
+        for answer in servers.take_answers() {
+            let result = match answer {
+                Ok(Answer::Hover(text)) if text.trim().is_empty() => {
+                    Err(io::Error::other("No information here"))
+                }
+                Ok(Answer::Hover(text)) => {
+                    self.hover = Some(text);
+                    Ok(())
+                }
+                Ok(Answer::Locations(query, locations)) => self.show_locations(query, &locations),
+                Ok(Answer::Edit(edit, None)) => self.apply_workspace_edit(edit),
+                Ok(Answer::Edit(edit, Some(request))) => {
+                    let made = self.apply_workspace_edit(edit);
+                    // The server hears of the changes before it is answered
+                    if made.is_ok() {
+                        self.run_change_hooks();
+                    }
+                    let answered = match self.extensions.get_mut::<LanguageServers>() {
+                        Some(servers) => servers.answer_edit(request, &made),
+                        None => Ok(()),
+                    };
+                    made.and(answered)
+                }
+                Ok(Answer::CodeActions(titles)) if titles.is_empty() => {
+                    Err(io::Error::other("No code actions here"))
+                }
+                Ok(Answer::CodeActions(titles)) => {
+                    self.picker = Some(Picker::new("Code actions"));
+                    self.picker_source = Some(PickerSource::CodeActions(titles));
+                    self.set_picker_query("");
+                    Ok(())
+                }
+                Ok(Answer::Completions(items)) => {
+                    // The popup may have been closed while the server was busy
+                    if let Some(completion) = self.completion.as_mut() {
+                        completion.waiting = false;
+                        completion.add_items(items);
+                        self.filter_completion();
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                self.set_message(e.to_string());
+            }
+        }
+    }
 
-        // Retrieve the file name, default to "[No Name]" if not available
-        let file_name = self
-            .editor_rows
-            .get_file_name()
-            .and_then(|path| path.file_name())
-            .and_then(|name| name.to_str())
-            .unwrap_or("[No Name]");
+    /// Returns the text of the row a location is in, from its buffer if
+    /// the file is open.
+    fn location_row(&self, location: &Location) -> Option<String> {
+        match self.buffers.find_by_path(&location.file) {
+            Some(id) => {
+                let rows = &self.buffers.get(id)?.editor_rows;
+                (location.row < rows.number_of_rows()).then(|| rows.get_row(location.row).into())
+            }
+            None => fs::read_to_string(&location.file)
+                .ok()?
+                .lines()
+                .nth(location.row)
+                .map(String::from),
+        }
+    }
 
-        // Create a string with the current line and total lines information
-        let line_info = format!(
-            "{}/{}",
-            self.cursor_controller.get_cursor_position().1 + 1,
-            self.editor_rows.number_of_rows()
-        );
+    /// Jumps to the only definition found, or lists the places found to
+    /// pick one from.
+    fn show_locations(&mut self, query: Query, locations: &[Location]) -> io::Result<()> {
+        let directory = env::current_dir()?;
+        let items: Vec<PickerItem> = locations
+            .iter()
+            .map(|location| {
+                let row = self.location_row(location).unwrap_or_default();
+                let x = location.encoding.byte_offset(&row, location.character);
+                let name = location
+                    .file
+                    .strip_prefix(&directory)
+                    .unwrap_or(&location.file);
+                PickerItem {
+                    label: format!("{}:{}: {}", name.display(), location.row + 1, row.trim()),
+                    highlights: Vec::new(),
+                    path: location.file.clone(),
+                    position: Some((x, location.row)),
+                    index: None,
+                }
+            })
+            .collect();
 
-        // Create a string with file information and the number of lines
-        let info = format!(
-            "{} -- {} lines",
-            file_name,
-            self.editor_rows.number_of_rows()
-        );
-        // Determine the length of the info, limiting it to the window width minus the length of line_info
-        let info_len = cmp::min(info.len(), self.window_size.0 - line_info.len());
+        let title = match query {
+            Query::Definition => "Definitions",
+            _ => "References",
+        };
+        match items.as_slice() {
+            [] => Err(io::Error::other(format!("No {} found", title.to_lowercase()))),
+            [item] if query == Query::Definition => {
+                let (path, (x, y)) = (item.path.clone(), item.position.unwrap_or_default());
+                self.push_jump();
+                self.open_file(Some(path))?;
+                self.place_cursor(x, y);
+                Ok(())
+            }
+            _ => {
+                self.picker = Some(Picker::new(title));
+                self.picker_source = Some(PickerSource::Locations(items));
+                self.set_picker_query("");
+                Ok(())
+            }
+        }
+    }
 
-        // Construct the status bar string.
-        // Format the file info to occupy the left side (info_len width) and line info on the right
-        let status_bar = format!(
-            "{:<info_len$}{:>line_info_len$}",
-            info,
-            line_info,
-            info_len = info_len,
-            line_info_len = self.window_size.0 - info_len
+    /// Remembers where the cursor is, to jump back to.
+    fn push_jump(&mut self) {
+        let window = self.layout().focused();
+        let jump = (
+            window.buffer_id,
+            window.cursor_controller.get_file_position(),
         );
+        if self.jumps.len() == JUMP_LIMIT {
+            self.jumps.remove(0);
+        }
+        self.jumps.push(jump);
+    }
 
-        // Append the constructed status bar string to the buffer
-        self.buffer.append_str(&status_bar);
-        // Reset the buffer's style to default
-        self.buffer.set_style(style::Attribute::Reset).unwrap();
-        // Append a new line character to move to the next line
-        self.buffer.append_str("\r\n");
+    /// Draws the hover popup below the cursor, or above it when there is
+    /// more room there. Returns where it was drawn, if there was room.
+    fn draw_hover(&mut self) -> io::Result<Option<Rect>> {
+        let Some(text) = &self.hover else {
+            return Ok(None);
+        };
+        let area = self.screen_area();
+        let (x, y) = self.get_cursor_position();
+        let widest = text.lines().map(|line| line.chars().count()).max();
+        let width = cmp::min(cmp::min(widest.unwrap_or(0), HOVER_WIDTH) + 2, area.width);
+        if width < 4 {
+            return Ok(None);
+        }
+        let rows: Vec<&str> = text.lines().collect();
+        let theme = PreviewTheme::named(&self.markdown_config.preview_theme);
+        let lines = Renderer::render(&rows, width - 2, theme);
+        let height = cmp::min(lines.len(), HOVER_HEIGHT) + 2;
+        let Some(rect) = popup::place(area, (x, y), width, height) else {
+            return Ok(None);
+        };
+        let inner = popup::draw_frame(&mut *self.buffer, rect, None)?;
+        markdown::draw(&mut *self.buffer, &lines, 0, inner)?;
+        Ok(Some(rect))
     }
 
-    fn draw_message_bar(&mut self) {
-        queue!(
-            self.buffer,
-            terminal::Clear(terminal::ClearType::UntilNewLine)
-        )
-        .unwrap();
-        if let Some(msg) = self.status_message.message() {
+    /// Draws the keys that may follow the pending prefix key in the bottom
+    /// right corner, above the status bar. Returns where it was drawn, if
+    /// there was room.
+    fn draw_key_hints(&mut self) -> io::Result<Option<Rect>> {
+        let Some((prefix, hints)) = &self.key_hints else {
+            return Ok(None);
+        };
+        let area = self.screen_area();
+        let keys_width = hints.iter().map(|(keys, _)| keys.chars().count()).max();
+        let keys_width = keys_width.unwrap_or(0);
+        let widest = hints.iter().map(|(_, name)| name.chars().count()).max();
+        let width = keys_width + 2 + widest.unwrap_or(0);
+        let width = cmp::min(cmp::max(width, prefix.chars().count() + 2), KEY_HINTS_WIDTH) + 2;
+        // The status bar stays visible below the popup
+        let height = cmp::min(hints.len() + 2, area.height.saturating_sub(1));
+        if width > area.width || width < 4 || height < 3 {
+            return Ok(None);
+        }
+        let rect = Rect {
+            x: area.x + area.width - width,
+            y: area.y + area.height - 1 - height,
+            width,
+            height,
+        };
+        let inner = popup::draw_frame(&mut *self.buffer, rect, Some(prefix))?;
+        for (row, (keys, name)) in hints.iter().take(inner.height).enumerate() {
+            let line = format!("{:<width$}  {}", keys, name, width = keys_width);
             self.buffer
-                .append_str(&msg[..cmp::min(self.window_size.0, msg.len())]);
+                .move_cursor_to(inner.x as u16, (inner.y + row) as u16)?;
+            self.buffer
+                .append_str(&line.chars().take(inner.width).collect::<String>());
         }
+        Ok(Some(rect))
     }
 
-    fn get_buffer(&mut self) -> &mut dyn EditorBufferPort {
-        &mut *self.buffer
+    /// Stacks the popups opened since the last time on top of the others,
+    /// and takes those closed off the stack.
+    fn sync_overlays(&mut self) {
+        let open = [
+            (OverlayKind::Hover, self.hover.is_some()),
+            (OverlayKind::KeyHints, self.key_hints.is_some()),
+            (OverlayKind::Completion, self.completion.is_some()),
+            (OverlayKind::GitPanel, self.git_panel.is_some()),
+            (OverlayKind::Picker, self.picker.is_some()),
+        ];
+        for (kind, is_open) in open {
+            match is_open {
+                true => self.overlays.open(kind),
+                false => self.overlays.close(kind),
+            }
+        }
     }
 
-    fn get_window_size(&self) -> io::Result<(usize, usize)> {
-        terminal::size().map(|(w, h)| (w as usize, h as usize - 2))
+    /// Draws overlay `kind`, returning where it was drawn if there was room.
+    fn draw_overlay(&mut self, kind: OverlayKind) -> io::Result<Option<Rect>> {
+        let area = self.screen_area();
+        match kind {
+            OverlayKind::Hover => self.draw_hover(),
+            OverlayKind::KeyHints => self.draw_key_hints(),
+            OverlayKind::Completion => {
+                let cursor = self.get_cursor_position();
+                let theme = PreviewTheme::named(&self.markdown_config.preview_theme);
+                match self.completion.as_mut() {
+                    Some(completion) => completion.draw(&mut *self.buffer, area, cursor, theme),
+                    None => Ok(None),
+                }
+            }
+            OverlayKind::GitPanel => match self.git_panel.as_mut() {
+                Some(panel) => panel.draw(&mut *self.buffer, area),
+                None => Ok(None),
+            },
+            OverlayKind::Picker => match self.picker.as_mut() {
+                Some(picker) => picker.draw(&mut *self.buffer, area),
+                None => Ok(None),
+            },
+        }
     }
 
-    fn get_cursor_position(&self) -> (usize, usize) {
-        self.cursor_controller.get_cursor_position()
+    /// Returns the row of the focused window up to the cursor, with the
+    /// buffer id and row number.
+    fn line_before_cursor(&self) -> (usize, usize, String) {
+        let (window, buffer) = self.focused_ref();
+        let (x, y) = window.cursor_controller.get_file_position();
+        let rows = &buffer.editor_rows;
+        let row = match y < rows.number_of_rows() {
+            true => rows.get_row(y),
+            false => "",
+        };
+        (buffer.id, y, row.get(..x).unwrap_or(row).to_string())
     }
 
-    fn set_cursor_position(&mut self, x: usize, y: usize) {
-        self.cursor_controller.set_cursor_position(x, y);
+    /// Opens the completion popup with the items of every source, asking
+    /// the language server of the focused buffer for more, as typing
+    /// `trigger` set it off if given.
+    fn open_completion(&mut self, trigger: Option<char>) {
+        let (buffer_id, y, line) = self.line_before_cursor();
+        let directory = env::current_dir().unwrap_or_default();
+        let mut completion = Completion::new(
+            buffer_id,
+            y,
+            self.completion_config.max_items,
+            self.completion_config.sources.clone(),
+        );
+        // The words of the current buffer come first
+        let mut buffers: Vec<&dyn EditorRowsPort> = Vec::new();
+        let all = self.buffers.buffers();
+        let (current, others): (Vec<&Buffer>, Vec<&Buffer>) =
+            all.iter().partition(|buffer| buffer.id == buffer_id);
+        buffers.extend(
+            current
+                .into_iter()
+                .chain(others)
+                .map(|buffer| &*buffer.editor_rows),
+        );
+        let request = CompletionRequest {
+            line: &line,
+            buffers,
+            directory: &directory,
+        };
+        for source in &self.completion_sources {
+            completion.add_items(source.complete(&request));
+        }
+
+        if self
+            .completion_config
+            .sources
+            .iter()
+            .any(|name| name == "lsp")
+        {
+            // The server has to know about what was just typed
+            self.run_change_hooks();
+            let asked =
+                self.ask_language_server(|servers, buffer| servers.complete(buffer, trigger));
+            completion.waiting = asked.is_ok();
+        }
+        self.completion = Some(completion);
+        self.filter_completion();
     }
 
-    fn move_cursor(&mut self, direction: event::KeyCode) {
-        self.cursor_controller
-            .move_cursor(direction, &*self.editor_rows);
+    /// Returns whether typing `ch` in the focused buffer has its language
+    /// server complete what comes next.
+    fn triggers_completion(&self, ch: char) -> bool {
+        let (window, buffer) = self.focused_ref();
+        let view = Self::buffer_view(window, buffer);
+        let lsp = self
+            .completion_config
+            .sources
+            .iter()
+            .any(|name| name == "lsp");
+        let servers = self.extensions.get::<LanguageServers>();
+        lsp && servers.is_some_and(|servers| servers.triggers_completion(&view, ch))
     }
 
-    fn scroll(&mut self) {
-        self.cursor_controller.scroll(&*self.editor_rows);
+    /// Narrows the completion popup down to what is typed, closing it when
+    /// the cursor left its row or nothing matches any more.
+    fn filter_completion(&mut self) {
+        let (buffer_id, y, line) = self.line_before_cursor();
+        let Some(completion) = self.completion.as_mut() else {
+            return;
+        };
+        if completion.buffer_id() != buffer_id || completion.row() != y {
+            self.completion = None;
+            return;
+        }
+        completion.filter(&line);
+        if completion.is_empty() && !completion.waiting {
+            self.completion = None;
+        }
     }
 
-    fn get_cursor_controller(&mut self) -> &mut dyn CursorControllerPort {
-        &mut *self.cursor_controller
+    /// Answers the calls the plugins made since the last time. Returns
+    /// whether more calls are expected soon, for the plugins to be looked at
+    /// again.
+    fn poll_plugins(&mut self) -> bool {
+        let mut expected = false;
+        let mut calls = Vec::new();
+        let mut stopped = Vec::new();
+        for (index, plugin) in self.extensions.all_mut::<Plugin>().enumerate() {
+            if let Err(message) = plugin.check_running() {
+                stopped.push(message);
+            }
+            calls.extend(plugin.take_calls().into_iter().map(|call| (index, call)));
+            expected |= plugin.expects_calls();
+        }
+        // A plugin making calls may well make more
+        let busy = expected || !calls.is_empty();
+        for message in stopped {
+            log_info!("{}", message);
+            self.set_message(message);
+        }
+
+        let edited = self.focused().1.undo_history.in_change();
+        for (index, call) in calls {
+            match call {
+                RpcMessage::Request { id, method, params } => {
+                    let result = self.plugin_call(&method, &params);
+                    if let Some(plugin) = self.extensions.all_mut::<Plugin>().nth(index) {
+                        plugin.respond(id, result);
+                    }
+                }
+                RpcMessage::Notification { method, params } => {
+                    if let Err(e) = self.plugin_call(&method, &params) {
+                        log_info!("Plugin notification {} failed: {}", method, e.message);
+                    }
+                }
+                RpcMessage::Response { .. } => {}
+            }
+        }
+        // The edits of a plugin are undone together, unless they are part of
+        // the user's own change
+        let buffer = self.focused().1;
+        if !edited && buffer.undo_history.in_change() {
+            buffer.undo_history.end_change();
+        }
+        busy
     }
 
-    fn get_editor_rows(&mut self) -> &mut dyn EditorRowsPort {
-        &mut *self.editor_rows
+    /// Reads parameter `name` of a plugin call.
+    fn plugin_param<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, RpcError> {
+        params
+            .get(name)
+            .and_then(|value| T::deserialize(value).ok())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Expected `{}`", name)))
+    }
+
+    /// Returns the buffer a plugin call is about: the one its `buffer`
+    /// parameter names, or the focused one without it.
+    fn plugin_buffer(&self, params: &Value) -> Result<usize, RpcError> {
+        if params.get("buffer").is_none_or(Value::is_null) {
+            return Ok(self.layout().focused().buffer_id);
+        }
+        let id: usize = Self::plugin_param(params, "buffer")?;
+        match self.buffers.get(id) {
+            Some(_) => Ok(id),
+            None => Err(RpcError::new(INVALID_PARAMS, format!("No buffer {}", id))),
+        }
+    }
+
+    /// Reads the `column` and `row` parameters of a plugin call, which have
+    /// to be a position in buffer `id`.
+    fn plugin_position(&self, id: usize, params: &Value) -> Result<(usize, usize), RpcError> {
+        let column: usize = Self::plugin_param(params, "column")?;
+        let row: usize = Self::plugin_param(params, "row")?;
+        let rows = &self.buffer_by_id(id)?.editor_rows;
+        let valid = match row.cmp(&rows.number_of_rows()) {
+            cmp::Ordering::Less => rows.get_row(row).is_char_boundary(column),
+            cmp::Ordering::Equal => column == 0,
+            cmp::Ordering::Greater => false,
+        };
+        match valid {
+            true => Ok((column, row)),
+            false => Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("No position {}:{} in the buffer", row, column),
+            )),
+        }
+    }
+
+    /// Returns buffer `id` of a plugin call.
+    fn buffer_by_id(&self, id: usize) -> Result<&Buffer, RpcError> {
+        self.buffers
+            .get(id)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("No buffer {}", id)))
+    }
+
+    /// Answers call `method` of a plugin. Calls about a buffer act on the
+    /// one their `buffer` parameter names, or on the focused one.
+    fn plugin_call(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        let read_only = || RpcError::new(INVALID_PARAMS, "Buffer is read-only");
+        let id = self.plugin_buffer(params)?;
+        let rows = &self.buffer_by_id(id)?.editor_rows;
+        match method {
+            "buffer/fileName" => Ok(json!(rows
+                .get_file_name()
+                .map(|file| file.to_string_lossy().into_owned()))),
+            "buffer/numberOfRows" => Ok(json!(rows.number_of_rows())),
+            "buffer/getRows" => Ok(json!(rows.get_rows())),
+            "buffer/getRow" => {
+                let row: usize = Self::plugin_param(params, "row")?;
+                match row < rows.number_of_rows() {
+                    true => Ok(json!(rows.get_row(row))),
+                    false => Err(RpcError::new(INVALID_PARAMS, format!("No row {}", row))),
+                }
+            }
+            "buffer/setRows" => {
+                let new_rows: Vec<String> = Self::plugin_param(params, "rows")?;
+                if new_rows.iter().any(|row| row.contains('\n')) {
+                    return Err(RpcError::new(INVALID_PARAMS, "Rows cannot hold newlines"));
+                }
+                if !self.begin_buffer_edit(id) {
+                    return Err(read_only());
+                }
+                let (x, y) = self.plugin_cursor(id)?;
+                if let Some(buffer) = self.buffers.get_mut(id) {
+                    buffer.editor_rows.set_rows(new_rows);
+                }
+                self.place_buffer_cursor(id, x, y);
+                Ok(Value::Null)
+            }
+            "buffer/insertChar" | "buffer/insertNewline" | "buffer/deleteChar" => {
+                let (x, y) = self.plugin_position(id, params)?;
+                let ch = match method {
+                    "buffer/insertChar" => {
+                        let text: String = Self::plugin_param(params, "char")?;
+                        let mut chars = text.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(ch), None) if ch != '\n' => Some(ch),
+                            _ => return Err(RpcError::new(INVALID_PARAMS, "Expected one char")),
+                        }
+                    }
+                    _ => None,
+                };
+                if !self.begin_buffer_edit(id) {
+                    return Err(read_only());
+                }
+                let cursor = self.plugin_cursor(id)?;
+                if let Some(buffer) = self.buffers.get_mut(id) {
+                    match (method, ch) {
+                        (_, Some(ch)) => buffer.editor_rows.insert_char(x, y, ch),
+                        ("buffer/insertNewline", _) => buffer.editor_rows.insert_newline(x, y),
+                        _ => buffer.editor_rows.delete_char(x, y),
+                    }
+                }
+                self.place_buffer_cursor(id, cursor.0, cursor.1);
+                Ok(Value::Null)
+            }
+            "cursor/getPosition" => {
+                let (column, row) = self.plugin_cursor(id)?;
+                Ok(json!({ "column": column, "row": row }))
+            }
+            "cursor/setPosition" => {
+                let column: usize = Self::plugin_param(params, "column")?;
+                let row: usize = Self::plugin_param(params, "row")?;
+                self.place_buffer_cursor(id, column, row);
+                Ok(Value::Null)
+            }
+            "status/setMessage" => {
+                let message: String = Self::plugin_param(params, "message")?;
+                self.set_message(message);
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("No method {}", method),
+            )),
+        }
+    }
+
+    /// Returns where the cursor of buffer `id` is, as `buffer_cursor` finds
+    /// it.
+    fn plugin_cursor(&mut self, id: usize) -> Result<(usize, usize), RpcError> {
+        self.buffer_cursor(id)
+            .map(|cursor| cursor.get_file_position())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("No buffer {}", id)))
+    }
+
+    /// Tells the extensions about the buffers edited since the last time.
+    fn run_change_hooks(&mut self) {
+        for id in std::mem::take(&mut self.edited) {
+            let Some(buffer) = self.buffers.get(id) else {
+                continue;
+            };
+            let view = BufferView {
+                id,
+                rows: &*buffer.editor_rows,
+                cursor: (0, 0),
+            };
+            for extension in self.extensions.iter_mut() {
+                extension.on_change(&view);
+            }
+        }
+    }
+
+    /// Returns the focused window together with the buffer it shows.
+    fn focused_ref(&self) -> (&Window, &Buffer) {
+        let window = self.layout().focused();
+        let buffer = self
+            .buffers
+            .get(window.buffer_id)
+            .expect("window shows a closed buffer");
+        (window, buffer)
+    }
+
+    /// Turns a search result into a picker item showing the matching line,
+    /// with the match highlighted.
+    fn grep_item(found: SearchMatch) -> PickerItem {
+        let prefix = format!("{}:{}: ", found.label, found.line + 1);
+        let text = found.text.trim_start();
+        let trimmed = found.text.len() - text.len();
+        let start = cmp::max(found.start, trimmed);
+        let end = cmp::max(found.end, start);
+
+        let first = prefix.chars().count() + found.text[trimmed..start].chars().count();
+        let length = found.text[start..end].chars().count();
+        PickerItem {
+            label: format!("{}{}", prefix, text),
+            highlights: (first..first + length).collect(),
+            path: found.path,
+            position: Some((found.start, found.line)),
+            index: None,
+        }
+    }
+
+    /// A window showing the previewed file from its first line.
+    fn preview_window(&self) -> Option<Window> {
+        self.preview.as_ref()?;
+        Some(Window {
+            id: 0,
+            buffer_id: 0,
+            cursor_controller: Box::new(CursorController::new(self.window_size)),
+            selection_anchor: None,
+        })
+    }
+
+    fn is_markdown(buffer: &Buffer) -> bool {
+        buffer
+            .editor_rows
+            .get_file_name()
+            .and_then(|file| file.extension())
+            .is_some_and(|extension| extension == "md" || extension == "markdown")
+    }
+
+    /// Keeps the rows rendered by a preview that is not live.
+    fn snapshot_markdown(&mut self) {
+        self.markdown_rows = self
+            .markdown_preview
+            .and_then(|id| self.buffers.get(id))
+            .map(|buffer| buffer.editor_rows.get_rows())
+            .unwrap_or_default();
+    }
+
+    fn draw_markdown_preview(&mut self) -> io::Result<()> {
+        // The preview follows the focus to other Markdown buffers
+        let focused = self.layout().focused().buffer_id;
+        let follows = self.buffers.get(focused).is_some_and(Self::is_markdown);
+        if self.markdown_preview.is_some_and(|id| id != focused) && follows {
+            self.markdown_preview = Some(focused);
+            self.snapshot_markdown();
+        }
+        let (Some(id), Some(rect)) = (self.markdown_preview, self.markdown_preview_rect()) else {
+            return Ok(());
+        };
+        let Some(buffer) = self.buffers.get(id) else {
+            return Ok(());
+        };
+
+        let live_rows;
+        let rows = if self.markdown_config.live_preview {
+            live_rows = buffer.editor_rows.get_rows();
+            &live_rows
+        } else {
+            &self.markdown_rows
+        };
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let theme = PreviewTheme::named(&self.markdown_config.preview_theme);
+        let lines = Renderer::render(&rows, rect.width, theme);
+
+        // Scroll along with the window showing the source, the focused one
+        // if it does
+        let layout = self.layout();
+        let source_window = Some(layout.focused())
+            .filter(|window| window.buffer_id == id)
+            .or_else(|| {
+                layout
+                    .windows()
+                    .into_iter()
+                    .find(|window| window.buffer_id == id)
+            });
+        let offset = source_window.map_or(0, |window| {
+            markdown::line_of_row(&lines, window.cursor_controller.get_row_offset())
+        });
+        let name = buffer
+            .editor_rows
+            .get_file_name()
+            .map(|file| file.display().to_string())
+            .unwrap_or_default();
+
+        let out = &mut *self.buffer;
+        let text = Rect {
+            height: rect.height.saturating_sub(1),
+            ..rect
+        };
+        markdown::draw(out, &lines, offset, text)?;
+
+        if rect.height > 0 {
+            out.move_cursor_to(rect.x as u16, (rect.y + rect.height - 1) as u16)?;
+            out.set_style(style::Attribute::Reverse)?;
+            let status: String = format!(
+                "{:<width$}",
+                format!("Preview: {}", name),
+                width = rect.width
+            )
+            .chars()
+            .take(rect.width)
+            .collect();
+            out.append_str(&status);
+            out.set_style(style::Attribute::Reset)?;
+        }
+
+        // Separate the preview from the windows
+        for y in rect.y..rect.y + rect.height {
+            out.move_cursor_to((rect.x - 1) as u16, y as u16)?;
+            out.append_char('│');
+        }
+        Ok(())
+    }
+
+    fn draw_explorer(&mut self) -> io::Result<()> {
+        let (Some(explorer), Some(rect)) = (&self.explorer, self.explorer_rect()) else {
+            return Ok(());
+        };
+        let out = &mut *self.buffer;
+        let entries = explorer.entries();
+        let selected = explorer.selected();
+
+        for i in 0..rect.height.saturating_sub(1) {
+            out.move_cursor_to(rect.x as u16, (rect.y + i) as u16)?;
+            let index = i + self.explorer_offset;
+            let Some(entry) = entries.get(index) else {
+                continue;
+            };
+            let marker = match (entry.is_dir, entry.expanded) {
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+                (false, _) => "  ",
+            };
+            let line = format!(
+                "{}{}{}{}",
+                "  ".repeat(entry.depth),
+                marker,
+                entry.name(),
+                if entry.is_dir { "/" } else { "" }
+            );
+            let line: String = line.chars().take(rect.width).collect();
+
+            // The selected entry is reversed while the explorer has focus
+            if index == selected {
+                out.set_style(if self.explorer_focused {
+                    style::Attribute::Reverse
+                } else {
+                    style::Attribute::Bold
+                })?;
+            }
+            out.append_str(&line);
+            out.set_style(style::Attribute::Reset)?;
+        }
+
+        // The status line names the directory being browsed
+        if rect.height > 0 {
+            out.move_cursor_to(rect.x as u16, (rect.y + rect.height - 1) as u16)?;
+            out.set_style(style::Attribute::Reverse)?;
+            if self.explorer_focused {
+                out.set_style(style::Attribute::Bold)?;
+            }
+            let root = explorer.root().display().to_string();
+            let status: String = format!("{:<width$}", root, width = rect.width)
+                .chars()
+                .take(rect.width)
+                .collect();
+            out.append_str(&status);
+            out.set_style(style::Attribute::Reset)?;
+        }
+
+        // Separate the explorer from the windows
+        for y in rect.y..rect.y + rect.height {
+            out.move_cursor_to((rect.x + rect.width) as u16, y as u16)?;
+            out.append_char('│');
+        }
+        Ok(())
+    }
+
+    /// Returns the label of every tab page with the column it starts at,
+    /// counting a column per char.
+    fn tab_labels(&self) -> Vec<(usize, String)> {
+        let mut x = 0;
+        self.tabs
+            .iter()
+            .enumerate()
+            .map(|(index, layout)| {
+                let buffer = self.buffers.get(layout.focused().buffer_id);
+                let name = buffer
+                    .and_then(|buffer| buffer.editor_rows.get_file_name())
+                    .and_then(|path| path.file_name())
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("[No Name]");
+                let dirty = buffer.is_some_and(|buffer| buffer.editor_rows.is_dirty());
+                let label = format!(" {} {}{} ", index + 1, name, if dirty { " +" } else { "" });
+                let start = x;
+                x += label.chars().count();
+                (start, label)
+            })
+            .collect()
+    }
+
+    /// Remembers the cursor of a closed window in the buffer it showed.
+    fn save_window_cursor(&mut self, window: &Window) {
+        if let Some(buffer) = self.buffers.get_mut(window.buffer_id) {
+            Self::copy_cursor(&*window.cursor_controller, &mut *buffer.cursor_controller);
+        }
+    }
+
+    fn placement(&self) -> WindowPlacement {
+        self.layout().place(self.layout_area())
+    }
+
+    /// Returns the area of the focused window, including its status line.
+    fn focused_rect(&self) -> Rect {
+        let focused = self.layout().focused().id;
+        self.placement()
+            .windows
+            .into_iter()
+            .find(|(id, _)| *id == focused)
+            .map(|(_, rect)| rect)
+            .unwrap_or_default()
+    }
+
+    /// Returns the focused window together with the buffer it shows.
+    fn focused(&mut self) -> (&mut Window, &mut Buffer) {
+        let window = self.tabs[self.current_tab].focused_mut();
+        let buffer = self
+            .buffers
+            .get_mut(window.buffer_id)
+            .expect("window shows a closed buffer");
+        (window, buffer)
+    }
+
+    fn copy_cursor(from: &dyn CursorControllerPort, to: &mut dyn CursorControllerPort) {
+        let (x, y) = from.get_file_position();
+        to.set_cursor_position(x, y);
+        to.set_offsets(from.get_row_offset(), from.get_col_offset());
+    }
+
+    fn new_window(&mut self, buffer_id: usize) -> Window {
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        Window {
+            id,
+            buffer_id,
+            cursor_controller: Box::new(CursorController::new(self.window_size)),
+            selection_anchor: None,
+        }
+    }
+
+    /// Adds a buffer holding `editor_rows`, with a fresh cursor and history.
+    fn add_buffer(&mut self, editor_rows: EditorRows) -> usize {
+        let id = self.buffers.add(
+            Box::new(editor_rows),
+            Box::new(CursorController::new(self.window_size)),
+            Box::new(UndoHistory::new(self.undo_limit)),
+        );
+        log_info!("Opened buffer {}", id);
+        if let Some(buffer) = self.buffers.get(id) {
+            let view = BufferView {
+                id,
+                rows: &*buffer.editor_rows,
+                cursor: (0, 0),
+            };
+            for extension in self.extensions.iter_mut() {
+                extension.on_open(&view);
+            }
+        }
+        id
+    }
+
+    /// Shows buffer `id` in the focused window. The window's position in the
+    /// buffer it leaves is remembered for the next time it is shown.
+    fn show_buffer(&mut self, id: usize) {
+        if self.tabs.is_empty() {
+            let window = self.new_window(id);
+            self.tabs.push(Box::new(WindowLayout::new(window)));
+        }
+
+        let (window, buffer) = self.focused();
+        if window.buffer_id != id {
+            Self::copy_cursor(&*window.cursor_controller, &mut *buffer.cursor_controller);
+            window.buffer_id = id;
+            let (window, buffer) = self.focused();
+            Self::copy_cursor(&*buffer.cursor_controller, &mut *window.cursor_controller);
+        }
+        self.buffers.switch_to(id);
+    }
+
+    /// Keeps the buffer list's current buffer in line with the focused window.
+    fn sync_current_buffer(&mut self) {
+        let id = self.layout().focused().buffer_id;
+        self.buffers.switch_to(id);
+    }
+
+    fn snapshot(window: &Window, buffer: &Buffer) -> Snapshot {
+        Snapshot {
+            rows: buffer.editor_rows.get_rows(),
+            cursor: window.cursor_controller.get_file_position(),
+        }
+    }
+
+    fn restore(window: &mut Window, buffer: &mut Buffer, snapshot: Snapshot) {
+        buffer.editor_rows.set_rows(snapshot.rows);
+        let (x, y) = snapshot.cursor;
+        window.cursor_controller.set_cursor_position(x, y);
+    }
+
+    /// Records the buffer state before the first edit of a change, so the
+    /// whole change can be undone in one step. Returns false, and leaves a
+    /// message, if the buffer cannot be edited.
+    fn begin_edit(&mut self) -> bool {
+        let id = self.layout().focused().buffer_id;
+        self.begin_buffer_edit(id)
+    }
+
+    /// Like `begin_edit`, for buffer `id` whether it is focused or not. The
+    /// cursor recorded is the one `buffer_cursor` finds.
+    fn begin_buffer_edit(&mut self, id: usize) -> bool {
+        let Some(cursor) = self.buffer_cursor(id) else {
+            return false;
+        };
+        let cursor = cursor.get_file_position();
+        let Some(buffer) = self.buffers.get_mut(id) else {
+            return false;
+        };
+        if buffer.editor_rows.is_read_only() {
+            self.set_message("Buffer is read-only".into());
+            return false;
+        }
+        if !buffer.undo_history.in_change() {
+            let rows = buffer.editor_rows.get_rows();
+            buffer.undo_history.record(Snapshot { rows, cursor });
+        }
+        self.edited.insert(id);
+        true
+    }
+
+    /// Draws the text area of `window` into `rect`, leaving out the last row
+    /// which holds the window's status line.
+    fn draw_window(
+        out: &mut dyn EditorBufferPort,
+        window: &Window,
+        editor_rows: &dyn EditorRowsPort,
+        rect: Rect,
+        underlines: &[(usize, Range<usize>, style::Color)],
+    ) -> io::Result<()> {
+        // Define the window dimensions
+        let screen_rows = rect.height.saturating_sub(1);
+        let screen_columns = rect.width;
+        let cursor_controller = &window.cursor_controller;
+
+        // Iterate through each row of the window
+        for i in 0..screen_rows {
+            out.move_cursor_to(rect.x as u16, (rect.y + i) as u16)?;
+
+            // Calculate the corresponding row in the file based on the current scroll position
+            let file_row = i + cursor_controller.get_row_offset();
+
+            // Check if the current screen row has a corresponding file row
+            if file_row >= editor_rows.number_of_rows() {
+                // If the file has no content and we're at the middle third of the window
+                if editor_rows.number_of_rows() == 0 && i == screen_rows / 3 {
+                    // Prepare a welcome message
+                    let mut welcome =
+                        format!("BARN Editor --- Version {}", env!("CARGO_PKG_VERSION"));
+
+                    // Truncate the message if it exceeds window width
+                    if welcome.len() > screen_columns {
+                        welcome.truncate(screen_columns);
+                    }
+
+                    // Calculate padding to center the message
+                    let mut padding = (screen_columns - welcome.len()) / 2;
+                    // Draw a tilde before the welcome message if there's space
+                    if padding != 0 {
+                        out.append_char('~');
+                        padding -= 1; // Reduce padding by one for the tilde
+                    }
+
+                    // Add spaces to pad out the message
+                    (0..padding).for_each(|_| out.append_char(' '));
+
+                    // Append the welcome message
+                    out.append_str(&welcome);
+                } else if screen_columns > 0 {
+                    // For empty file rows, draw a tilde
+                    out.append_char('~');
+                }
+            } else {
+                // Get the content of the file row and the column offset for horizontal scrolling
+                let row = editor_rows.get_render(file_row);
+                let col_offset = cursor_controller.get_col_offset();
+                let editor_row = editor_rows.get_editor_row(file_row);
+                let highlight = Self::selected_columns(window, editor_row, file_row);
+                let underlined_columns: Vec<(usize, usize, style::Color)> = underlines
+                    .iter()
+                    .filter(|(at, ..)| *at == file_row)
+                    .map(|(_, range, color)| {
+                        (
+                            editor_row.render_x(range.start),
+                            editor_row.render_x(range.end),
+                            *color,
+                        )
+                    })
+                    .collect();
+
+                // Append the part of the row that fits in the window, reversing the selected
+                // part and underlining what the extensions underline
+                let mut selected = false;
+                let mut underlined: Option<style::Color> = None;
+                for (column, ch) in row
+                    .chars()
+                    .enumerate()
+                    .skip(col_offset)
+                    .take(screen_columns)
+                {
+                    let in_selection =
+                        highlight.is_some_and(|(from, to)| column >= from && column < to);
+                    if in_selection != selected {
+                        selected = in_selection;
+                        out.set_style(if selected {
+                            style::Attribute::Reverse
+                        } else {
+                            style::Attribute::NoReverse
+                        })?;
+                    }
+                    let underline = underlined_columns
+                        .iter()
+                        .find(|(from, to, _)| column >= *from && column < *to)
+                        .map(|(.., color)| *color);
+                    if underline != underlined {
+                        underlined = underline;
+                        match underlined {
+                            Some(color) => {
+                                out.set_color(color)?;
+                                out.set_style(style::Attribute::Underlined)?;
+                            }
+                            None => {
+                                out.set_style(style::Attribute::NoUnderline)?;
+                                out.set_color(style::Color::Reset)?;
+                            }
+                        }
+                    }
+                    out.append_char(ch);
+                }
+                if selected {
+                    out.set_style(style::Attribute::NoReverse)?;
+                }
+                if underlined.is_some() {
+                    out.set_style(style::Attribute::NoUnderline)?;
+                    out.set_color(style::Color::Reset)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the screen columns of row `at` covered by the window's
+    /// selection, as a half-open range.
+    fn selected_columns(window: &Window, row: &dyn RowPort, at: usize) -> Option<(usize, usize)> {
+        let (start, end) = window.selection()?;
+        if at < start.1 || at > end.1 {
+            return None;
+        }
+        let content = row.get_content();
+        let from = if at == start.1 {
+            row.render_x(cmp::min(start.0, content.len()))
+        } else {
+            0
+        };
+        let to = if at == end.1 {
+            row.render_x(Self::end_of_char(content, end.0))
+        } else {
+            row.render_x(content.len())
+        };
+        Some((from, to))
+    }
+
+    /// Returns the byte offset just past the character at `index`.
+    fn end_of_char(content: &str, index: usize) -> usize {
+        content
+            .get(index..)
+            .and_then(|tail| tail.chars().next())
+            .map_or(content.len(), |c| index + c.len_utf8())
+    }
+
+    /// Returns the selection of the focused window clamped to the text, or
+    /// `None` if nothing is selected.
+    fn clamped_selection(&mut self) -> Option<((usize, usize), (usize, usize))> {
+        let (window, buffer) = self.focused();
+        let (start, end) = window.selection()?;
+        let rows = buffer.editor_rows.number_of_rows();
+        if rows == 0 {
+            return None;
+        }
+        // The cursor may sit on the line past the end of the file
+        let clamp = |(x, y): (usize, usize)| {
+            let y = cmp::min(y, rows - 1);
+            (cmp::min(x, buffer.editor_rows.get_row(y).len()), y)
+        };
+        Some((clamp(start), clamp(end)))
+    }
+
+    fn selected_text(rows: &[String], start: (usize, usize), end: (usize, usize)) -> String {
+        let end_x = Self::end_of_char(&rows[end.1], end.0);
+        if start.1 == end.1 {
+            return rows[start.1][start.0..end_x].to_string();
+        }
+        let mut text = rows[start.1][start.0..].to_string();
+        for row in &rows[start.1 + 1..end.1] {
+            text.push('\n');
+            text.push_str(row);
+        }
+        text.push('\n');
+        text.push_str(&rows[end.1][..end_x]);
+        text
+    }
+
+    /// Maps a screen cell inside `rect` to a position in the buffer shown by
+    /// `window`, expanding tabs the same way they are drawn.
+    fn position_at(
+        window: &Window,
+        buffer: &Buffer,
+        rect: Rect,
+        x: usize,
+        y: usize,
+    ) -> (usize, usize) {
+        let rows = buffer.editor_rows.number_of_rows();
+        if rows == 0 {
+            return (0, 0);
+        }
+        let cursor_controller = &window.cursor_controller;
+        let file_y = cmp::min(
+            y.saturating_sub(rect.y) + cursor_controller.get_row_offset(),
+            rows - 1,
+        );
+        let render_x = x.saturating_sub(rect.x) + cursor_controller.get_col_offset();
+        let file_x = buffer
+            .editor_rows
+            .get_editor_row(file_y)
+            .index_at_render_x(render_x);
+        (file_x, file_y)
+    }
+
+    /// Returns the window whose text area covers screen cell (`x`, `y`).
+    fn window_at(&self, x: usize, y: usize) -> Option<(usize, Rect)> {
+        self.placement().windows.into_iter().find(|(_, rect)| {
+            // The last row of a window is its status line
+            let text_area = Rect {
+                height: rect.height.saturating_sub(1),
+                ..*rect
+            };
+            text_area.contains(x, y)
+        })
+    }
+
+    /// Returns the start of `text` that fits in `width` columns, counting a
+    /// column per char.
+    fn fit(text: &str, width: usize) -> &str {
+        let end = text
+            .char_indices()
+            .map(|(at, _)| at)
+            .nth(width)
+            .unwrap_or(text.len());
+        &text[..end]
+    }
+
+    fn draw_window_status(
+        out: &mut dyn EditorBufferPort,
+        window: &Window,
+        editor_rows: &dyn EditorRowsPort,
+        rect: Rect,
+        focused: bool,
+    ) -> io::Result<()> {
+        if rect.height == 0 || rect.width == 0 {
+            return Ok(());
+        }
+        out.move_cursor_to(rect.x as u16, (rect.y + rect.height - 1) as u16)?;
+
+        // Set the style of the buffer to Reverse (inverts the foreground and background colors).
+        // Only the focused window's status line is bold.
+        out.set_style(style::Attribute::Reverse)?;
+        if focused {
+            out.set_style(style::Attribute::Bold)?;
+        }
+
+        // Retrieve the file name, default to "[No Name]" if not available
+        let file_name = editor_rows
+            .get_file_name()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or("[No Name]");
+
+        // Create a string with the current line and total lines information
+        let line_info = format!(
+            "{}/{}",
+            window.cursor_controller.get_file_position().1 + 1,
+            editor_rows.number_of_rows()
+        );
+
+        // Create a string with file information and the number of lines
+        let info = format!(
+            "{}{}{} -- {} lines",
+            file_name,
+            if editor_rows.is_read_only() {
+                " [RO]"
+            } else {
+                ""
+            },
+            if editor_rows.is_dirty() { " [+]" } else { "" },
+            editor_rows.number_of_rows()
+        );
+        // Determine the length of the info in chars, limiting it to the window width minus the
+        // length of line_info
+        let info_len = cmp::min(
+            info.chars().count(),
+            rect.width.saturating_sub(line_info.len()),
+        );
+        let info: String = info.chars().take(info_len).collect();
+
+        // Construct the status bar string.
+        // Format the file info to occupy the left side (info_len width) and line info on the right
+        let status_bar = format!(
+            "{:<info_len$}{:>line_info_len$}",
+            info,
+            line_info,
+            info_len = info_len,
+            line_info_len = rect.width - info_len
+        );
+        let status_bar: String = status_bar.chars().take(rect.width).collect();
+
+        // Append the constructed status bar string to the buffer
+        out.append_str(&status_bar);
+        // Reset the buffer's style to default
+        out.set_style(style::Attribute::Reset)
+    }
+}
+
+impl EditorDomainPort for EditorDomain {
+    fn new(window_size: (usize, usize), config: &Config) -> Self {
+        log_info!(
+            "Initializing editor domain with window size: {:?}",
+            window_size
+        );
+        Self {
+            window_size,
+            buffer: Box::new(EditorBuffer::new()),
+            buffers: Box::new(BufferList::new()),
+            tabs: Vec::new(),
+            current_tab: 0,
+            drag_origin: None,
+            register: String::new(),
+            explorer: None,
+            explorer_focused: false,
+            explorer_offset: 0,
+            explorer_config: config.extensions.file_explorer.clone(),
+            preview: None,
+            overlays: Box::new(OverlayStack::new()),
+            picker: None,
+            picker_source: None,
+            search_config: config.search.clone(),
+            extensions: ExtensionRegistry::new(extensions::built_in(), &config.extensions.tables),
+            edited: HashSet::new(),
+            script_commands: Vec::new(),
+            palette_choice: None,
+            jumps: Vec::new(),
+            hover: None,
+            key_hints: None,
+            completion: None,
+            completion_sources: config
+                .completion
+                .sources
+                .iter()
+                .filter_map(|name| {
+                    let mut sources = completion_sources::built_in().into_iter();
+                    sources.find(|source| source.name() == name)
+                })
+                .collect(),
+            completion_config: config.completion.clone(),
+            git_panel: None,
+            commit_buffer: None,
+            markdown_preview: None,
+            markdown_rows: Vec::new(),
+            markdown_config: config.extensions.markdown_preview.clone(),
+            next_window_id: 1,
+            status_message: Box::new(StatusMessage::new("HELP: Ctrl-Q = Quit".into())),
+            command_line: None,
+            undo_limit: config.editor_behavior.undo_limit,
+        }
+    }
+
+    fn draw_rows(&mut self) -> io::Result<()> {
+        let placement = self.placement();
+        let layout = &self.tabs[self.current_tab];
+        let preview_window = self.preview_window();
+
+        for (id, rect) in &placement.windows {
+            let Some(window) = layout.windows().into_iter().find(|w| w.id == *id) else {
+                continue;
+            };
+            let Some(buffer) = self.buffers.get(window.buffer_id) else {
+                continue;
+            };
+            // The focused window shows the file previewed from the explorer
+            match (&self.preview, &preview_window) {
+                (Some(preview), Some(preview_window)) if *id == layout.focused().id => {
+                    Self::draw_window(&mut *self.buffer, preview_window, &**preview, *rect, &[])?
+                }
+                _ => {
+                    // The extensions draw their gutters side by side, in front of the text
+                    let view = Self::buffer_view(window, buffer);
+                    let first = window.cursor_controller.get_row_offset();
+                    let mut x = rect.x;
+                    let mut underlines = Vec::new();
+                    let widths = self.gutter_widths(buffer.id, rect.width);
+                    for (extension, width) in self.extensions.iter().zip(widths) {
+                        let decorations = extension.decorations(&view, first..first + rect.height);
+                        if width > 0 {
+                            let columns = Rect { x, width, ..*rect };
+                            Self::draw_gutter(&mut *self.buffer, window, &decorations, columns)?;
+                            x += width;
+                        }
+                        underlines.extend(decorations.into_iter().filter_map(|decoration| {
+                            match decoration {
+                                Decoration::Underline { row, range, color } => {
+                                    Some((row, range, color))
+                                }
+                                Decoration::Gutter { .. } => None,
+                            }
+                        }));
+                    }
+                    let text_rect = self.text_rect(buffer.id, *rect);
+                    Self::draw_window(
+                        &mut *self.buffer,
+                        window,
+                        &*buffer.editor_rows,
+                        text_rect,
+                        &underlines,
+                    )?
+                }
+            }
+        }
+
+        // Draw the separators between side by side windows
+        for separator in &placement.separators {
+            for y in separator.y..separator.y + separator.height {
+                self.buffer.move_cursor_to(separator.x as u16, y as u16)?;
+                self.buffer.append_char('│');
+            }
+        }
+
+        self.draw_explorer()?;
+        self.draw_markdown_preview()
+    }
+
+    fn draw_status_bar(&mut self) {
+        let placement = self.placement();
+        let layout = &self.tabs[self.current_tab];
+        let focused = layout.focused().id;
+        let preview_window = self.preview_window();
+
+        for (id, rect) in &placement.windows {
+            let Some(window) = layout.windows().into_iter().find(|w| w.id == *id) else {
+                continue;
+            };
+            let Some(buffer) = self.buffers.get(window.buffer_id) else {
+                continue;
+            };
+            let is_focused = *id == focused && !self.explorer_focused;
+            match (&self.preview, &preview_window) {
+                (Some(preview), Some(preview_window)) if *id == focused => {
+                    Self::draw_window_status(
+                        &mut *self.buffer,
+                        preview_window,
+                        &**preview,
+                        *rect,
+                        is_focused,
+                    )
+                }
+                _ => Self::draw_window_status(
+                    &mut *self.buffer,
+                    window,
+                    &*buffer.editor_rows,
+                    *rect,
+                    is_focused,
+                ),
+            }
+            .unwrap();
+        }
+    }
+
+    fn draw_message_bar(&mut self) {
+        self.buffer
+            .move_cursor_to(0, (self.window_size.1 + 2) as u16)
+            .unwrap();
+        queue!(
+            self.buffer,
+            terminal::Clear(terminal::ClearType::UntilNewLine)
+        )
+        .unwrap();
+        if let Some(line) = &self.command_line {
+            self.buffer.append_str(Self::fit(line, self.window_size.0));
+        } else if let Some(msg) = self.status_message.message() {
+            self.buffer.append_str(Self::fit(msg, self.window_size.0));
+        } else {
+            // Without a message, the extensions may say something about the cursor position
+            let (window, buffer) = self.focused_ref();
+            let view = Self::buffer_view(window, buffer);
+            let text = self
+                .extensions
+                .iter()
+                .find_map(|extension| extension.status_text(&view));
+            if let Some(text) = text {
+                self.buffer.append_str(Self::fit(&text, self.window_size.0));
+            }
+        }
+    }
+
+    fn set_message(&mut self, message: String) {
+        self.status_message.set_message(message);
+    }
+
+    fn set_command_line(&mut self, line: Option<String>) {
+        self.command_line = line;
+    }
+
+    fn get_buffer(&mut self) -> &mut dyn EditorBufferPort {
+        &mut *self.buffer
+    }
+
+    fn get_window_size(&self) -> io::Result<(usize, usize)> {
+        // The size of the text area of the focused window
+        let focused = self.layout().focused();
+        let rect = self.text_rect(focused.buffer_id, self.focused_rect());
+        Ok((rect.width, rect.height.saturating_sub(1)))
+    }
+
+    fn set_window_size(&mut self, window_size: (usize, usize)) {
+        log_info!("Resizing editor to window size: {:?}", window_size);
+        self.window_size = window_size;
+        // Recompute every window's viewport and pull its offsets back so the
+        // cursor stays visible in the new size
+        self.scroll();
+    }
+
+    fn get_cursor_position(&self) -> (usize, usize) {
+        // While a command is typed the cursor sits at the end of the command line
+        if let Some(line) = &self.command_line {
+            let column = cmp::min(line.chars().count(), self.window_size.0.saturating_sub(1));
+            return (column, self.window_size.1 + 2);
+        }
+        // The cursor goes to the topmost popup taking keys
+        match (self.overlays.focused(), &self.picker, &self.git_panel) {
+            (Some(OverlayKind::Picker), Some(picker), _) => {
+                return picker.cursor_position(self.screen_area());
+            }
+            (Some(OverlayKind::GitPanel), _, Some(panel)) => {
+                return panel.cursor_position(self.screen_area());
+            }
+            _ => {}
+        }
+        if let (true, Some(explorer), Some(rect)) =
+            (self.explorer_focused, &self.explorer, self.explorer_rect())
+        {
+            let row = explorer.selected().saturating_sub(self.explorer_offset);
+            return (rect.x, rect.y + row);
+        }
+        let focused = self.layout().focused();
+        let rect = self.text_rect(focused.buffer_id, self.focused_rect());
+        let (x, y) = focused.cursor_controller.get_cursor_position();
+        (rect.x + x, rect.y + y)
+    }
+
+    fn set_cursor_position(&mut self, x: usize, y: usize) {
+        self.layout_mut()
+            .focused_mut()
+            .cursor_controller
+            .set_cursor_position(x, y);
+    }
+
+    fn move_cursor(&mut self, direction: event::KeyCode) {
+        let (window, buffer) = self.focused();
+        window
+            .cursor_controller
+            .move_cursor(direction, &*buffer.editor_rows);
+    }
+
+    fn scroll(&mut self) {
+        // Every window is drawn, so every window's offsets have to be up to date
+        let placement = self.placement();
+        let text_rects: Vec<(usize, Rect)> = self
+            .layout()
+            .windows()
+            .iter()
+            .filter_map(|window| {
+                let (_, rect) = placement.windows.iter().find(|(id, _)| *id == window.id)?;
+                Some((window.id, self.text_rect(window.buffer_id, *rect)))
+            })
+            .collect();
+        for window in self.tabs[self.current_tab].windows_mut() {
+            let Some(rect) = text_rects
+                .iter()
+                .find(|(id, _)| *id == window.id)
+                .map(|(_, rect)| rect)
+            else {
+                continue;
+            };
+            let Some(buffer) = self.buffers.get(window.buffer_id) else {
+                continue;
+            };
+            window
+                .cursor_controller
+                .set_screen_size((rect.width, rect.height.saturating_sub(1)));
+            window.cursor_controller.scroll(&*buffer.editor_rows);
+        }
+
+        // Keep the selected explorer entry in view
+        if let (Some(explorer), Some(rect)) = (&self.explorer, self.explorer_rect()) {
+            let height = cmp::max(rect.height.saturating_sub(1), 1);
+            let selected = explorer.selected();
+            self.explorer_offset = self
+                .explorer_offset
+                .clamp(selected.saturating_sub(height - 1), selected);
+        }
+    }
+
+    fn get_cursor_controller(&mut self) -> &mut dyn CursorControllerPort {
+        &mut *self.layout_mut().focused_mut().cursor_controller
+    }
+
+    fn get_editor_rows(&mut self) -> &mut dyn EditorRowsPort {
+        &mut *self.focused().1.editor_rows
+    }
+
+    fn open_file(&mut self, file: Option<path::PathBuf>) -> io::Result<usize> {
+        // The opened file is shown, rather than whatever the explorer previews
+        self.leave_explorer();
+        let id = match file {
+            None => self.add_buffer(EditorRows::new()),
+            Some(file) => match self.buffers.find_by_path(&file) {
+                Some(id) => id,
+                None => self.add_buffer(EditorRows::from_file(file)?),
+            },
+        };
+        self.show_buffer(id);
+        Ok(id)
+    }
+
+    fn next_buffer(&mut self) {
+        self.buffers.next();
+        self.show_buffer(self.buffers.current().id);
+    }
+
+    fn previous_buffer(&mut self) {
+        self.buffers.previous();
+        self.show_buffer(self.buffers.current().id);
+    }
+
+    fn switch_buffer(&mut self, id: usize) -> io::Result<()> {
+        if self.buffers.get(id).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Buffer {} does not exist", id),
+            ));
+        }
+        self.show_buffer(id);
+        Ok(())
+    }
+
+    fn close_buffer(&mut self, force: bool) -> io::Result<()> {
+        let current = self.buffers.current();
+        if current.editor_rows.is_dirty() && !force {
+            return Err(io::Error::other(format!(
+                "No write since last change for buffer {} (add ! to override)",
+                current.id
+            )));
+        }
+        let id = current.id;
+        let view = BufferView {
+            id,
+            rows: &*current.editor_rows,
+            cursor: (0, 0),
+        };
+        for extension in self.extensions.iter_mut() {
+            extension.on_close(&view);
+        }
+        self.buffers.remove(id);
+        // There is always at least one buffer to show
+        if self.buffers.buffers().is_empty() {
+            self.add_buffer(EditorRows::new());
+        }
+
+        // Windows that showed the closed buffer move on to the new current one
+        let replacement = self.buffers.current().id;
+        let saved_cursor = &self.buffers.current().cursor_controller;
+        for window in self.tabs.iter_mut().flat_map(|layout| layout.windows_mut()) {
+            if window.buffer_id == id {
+                window.buffer_id = replacement;
+                Self::copy_cursor(&**saved_cursor, &mut *window.cursor_controller);
+            }
+        }
+        self.sync_current_buffer();
+        Ok(())
+    }
+
+    fn list_buffers(&self) -> Vec<String> {
+        let current_id = self
+            .buffers
+            .buffers()
+            .first()
+            .map(|_| self.buffers.current().id);
+        self.buffers
+            .buffers()
+            .iter()
+            .map(|buffer| {
+                let name = buffer
+                    .editor_rows
+                    .get_file_name()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "[No Name]".into());
+                // Buffers shown in a window report that window's cursor line
+                let line = self
+                    .tabs
+                    .iter()
+                    .flat_map(|layout| layout.windows())
+                    .find(|window| window.buffer_id == buffer.id)
+                    .map(|window| &window.cursor_controller)
+                    .unwrap_or(&buffer.cursor_controller)
+                    .get_file_position()
+                    .1;
+                format!(
+                    "{}{}{} \"{}\" line {}",
+                    buffer.id,
+                    if Some(buffer.id) == current_id {
+                        " %"
+                    } else {
+                        "  "
+                    },
+                    if buffer.editor_rows.is_dirty() {
+                        "+"
+                    } else {
+                        " "
+                    },
+                    name,
+                    line + 1
+                )
+            })
+            .collect()
+    }
+
+    fn has_unsaved_changes(&self) -> bool {
+        self.buffers
+            .buffers()
+            .iter()
+            .any(|buffer| buffer.editor_rows.is_dirty())
+    }
+
+    fn split_window(
+        &mut self,
+        direction: SplitDirection,
+        file: Option<path::PathBuf>,
+    ) -> io::Result<()> {
+        // The new window starts out as a copy of the focused one
+        let buffer_id = self.layout().focused().buffer_id;
+        let mut window = self.new_window(buffer_id);
+        Self::copy_cursor(
+            &*self.layout().focused().cursor_controller,
+            &mut *window.cursor_controller,
+        );
+        self.layout_mut().split(direction, window);
+
+        if let Some(file) = file {
+            self.open_file(Some(file))?;
+        }
+        Ok(())
+    }
+
+    fn close_window(&mut self) -> bool {
+        match self.layout_mut().close() {
+            Some(window) => {
+                self.save_window_cursor(&window);
+                self.sync_current_buffer();
+                true
+            }
+            // Closing the last window of a tab page closes the tab page
+            None => self.close_tab(),
+        }
+    }
+
+    fn only_window(&mut self) {
+        for window in self.layout_mut().only() {
+            self.save_window_cursor(&window);
+        }
+    }
+
+    fn focus_window(&mut self, direction: FocusDirection) {
+        // The explorer sits to the left of every window
+        if self.explorer_focused {
+            if direction == FocusDirection::Right {
+                self.leave_explorer();
+            }
+            return;
+        }
+        let area = self.layout_area();
+        let moved = self.layout_mut().focus(direction, area);
+        if !moved && direction == FocusDirection::Left && self.explorer.is_some() {
+            self.explorer_focused = true;
+            self.update_preview();
+        }
+        self.sync_current_buffer();
+    }
+
+    fn focus_next_window(&mut self) {
+        if self.explorer_focused {
+            self.leave_explorer();
+            return;
+        }
+        self.layout_mut().focus_next();
+        self.sync_current_buffer();
+    }
+
+    fn resize_window(&mut self, direction: SplitDirection, delta: isize) {
+        let area = self.layout_area();
+        self.layout_mut().resize(direction, delta, area);
+    }
+
+    fn new_tab(&mut self, file: Option<path::PathBuf>) -> io::Result<()> {
+        // The new tab page starts out showing the focused window's buffer
+        let buffer_id = self.layout().focused().buffer_id;
+        let mut window = self.new_window(buffer_id);
+        Self::copy_cursor(
+            &*self.layout().focused().cursor_controller,
+            &mut *window.cursor_controller,
+        );
+        self.current_tab += 1;
+        self.tabs
+            .insert(self.current_tab, Box::new(WindowLayout::new(window)));
+
+        if let Some(file) = file {
+            self.open_file(Some(file))?;
+        }
+        Ok(())
+    }
+
+    fn close_tab(&mut self) -> bool {
+        if self.tabs.len() < 2 {
+            return false;
+        }
+        let layout = self.tabs.remove(self.current_tab);
+        for window in layout.windows() {
+            self.save_window_cursor(window);
+        }
+        self.current_tab = cmp::min(self.current_tab, self.tabs.len() - 1);
+        self.sync_current_buffer();
+        true
+    }
+
+    fn next_tab(&mut self) {
+        self.current_tab = (self.current_tab + 1) % self.tabs.len();
+        self.sync_current_buffer();
+    }
+
+    fn previous_tab(&mut self) {
+        self.current_tab = (self.current_tab + self.tabs.len() - 1) % self.tabs.len();
+        self.sync_current_buffer();
+    }
+
+    fn switch_tab(&mut self, index: usize) -> io::Result<()> {
+        if index >= self.tabs.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Tab page {} does not exist", index + 1),
+            ));
+        }
+        self.current_tab = index;
+        self.sync_current_buffer();
+        Ok(())
+    }
+
+    fn tab_at(&self, x: usize) -> Option<usize> {
+        self.tab_labels()
+            .iter()
+            .position(|(start, label)| x >= *start && x < start + label.chars().count())
+    }
+
+    fn draw_tab_line(&mut self) {
+        self.buffer.move_cursor_to(0, 0).unwrap();
+        let mut width = 0;
+        for (index, (_, label)) in self.tab_labels().into_iter().enumerate() {
+            if width >= self.window_size.0 {
+                break;
+            }
+            // The current tab page is drawn bold, the others reversed
+            let attribute = if index == self.current_tab {
+                style::Attribute::Bold
+            } else {
+                style::Attribute::Reverse
+            };
+            self.buffer.set_style(attribute).unwrap();
+            let label: String = label.chars().take(self.window_size.0 - width).collect();
+            self.buffer.append_str(&label);
+            self.buffer.set_style(style::Attribute::Reset).unwrap();
+            width += label.chars().count();
+        }
+
+        // Fill the rest of the line
+        self.buffer.set_style(style::Attribute::Reverse).unwrap();
+        self.buffer
+            .append_str(&" ".repeat(self.window_size.0.saturating_sub(width)));
+        self.buffer.set_style(style::Attribute::Reset).unwrap();
+    }
+
+    fn click(&mut self, x: usize, y: usize) {
+        self.drag_origin = None;
+        // Popups keep the clicks on them from the windows below, and the
+        // picker and git panel keep every click while they are open
+        let on_overlay = self.overlays.at(x, y).is_some();
+        let focused = self.overlays.focused();
+        let modal = focused.is_some_and(|kind| kind != OverlayKind::Completion);
+        if on_overlay || modal {
+            return;
+        }
+        // Clicking an explorer entry selects it, and clicking it again opens it
+        let panel = self.explorer_rect();
+        if let (Some(explorer), Some(rect)) = (&mut self.explorer, panel) {
+            if rect.contains(x, y) && y < rect.y + rect.height - 1 {
+                let index = y - rect.y + self.explorer_offset;
+                let again = self.explorer_focused && explorer.selected() == index;
+                explorer.select(index);
+                self.explorer_focused = true;
+                self.update_preview();
+                if again {
+                    if let Err(e) = self.explorer_open() {
+                        self.set_message(e.to_string());
+                    }
+                }
+                return;
+            }
+        }
+        let Some((id, rect)) = self.window_at(x, y) else {
+            return;
+        };
+        self.leave_explorer();
+        self.layout_mut().focus_id(id);
+        self.sync_current_buffer();
+
+        let rect = self.text_rect(self.layout().focused().buffer_id, rect);
+        let (window, buffer) = self.focused();
+        let (file_x, file_y) = Self::position_at(window, buffer, rect, x, y);
+        window.cursor_controller.set_cursor_position(file_x, file_y);
+        window.selection_anchor = None;
+        self.drag_origin = Some((file_x, file_y));
+    }
+
+    fn drag(&mut self, x: usize, y: usize) {
+        let Some(origin) = self.drag_origin else {
+            return;
+        };
+        // Dragging past the edges of the window selects up to its border
+        let rect = self.text_rect(self.layout().focused().buffer_id, self.focused_rect());
+        let x = x.clamp(rect.x, (rect.x + rect.width).saturating_sub(1));
+        let y = y.clamp(rect.y, (rect.y + rect.height).saturating_sub(2));
+
+        let (window, buffer) = self.focused();
+        let (file_x, file_y) = Self::position_at(window, buffer, rect, x, y);
+        window.selection_anchor = Some(origin);
+        window.cursor_controller.set_cursor_position(file_x, file_y);
+    }
+
+    fn scroll_view(&mut self, x: usize, y: usize, lines: isize) {
+        // The wheel over a list moves its selection
+        match self.overlays.at(x, y) {
+            Some(OverlayKind::Completion) => return self.completion_move(lines),
+            Some(OverlayKind::GitPanel) => return self.git_panel_move(lines),
+            Some(OverlayKind::Picker) => return self.picker_move(lines),
+            Some(OverlayKind::Hover | OverlayKind::KeyHints) | None => {}
+        }
+        if self.explorer_rect().is_some_and(|rect| rect.contains(x, y)) {
+            self.explorer_move(lines);
+            return;
+        }
+        let Some((id, rect)) = self.window_at(x, y) else {
+            return;
+        };
+        let Some(window) = self.tabs[self.current_tab]
+            .windows_mut()
+            .into_iter()
+            .find(|window| window.id == id)
+        else {
+            return;
+        };
+        let Some(buffer) = self.buffers.get(window.buffer_id) else {
+            return;
+        };
+
+        let cursor_controller = &mut window.cursor_controller;
+        let rows = buffer.editor_rows.number_of_rows();
+        let height = cmp::max(rect.height.saturating_sub(1), 1);
+        let row_offset = cursor_controller
+            .get_row_offset()
+            .saturating_add_signed(lines)
+            .min(rows.saturating_sub(1));
+        cursor_controller.set_offsets(row_offset, cursor_controller.get_col_offset());
+
+        // Keep the cursor inside the viewport, or scrolling would snap back to it
+        let (cursor_x, cursor_y) = cursor_controller.get_file_position();
+        let cursor_y = cursor_y.clamp(row_offset, row_offset + height - 1);
+        cursor_controller.set_cursor_position(cursor_x, cursor_y);
+    }
+
+    fn has_selection(&self) -> bool {
+        self.layout().focused().selection_anchor.is_some()
+    }
+
+    fn clear_selection(&mut self) {
+        self.layout_mut().focused_mut().selection_anchor = None;
+    }
+
+    fn yank_selection(&mut self) {
+        if let Some((start, end)) = self.clamped_selection() {
+            let rows = self.focused().1.editor_rows.get_rows();
+            self.register = Self::selected_text(&rows, start, end);
+        }
+        self.clear_selection();
+    }
+
+    fn delete_selection(&mut self) {
+        let Some((start, end)) = self.clamped_selection() else {
+            self.clear_selection();
+            return;
+        };
+        if !self.begin_edit() {
+            return;
+        }
+        let (window, buffer) = self.focused();
+        let mut rows = buffer.editor_rows.get_rows();
+        let end_x = Self::end_of_char(&rows[end.1], end.0);
+
+        let text = Self::selected_text(&rows, start, end);
+        let joined = format!("{}{}", &rows[start.1][..start.0], &rows[end.1][end_x..]);
+        rows.splice(start.1..=end.1, [joined]);
+        buffer.editor_rows.set_rows(rows);
+        buffer.undo_history.end_change();
+
+        window
+            .cursor_controller
+            .set_cursor_position(start.0, start.1);
+        window.selection_anchor = None;
+        self.register = text;
+    }
+
+    fn paste(&mut self) {
+        let text = self.register.clone();
+        for ch in text.chars() {
+            if ch == '\n' {
+                self.insert_newline();
+            } else {
+                self.insert_char(ch);
+            }
+        }
+        self.end_change();
+    }
+
+    fn open_explorer(&mut self, directory: Option<path::PathBuf>) -> io::Result<()> {
+        if !self.explorer_config.enabled {
+            return Err(io::Error::other("The file explorer is disabled"));
+        }
+        let root = directory.unwrap_or_else(|| self.default_explorer_root());
+        self.explorer = Some(Box::new(FileExplorer::new(
+            root,
+            self.explorer_config.show_hidden_files,
+        )?));
+        self.explorer_offset = 0;
+        self.explorer_focused = true;
+        self.update_preview();
+        Ok(())
+    }
+
+    fn close_explorer(&mut self) {
+        self.explorer = None;
+        self.leave_explorer();
+    }
+
+    fn toggle_explorer(&mut self) -> io::Result<()> {
+        match (&self.explorer, self.explorer_focused) {
+            (None, _) => return self.open_explorer(None),
+            (Some(_), true) => self.close_explorer(),
+            (Some(_), false) => {
+                self.explorer_focused = true;
+                self.update_preview();
+            }
+        }
+        Ok(())
+    }
+
+    fn explorer_focused(&self) -> bool {
+        self.explorer_focused
+    }
+
+    fn leave_explorer(&mut self) {
+        self.explorer_focused = false;
+        self.preview = None;
+    }
+
+    fn explorer_move(&mut self, delta: isize) {
+        if let Some(explorer) = self.explorer.as_mut() {
+            let index = explorer.selected().saturating_add_signed(delta);
+            explorer.select(index);
+        }
+        self.update_preview();
+    }
+
+    fn explorer_open(&mut self) -> io::Result<()> {
+        let explorer = self.explorer_mut()?;
+        let Some(entry) = explorer.selected_entry() else {
+            return Ok(());
+        };
+        if entry.is_dir {
+            explorer.toggle()?;
+            self.update_preview();
+            return Ok(());
+        }
+        self.leave_explorer();
+        self.open_file(Some(entry.path))?;
+        Ok(())
+    }
+
+    fn explorer_collapse(&mut self) {
+        if let Some(explorer) = self.explorer.as_mut() {
+            explorer.collapse();
+        }
+        self.update_preview();
+    }
+
+    fn explorer_selection(&self) -> Option<String> {
+        let entry = self.explorer.as_ref()?.selected_entry()?;
+        Some(entry.name())
+    }
+
+    fn explorer_create(&mut self, name: &str) -> io::Result<()> {
+        let path = self.explorer_mut()?.create(name)?;
+        self.set_message(format!("Created {}", path.display()));
+        self.update_preview();
+        Ok(())
+    }
+
+    fn explorer_rename(&mut self, name: &str) -> io::Result<()> {
+        let explorer = self.explorer_mut()?;
+        let Some(entry) = explorer.selected_entry() else {
+            return Ok(());
+        };
+        let old = fs::canonicalize(&entry.path)?;
+        let new = explorer.rename(name)?;
+
+        // Buffers showing the renamed file, or a file inside the renamed
+        // directory, follow it to its new name
+        let ids: Vec<usize> = self.buffers.buffers().iter().map(|b| b.id).collect();
+        for id in ids {
+            let Some(buffer) = self.buffers.get_mut(id) else {
+                continue;
+            };
+            let Some(file_name) = buffer.editor_rows.get_file_name() else {
+                continue;
+            };
+            let canonical = fs::canonicalize(file_name).unwrap_or_else(|_| file_name.clone());
+            if let Ok(rest) = canonical.strip_prefix(&old) {
+                let renamed = if rest.as_os_str().is_empty() {
+                    new.clone()
+                } else {
+                    new.join(rest)
+                };
+                buffer.editor_rows.set_file_name(renamed);
+            }
+        }
+        self.set_message(format!("Renamed to {}", new.display()));
+        self.update_preview();
+        Ok(())
+    }
+
+    fn explorer_delete(&mut self) -> io::Result<()> {
+        let explorer = self.explorer_mut()?;
+        let name = explorer.selected_entry().map(|entry| entry.name());
+        explorer.delete()?;
+        if let Some(name) = name {
+            self.set_message(format!("Deleted {}", name));
+        }
+        self.update_preview();
+        Ok(())
+    }
+
+    fn explorer_refresh(&mut self) -> io::Result<()> {
+        self.explorer_mut()?.refresh()?;
+        self.update_preview();
+        Ok(())
+    }
+
+    fn open_finder(&mut self) {
+        let finder = FileFinder::new(".".into(), self.explorer_config.show_hidden_files);
+        self.picker_source = Some(PickerSource::Files(Box::new(finder)));
+        self.picker = Some(Picker::new("Files"));
+        self.set_picker_query("");
+    }
+
+    fn open_grep(&mut self, pattern: &str) {
+        self.picker_source = Some(PickerSource::Grep(None));
+        self.picker = Some(Picker::new("Grep"));
+        self.set_picker_query(pattern);
+    }
+
+    fn set_picker_query(&mut self, query: &str) {
+        let (Some(picker), Some(source)) = (self.picker.as_mut(), self.picker_source.as_mut())
+        else {
+            return;
+        };
+        picker.query = query.into();
+        match source {
+            PickerSource::Files(finder) => {
+                let items = finder
+                    .find(query)
+                    .into_iter()
+                    .map(|found| PickerItem {
+                        label: found.label,
+                        highlights: found.positions,
+                        path: found.path,
+                        position: None,
+                        index: None,
+                    })
+                    .collect();
+                picker.set_items(items);
+            }
+            PickerSource::Spelling {
+                suggestions: labels,
+                ..
+            }
+            | PickerSource::CodeActions(labels)
+            | PickerSource::Palette(labels) => {
+                let items = labels
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, suggestion)| {
+                        let (_, highlights) = fuzzy_match(query, suggestion)?;
+                        Some(PickerItem {
+                            label: suggestion.clone(),
+                            highlights,
+                            path: path::PathBuf::new(),
+                            position: None,
+                            index: Some(index),
+                        })
+                    })
+                    .collect();
+                picker.set_items(items);
+            }
+            PickerSource::Locations(locations) => {
+                let items = locations
+                    .iter()
+                    .filter_map(|location| {
+                        let (_, highlights) = fuzzy_match(query, &location.label)?;
+                        Some(PickerItem {
+                            highlights,
+                            ..location.clone()
+                        })
+                    })
+                    .collect();
+                picker.set_items(items);
+            }
+            PickerSource::Grep(search) => {
+                // Stop the previous search before starting the next one
+                *search = None;
+                picker.set_items(Vec::new());
+                picker.searching = false;
+                if query.is_empty() {
+                    return;
+                }
+                let options = SearchOptions {
+                    ignore_case: self.search_config.ignore_case,
+                    regex: self.search_config.regex_search,
+                    show_hidden: self.explorer_config.show_hidden_files,
+                };
+                match ProjectSearch::start(".".into(), query, options) {
+                    Ok(started) => {
+                        *search = Some(Box::new(started));
+                        picker.searching = true;
+                    }
+                    Err(e) => self.status_message.set_message(e.to_string()),
+                }
+            }
+        }
+    }
+
+    fn poll_tasks(&mut self) -> bool {
+        self.sync_overlays();
+        self.run_change_hooks();
+        self.apply_script_results();
+        self.apply_language_answers();
+        let mut busy = self.poll_plugins();
+        let mut errors = Vec::new();
+        for extension in self.extensions.iter_mut() {
+            match extension.poll() {
+                Ok(waiting) => busy |= waiting,
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        for error in errors {
+            log_info!("{}", error);
+            self.set_message(error);
+        }
+
+        let (Some(picker), Some(PickerSource::Grep(Some(search)))) =
+            (self.picker.as_mut(), self.picker_source.as_mut())
+        else {
+            return busy;
+        };
+        let items = search.poll().into_iter().map(Self::grep_item).collect();
+        picker.push_items(items);
+        picker.searching = !search.is_done();
+        picker.searching || busy
+    }
+
+    fn picker_move(&mut self, delta: isize) {
+        if let Some(picker) = self.picker.as_mut() {
+            picker.move_selection(delta);
+        }
+    }
+
+    fn picker_accept(&mut self) -> io::Result<()> {
+        // A picked correction replaces the misspelled word
+        if let Some(PickerSource::Spelling { row, word, .. }) = &self.picker_source {
+            let (row, word) = (*row, word.clone());
+            let correction = self
+                .picker
+                .as_ref()
+                .and_then(|picker| picker.selected())
+                .map(|item| item.label.clone());
+            self.close_picker();
+            if let Some(correction) = correction {
+                self.replace_text(row, word, &correction);
+                self.end_change();
+            }
+            return Ok(());
+        }
+
+        if let Some(PickerSource::Palette(_)) = &self.picker_source {
+            self.palette_choice = self
+                .picker
+                .as_ref()
+                .and_then(|picker| picker.selected())
+                .and_then(|item| item.index);
+            self.close_picker();
+            return Ok(());
+        }
+        // A picked code action is run by the server that offered it
+        if let Some(PickerSource::CodeActions(_)) = &self.picker_source {
+            let index = self
+                .picker
+                .as_ref()
+                .and_then(|picker| picker.selected())
+                .and_then(|item| item.index);
+            self.close_picker();
+            let (Some(index), Some(servers)) = (index, self.extensions.get_mut::<LanguageServers>())
+            else {
+                return Ok(());
+            };
+            if let Some(edit) = servers.run_code_action(index)? {
+                self.apply_workspace_edit(edit)?;
+            }
+            return Ok(());
+        }
+
+        let selected = self
+            .picker
+            .as_ref()
+            .and_then(|picker| picker.selected())
+            .map(|item| (item.path.clone(), item.position));
+        // Going to a place a language server pointed to can be gone back on
+        let jumps = matches!(self.picker_source, Some(PickerSource::Locations(_)));
+        self.close_picker();
+        let Some((path, position)) = selected else {
+            return Ok(());
+        };
+        if jumps {
+            self.push_jump();
+        }
+        self.open_file(Some(path))?;
+        if let Some((x, y)) = position {
+            self.set_cursor_position(x, y);
+        }
+        Ok(())
+    }
+
+    fn close_picker(&mut self) {
+        self.picker = None;
+        self.picker_source = None;
+    }
+
+    fn open_palette(&mut self, labels: Vec<String>) {
+        self.picker = Some(Picker::new("Commands"));
+        self.picker_source = Some(PickerSource::Palette(labels));
+        self.set_picker_query("");
+    }
+
+    fn take_palette_choice(&mut self) -> Option<usize> {
+        self.palette_choice.take()
+    }
+
+    fn picker_open(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    fn draw_popup(&mut self) {
+        self.sync_overlays();
+        // Bottom to top, each popup covering those below it
+        let kinds: Vec<OverlayKind> = self
+            .overlays
+            .overlays()
+            .iter()
+            .map(|overlay| overlay.kind)
+            .collect();
+        for kind in kinds {
+            let rect = self.draw_overlay(kind).unwrap();
+            self.overlays.set_rect(kind, rect.unwrap_or_default());
+        }
+    }
+
+    fn focused_overlay(&self) -> Option<OverlayKind> {
+        self.overlays.focused()
+    }
+
+    fn insert_char(&mut self, ch: char) {
+        if !self.begin_edit() {
+            return;
+        }
+        let (window, buffer) = self.focused();
+        let (x, y) = window.cursor_controller.get_file_position();
+        buffer.editor_rows.insert_char(x, y, ch);
+        window
+            .cursor_controller
+            .set_cursor_position(x + ch.len_utf8(), y);
+    }
+
+    fn insert_newline(&mut self) {
+        if !self.begin_edit() {
+            return;
+        }
+        let (window, buffer) = self.focused();
+        let (x, y) = window.cursor_controller.get_file_position();
+        buffer.editor_rows.insert_newline(x, y);
+        window.cursor_controller.set_cursor_position(0, y + 1);
+    }
+
+    fn delete_char(&mut self) {
+        let (window, buffer) = self.focused();
+        let (x, y) = window.cursor_controller.get_file_position();
+        if y >= buffer.editor_rows.number_of_rows() || (x == 0 && y == 0) {
+            return;
+        }
+        if !self.begin_edit() {
+            return;
+        }
+        let (window, buffer) = self.focused();
+        let (new_x, new_y) = if x == 0 {
+            (buffer.editor_rows.get_row(y - 1).len(), y - 1)
+        } else {
+            let previous = buffer.editor_rows.get_row(y)[..x]
+                .chars()
+                .next_back()
+                .map_or(0, char::len_utf8);
+            (x - previous, y)
+        };
+        buffer.editor_rows.delete_char(x, y);
+        window.cursor_controller.set_cursor_position(new_x, new_y);
+    }
+
+    fn end_change(&mut self) {
+        self.focused().1.undo_history.end_change();
+    }
+
+    fn complete(&mut self) -> io::Result<()> {
+        self.open_completion(None);
+        match self.completion {
+            Some(_) => Ok(()),
+            None => Err(io::Error::other("No completions")),
+        }
+    }
+
+    fn update_completion(&mut self, typed: Option<char>) {
+        if self.completion.is_some() {
+            self.filter_completion();
+            return;
+        }
+        let Some(ch) = typed.filter(|_| self.completion_config.auto) else {
+            return;
+        };
+        // Characters such as `.` have the language server complete what
+        // comes next, whatever was typed before
+        let (_, _, line) = self.line_before_cursor();
+        let typed = line[word_start(&line)..].chars().count();
+        if self.triggers_completion(ch) {
+            self.open_completion(Some(ch));
+        } else if is_word_char(ch) && typed >= self.completion_config.min_prefix {
+            self.open_completion(None);
+        }
+    }
+
+    fn completion_move(&mut self, delta: isize) {
+        if let Some(completion) = self.completion.as_mut() {
+            completion.move_selection(delta);
+        }
+    }
+
+    fn completion_accept(&mut self) -> bool {
+        let (buffer_id, y, line) = self.line_before_cursor();
+        let Some(completion) = self.completion.take() else {
+            return false;
+        };
+        let item = completion
+            .selected()
+            .filter(|_| completion.buffer_id() == buffer_id && completion.row() == y);
+        let Some(item) = item else {
+            return false;
+        };
+        let start = cmp::min(item.start, line.len());
+        let x = line.len();
+        // Snippets may span several rows, the cursor ending up after them
+        let mut inserted = item.insert.split('\n');
+        let first = inserted.next().unwrap_or_default();
+        let rest: Vec<&str> = inserted.collect();
+        if rest.is_empty() {
+            self.replace_text(y, start..x, first);
+            return true;
+        }
+        if !self.begin_edit() {
+            return true;
+        }
+        let (window, buffer) = self.focused();
+        let mut rows = buffer.editor_rows.get_rows();
+        let Some(row) = rows.get_mut(y) else {
+            return true;
+        };
+        let after = row.split_off(x);
+        row.truncate(start);
+        row.push_str(first);
+        let last = rest.len() - 1;
+        let mut added: Vec<String> = rest.iter().map(|text| text.to_string()).collect();
+        let cursor = (added[last].len(), y + rest.len());
+        added[last].push_str(&after);
+        rows.splice(y + 1..y + 1, added);
+        buffer.editor_rows.set_rows(rows);
+        window
+            .cursor_controller
+            .set_cursor_position(cursor.0, cursor.1);
+        true
+    }
+
+    fn close_completion(&mut self) {
+        self.completion = None;
+    }
+
+    fn undo(&mut self) -> bool {
+        let (window, buffer) = self.focused();
+        let current = Self::snapshot(window, buffer);
+        match buffer.undo_history.undo(current) {
+            Some(previous) => {
+                Self::restore(window, buffer, previous);
+                // The extensions hear of it like of any other edit
+                let id = buffer.id;
+                self.edited.insert(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn redo(&mut self) -> bool {
+        let (window, buffer) = self.focused();
+        let current = Self::snapshot(window, buffer);
+        match buffer.undo_history.redo(current) {
+            Some(next) => {
+                Self::restore(window, buffer, next);
+                let id = buffer.id;
+                self.edited.insert(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn jump_to_hunk(&mut self, forward: bool) -> bool {
+        let window = self.layout().focused();
+        let Some(buffer) = self.buffers.get(window.buffer_id) else {
+            return false;
+        };
+        let Some(hunks) = self
+            .extensions
+            .get::<GitIntegration>()
+            .and_then(|git| git.hunks(buffer.id))
+        else {
+            return false;
+        };
+        let (_, row) = window.cursor_controller.get_file_position();
+        let last_row = buffer.editor_rows.number_of_rows().saturating_sub(1);
+        // Deleted rows are reached through the row below them
+        let mut starts = hunks.iter().map(|hunk| cmp::min(hunk.start, last_row));
+        let target = if forward {
+            starts.find(|start| *start > row)
+        } else {
+            starts.rev().find(|start| *start < row)
+        };
+        match target {
+            Some(target) => {
+                self.set_cursor_position(0, target);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn format_buffer(&mut self) -> io::Result<()> {
+        // Only language servers format selections. Whole buffers go to the
+        // formatter of their language, if there is one.
+        let range = self.selected_range();
+        let window = self.tabs[self.current_tab].focused();
+        let buffer = self
+            .buffers
+            .get(window.buffer_id)
+            .ok_or_else(|| io::Error::other("No buffer"))?;
+        let view = Self::buffer_view(window, buffer);
+        let external = self
+            .extensions
+            .get::<CodeFormatter>()
+            .is_some_and(|formatter| formatter.formats(&view));
+        if let Some(servers) = self
+            .extensions
+            .get_mut::<LanguageServers>()
+            .filter(|servers| range.is_some() || (!external && servers.formats(&view)))
+        {
+            return servers.format(&view, range);
+        }
+        if range.is_some() {
+            return Err(io::Error::other("Language servers are disabled"));
+        }
+
+        let formatter = self
+            .extensions
+            .get::<CodeFormatter>()
+            .ok_or_else(|| io::Error::other("Code formatting is disabled"))?;
+        if let Some(rows) = formatter.format(&view)? {
+            self.replace_rows(rows);
+        }
+        Ok(())
+    }
+
+    fn goto_definition(&mut self) -> io::Result<()> {
+        self.ask_language_server(|servers, buffer| servers.query(buffer, Query::Definition))
+    }
+
+    fn find_references(&mut self) -> io::Result<()> {
+        self.ask_language_server(|servers, buffer| servers.query(buffer, Query::References))
+    }
+
+    fn show_hover(&mut self) -> io::Result<()> {
+        self.ask_language_server(|servers, buffer| servers.query(buffer, Query::Hover))
+    }
+
+    fn rename_symbol(&mut self, name: &str) -> io::Result<()> {
+        self.ask_language_server(|servers, buffer| servers.rename(buffer, name))
+    }
+
+    fn code_actions(&mut self) -> io::Result<()> {
+        let range = self.selected_range();
+        self.ask_language_server(|servers, buffer| {
+            servers.code_actions(buffer, range.unwrap_or((buffer.cursor, buffer.cursor)))
+        })
+    }
+
+    fn close_hover(&mut self) {
+        self.hover = None;
+    }
+
+    fn show_key_hints(&mut self, prefix: String, hints: Vec<(String, String)>) {
+        self.key_hints = Some((prefix, hints));
+    }
+
+    fn close_key_hints(&mut self) {
+        self.key_hints = None;
+    }
+
+    fn jump_back(&mut self) -> bool {
+        while let Some((id, (x, y))) = self.jumps.pop() {
+            // Buffers closed since are skipped
+            if self.buffers.get(id).is_some() {
+                self.show_buffer(id);
+                self.place_cursor(x, y);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn run_key_hooks(&mut self, key: &event::KeyEvent) {
+        let window = self.tabs[self.current_tab].focused();
+        let Some(buffer) = self.buffers.get(window.buffer_id) else {
+            return;
+        };
+        let view = Self::buffer_view(window, buffer);
+        let edits: Vec<BufferEdit> = self
+            .extensions
+            .iter_mut()
+            .filter_map(|extension| extension.on_key(&view, key))
+            .collect();
+        for edit in edits {
+            self.apply_edit(edit);
+        }
+    }
+
+    fn extension_commands(&self) -> Vec<String> {
+        self.extensions
+            .iter()
+            .flat_map(|extension| extension.commands())
+            .collect()
+    }
+
+    fn extension_summaries(&self) -> Vec<ExtensionSummary> {
+        self.extensions
+            .iter()
+            .map(|extension| ExtensionSummary {
+                name: extension.name().to_string(),
+                description: extension.description().to_string(),
+                commands: extension.commands(),
+            })
+            .collect()
+    }
+
+    fn extension_options(&self) -> Vec<(String, ExtensionOptions)> {
+        extensions::built_in()
+            .iter()
+            .filter_map(|extension| Some((extension.name().to_string(), extension.options()?)))
+            .collect()
+    }
+
+    fn run_extension_command(&mut self, name: &str, argument: Option<&str>) -> io::Result<()> {
+        let window = self.tabs[self.current_tab].focused();
+        let buffer = self
+            .buffers
+            .get(window.buffer_id)
+            .ok_or_else(|| io::Error::other("No buffer"))?;
+        let view = Self::buffer_view(window, buffer);
+        self.extensions
+            .iter_mut()
+            .find_map(|extension| extension.run_command(&view, name, argument))
+            .unwrap_or_else(|| {
+                let line = match argument {
+                    Some(argument) => format!("{} {}", name, argument),
+                    None => name.to_string(),
+                };
+                Err(io::Error::other(format!("Not an editor command: {}", line)))
+            })
+    }
+
+    fn key_bindings(&self, mode: &str) -> Vec<String> {
+        self.extensions
+            .iter()
+            .flat_map(|extension| extension.key_bindings(mode))
+            .collect()
+    }
+
+    fn run_key_binding(&mut self, mode: &str, key: &event::KeyEvent) -> bool {
+        let window = self.tabs[self.current_tab].focused();
+        let Some(buffer) = self.buffers.get(window.buffer_id) else {
+            return false;
+        };
+        let view = Self::buffer_view(window, buffer);
+        let result = self
+            .extensions
+            .iter_mut()
+            .find_map(|extension| extension.run_key_binding(&view, mode, key));
+        if let Some(Err(e)) = &result {
+            self.set_message(e.to_string());
+        }
+        result.is_some()
+    }
+
+    fn take_script_commands(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.script_commands)
+    }
+
+    fn before_save(&mut self) -> io::Result<()> {
+        let window = self.tabs[self.current_tab].focused();
+        let Some(buffer) = self.buffers.get(window.buffer_id) else {
+            return Ok(());
+        };
+        let view = Self::buffer_view(window, buffer);
+        let mut edits = Vec::new();
+        let mut errors = Vec::new();
+        for extension in self.extensions.iter_mut() {
+            match extension.on_save(&view) {
+                Ok(edit) => edits.extend(edit),
+                Err(e) => errors.push(format!("{}: {}", extension.name(), e)),
+            }
+        }
+        for edit in edits {
+            self.apply_edit(edit);
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(io::Error::other(errors.join("; "))),
+        }
+    }
+
+    fn after_save(&mut self) -> io::Result<Option<String>> {
+        let id = self.layout().focused().buffer_id;
+        if let Some((_, root)) = self
+            .commit_buffer
+            .clone()
+            .filter(|(commit, _)| *commit == id)
+        {
+            return self.commit_buffer_saved(&root).map(Some);
+        }
+
+        let window = self.tabs[self.current_tab].focused();
+        let Some(buffer) = self.buffers.get(id) else {
+            return Ok(None);
+        };
+        let view = Self::buffer_view(window, buffer);
+        let mut messages = Vec::new();
+        let mut errors = Vec::new();
+        for extension in self.extensions.iter_mut() {
+            match extension.after_save(&view) {
+                Ok(message) => messages.extend(message),
+                Err(e) => errors.push(format!("{}: {}", extension.name(), e)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(io::Error::other(errors.join("; ")));
+        }
+        Ok(Some(messages.join("; ")).filter(|message| !message.is_empty()))
+    }
+
+    fn save(&mut self, file_name: Option<path::PathBuf>) -> io::Result<usize> {
+        let buffer = self.focused().1;
+        if buffer.editor_rows.is_read_only() {
+            return Err(io::Error::other("Buffer is read-only"));
+        }
+        if let Some(file_name) = file_name {
+            buffer.editor_rows.set_file_name(file_name);
+        }
+        let written = buffer.editor_rows.save()?;
+
+        let id = buffer.id;
+        if self.markdown_preview == Some(id) {
+            self.snapshot_markdown();
+        }
+        Ok(written)
+    }
+
+    fn toggle_markdown_preview(&mut self) -> io::Result<()> {
+        if self.markdown_preview.take().is_some() {
+            return Ok(());
+        }
+        if !self.markdown_config.enabled {
+            return Err(io::Error::other("Markdown preview is disabled"));
+        }
+        let id = self.layout().focused().buffer_id;
+        if !self.buffers.get(id).is_some_and(Self::is_markdown) {
+            return Err(io::Error::other("Not a Markdown buffer"));
+        }
+        self.markdown_preview = Some(id);
+        self.snapshot_markdown();
+        Ok(())
+    }
+
+    fn toggle_blame(&mut self) -> io::Result<()> {
+        let window = self.tabs[self.current_tab].focused();
+        let Some(buffer) = self.buffers.get(window.buffer_id) else {
+            return Ok(());
+        };
+        let git = self
+            .extensions
+            .get_mut::<GitIntegration>()
+            .ok_or_else(|| io::Error::other("Git integration is disabled"))?;
+        git.toggle_blame(&Self::buffer_view(window, buffer))
+    }
+
+    fn open_git_panel(&mut self) -> io::Result<()> {
+        let root = self.repository_root()?;
+        self.git_panel = Some(GitPanel::new(root));
+        self.refresh_git_panel()
+    }
+
+    fn close_git_panel(&mut self) {
+        self.git_panel = None;
+    }
+
+    fn git_panel_move(&mut self, delta: isize) {
+        if let Some(panel) = self.git_panel.as_mut() {
+            panel.move_selection(delta);
+        }
+    }
+
+    fn git_panel_stage(&mut self, stage: bool) -> io::Result<()> {
+        let Some(panel) = &self.git_panel else {
+            return Ok(());
+        };
+        let git = self.git()?.git();
+        let root = &panel.root;
+        match panel.selected() {
+            Some(GitPanelRow::File { path, staged, .. }) if *staged != stage => {
+                if stage {
+                    git.stage(root, path)?
+                } else {
+                    git.unstage(root, path)?
+                }
+            }
+            Some(GitPanelRow::Hunk { hunk, staged, .. }) if *staged != stage => {
+                git.apply_to_index(root, hunk, !stage)?
+            }
+            _ => return Ok(()),
+        }
+        self.refresh_git_panel()
+    }
+
+    fn git_panel_toggle_hunks(&mut self) -> io::Result<()> {
+        let Some(panel) = self.git_panel.as_mut() else {
+            return Ok(());
+        };
+        let key = match panel.selected() {
+            Some(
+                GitPanelRow::File { path, staged, .. } | GitPanelRow::Hunk { path, staged, .. },
+            ) => (path.clone(), *staged),
+            _ => return Ok(()),
+        };
+        if !panel.expanded.remove(&key) {
+            panel.expanded.insert(key);
+        }
+        self.refresh_git_panel()
+    }
+
+    fn git_panel_open_file(&mut self) -> io::Result<()> {
+        let file = match self.git_panel.as_ref().and_then(|panel| {
+            let root = &panel.root;
+            match panel.selected()? {
+                GitPanelRow::File { path, .. } | GitPanelRow::Hunk { path, .. } => {
+                    Some(root.join(path))
+                }
+                GitPanelRow::Section { .. } => None,
+            }
+        }) {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        self.git_panel = None;
+        self.open_file(Some(file))?;
+        Ok(())
+    }
+
+    fn git_panel_refresh(&mut self) -> io::Result<()> {
+        self.refresh_git_panel()
+    }
+
+    fn start_commit(&mut self) -> io::Result<()> {
+        let root = match &self.git_panel {
+            Some(panel) => panel.root.clone(),
+            None => self.repository_root()?,
+        };
+        let git = self.git()?;
+        let staged: Vec<String> = git
+            .git()
+            .status(&root)?
+            .into_iter()
+            .filter(|status| !matches!(status.staged, ' ' | '?'))
+            .map(|status| format!("#\t{} {}", status.staged, status.path.to_string_lossy()))
+            .collect();
+        if staged.is_empty() {
+            return Err(io::Error::other("Nothing staged to commit"));
+        }
+
+        let mut rows = vec![
+            git.commit_message(),
+            String::new(),
+            "# Save this buffer to commit, or close it with :bd! to abort.".into(),
+            "# Lines starting with '#' are left out of the message.".into(),
+            "#".into(),
+            "# Changes to be committed:".into(),
+        ];
+        rows.extend(staged);
+
+        let file = git.git().commit_message_file(&root)?;
+        let mut editor_rows = EditorRows::new();
+        editor_rows.set_rows(rows);
+        editor_rows.set_file_name(file);
+        let id = self.add_buffer(editor_rows);
+        self.git_panel = None;
+        self.leave_explorer();
+        self.show_buffer(id);
+        self.commit_buffer = Some((id, root));
+        Ok(())
+    }
+
+    fn spell_suggest(&mut self) -> io::Result<()> {
+        let spell = self
+            .extensions
+            .get::<SpellCheck>()
+            .ok_or_else(|| io::Error::other("Spell checking is off"))?;
+        let Some((row, word)) = self.word_at_cursor() else {
+            return Err(io::Error::other("No word under the cursor"));
+        };
+        let typed = self.focused_ref().1.editor_rows.get_row(row)[word.clone()].to_string();
+        let suggestions = spell.suggest(&typed, SUGGESTION_LIMIT);
+        if suggestions.is_empty() {
+            return Err(io::Error::other(format!(
+                "No suggestions for \"{}\"",
+                typed
+            )));
+        }
+        self.picker = Some(Picker::new(&format!("Change \"{}\" to", typed)));
+        self.picker_source = Some(PickerSource::Spelling {
+            row,
+            word,
+            suggestions,
+        });
+        self.set_picker_query("");
+        Ok(())
+    }
+
+    fn spell_add_word(&mut self) -> io::Result<String> {
+        let Some((row, word)) = self.word_at_cursor() else {
+            return Err(io::Error::other("No word under the cursor"));
+        };
+        let word = self.focused_ref().1.editor_rows.get_row(row)[word].to_string();
+        let spell = self
+            .extensions
+            .get_mut::<SpellCheck>()
+            .ok_or_else(|| io::Error::other("Spell checking is off"))?;
+        spell.add_word(&word)?;
+        Ok(word)
+    }
+
+    fn jump_to_misspelling(&mut self, forward: bool) -> bool {
+        let (window, buffer) = self.focused_ref();
+        let (x, y) = window.cursor_controller.get_file_position();
+        let Some(spell) = self.extensions.get::<SpellCheck>() else {
+            return false;
+        };
+        let rows = &*buffer.editor_rows;
+        let misspellings = spell.misspellings(buffer.id, 0..rows.number_of_rows());
+        let mut positions = misspellings.iter().map(|(row, word)| (word.start, *row));
+        let target = if forward {
+            positions.find(|(start, row)| (*row, *start) > (y, x))
+        } else {
+            positions.rev().find(|(start, row)| (*row, *start) < (y, x))
+        };
+        match target {
+            Some((start, row)) => {
+                self.set_cursor_position(start, row);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn open_revision(&mut self, revision: &str) -> io::Result<()> {
+        let file = self
+            .focused()
+            .1
+            .editor_rows
+            .get_file_name()
+            .cloned()
+            .ok_or_else(|| io::Error::other("No file name"))?;
+        let content = self.git()?.git().show(&file, revision)?;
+
+        // Named after the file and revision, so opening it again switches to it
+        let mut name = file.clone().into_os_string();
+        name.push(format!("@{}", revision));
+        let name = path::PathBuf::from(name);
+        let id = match self.buffers.find_by_path(&name) {
+            Some(id) => id,
+            None => self.add_buffer(EditorRows::read_only(name, &content)),
+        };
+        self.leave_explorer();
+        self.show_buffer(id);
+        Ok(())
+    }
+
+    fn open_read_only(&mut self, name: path::PathBuf, text: &str, row: usize) {
+        let id = match self.buffers.find_by_path(&name) {
+            Some(id) => id,
+            None => self.add_buffer(EditorRows::read_only(name, text)),
+        };
+        self.push_jump();
+        self.leave_explorer();
+        self.show_buffer(id);
+        self.place_cursor(0, row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(width: usize) -> EditorDomain {
+        let config = "[cursor_keymaps]\ndown = 'j'\nup = 'k'\nleft = 'h'\nright = 'l'\n";
+        EditorDomain::new((width, 10), &toml::from_str(config).unwrap())
+    }
+
+    #[test]
+    fn fits_text_by_chars() {
+        assert_eq!(EditorDomain::fit("héllo wörld", 5), "héllo");
+        assert_eq!(EditorDomain::fit("日本語", 2), "日本");
+        assert_eq!(EditorDomain::fit("short", 80), "short");
+    }
+
+    #[test]
+    fn draws_messages_wider_than_the_window() {
+        let mut domain = domain(10);
+        domain.set_message("Error: файл не найден".into());
+        domain.draw_message_bar();
+        domain.set_command_line(Some(":e ファイル名ファイル名".into()));
+        domain.draw_message_bar();
+    }
+
+    #[test]
+    fn records_no_undo_step_for_edits_outside_the_buffer() {
+        let mut domain = domain(80);
+        domain.open_file(None).unwrap();
+        let rows = |domain: &mut EditorDomain| domain.focused().1.editor_rows.get_rows();
+        domain.replace_rows(vec!["abc".into()]);
+        domain.replace_text(3, 0..1, "x");
+        domain.replace_text(0, 2..9, "x");
+        assert_eq!(rows(&mut domain), vec!["abc"]);
+        assert!(domain.undo());
+        assert!(!domain.undo());
+
+        domain.replace_rows(vec!["abc".into()]);
+        domain.replace_text(0, 1..2, "x");
+        domain.end_change();
+        assert_eq!(rows(&mut domain), vec!["axc"]);
+        assert!(domain.undo());
+        assert_eq!(rows(&mut domain), vec!["abc"]);
     }
 }
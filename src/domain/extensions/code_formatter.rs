@@ -0,0 +1,86 @@
+use crate::adapters::formatter::ExternalFormatter;
+use crate::ports::config::CodeFormatterConfig;
+use crate::ports::extension::{BufferEdit, BufferView, Extension, ExtensionOptions};
+use crate::ports::formatter::FormatterPort;
+use std::any::Any;
+use std::io;
+
+/// Pipes buffers through the formatter of their language.
+pub struct CodeFormatter {
+    formatter: Option<Box<dyn FormatterPort>>,
+    config: CodeFormatterConfig,
+}
+
+impl CodeFormatter {
+    /// Returns whether there is a formatter for the language of `buffer`.
+    pub fn formats(&self, buffer: &BufferView) -> bool {
+        buffer
+            .rows
+            .get_file_name()
+            .zip(self.formatter.as_ref())
+            .is_some_and(|(file, formatter)| formatter.formats(file))
+    }
+
+    /// Returns the rows of `buffer` formatted, or `None` if formatting
+    /// leaves them as they are.
+    pub fn format(&self, buffer: &BufferView) -> io::Result<Option<Vec<String>>> {
+        let file = buffer
+            .rows
+            .get_file_name()
+            .ok_or_else(|| io::Error::other("No file name"))?;
+        let formatter = self
+            .formatter
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Code formatting is disabled"))?;
+        let rows = buffer.rows.get_rows();
+        let text: String = rows.iter().map(|row| format!("{}\n", row)).collect();
+        let formatted: Vec<String> = formatter
+            .format(file, &text)?
+            .lines()
+            .map(String::from)
+            .collect();
+        Ok(Some(formatted).filter(|formatted| *formatted != rows))
+    }
+}
+
+impl Extension for CodeFormatter {
+    fn new() -> Self {
+        Self {
+            formatter: None,
+            config: CodeFormatterConfig::default(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "code_formatter"
+    }
+
+    fn description(&self) -> &str {
+        "Runs buffers through the formatter of their language."
+    }
+
+    fn options(&self) -> Option<ExtensionOptions> {
+        Some(super::options_of::<CodeFormatterConfig>())
+    }
+
+    fn init(&mut self, options: &toml::Table) -> io::Result<()> {
+        self.config = super::parse_options(options)?;
+        self.formatter = Some(Box::new(ExternalFormatter::new(&self.config)));
+        Ok(())
+    }
+
+    fn on_save(&mut self, buffer: &BufferView) -> io::Result<Option<BufferEdit>> {
+        if !self.config.format_on_save || !self.formats(buffer) || buffer.rows.is_read_only() {
+            return Ok(None);
+        }
+        Ok(self.format(buffer)?.map(BufferEdit::ReplaceAll))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,306 @@
+use crate::adapters::git::GitCli;
+use crate::ports::config::GitIntegrationConfig;
+use crate::ports::extension::{BufferView, Decoration, Extension, ExtensionOptions};
+use crate::ports::git::{BlameLine, GitPort};
+use crate::toolshed::date::today;
+use crate::toolshed::diff::{diff_lines, Hunk, HunkKind};
+use crossterm::style;
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::{fs, io, path};
+
+/// Width of the column of git change markers: the marker and a space.
+const SIGN_COLUMN_WIDTH: usize = 2;
+/// Width of the blame column: the commit, the author and the date, each
+/// followed by a space.
+const BLAME_COLUMN_WIDTH: usize = 35;
+
+/// Marks the rows that differ from the last commit, blames rows and
+/// commits saved files if asked to.
+pub struct GitIntegration {
+    git: Box<dyn GitPort>,
+    config: GitIntegrationConfig,
+    /// The file of each buffer that has one, by buffer id.
+    files: HashMap<usize, path::PathBuf>,
+    /// The rows of each tracked buffer's file as of the last commit, by
+    /// buffer id, which the buffer is compared with to place change markers.
+    bases: HashMap<usize, Vec<String>>,
+    /// How each tracked buffer differs from its base, by buffer id, worked
+    /// out again when either changes rather than on each draw.
+    hunks: HashMap<usize, Vec<Hunk>>,
+    /// Who last changed each row, by buffer id, for the buffers in blame mode.
+    blames: HashMap<usize, Vec<BlameLine>>,
+}
+
+impl GitIntegration {
+    pub fn git(&self) -> &dyn GitPort {
+        &*self.git
+    }
+
+    /// The message commits start with, for today.
+    pub fn commit_message(&self) -> String {
+        self.config
+            .commit_message_template
+            .replace("{date}", &today())
+    }
+
+    /// Returns the root of the repository holding `file`, or the working
+    /// directory if there is no file.
+    pub fn repository_root(&self, file: Option<&path::Path>) -> io::Result<path::PathBuf> {
+        let directory = file
+            .and_then(|file| fs::canonicalize(file).ok())
+            .and_then(|file| file.parent().map(path::Path::to_path_buf))
+            .unwrap_or_else(|| ".".into());
+        self.git.repository_root(&directory)
+    }
+
+    /// Returns how buffer `id` differs from its committed version, or `None`
+    /// if it is not tracked.
+    pub fn hunks(&self, id: usize) -> Option<&[Hunk]> {
+        self.hunks.get(&id).map(Vec::as_slice)
+    }
+
+    /// Shows or hides who last changed each row of `buffer`.
+    pub fn toggle_blame(&mut self, buffer: &BufferView) -> io::Result<()> {
+        if self.blames.remove(&buffer.id).is_some() {
+            return Ok(());
+        }
+        self.load_blame(buffer.id);
+        if self.blames.contains_key(&buffer.id) {
+            Ok(())
+        } else {
+            Err(io::Error::other("File is not tracked by git"))
+        }
+    }
+
+    /// Catches up with a new commit: change markers and blame of `buffers`
+    /// are relative to `HEAD`.
+    pub fn after_commit(&mut self, buffers: &[BufferView]) {
+        for buffer in buffers {
+            self.reload(buffer);
+        }
+    }
+
+    /// Reads the committed version of `buffer`'s file and its blame again.
+    fn reload(&mut self, buffer: &BufferView) {
+        self.load_base(buffer.id);
+        self.compare(buffer);
+        if self.blames.contains_key(&buffer.id) {
+            self.load_blame(buffer.id);
+        }
+    }
+
+    /// Compares `buffer` with its committed version, if it is tracked.
+    fn compare(&mut self, buffer: &BufferView) {
+        let Some(base) = self.bases.get(&buffer.id) else {
+            self.hunks.remove(&buffer.id);
+            return;
+        };
+        let old: Vec<&str> = base.iter().map(String::as_str).collect();
+        let rows = buffer.rows;
+        let new: Vec<&str> = (0..rows.number_of_rows())
+            .map(|at| rows.get_row(at))
+            .collect();
+        self.hunks.insert(buffer.id, diff_lines(&old, &new));
+    }
+
+    /// Reads the committed version of buffer `id`'s file, if it is tracked.
+    fn load_base(&mut self, id: usize) {
+        let content = self
+            .files
+            .get(&id)
+            .and_then(|file| self.git.head_content(file));
+        match content {
+            Some(content) => self
+                .bases
+                .insert(id, content.lines().map(String::from).collect()),
+            None => self.bases.remove(&id),
+        };
+    }
+
+    /// Blames the saved file of buffer `id`, leaving blame mode if that fails.
+    fn load_blame(&mut self, id: usize) {
+        let blame = self
+            .files
+            .get(&id)
+            .and_then(|file| self.git.blame(file).ok());
+        match blame {
+            Some(blame) => self.blames.insert(id, blame),
+            None => self.blames.remove(&id),
+        };
+    }
+
+    /// Commits the saved file of `buffer` on its own, with the message of
+    /// the configured template.
+    fn auto_commit(
+        &mut self,
+        buffer: &BufferView,
+        file: &path::Path,
+    ) -> io::Result<Option<String>> {
+        let root = self.repository_root(Some(file))?;
+        self.git.stage(&root, &fs::canonicalize(file)?)?;
+        // Saving without changes leaves nothing to commit
+        let statuses = self.git.status(&root)?;
+        if statuses
+            .iter()
+            .all(|status| matches!(status.staged, ' ' | '?'))
+        {
+            return Ok(None);
+        }
+        let message = self.commit_message();
+        let hash = self.git.commit(&root, &message)?;
+        self.reload(buffer);
+        Ok(Some(format!("Committed {}: {}", hash, message)))
+    }
+
+    /// Returns the widths of the blame and sign columns in front of the
+    /// rows of `buffer`.
+    fn column_widths(&self, buffer: &BufferView) -> (usize, usize) {
+        let blame = match self.blames.contains_key(&buffer.id) {
+            true => BLAME_COLUMN_WIDTH,
+            false => 0,
+        };
+        let signs = match self.bases.contains_key(&buffer.id) {
+            true => SIGN_COLUMN_WIDTH,
+            false => 0,
+        };
+        (blame, signs)
+    }
+}
+
+impl Extension for GitIntegration {
+    fn new() -> Self {
+        Self {
+            git: Box::new(GitCli::new()),
+            config: GitIntegrationConfig::default(),
+            files: HashMap::new(),
+            bases: HashMap::new(),
+            hunks: HashMap::new(),
+            blames: HashMap::new(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "git_integration"
+    }
+
+    fn description(&self) -> &str {
+        "Marks the rows changed since the last commit, shows who changed them and commits files."
+    }
+
+    fn options(&self) -> Option<ExtensionOptions> {
+        Some(super::options_of::<GitIntegrationConfig>())
+    }
+
+    fn init(&mut self, options: &toml::Table) -> io::Result<()> {
+        self.config = super::parse_options(options)?;
+        Ok(())
+    }
+
+    fn on_open(&mut self, buffer: &BufferView) {
+        if let Some(file) = buffer.rows.get_file_name() {
+            self.files.insert(buffer.id, file.clone());
+            self.load_base(buffer.id);
+            self.compare(buffer);
+        }
+    }
+
+    fn on_change(&mut self, buffer: &BufferView) {
+        if self.bases.contains_key(&buffer.id) {
+            self.compare(buffer);
+        }
+    }
+
+    fn after_save(&mut self, buffer: &BufferView) -> io::Result<Option<String>> {
+        let Some(file) = buffer.rows.get_file_name() else {
+            return Ok(None);
+        };
+        // A buffer saved under a new name is compared with that file
+        if self.files.get(&buffer.id) != Some(file) {
+            self.files.insert(buffer.id, file.clone());
+            self.load_base(buffer.id);
+            self.compare(buffer);
+        }
+        // The saved rows are blamed on the working tree from now on
+        if self.blames.contains_key(&buffer.id) {
+            self.load_blame(buffer.id);
+        }
+        if self.config.auto_commit {
+            return self.auto_commit(buffer, file);
+        }
+        Ok(None)
+    }
+
+    fn on_close(&mut self, buffer: &BufferView) {
+        self.files.remove(&buffer.id);
+        self.bases.remove(&buffer.id);
+        self.hunks.remove(&buffer.id);
+        self.blames.remove(&buffer.id);
+    }
+
+    fn gutter_width(&self, buffer: &BufferView) -> usize {
+        let (blame, signs) = self.column_widths(buffer);
+        blame + signs
+    }
+
+    fn decorations(&self, buffer: &BufferView, rows: Range<usize>) -> Vec<Decoration> {
+        let (blame_width, _) = self.column_widths(buffer);
+        let mut decorations = Vec::new();
+        if let Some(blame) = self.blames.get(&buffer.id) {
+            // Rows added since the file was saved have no blame yet
+            for row in rows.clone() {
+                let Some(line) = blame.get(row) else {
+                    break;
+                };
+                decorations.push(Decoration::Gutter {
+                    row,
+                    column: 0,
+                    text: format!(
+                        "{:<8} {:<14.14} {:<10} ",
+                        line.commit.as_deref().unwrap_or_default(),
+                        line.author,
+                        line.date
+                    ),
+                    color: style::Color::DarkGrey,
+                });
+            }
+        }
+
+        for hunk in self.hunks(buffer.id).unwrap_or_default() {
+            let (sign, color, marked) = match hunk.kind {
+                HunkKind::Added => (
+                    '+',
+                    style::Color::Green,
+                    hunk.start..hunk.start + hunk.count,
+                ),
+                HunkKind::Modified => (
+                    '~',
+                    style::Color::Yellow,
+                    hunk.start..hunk.start + hunk.count,
+                ),
+                // Deleted rows are marked below the row before them, or
+                // above the first row
+                HunkKind::Deleted if hunk.start == 0 => ('‾', style::Color::Red, 0..1),
+                HunkKind::Deleted => ('_', style::Color::Red, hunk.start - 1..hunk.start),
+            };
+            for row in marked.filter(|row| rows.contains(row)) {
+                decorations.push(Decoration::Gutter {
+                    row,
+                    column: blame_width,
+                    text: sign.to_string(),
+                    color,
+                });
+            }
+        }
+        decorations
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,1243 @@
+use crate::adapters::completion_sources::word_start;
+use crate::domain::language_client::{LanguageClient, PositionEncoding};
+use crate::domain::workspace_edit::{FileEdit, TextEdit, WorkspaceEdit};
+use crate::ports::completion::CompletionItem;
+use crate::ports::config::{LanguageServerConfig, LanguageServersConfig};
+use crate::ports::extension::{BufferView, Decoration, Extension, ExtensionOptions};
+use crate::ports::rpc::RpcMessage;
+use crate::toolshed::uri;
+use crossterm::style;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::{env, io, path};
+
+/// The servers known out of the box: their language, the extensions of the
+/// files they are for and the command starting them.
+const SERVERS: &[(&str, &[&str], &str)] = &[
+    ("rust", &["rs"], "rust-analyzer"),
+    ("python", &["py"], "pylsp"),
+    (
+        "typescript",
+        &["ts", "tsx", "js", "jsx"],
+        "typescript-language-server --stdio",
+    ),
+    ("go", &["go"], "gopls"),
+    ("c", &["c", "h", "cc", "cpp", "hpp"], "clangd"),
+];
+
+/// How serious a diagnostic is, most serious first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Information => "info",
+            Severity::Hint => "hint",
+        }
+    }
+
+    fn color(self) -> style::Color {
+        match self {
+            Severity::Error => style::Color::Red,
+            Severity::Warning => style::Color::Yellow,
+            Severity::Information => style::Color::Blue,
+            Severity::Hint => style::Color::Cyan,
+        }
+    }
+}
+
+/// A problem a server found in a document. Positions are rows and
+/// characters, counted in the encoding of the server.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+    /// What found the problem, such as `rustc`.
+    pub source: Option<String>,
+    /// The diagnostic as the server sent it, handed back when asking for
+    /// fixes.
+    sent: Value,
+}
+
+impl Diagnostic {
+    fn parse(value: &Value) -> Option<Self> {
+        let position = |at: &Value| {
+            Some((
+                at["line"].as_u64()? as usize,
+                at["character"].as_u64()? as usize,
+            ))
+        };
+        Some(Self {
+            start: position(&value["range"]["start"])?,
+            end: position(&value["range"]["end"])?,
+            severity: match value["severity"].as_u64() {
+                Some(2) => Severity::Warning,
+                Some(3) => Severity::Information,
+                Some(4) => Severity::Hint,
+                _ => Severity::Error,
+            },
+            message: value["message"].as_str()?.to_string(),
+            source: value["source"].as_str().map(String::from),
+            sent: value.clone(),
+        })
+    }
+}
+
+/// What a buffer's server is asked about the symbol under the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    Definition,
+    References,
+    Hover,
+}
+
+impl Query {
+    fn method(self) -> &'static str {
+        match self {
+            Query::Definition => "textDocument/definition",
+            Query::References => "textDocument/references",
+            Query::Hover => "textDocument/hover",
+        }
+    }
+
+    /// The capability of servers that answer the query.
+    fn capability(self) -> &'static str {
+        match self {
+            Query::Definition => "definitionProvider",
+            Query::References => "referencesProvider",
+            Query::Hover => "hoverProvider",
+        }
+    }
+}
+
+/// A place in a file a server pointed to. The character is counted in
+/// `encoding`, which takes the text of the row to turn into a byte offset.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub file: path::PathBuf,
+    pub row: usize,
+    pub character: usize,
+    pub encoding: PositionEncoding,
+}
+
+/// A server's answer to a query.
+pub enum Answer {
+    Locations(Query, Vec<Location>),
+    /// The type and documentation of a symbol, as Markdown.
+    Hover(String),
+    /// Changes to make to files, such as those renaming a symbol, with
+    /// the request to answer through `answer_edit` if a server asked for
+    /// them.
+    Edit(WorkspaceEdit, Option<EditRequest>),
+    /// The titles of the code actions offered, to run one with
+    /// `run_code_action`.
+    CodeActions(Vec<String>),
+    Completions(Vec<CompletionItem>),
+}
+
+/// A server's request to make a workspace edit, waiting for the editor
+/// to try it.
+pub struct EditRequest {
+    language: String,
+    id: Value,
+}
+
+/// What a request to a server was sent for, to make sense of its answer.
+enum Request {
+    Query(Query),
+    Rename,
+    CodeActions,
+    /// Formatting of the document of buffer `buffer` as of `version`.
+    Format {
+        buffer: usize,
+        version: i64,
+    },
+    /// Running the command of a code action, which edits through
+    /// `workspace/applyEdit` if at all.
+    Command,
+    /// Completion where the cursor is at the end of `line`, in the word
+    /// starting at byte offset `word_start`.
+    Completion {
+        line: String,
+        word_start: usize,
+    },
+}
+
+/// A buffer the server of its language was told about.
+struct Document {
+    uri: String,
+    language: String,
+    version: i64,
+}
+
+/// Starts a language server for each language of the files opened, keeps
+/// it in sync with their buffers and shows the problems it finds.
+pub struct LanguageServers {
+    servers: HashMap<String, LanguageServerConfig>,
+    /// The project the servers work on, the working directory.
+    root: path::PathBuf,
+    clients: HashMap<String, LanguageClient>,
+    /// The languages whose server failed, which are not tried again.
+    failed: HashSet<String>,
+    /// The open documents, by buffer id.
+    documents: HashMap<usize, Document>,
+    /// The latest diagnostics of each document, by URI.
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Why servers failed, until the next poll reports it.
+    errors: Vec<String>,
+    /// The requests sent, by language and request id.
+    pending: HashMap<(String, u64), Request>,
+    answers: Vec<io::Result<Answer>>,
+    /// The code actions last offered, with the language of their server.
+    code_actions: Option<(String, Vec<Value>)>,
+}
+
+impl LanguageServers {
+    /// Returns the language of `file` among those with a server.
+    fn language(&self, file: &path::Path) -> Option<String> {
+        let extension = file.extension()?.to_str()?;
+        self.servers
+            .iter()
+            .find(|(_, server)| server.file_extensions.iter().any(|e| e == extension))
+            .map(|(language, _)| language.clone())
+    }
+
+    /// The language id the protocol gives files with `extension`, for
+    /// servers handling several.
+    fn language_id<'a>(language: &'a str, extension: &str) -> &'a str {
+        match extension {
+            "js" => "javascript",
+            "jsx" => "javascriptreact",
+            "tsx" => "typescriptreact",
+            "cc" | "cpp" | "hpp" => "cpp",
+            _ => language,
+        }
+    }
+
+    fn text(buffer: &BufferView) -> String {
+        buffer
+            .rows
+            .get_rows()
+            .iter()
+            .map(|row| format!("{}\n", row))
+            .collect()
+    }
+
+    /// Returns the client of `language`, starting its server if need be.
+    fn client(&mut self, language: &str) -> Option<&mut LanguageClient> {
+        if !self.clients.contains_key(language) && !self.failed.contains(language) {
+            let server = self.servers.get(language)?;
+            match LanguageClient::start(server, &self.root) {
+                Ok(client) => {
+                    self.clients.insert(language.to_string(), client);
+                }
+                Err(e) => {
+                    self.errors.push(format!(
+                        "Could not start the {} language server {}: {}",
+                        language, server.command, e
+                    ));
+                    self.failed.insert(language.to_string());
+                }
+            }
+        }
+        self.clients.get_mut(language)
+    }
+
+    /// Sends `method` to the server of `language`, noting if it fails.
+    fn notify(&mut self, language: &str, method: &str, params: Value) {
+        let Some(client) = self.client(language) else {
+            return;
+        };
+        if let Err(e) = client.notify(method, params) {
+            self.errors
+                .push(format!("The {} language server failed: {}", language, e));
+        }
+    }
+
+    fn open(&mut self, buffer: &BufferView) {
+        let Some(file) = buffer.rows.get_file_name() else {
+            return;
+        };
+        let Some(language) = self.language(file) else {
+            return;
+        };
+        if self.client(&language).is_none() {
+            return;
+        }
+        let uri = uri::file_uri(&self.root.join(file));
+        let extension = file
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let params = json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": Self::language_id(&language, extension),
+                "version": 1,
+                "text": Self::text(buffer),
+            },
+        });
+        self.notify(&language, "textDocument/didOpen", params);
+        let document = Document {
+            uri,
+            language,
+            version: 1,
+        };
+        self.documents.insert(buffer.id, document);
+    }
+
+    fn close(&mut self, id: usize) {
+        let Some(document) = self.documents.remove(&id) else {
+            return;
+        };
+        self.diagnostics.remove(&document.uri);
+        let params = json!({ "textDocument": { "uri": document.uri } });
+        self.notify(&document.language, "textDocument/didClose", params);
+    }
+
+    /// Returns the document of `buffer` and the client of its server,
+    /// which has to be able to do `capability`.
+    fn server(
+        &mut self,
+        buffer: &BufferView,
+        capability: &str,
+    ) -> io::Result<(&Document, &mut LanguageClient)> {
+        let document = self
+            .documents
+            .get(&buffer.id)
+            .ok_or_else(|| io::Error::other("No language server for this buffer"))?;
+        let client = self
+            .clients
+            .get_mut(&document.language)
+            .ok_or_else(|| io::Error::other("No language server for this buffer"))?;
+        if !client.supports(capability) {
+            return Err(io::Error::other(format!(
+                "The {} language server cannot do that",
+                document.language
+            )));
+        }
+        Ok((document, client))
+    }
+
+    /// Sends `method` about the document of `buffer` to its server, with
+    /// the params `params` returns given the encoding of the server. The
+    /// answer comes later, through `take_answers`.
+    fn request(
+        &mut self,
+        buffer: &BufferView,
+        capability: &str,
+        method: &str,
+        request: Request,
+        params: impl FnOnce(PositionEncoding) -> Value,
+    ) -> io::Result<()> {
+        let (document, client) = self.server(buffer, capability)?;
+        let mut params = params(client.encoding());
+        params["textDocument"] = json!({ "uri": document.uri });
+        let language = document.language.clone();
+        let id = client.request(method, params)?;
+        self.pending.insert((language, id), request);
+        Ok(())
+    }
+
+    /// Returns byte offset `x` of row `y` of `buffer` as a position of the
+    /// protocol.
+    fn position(buffer: &BufferView, encoding: PositionEncoding, (x, y): (usize, usize)) -> Value {
+        let row = match y < buffer.rows.number_of_rows() {
+            true => buffer.rows.get_row(y),
+            false => "",
+        };
+        json!({ "line": y, "character": encoding.character(row, x) })
+    }
+
+    /// Returns byte range `start..end` of `buffer`, where positions are
+    /// byte offsets and rows, as a range of the protocol.
+    fn range(
+        buffer: &BufferView,
+        encoding: PositionEncoding,
+        (start, end): ((usize, usize), (usize, usize)),
+    ) -> Value {
+        json!({
+            "start": Self::position(buffer, encoding, start),
+            "end": Self::position(buffer, encoding, end),
+        })
+    }
+
+    /// Asks the server of `buffer` about the symbol under the cursor.
+    pub fn query(&mut self, buffer: &BufferView, query: Query) -> io::Result<()> {
+        let (capability, method) = (query.capability(), query.method());
+        self.request(buffer, capability, method, Request::Query(query), |encoding| {
+            let mut params = json!({ "position": Self::position(buffer, encoding, buffer.cursor) });
+            if query == Query::References {
+                params["context"] = json!({ "includeDeclaration": true });
+            }
+            params
+        })
+    }
+
+    /// Asks the server of `buffer` for the edits renaming the symbol under
+    /// the cursor to `name` wherever it is used.
+    pub fn rename(&mut self, buffer: &BufferView, name: &str) -> io::Result<()> {
+        self.request(
+            buffer,
+            "renameProvider",
+            "textDocument/rename",
+            Request::Rename,
+            |encoding| {
+                json!({
+                    "position": Self::position(buffer, encoding, buffer.cursor),
+                    "newName": name,
+                })
+            },
+        )
+    }
+
+    /// Asks the server of `buffer` for the code actions, such as fixes of
+    /// the problems it found, of byte range `range`.
+    pub fn code_actions(
+        &mut self,
+        buffer: &BufferView,
+        range: ((usize, usize), (usize, usize)),
+    ) -> io::Result<()> {
+        let ((_, first), (_, last)) = range;
+        let diagnostics: Vec<Value> = self
+            .documents
+            .get(&buffer.id)
+            .and_then(|document| self.diagnostics.get(&document.uri))
+            .into_iter()
+            .flatten()
+            .filter(|diagnostic| diagnostic.start.0 <= last && diagnostic.end.0 >= first)
+            .map(|diagnostic| diagnostic.sent.clone())
+            .collect();
+        self.request(
+            buffer,
+            "codeActionProvider",
+            "textDocument/codeAction",
+            Request::CodeActions,
+            |encoding| {
+                json!({
+                    "range": Self::range(buffer, encoding, range),
+                    "context": { "diagnostics": diagnostics, "triggerKind": 1 },
+                })
+            },
+        )
+    }
+
+    /// Runs code action `index` of those last offered. Returns the edit it
+    /// makes, if any; what its command does comes later, if anything.
+    pub fn run_code_action(&mut self, index: usize) -> io::Result<Option<WorkspaceEdit>> {
+        let (language, actions) = self
+            .code_actions
+            .take()
+            .ok_or_else(|| io::Error::other("No code actions"))?;
+        let action = actions
+            .get(index)
+            .ok_or_else(|| io::Error::other("No such code action"))?;
+        let client = self
+            .clients
+            .get_mut(&language)
+            .ok_or_else(|| io::Error::other(format!("The {} language server stopped", language)))?;
+        let edit = match action.get("edit") {
+            Some(edit) => Some(WorkspaceEdit::parse(edit, client.encoding())?),
+            None => None,
+        };
+
+        // Either a bare command or an action with a command to run after
+        // its edit
+        let command = match &action["command"] {
+            Value::String(_) => Some(action),
+            command @ Value::Object(_) => Some(command),
+            _ => None,
+        };
+        if let Some(command) = command {
+            let mut params = json!({ "command": command["command"] });
+            if let Some(arguments) = command.get("arguments") {
+                params["arguments"] = arguments.clone();
+            }
+            let id = client.request("workspace/executeCommand", params)?;
+            self.pending.insert((language, id), Request::Command);
+        }
+        Ok(edit)
+    }
+
+    /// Asks the server of `buffer` for completions at the cursor, as typing
+    /// `trigger` set it off if given.
+    pub fn complete(&mut self, buffer: &BufferView, trigger: Option<char>) -> io::Result<()> {
+        let (x, y) = buffer.cursor;
+        let line = match y < buffer.rows.number_of_rows() {
+            true => buffer.rows.get_row(y),
+            false => "",
+        };
+        let line = line.get(..x).unwrap_or(line).to_string();
+        let request = Request::Completion {
+            word_start: word_start(&line),
+            line,
+        };
+        let context = match trigger {
+            Some(trigger) => json!({ "triggerKind": 2, "triggerCharacter": trigger.to_string() }),
+            None => json!({ "triggerKind": 1 }),
+        };
+        self.request(
+            buffer,
+            "completionProvider",
+            "textDocument/completion",
+            request,
+            |encoding| {
+                json!({
+                    "position": Self::position(buffer, encoding, buffer.cursor),
+                    "context": context,
+                })
+            },
+        )
+    }
+
+    /// Returns whether typing `ch` in `buffer` has its server complete
+    /// what comes next, as `.` does in many languages.
+    pub fn triggers_completion(&self, buffer: &BufferView, ch: char) -> bool {
+        self.documents
+            .get(&buffer.id)
+            .and_then(|document| self.clients.get(&document.language))
+            .and_then(|client| {
+                client.capability("completionProvider")["triggerCharacters"].as_array()
+            })
+            .is_some_and(|triggers| triggers.iter().any(|trigger| *trigger == ch.to_string()))
+    }
+
+    /// Returns the text a snippet inserts, keeping the placeholders of its
+    /// tab stops and the first of their choices.
+    fn snippet_text(snippet: &str) -> String {
+        let mut text = String::new();
+        let mut chars = snippet.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => text.extend(chars.next()),
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    let mut inner = String::new();
+                    let mut depth = 1;
+                    for ch in chars.by_ref() {
+                        match ch {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                        inner.push(ch);
+                    }
+                    // `${1:placeholder}` or `${1|first,second|}`
+                    let inner = inner.trim_start_matches(|ch: char| ch.is_ascii_digit());
+                    let placeholder = match inner.strip_prefix(':') {
+                        Some(placeholder) => placeholder,
+                        None => inner
+                            .strip_prefix('|')
+                            .and_then(|choices| choices.split([',', '|']).next())
+                            .unwrap_or_default(),
+                    };
+                    text.push_str(&Self::snippet_text(placeholder));
+                }
+                '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                    while chars.peek().is_some_and(char::is_ascii_digit) {
+                        chars.next();
+                    }
+                }
+                ch => text.push(ch),
+            }
+        }
+        text
+    }
+
+    fn completion_items(
+        result: &Value,
+        line: &str,
+        word_start: usize,
+        encoding: PositionEncoding,
+    ) -> Vec<CompletionItem> {
+        // Either the items or a list holding them
+        let items = match result {
+            Value::Array(items) => Some(items),
+            result => result["items"].as_array(),
+        };
+        items
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                let label = item["label"].as_str()?.to_string();
+                // Either a `TextEdit` or an `InsertReplaceEdit`
+                let edit = &item["textEdit"];
+                let start = edit
+                    .get("range")
+                    .or(edit.get("insert"))
+                    .and_then(|range| range["start"]["character"].as_u64())
+                    .map_or(word_start, |character| {
+                        encoding.byte_offset(line, character as usize)
+                    });
+                let text = edit["newText"]
+                    .as_str()
+                    .or(item["insertText"].as_str())
+                    .unwrap_or(&label);
+                let insert = match item["insertTextFormat"].as_u64() {
+                    Some(2) => Self::snippet_text(text),
+                    _ => text.to_string(),
+                };
+                let documentation = Self::hover_text(&item["documentation"]);
+                Some(CompletionItem {
+                    insert,
+                    start,
+                    detail: item["detail"].as_str().map(String::from),
+                    documentation: Some(documentation).filter(|text| !text.is_empty()),
+                    source: "lsp".into(),
+                    label,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns whether the server of `buffer` formats documents.
+    pub fn formats(&self, buffer: &BufferView) -> bool {
+        self.documents
+            .get(&buffer.id)
+            .and_then(|document| self.clients.get(&document.language))
+            .is_some_and(|client| client.supports("documentFormattingProvider"))
+    }
+
+    /// Asks the server of `buffer` for the edits formatting it, or only
+    /// byte range `range` of it.
+    pub fn format(
+        &mut self,
+        buffer: &BufferView,
+        range: Option<((usize, usize), (usize, usize))>,
+    ) -> io::Result<()> {
+        let version = self
+            .documents
+            .get(&buffer.id)
+            .map_or(0, |document| document.version);
+        let request = Request::Format {
+            buffer: buffer.id,
+            version,
+        };
+        // Required, though servers mostly follow the settings of the project
+        let options = json!({ "tabSize": 4, "insertSpaces": true });
+        match range {
+            None => self.request(
+                buffer,
+                "documentFormattingProvider",
+                "textDocument/formatting",
+                request,
+                |_| json!({ "options": options }),
+            ),
+            Some(range) => self.request(
+                buffer,
+                "documentRangeFormattingProvider",
+                "textDocument/rangeFormatting",
+                request,
+                |encoding| {
+                    json!({
+                        "range": Self::range(buffer, encoding, range),
+                        "options": options,
+                    })
+                },
+            ),
+        }
+    }
+
+    /// Returns the answers to requests received since the last call.
+    pub fn take_answers(&mut self) -> Vec<io::Result<Answer>> {
+        std::mem::take(&mut self.answers)
+    }
+
+    /// Tells the server that asked for an edit whether it was made.
+    pub fn answer_edit(&mut self, request: EditRequest, made: &io::Result<()>) -> io::Result<()> {
+        match self.clients.get_mut(&request.language) {
+            Some(client) => client.respond(request.id, Ok(Self::applied(made))),
+            // A server that stopped is not waiting any more
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the result of `workspace/applyEdit` for an edit `made` or not.
+    fn applied(made: &io::Result<()>) -> Value {
+        match made {
+            Ok(()) => json!({ "applied": true }),
+            Err(e) => json!({ "applied": false, "failureReason": e.to_string() }),
+        }
+    }
+
+    fn parse_locations(result: &Value, encoding: PositionEncoding) -> Vec<Location> {
+        let items: Vec<&Value> = match result {
+            Value::Array(items) => items.iter().collect(),
+            Value::Null => Vec::new(),
+            location => vec![location],
+        };
+        items
+            .into_iter()
+            .filter_map(|item| {
+                // Either a `Location` or a `LocationLink`
+                let uri = item.get("targetUri").or(item.get("uri"))?.as_str()?;
+                let start = &item
+                    .get("targetSelectionRange")
+                    .or(item.get("range"))?["start"];
+                Some(Location {
+                    file: uri::uri_path(uri)?,
+                    row: start["line"].as_u64()? as usize,
+                    character: start["character"].as_u64()? as usize,
+                    encoding,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the contents of a hover as Markdown.
+    fn hover_text(contents: &Value) -> String {
+        match contents {
+            Value::String(text) => text.clone(),
+            Value::Array(items) => items
+                .iter()
+                .map(Self::hover_text)
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            // Either code in a language or `MarkupContent`
+            Value::Object(_) => match (contents["language"].as_str(), contents["value"].as_str()) {
+                (Some(language), Some(code)) => format!("```{}\n{}\n```", language, code),
+                (None, Some(text)) => text.to_string(),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    /// Makes sense of the answer to `request`, if there is anything to
+    /// show of it.
+    fn answer(
+        request: Request,
+        result: Value,
+        encoding: PositionEncoding,
+        documents: &HashMap<usize, Document>,
+    ) -> io::Result<Option<Answer>> {
+        let answer = match request {
+            Request::Query(Query::Hover) => Answer::Hover(Self::hover_text(&result["contents"])),
+            Request::Query(query) => {
+                Answer::Locations(query, Self::parse_locations(&result, encoding))
+            }
+            Request::Rename if result.is_null() => {
+                return Err(io::Error::other("Nothing to rename here"))
+            }
+            Request::Rename => Answer::Edit(WorkspaceEdit::parse(&result, encoding)?, None),
+            Request::Format { buffer, version } => {
+                let document = documents
+                    .get(&buffer)
+                    .ok_or_else(|| io::Error::other("The formatted buffer was closed"))?;
+                if document.version != version {
+                    return Err(io::Error::other(
+                        "The buffer changed while it was being formatted",
+                    ));
+                }
+                let file = uri::uri_path(&document.uri)
+                    .ok_or_else(|| io::Error::other("The formatted buffer has no file"))?;
+                let edits = result
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(TextEdit::parse)
+                    .collect();
+                let edit = WorkspaceEdit {
+                    files: vec![FileEdit { file, edits }],
+                    encoding,
+                };
+                Answer::Edit(edit, None)
+            }
+            Request::Completion { line, word_start } => {
+                Answer::Completions(Self::completion_items(&result, &line, word_start, encoding))
+            }
+            Request::CodeActions | Request::Command => return Ok(None),
+        };
+        Ok(Some(answer))
+    }
+
+    /// Returns the diagnostics of the document of buffer `id`, least
+    /// serious first.
+    fn buffer_diagnostics(&self, id: usize) -> Vec<&Diagnostic> {
+        let Some(document) = self.documents.get(&id) else {
+            return Vec::new();
+        };
+        let mut diagnostics: Vec<&Diagnostic> = self
+            .diagnostics
+            .get(&document.uri)
+            .into_iter()
+            .flatten()
+            .collect();
+        diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity));
+        diagnostics
+    }
+}
+
+impl Extension for LanguageServers {
+    fn new() -> Self {
+        Self {
+            servers: HashMap::new(),
+            root: path::PathBuf::new(),
+            clients: HashMap::new(),
+            failed: HashSet::new(),
+            documents: HashMap::new(),
+            diagnostics: HashMap::new(),
+            errors: Vec::new(),
+            pending: HashMap::new(),
+            answers: Vec::new(),
+            code_actions: None,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "language_servers"
+    }
+
+    fn description(&self) -> &str {
+        "Starts the language server of each language opened, for diagnostics, completion and navigation."
+    }
+
+    fn options(&self) -> Option<ExtensionOptions> {
+        Some(super::options_of::<LanguageServersConfig>())
+    }
+
+    fn init(&mut self, options: &toml::Table) -> io::Result<()> {
+        let config: LanguageServersConfig = super::parse_options(options)?;
+        for (language, extensions, command) in SERVERS {
+            let mut words = command.split_whitespace().map(String::from);
+            let server = LanguageServerConfig {
+                command: words.next().unwrap_or_default(),
+                args: words.collect(),
+                file_extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            };
+            self.servers.insert(language.to_string(), server);
+        }
+        self.servers.extend(config.servers);
+        self.root = env::current_dir()?;
+        Ok(())
+    }
+
+    fn on_open(&mut self, buffer: &BufferView) {
+        self.open(buffer);
+    }
+
+    fn on_change(&mut self, buffer: &BufferView) {
+        let text = Self::text(buffer);
+        let Some(document) = self.documents.get_mut(&buffer.id) else {
+            return;
+        };
+        document.version += 1;
+        let params = json!({
+            "textDocument": { "uri": document.uri, "version": document.version },
+            "contentChanges": [{ "text": text }],
+        });
+        let language = document.language.clone();
+        self.notify(&language, "textDocument/didChange", params);
+    }
+
+    fn after_save(&mut self, buffer: &BufferView) -> io::Result<Option<String>> {
+        // A buffer saved under a new name is a new document
+        let uri = buffer
+            .rows
+            .get_file_name()
+            .map(|file| uri::file_uri(&self.root.join(file)));
+        match self.documents.get(&buffer.id) {
+            Some(document) if Some(&document.uri) == uri.as_ref() => {
+                let params = json!({
+                    "textDocument": { "uri": document.uri },
+                    "text": Self::text(buffer),
+                });
+                let language = document.language.clone();
+                self.notify(&language, "textDocument/didSave", params);
+            }
+            _ => {
+                self.close(buffer.id);
+                self.open(buffer);
+            }
+        }
+        Ok(None)
+    }
+
+    fn on_close(&mut self, buffer: &BufferView) {
+        self.close(buffer.id);
+    }
+
+    fn poll(&mut self) -> io::Result<bool> {
+        let mut errors = std::mem::take(&mut self.errors);
+        let mut received = false;
+        let mut published = Vec::new();
+        let mut stopped = Vec::new();
+        for (language, client) in self.clients.iter_mut() {
+            match client.receive() {
+                Ok(messages) => {
+                    received |= !messages.is_empty();
+                    for message in messages {
+                        match message {
+                            RpcMessage::Notification { method, params }
+                                if method == "textDocument/publishDiagnostics" =>
+                            {
+                                published.push(params)
+                            }
+                            RpcMessage::Response { id, result } => {
+                                let Some(request) = self.pending.remove(&(language.clone(), id))
+                                else {
+                                    continue;
+                                };
+                                let answer = match (request, result) {
+                                    (_, Err(e)) => Err(io::Error::other(e.message)),
+                                    (Request::CodeActions, Ok(result)) => {
+                                        // Disabled actions are left out
+                                        let actions: Vec<Value> = result
+                                            .as_array()
+                                            .into_iter()
+                                            .flatten()
+                                            .filter(|action| action.get("disabled").is_none())
+                                            .cloned()
+                                            .collect();
+                                        let titles = actions
+                                            .iter()
+                                            .map(|action| {
+                                                action["title"].as_str().unwrap_or("?").to_string()
+                                            })
+                                            .collect();
+                                        self.code_actions = Some((language.clone(), actions));
+                                        Ok(Some(Answer::CodeActions(titles)))
+                                    }
+                                    (request, Ok(result)) => Self::answer(
+                                        request,
+                                        result,
+                                        client.encoding(),
+                                        &self.documents,
+                                    ),
+                                };
+                                if let Some(answer) = answer.transpose() {
+                                    self.answers.push(answer);
+                                }
+                            }
+                            // The only request passed on, answered once the
+                            // editor has tried making the edit
+                            RpcMessage::Request { id, params, .. } => {
+                                match WorkspaceEdit::parse(&params["edit"], client.encoding()) {
+                                    Ok(edit) => {
+                                        let request = EditRequest {
+                                            language: language.clone(),
+                                            id,
+                                        };
+                                        self.answers.push(Ok(Answer::Edit(edit, Some(request))))
+                                    }
+                                    Err(e) => {
+                                        let applied = Self::applied(&Err(e));
+                                        if let Err(e) = client.respond(id, Ok(applied)) {
+                                            errors.push(format!(
+                                                "The {} language server failed: {}",
+                                                language, e
+                                            ));
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Err(e) => {
+                    errors.push(format!("The {} language server failed: {}", language, e));
+                    stopped.push(language.clone());
+                }
+            }
+            if !client.is_running() {
+                errors.push(format!("The {} language server stopped", language));
+                stopped.push(language.clone());
+            }
+        }
+
+        for params in published {
+            let Some(uri) = params["uri"].as_str() else {
+                continue;
+            };
+            let diagnostics = params["diagnostics"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Diagnostic::parse)
+                .collect();
+            self.diagnostics.insert(uri.to_string(), diagnostics);
+        }
+        for language in stopped {
+            self.clients.remove(&language);
+            self.failed.insert(language.clone());
+            self.pending.retain(|(asked, _), _| *asked != language);
+            let documents = &mut self.documents;
+            let diagnostics = &mut self.diagnostics;
+            documents.retain(|_, document| {
+                let keep = document.language != language;
+                if !keep {
+                    diagnostics.remove(&document.uri);
+                }
+                keep
+            });
+        }
+
+        // Idle servers are left alone until something happens again
+        let waiting = received
+            || !self.pending.is_empty()
+            || self.clients.values().any(LanguageClient::expects_more);
+        match errors.is_empty() {
+            true => Ok(waiting),
+            false => Err(io::Error::other(errors.join("; "))),
+        }
+    }
+
+    fn gutter_width(&self, buffer: &BufferView) -> usize {
+        match self.documents.contains_key(&buffer.id) {
+            true => 2,
+            false => 0,
+        }
+    }
+
+    fn decorations(&self, buffer: &BufferView, rows: Range<usize>) -> Vec<Decoration> {
+        let Some(client) = self
+            .documents
+            .get(&buffer.id)
+            .and_then(|document| self.clients.get(&document.language))
+        else {
+            return Vec::new();
+        };
+        let encoding = client.encoding();
+        let number_of_rows = buffer.rows.number_of_rows();
+        let mut decorations = Vec::new();
+        for diagnostic in self.buffer_diagnostics(buffer.id) {
+            let color = diagnostic.severity.color();
+            if rows.contains(&diagnostic.start.0) {
+                decorations.push(Decoration::Gutter {
+                    row: diagnostic.start.0,
+                    column: 0,
+                    text: diagnostic.severity.name()[..1].to_uppercase(),
+                    color,
+                });
+            }
+            let last = diagnostic
+                .end
+                .0
+                .min(rows.end.min(number_of_rows).saturating_sub(1));
+            for row in diagnostic.start.0.max(rows.start)..=last {
+                let text = buffer.rows.get_row(row);
+                let start = match row == diagnostic.start.0 {
+                    true => encoding.byte_offset(text, diagnostic.start.1),
+                    false => 0,
+                };
+                let mut end = match row == diagnostic.end.0 {
+                    true => encoding.byte_offset(text, diagnostic.end.1),
+                    false => text.len(),
+                };
+                // An empty range marks the char it starts at
+                if end <= start {
+                    end = start + text[start..].chars().next().map_or(0, char::len_utf8);
+                }
+                if start < end {
+                    decorations.push(Decoration::Underline {
+                        row,
+                        range: start..end,
+                        color,
+                    });
+                }
+            }
+        }
+        decorations
+    }
+
+    fn status_text(&self, buffer: &BufferView) -> Option<String> {
+        let row = buffer.cursor.1;
+        let diagnostic = self
+            .buffer_diagnostics(buffer.id)
+            .into_iter()
+            .rev()
+            .find(|diagnostic| diagnostic.start.0 <= row && row <= diagnostic.end.0)?;
+        let message = diagnostic.message.lines().next().unwrap_or_default();
+        Some(match &diagnostic.source {
+            Some(source) => format!("{}: {} ({})", diagnostic.severity.name(), message, source),
+            None => format!("{}: {}", diagnostic.severity.name(), message),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::editor_rows::EditorRows;
+    use crate::ports::editor_rows::EditorRowsPort;
+    use std::time::{Duration, Instant};
+    use std::{fs, process, thread};
+
+    /// A language server answering `initialize`, logging every message it
+    /// gets to the file it is given and publishing a diagnostic on
+    /// `textDocument/didOpen`.
+    const MOCK_SERVER: &str = r#"
+log=$1
+reply() {
+    printf 'Content-Length: %d\r\n\r\n%s' "${#1}" "$1"
+}
+while IFS= read -r header; do
+    case $header in
+    Content-Length:*)
+        length=${header#Content-Length: }
+        length=${length%?}
+        ;;
+    ?)
+        body=$(dd bs=1 count="$length" 2>/dev/null)
+        printf '%s\n' "$body" >>"$log"
+        case $body in
+        *'"method":"initialize"'*)
+            id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            reply '{"jsonrpc":"2.0","id":'"$id"',"result":{"capabilities":{"positionEncoding":"utf-8","textDocumentSync":1}}}'
+            ;;
+        *'"method":"textDocument/didOpen"'*)
+            uri=$(printf '%s' "$body" | sed -n 's/.*"uri":"\([^"]*\)".*/\1/p')
+            reply '{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"'"$uri"'","diagnostics":[{"range":{"start":{"line":0,"character":4},"end":{"line":0,"character":7}},"severity":1,"message":"unknown name\nsecond line","source":"mock"}]}}'
+            ;;
+        esac
+        ;;
+    esac
+done
+"#;
+
+    /// Polls `servers` until `done` holds, failing after a few seconds.
+    fn poll_until(servers: &mut LanguageServers, mut done: impl FnMut(&LanguageServers) -> bool) {
+        let start = Instant::now();
+        while !done(servers) {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "The server did not answer"
+            );
+            servers.poll().expect("The server failed");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Returns the methods of the messages the mock server got, in order.
+    fn methods(log: &path::Path) -> Vec<String> {
+        fs::read_to_string(log)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter_map(|message| message["method"].as_str().map(String::from))
+            .collect()
+    }
+
+    #[test]
+    fn syncs_documents_and_shows_diagnostics() {
+        let root = env::temp_dir().join(format!("barn-language-servers-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let script = root.join("server.sh");
+        let log = root.join("messages.log");
+        let file = root.join("main.mock");
+        fs::write(&script, MOCK_SERVER).unwrap();
+        fs::write(&file, "let name = 1\n").unwrap();
+        let _ = fs::remove_file(&log);
+
+        let mut servers = LanguageServers::new();
+        let server = LanguageServerConfig {
+            command: "sh".into(),
+            args: vec![script.display().to_string(), log.display().to_string()],
+            file_extensions: vec!["mock".into()],
+        };
+        servers.servers.insert("mock".into(), server);
+        servers.root = root.clone();
+
+        let mut rows = EditorRows::from_file(file.clone()).unwrap();
+        let view = BufferView {
+            id: 1,
+            rows: &rows,
+            cursor: (0, 0),
+        };
+        servers.on_open(&view);
+        poll_until(&mut servers, |servers| {
+            !servers.buffer_diagnostics(1).is_empty()
+        });
+
+        let decorations = servers.decorations(&view, 0..1);
+        assert!(decorations.iter().any(|decoration| matches!(
+            decoration,
+            Decoration::Gutter { row: 0, column: 0, text, .. } if text == "E"
+        )));
+        assert!(decorations.iter().any(|decoration| matches!(
+            decoration,
+            Decoration::Underline { row: 0, range, .. } if *range == (4..7)
+        )));
+        assert_eq!(
+            servers.status_text(&view).as_deref(),
+            Some("error: unknown name (mock)")
+        );
+
+        rows.set_rows(vec!["let other = 1".into()]);
+        let view = BufferView {
+            id: 1,
+            rows: &rows,
+            cursor: (0, 0),
+        };
+        servers.on_change(&view);
+        servers.after_save(&view).unwrap();
+        poll_until(&mut servers, |_| methods(&log).len() >= 5);
+
+        assert_eq!(
+            methods(&log),
+            [
+                "initialize",
+                "initialized",
+                "textDocument/didOpen",
+                "textDocument/didChange",
+                "textDocument/didSave",
+            ]
+        );
+        let messages: Vec<Value> = fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let uri = uri::file_uri(&file);
+        assert_eq!(messages[2]["params"]["textDocument"]["uri"], uri.as_str());
+        assert_eq!(messages[2]["params"]["textDocument"]["languageId"], "mock");
+        assert_eq!(
+            messages[2]["params"]["textDocument"]["text"],
+            "let name = 1\n"
+        );
+        assert_eq!(messages[3]["params"]["textDocument"]["version"], 2);
+        assert_eq!(
+            messages[3]["params"]["contentChanges"][0]["text"],
+            "let other = 1\n"
+        );
+        assert_eq!(messages[4]["params"]["text"], "let other = 1\n");
+
+        // Once nothing more is expected, the editor stops polling for it
+        let start = Instant::now();
+        while servers.poll().unwrap() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "The servers stayed busy"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        drop(servers);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,128 @@
+//! `extensions`
+//!
+//! The features barn sets up from the `[extensions]` tables of the config,
+//! and the registry holding the enabled ones.
+
+pub mod code_formatter;
+pub mod git;
+pub mod language_servers;
+pub mod plugin;
+pub mod scripting;
+pub mod spell_check;
+
+use crate::log_info;
+use crate::ports::extension::{Extension, ExtensionOptions};
+use crate::toolshed::schema;
+use code_formatter::CodeFormatter;
+use git::GitIntegration;
+use language_servers::LanguageServers;
+use plugin::Plugin;
+use scripting::Scripting;
+use spell_check::SpellCheck;
+
+/// The extensions that come with barn.
+pub fn built_in() -> Vec<Box<dyn Extension>> {
+    vec![
+        Box::new(GitIntegration::new()),
+        Box::new(SpellCheck::new()),
+        Box::new(CodeFormatter::new()),
+        Box::new(Scripting::new()),
+        Box::new(LanguageServers::new()),
+    ]
+}
+
+/// Holds the enabled extensions, in the order their hooks run.
+#[derive(Default)]
+pub struct ExtensionRegistry {
+    extensions: Vec<Box<dyn Extension>>,
+}
+
+impl ExtensionRegistry {
+    /// Sets up the extensions of `available` enabled by their table in
+    /// `tables`, the `[extensions]` tables by name. Any other table with a
+    /// `command` sets up a plugin running that command; the rest are not the
+    /// registry's and are left alone.
+    pub fn new(mut available: Vec<Box<dyn Extension>>, tables: &toml::Table) -> Self {
+        let mut registry = Self::default();
+        let names: Vec<String> = available
+            .iter()
+            .map(|extension| extension.name().to_string())
+            .collect();
+        for (name, table) in tables.iter().filter(|(name, _)| !names.contains(name)) {
+            if table.get("command").is_some() {
+                available.push(Box::new(Plugin::named(name)));
+            }
+        }
+        for extension in available {
+            registry.register(extension, tables);
+        }
+        registry
+    }
+
+    /// Adds `extension` if its table in `tables` enables it, passing it the
+    /// options of the table.
+    pub fn register(&mut self, mut extension: Box<dyn Extension>, tables: &toml::Table) {
+        let options = tables
+            .get(extension.name())
+            .and_then(toml::Value::as_table)
+            .cloned()
+            .unwrap_or_default();
+        let enabled = options
+            .get("enabled")
+            .and_then(toml::Value::as_bool)
+            .unwrap_or(extension.enabled_by_default());
+        if !enabled {
+            return;
+        }
+        match extension.init(&options) {
+            Ok(()) => self.extensions.push(extension),
+            Err(e) => {
+                log_info!("The {} extension is off: {}", extension.name(), e);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Extension> {
+        self.extensions.iter().map(|extension| &**extension)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Extension>> {
+        self.extensions.iter_mut()
+    }
+
+    /// Returns every enabled extension of type `T`, such as the plugins.
+    pub fn all_mut<T: 'static>(&mut self) -> impl Iterator<Item = &mut T> {
+        self.extensions
+            .iter_mut()
+            .filter_map(|extension| extension.as_any_mut().downcast_mut())
+    }
+
+    /// Returns the extension of type `T`, if it is enabled.
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.extensions
+            .iter()
+            .find_map(|extension| extension.as_any().downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.extensions
+            .iter_mut()
+            .find_map(|extension| extension.as_any_mut().downcast_mut())
+    }
+}
+
+/// Describes the options an extension reads into `T` from its table.
+fn options_of<T: serde::de::DeserializeOwned + std::fmt::Debug + 'static>() -> ExtensionOptions {
+    ExtensionOptions {
+        fields: schema::fields::<T>(),
+        defaults: schema::defaults::<T>()
+            .map(|defaults| Box::new(defaults) as Box<dyn std::fmt::Debug>),
+    }
+}
+
+/// Reads the options of an extension's table into its config.
+fn parse_options<T: serde::de::DeserializeOwned>(options: &toml::Table) -> std::io::Result<T> {
+    toml::Value::Table(options.clone())
+        .try_into()
+        .map_err(std::io::Error::other)
+}
@@ -0,0 +1,233 @@
+use crate::adapters::rpc::RpcProcess;
+use crate::log_info;
+use crate::ports::extension::{BufferView, Extension};
+use crate::ports::rpc::{RpcError, RpcMessage, RpcPort, INVALID_PARAMS};
+use serde_json::{json, Value};
+use std::any::Any;
+use std::{env, io, time};
+
+/// How long after telling a plugin of something the calls it makes in
+/// answer are watched for.
+const ANSWER_WAIT: time::Duration = time::Duration::from_secs(1);
+
+/// A program extending the editor from its own process, talking JSON-RPC
+/// over its standard input and output. The editor tells it about buffers
+/// being opened, changed and saved, and answers its calls to read and edit
+/// buffers, move the cursor, show messages and add commands. Calls about a
+/// buffer take the id the notifications gave as `buffer`, or act on the
+/// focused buffer without it.
+pub struct Plugin {
+    /// The name of the plugin's table.
+    name: String,
+    connection: Option<Box<dyn RpcPort>>,
+    /// The commands the plugin registered, run by the plugin itself.
+    commands: Vec<String>,
+    /// The id of the `initialize` request until it is answered. The answer
+    /// may list commands.
+    initialize: Option<u64>,
+    /// Until when calls made in answer to the last notification are expected.
+    expected_until: Option<time::Instant>,
+}
+
+impl Plugin {
+    /// Returns the plugin configured by table `name`, to be started by
+    /// `init`.
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            connection: None,
+            commands: Vec::new(),
+            initialize: None,
+            expected_until: None,
+        }
+    }
+
+    /// Sends a notification, dropping the connection if the plugin is
+    /// gone.
+    fn notify(&mut self, method: &str, params: Value) {
+        let Some(connection) = self.connection.as_mut() else {
+            return;
+        };
+        match connection.notify(method, params) {
+            Ok(()) => self.expected_until = Some(time::Instant::now() + ANSWER_WAIT),
+            Err(_) => self.connection = None,
+        }
+    }
+
+    fn notify_buffer(&mut self, method: &str, buffer: &BufferView) {
+        let file = buffer
+            .rows
+            .get_file_name()
+            .map(|file| file.to_string_lossy().into_owned());
+        self.notify(method, json!({ "buffer": buffer.id, "file": file }));
+    }
+
+    fn register_command(&mut self, name: &str) {
+        if !self.commands.iter().any(|command| command == name) {
+            self.commands.push(name.to_string());
+        }
+    }
+
+    /// Returns the calls the plugin made since the last time that the
+    /// editor has to answer. Command registrations are taken care of here.
+    pub fn take_calls(&mut self) -> Vec<RpcMessage> {
+        let Some(messages) = self
+            .connection
+            .as_mut()
+            .map(|connection| connection.receive())
+        else {
+            return Vec::new();
+        };
+        let mut calls = Vec::new();
+        for message in messages {
+            match message {
+                RpcMessage::Request { id, method, params } if method == "commands/register" => {
+                    let result = match params.get("name").and_then(Value::as_str) {
+                        Some(name) => {
+                            self.register_command(name);
+                            Ok(Value::Null)
+                        }
+                        None => Err(RpcError::new(INVALID_PARAMS, "Expected `name`")),
+                    };
+                    self.respond(id, result);
+                }
+                RpcMessage::Response { id, result } if Some(id) == self.initialize => {
+                    self.initialize = None;
+                    match result {
+                        Ok(result) => {
+                            let commands = result.get("commands").and_then(Value::as_array);
+                            for name in commands.into_iter().flatten().filter_map(Value::as_str) {
+                                self.register_command(name);
+                            }
+                        }
+                        Err(e) => {
+                            log_info!("The {} plugin failed to start: {}", self.name, e.message);
+                        }
+                    }
+                }
+                RpcMessage::Response { .. } => {}
+                call => calls.push(call),
+            }
+        }
+        calls
+    }
+
+    /// Answers request `id` of the plugin.
+    pub fn respond(&mut self, id: Value, result: Result<Value, RpcError>) {
+        if let Some(connection) = self.connection.as_mut() {
+            let _ = connection.respond(id, result);
+        }
+    }
+
+    /// Lets go of the plugin once it stopped. `Err` says it just stopped.
+    pub fn check_running(&mut self) -> Result<(), String> {
+        match self
+            .connection
+            .as_mut()
+            .map(|connection| connection.is_running())
+        {
+            Some(false) => {
+                self.connection = None;
+                self.commands.clear();
+                Err(format!("The {} plugin stopped", self.name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns whether the running plugin may soon make calls: it is still
+    /// starting, or it was told of something moments ago.
+    pub fn expects_calls(&self) -> bool {
+        self.connection.is_some()
+            && (self.initialize.is_some()
+                || self
+                    .expected_until
+                    .is_some_and(|until| time::Instant::now() < until))
+    }
+}
+
+impl Extension for Plugin {
+    fn new() -> Self {
+        Self::named("plugin")
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "Runs a program talking JSON-RPC over its standard input and output."
+    }
+
+    fn init(&mut self, options: &toml::Table) -> io::Result<()> {
+        let command = options
+            .get("command")
+            .and_then(toml::Value::as_str)
+            .ok_or_else(|| io::Error::other("No command to run"))?;
+        let args: Vec<String> = options
+            .get("args")
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|arg| arg.as_str().map(String::from))
+            .collect();
+        let mut connection = RpcProcess::spawn(command, &args, &env::current_dir()?)?;
+        let options = serde_json::to_value(options).map_err(io::Error::other)?;
+        let initialize = connection.request(
+            "initialize",
+            json!({
+                "name": self.name,
+                "editor": { "name": "barn", "version": env!("CARGO_PKG_VERSION") },
+                "options": options,
+            }),
+        )?;
+        self.initialize = Some(initialize);
+        self.connection = Some(Box::new(connection));
+        Ok(())
+    }
+
+    fn on_open(&mut self, buffer: &BufferView) {
+        self.notify_buffer("buffer/opened", buffer);
+    }
+
+    fn on_change(&mut self, buffer: &BufferView) {
+        self.notify_buffer("buffer/changed", buffer);
+    }
+
+    fn after_save(&mut self, buffer: &BufferView) -> io::Result<Option<String>> {
+        self.notify_buffer("buffer/saved", buffer);
+        Ok(None)
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.commands.clone()
+    }
+
+    fn run_command(
+        &mut self,
+        _buffer: &BufferView,
+        name: &str,
+        argument: Option<&str>,
+    ) -> Option<io::Result<()>> {
+        if !self.commands.iter().any(|command| command == name) {
+            return None;
+        }
+        let connection = self.connection.as_mut()?;
+        let sent = connection.notify(
+            "commands/run",
+            json!({ "name": name, "argument": argument }),
+        );
+        if sent.is_ok() {
+            self.expected_until = Some(time::Instant::now() + ANSWER_WAIT);
+        }
+        Some(sent)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
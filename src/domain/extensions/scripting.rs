@@ -0,0 +1,162 @@
+use crate::adapters::script_engine::RhaiEngine;
+use crate::ports::config::ScriptingConfig;
+use crate::ports::extension::{BufferView, Extension, ExtensionOptions};
+use crate::ports::script_engine::{ScriptContext, ScriptEnginePort};
+use crate::toolshed::keys;
+use crossterm::event;
+use std::any::Any;
+use std::{io, path};
+
+/// Runs the init script next to the config, and the commands, key bindings
+/// and event handlers it defines.
+pub struct Scripting {
+    engine: Option<Box<dyn ScriptEnginePort>>,
+    /// The calls that changed something, for the editor to apply.
+    results: Vec<ScriptContext>,
+}
+
+impl Scripting {
+    fn context(buffer: &BufferView) -> ScriptContext {
+        ScriptContext {
+            buffer_id: buffer.id,
+            file_name: buffer
+                .rows
+                .get_file_name()
+                .map(|file| file.to_string_lossy().into_owned()),
+            rows: buffer.rows.get_rows(),
+            cursor: buffer.cursor,
+            ..ScriptContext::default()
+        }
+    }
+
+    /// Keeps what a call changed, if anything.
+    fn keep(&mut self, context: ScriptContext) {
+        if context.edited
+            || context.moved
+            || !context.messages.is_empty()
+            || !context.commands.is_empty()
+        {
+            self.results.push(context);
+        }
+    }
+
+    fn emit(&mut self, buffer: &BufferView, event: &str) {
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
+        let mut context = Self::context(buffer);
+        if let Err(e) = engine.emit(&mut context, event) {
+            context
+                .messages
+                .push(format!("The {} handler failed: {}", event, e));
+        }
+        self.keep(context);
+    }
+
+    /// Returns what the scripts changed since the last call, call by call.
+    pub fn take_results(&mut self) -> Vec<ScriptContext> {
+        std::mem::take(&mut self.results)
+    }
+}
+
+impl Extension for Scripting {
+    fn new() -> Self {
+        Self {
+            engine: None,
+            results: Vec::new(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "scripting"
+    }
+
+    fn description(&self) -> &str {
+        "Runs the Rhai init script, which defines commands, key bindings and event handlers."
+    }
+
+    fn options(&self) -> Option<ExtensionOptions> {
+        Some(super::options_of::<ScriptingConfig>())
+    }
+
+    fn init(&mut self, options: &toml::Table) -> io::Result<()> {
+        let config: ScriptingConfig = super::parse_options(options)?;
+        let file = path::Path::new(&config.init_script);
+        if !file.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No init script {}", file.display()),
+            ));
+        }
+        let mut engine = RhaiEngine::new(&config);
+        engine.load(file)?;
+        self.engine = Some(Box::new(engine));
+        Ok(())
+    }
+
+    fn on_open(&mut self, buffer: &BufferView) {
+        self.emit(buffer, "open");
+    }
+
+    fn on_change(&mut self, buffer: &BufferView) {
+        self.emit(buffer, "change");
+    }
+
+    fn after_save(&mut self, buffer: &BufferView) -> io::Result<Option<String>> {
+        self.emit(buffer, "save");
+        Ok(None)
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.engine
+            .as_ref()
+            .map(|engine| engine.commands())
+            .unwrap_or_default()
+    }
+
+    fn run_command(
+        &mut self,
+        buffer: &BufferView,
+        name: &str,
+        argument: Option<&str>,
+    ) -> Option<io::Result<()>> {
+        let mut context = Self::context(buffer);
+        let result = self
+            .engine
+            .as_mut()?
+            .run_command(&mut context, name, argument)?;
+        self.keep(context);
+        Some(result)
+    }
+
+    fn key_bindings(&self, mode: &str) -> Vec<String> {
+        self.engine
+            .as_ref()
+            .map(|engine| engine.key_bindings(mode))
+            .unwrap_or_default()
+    }
+
+    fn run_key_binding(
+        &mut self,
+        buffer: &BufferView,
+        mode: &str,
+        key: &event::KeyEvent,
+    ) -> Option<io::Result<()>> {
+        let key = keys::key_name(key)?;
+        let mut context = Self::context(buffer);
+        let result = self
+            .engine
+            .as_mut()?
+            .run_key_binding(&mut context, mode, &key)?;
+        self.keep(context);
+        Some(result)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
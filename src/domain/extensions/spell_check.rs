@@ -0,0 +1,195 @@
+use crate::adapters::spell_checker::SpellChecker;
+use crate::ports::config::SpellCheckerConfig;
+use crate::ports::extension::{BufferEdit, BufferView, Decoration, Extension, ExtensionOptions};
+use crate::ports::spell_checker::SpellCheckerPort;
+use crate::toolshed::edit_distance::edit_distance;
+use crate::toolshed::prose::{self, ProseScanner};
+use crossterm::{event, style};
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::{io, path};
+
+/// The misspelled words of a row, as byte ranges and the words there.
+type Misspelled = Vec<(Range<usize>, String)>;
+
+/// Underlines misspelled words, in all of a text file but only in the
+/// comments and strings of source code.
+pub struct SpellCheck {
+    checker: Option<Box<dyn SpellCheckerPort>>,
+    config: SpellCheckerConfig,
+    /// The misspelled words of each buffer, by buffer id and then by row,
+    /// found again when the buffer changes rather than on each draw.
+    found: HashMap<usize, Vec<Misspelled>>,
+}
+
+impl SpellCheck {
+    /// Returns the misspelled words of buffer `id` in rows `range`, as rows
+    /// and byte ranges.
+    pub fn misspellings(&self, id: usize, range: Range<usize>) -> Vec<(usize, Range<usize>)> {
+        let Some(rows) = self.found.get(&id) else {
+            return Vec::new();
+        };
+        rows.iter()
+            .enumerate()
+            .skip(range.start)
+            .take(range.len())
+            .flat_map(|(at, words)| words.iter().map(move |(word, _)| (at, word.clone())))
+            .collect()
+    }
+
+    /// Finds the misspelled words of `buffer` again.
+    fn check_buffer(&mut self, buffer: &BufferView) {
+        let Some(checker) = &self.checker else {
+            return;
+        };
+        let rows = buffer.rows;
+        let Some(kind) = prose::text_kind(rows.get_file_name().map(path::PathBuf::as_path)) else {
+            self.found.remove(&buffer.id);
+            return;
+        };
+        // Comments can span rows, so each row is scanned after the ones above
+        let mut scanner = ProseScanner::new(kind);
+        let found = (0..rows.number_of_rows())
+            .map(|at| {
+                let row = rows.get_row(at);
+                scanner
+                    .scan_row(row)
+                    .into_iter()
+                    .flat_map(|range| prose::words(row, range))
+                    .map(|word| (word.clone(), row[word].to_string()))
+                    .filter(|(_, word)| !checker.check(word))
+                    .collect()
+            })
+            .collect();
+        self.found.insert(buffer.id, found);
+    }
+
+    /// Returns up to `limit` corrections for `word`, closest first.
+    pub fn suggest(&self, word: &str, limit: usize) -> Vec<String> {
+        self.checker
+            .as_ref()
+            .map(|checker| checker.suggest(word, limit))
+            .unwrap_or_default()
+    }
+
+    /// Accepts `word` from now on, in this session and the next ones.
+    pub fn add_word(&mut self, word: &str) -> io::Result<()> {
+        let Some(checker) = self.checker.as_mut() else {
+            return Err(io::Error::other("Spell checking is off"));
+        };
+        checker.add_word(word)?;
+        for words in self.found.values_mut().flatten() {
+            words.retain(|(_, word)| !checker.check(word));
+        }
+        Ok(())
+    }
+}
+
+impl Extension for SpellCheck {
+    fn new() -> Self {
+        Self {
+            checker: None,
+            config: SpellCheckerConfig::default(),
+            found: HashMap::new(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "spell_checker"
+    }
+
+    fn description(&self) -> &str {
+        "Underlines misspelled words and suggests corrections."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn options(&self) -> Option<ExtensionOptions> {
+        Some(super::options_of::<SpellCheckerConfig>())
+    }
+
+    fn init(&mut self, options: &toml::Table) -> io::Result<()> {
+        self.config = super::parse_options(options)?;
+        self.checker = Some(Box::new(SpellChecker::new(&self.config)?));
+        Ok(())
+    }
+
+    fn on_open(&mut self, buffer: &BufferView) {
+        self.check_buffer(buffer);
+    }
+
+    fn on_change(&mut self, buffer: &BufferView) {
+        self.check_buffer(buffer);
+    }
+
+    /// A buffer saved under a new name may hold another kind of text.
+    fn after_save(&mut self, buffer: &BufferView) -> io::Result<Option<String>> {
+        self.check_buffer(buffer);
+        Ok(None)
+    }
+
+    fn on_close(&mut self, buffer: &BufferView) {
+        self.found.remove(&buffer.id);
+    }
+
+    /// Corrects the misspelled word just typed, when a char ending it is
+    /// typed, if a known word is a single edit away from it.
+    fn on_key(&mut self, buffer: &BufferView, key: &event::KeyEvent) -> Option<BufferEdit> {
+        let event::KeyCode::Char(ch) = key.code else {
+            return None;
+        };
+        if !self.config.auto_correct || ch.is_alphanumeric() || ch == '\'' {
+            return None;
+        }
+        let (x, y) = buffer.cursor;
+        if y >= buffer.rows.number_of_rows() {
+            return None;
+        }
+        let row = buffer.rows.get_row(y);
+        let word = prose::words(row, 0..row.len())
+            .into_iter()
+            .find(|word| word.end == x)?;
+        if !self
+            .misspellings(buffer.id, y..y + 1)
+            .iter()
+            .any(|(_, misspelled)| *misspelled == word)
+        {
+            return None;
+        }
+        let typed = &row[word.clone()];
+        let correction = self.suggest(typed, 1).pop()?;
+        if edit_distance(&typed.to_lowercase(), &correction.to_lowercase()) > 1 {
+            return None;
+        }
+        Some(BufferEdit::Replace {
+            row: y,
+            range: word,
+            text: correction,
+        })
+    }
+
+    fn decorations(&self, buffer: &BufferView, rows: Range<usize>) -> Vec<Decoration> {
+        if !self.config.highlight_mistakes {
+            return Vec::new();
+        }
+        self.misspellings(buffer.id, rows)
+            .into_iter()
+            .map(|(row, range)| Decoration::Underline {
+                row,
+                range,
+                color: style::Color::Red,
+            })
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
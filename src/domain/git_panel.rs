@@ -0,0 +1,182 @@
+//! `git_panel.rs`
+//!
+//! A popup listing the changes in a git repository, staged and unstaged,
+//! from which files and single hunks are staged before committing.
+
+use super::popup;
+use crate::ports::editor_buffer::EditorBufferPort;
+use crate::ports::git::{FileStatus, PatchHunk};
+use crate::ports::window::Rect;
+use crossterm::style;
+use std::collections::HashSet;
+use std::{cmp, io, path};
+
+/// A line of the panel.
+pub enum GitPanelRow {
+    /// The heading of the staged or the unstaged changes.
+    Section { title: String },
+    File {
+        path: path::PathBuf,
+        /// Whether the row is about the staged changes to the file.
+        staged: bool,
+        status: char,
+    },
+    Hunk {
+        path: path::PathBuf,
+        staged: bool,
+        hunk: PatchHunk,
+    },
+}
+
+pub struct GitPanel {
+    /// The root of the repository shown.
+    pub root: path::PathBuf,
+    /// The files whose hunks are listed below them, with whether they are
+    /// the staged ones.
+    pub expanded: HashSet<(path::PathBuf, bool)>,
+    rows: Vec<GitPanelRow>,
+    selected: usize,
+    /// The first row shown, when the rows do not fit the popup.
+    offset: usize,
+}
+
+impl GitPanel {
+    pub fn new(root: path::PathBuf) -> Self {
+        Self {
+            root,
+            expanded: HashSet::new(),
+            rows: Vec::new(),
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    /// Lays out `statuses` in a staged and an unstaged section. `hunks`
+    /// returns the hunks listed under an expanded file. The selection stays
+    /// on the same line where possible.
+    pub fn set_statuses(
+        &mut self,
+        statuses: &[FileStatus],
+        mut hunks: impl FnMut(&path::Path, bool) -> Vec<PatchHunk>,
+    ) {
+        let mut rows = Vec::new();
+        for staged in [true, false] {
+            let files: Vec<(&FileStatus, char)> = statuses
+                .iter()
+                .map(|file| (file, if staged { file.staged } else { file.unstaged }))
+                .filter(|(_, status)| *status != ' ' && (!staged || *status != '?'))
+                .collect();
+            let title = if staged { "Staged" } else { "Unstaged" };
+            rows.push(GitPanelRow::Section {
+                title: format!("{} ({})", title, files.len()),
+            });
+            for (file, status) in files {
+                rows.push(GitPanelRow::File {
+                    path: file.path.clone(),
+                    staged,
+                    status,
+                });
+                if self.expanded.contains(&(file.path.clone(), staged)) {
+                    rows.extend(hunks(&file.path, staged).into_iter().map(|hunk| {
+                        GitPanelRow::Hunk {
+                            path: file.path.clone(),
+                            staged,
+                            hunk,
+                        }
+                    }));
+                }
+            }
+        }
+        self.rows = rows;
+        self.selected = cmp::min(self.selected, self.rows.len().saturating_sub(1));
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn selected(&self) -> Option<&GitPanelRow> {
+        self.rows.get(self.selected)
+    }
+
+    /// Returns the area of the popup, centered in `area`.
+    pub fn rect(area: Rect) -> Rect {
+        let width = cmp::min(area.width, cmp::max(area.width * 4 / 5, 20));
+        let height = cmp::min(area.height, cmp::max(area.height * 4 / 5, 6));
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the screen position of the start of the selected row.
+    pub fn cursor_position(&self, area: Rect) -> (usize, usize) {
+        let rect = Self::rect(area);
+        (
+            rect.x + 1,
+            rect.y + 1 + self.selected.saturating_sub(self.offset),
+        )
+    }
+
+    fn label(row: &GitPanelRow) -> String {
+        match row {
+            GitPanelRow::Section { title } => title.clone(),
+            GitPanelRow::File { path, status, .. } => {
+                format!("  {} {}", status, path.to_string_lossy())
+            }
+            GitPanelRow::Hunk { hunk, .. } => format!("      {}", hunk.title()),
+        }
+    }
+
+    /// Draws the popup over `area`: the rows that fit and a line of key
+    /// hints, inside a border. Returns where it was drawn, if there was room.
+    pub fn draw(&mut self, out: &mut dyn EditorBufferPort, area: Rect) -> io::Result<Option<Rect>> {
+        let rect = Self::rect(area);
+        if rect.width < 4 || rect.height < 4 {
+            return Ok(None);
+        }
+        let rows = rect.height - 3;
+
+        // Keep the selected row in view
+        self.offset = self
+            .offset
+            .clamp(self.selected.saturating_sub(rows - 1), self.selected);
+
+        let name = self
+            .root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let inner = popup::draw_frame(out, rect, Some(&format!("Git: {}", name)))?;
+
+        for row in 0..rows {
+            let index = row + self.offset;
+            let Some(panel_row) = self.rows.get(index) else {
+                break;
+            };
+            out.move_cursor_to(inner.x as u16, (inner.y + row) as u16)?;
+            let label: String = Self::label(panel_row).chars().take(inner.width).collect();
+            if index == self.selected {
+                out.set_style(style::Attribute::Reverse)?;
+            }
+            if let GitPanelRow::Section { .. } = panel_row {
+                out.set_style(style::Attribute::Bold)?;
+            }
+            out.append_str(&format!("{:<width$}", label, width = inner.width));
+            out.set_style(style::Attribute::Reset)?;
+        }
+
+        let hints: String = " s stage  u unstage  = hunks  c commit  q close"
+            .chars()
+            .take(inner.width)
+            .collect();
+        out.move_cursor_to(inner.x as u16, (inner.y + inner.height - 1) as u16)?;
+        out.append_str(&hints);
+        Ok(Some(rect))
+    }
+}
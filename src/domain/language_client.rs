@@ -0,0 +1,251 @@
+//! `language_client.rs`
+//!
+//! The client end of a connection to a language server: the `initialize`
+//! handshake, the encoding positions are counted in, and answers to the
+//! requests servers make of their clients.
+
+use crate::adapters::rpc::RpcProcess;
+use crate::ports::config::LanguageServerConfig;
+use crate::ports::rpc::{RpcError, RpcMessage, RpcPort, METHOD_NOT_FOUND};
+use crate::toolshed::uri;
+use serde_json::{json, Value};
+use std::{cmp, io, path, process, time};
+
+/// The kinds of code actions asked for, which servers only send as
+/// actions rather than bare commands to clients listing them.
+const CODE_ACTION_KINDS: [&str; 8] = [
+    "",
+    "quickfix",
+    "refactor",
+    "refactor.extract",
+    "refactor.inline",
+    "refactor.rewrite",
+    "source",
+    "source.organizeImports",
+];
+
+/// How long after being told of a change a server is watched for the
+/// diagnostics it publishes in answer.
+const DIAGNOSTICS_WAIT: time::Duration = time::Duration::from_secs(2);
+
+/// How the characters of a position in a row are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    /// The default of the protocol.
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// Returns the byte offset in `row` of position `character`, which is
+    /// kept within the row.
+    pub fn byte_offset(self, row: &str, character: usize) -> usize {
+        match self {
+            PositionEncoding::Utf8 => {
+                let mut offset = cmp::min(character, row.len());
+                while !row.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                offset
+            }
+            PositionEncoding::Utf16 => {
+                let mut units = 0;
+                for (offset, ch) in row.char_indices() {
+                    if units >= character {
+                        return offset;
+                    }
+                    units += ch.len_utf16();
+                }
+                row.len()
+            }
+        }
+    }
+
+    /// Returns the position of byte offset `offset` of `row`.
+    pub fn character(self, row: &str, offset: usize) -> usize {
+        match self {
+            PositionEncoding::Utf8 => cmp::min(offset, row.len()),
+            PositionEncoding::Utf16 => row
+                .char_indices()
+                .take_while(|(at, _)| *at < offset)
+                .map(|(_, ch)| ch.len_utf16())
+                .sum(),
+        }
+    }
+}
+
+/// A connection to a running language server.
+pub struct LanguageClient {
+    connection: Box<dyn RpcPort>,
+    /// The id of the `initialize` request.
+    initialize: u64,
+    /// Whether the server answered `initialize`. Until it does, the
+    /// notifications for it wait in `queued`.
+    initialized: bool,
+    queued: Vec<(String, Value)>,
+    /// Until when diagnostics for the changes last sent are expected.
+    expected_until: Option<time::Instant>,
+    encoding: PositionEncoding,
+    /// What the server said it can do.
+    capabilities: Value,
+}
+
+impl LanguageClient {
+    /// Starts the server of `config` on the project in `root`.
+    pub fn start(config: &LanguageServerConfig, root: &path::Path) -> io::Result<Self> {
+        let mut connection = RpcProcess::spawn(&config.command, &config.args, root)?;
+        let root_uri = uri::file_uri(root);
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let initialize = connection.request(
+            "initialize",
+            json!({
+                "processId": process::id(),
+                "clientInfo": { "name": "barn", "version": env!("CARGO_PKG_VERSION") },
+                "rootUri": root_uri,
+                "workspaceFolders": [{ "uri": root_uri, "name": name }],
+                "capabilities": {
+                    "general": { "positionEncodings": ["utf-8", "utf-16"] },
+                    "workspace": {
+                        "applyEdit": true,
+                        "workspaceEdit": { "documentChanges": true },
+                    },
+                    "textDocument": {
+                        "synchronization": { "didSave": true },
+                        "publishDiagnostics": {},
+                        "definition": { "linkSupport": true },
+                        "references": {},
+                        "hover": { "contentFormat": ["markdown", "plaintext"] },
+                        "completion": {
+                            "completionItem": {
+                                "snippetSupport": true,
+                                "documentationFormat": ["markdown", "plaintext"],
+                            },
+                        },
+                        "rename": {},
+                        "codeAction": {
+                            "codeActionLiteralSupport": {
+                                "codeActionKind": { "valueSet": CODE_ACTION_KINDS },
+                            },
+                        },
+                        "formatting": {},
+                        "rangeFormatting": {},
+                    },
+                },
+            }),
+        )?;
+        Ok(Self {
+            connection: Box::new(connection),
+            initialize,
+            initialized: false,
+            queued: Vec::new(),
+            expected_until: None,
+            encoding: PositionEncoding::Utf16,
+            capabilities: Value::Null,
+        })
+    }
+
+    pub fn encoding(&self) -> PositionEncoding {
+        self.encoding
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        if !self.initialized {
+            self.queued.push((method.to_string(), params));
+            return Ok(());
+        }
+        self.expected_until = Some(time::Instant::now() + DIAGNOSTICS_WAIT);
+        self.connection.notify(method, params)
+    }
+
+    /// Returns whether the server may soon send more: it is still starting,
+    /// or it was told of a change moments ago.
+    pub fn expects_more(&self) -> bool {
+        !self.initialized
+            || self
+                .expected_until
+                .is_some_and(|until| time::Instant::now() < until)
+    }
+
+    /// Sends a request, returning the id its response comes with.
+    pub fn request(&mut self, method: &str, params: Value) -> io::Result<u64> {
+        if !self.initialized {
+            return Err(io::Error::other("The language server is still starting"));
+        }
+        self.connection.request(method, params)
+    }
+
+    /// Returns what the server said of `capability`, such as
+    /// `definitionProvider`, or `null` if nothing.
+    pub fn capability(&self, capability: &str) -> &Value {
+        &self.capabilities[capability]
+    }
+
+    /// Returns whether the server has `capability`.
+    pub fn supports(&self, capability: &str) -> bool {
+        !matches!(
+            self.capability(capability),
+            Value::Null | Value::Bool(false)
+        )
+    }
+
+    /// Answers request `id` of the server.
+    pub fn respond(&mut self, id: Value, result: Result<Value, RpcError>) -> io::Result<()> {
+        self.connection.respond(id, result)
+    }
+
+    /// Answers a request of the server. Only what servers commonly ask
+    /// right after starting is known.
+    fn answer(method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            // Settings are left to the server's defaults
+            "workspace/configuration" => {
+                let items = params["items"].as_array().map_or(0, Vec::len);
+                Ok(Value::Array(vec![Value::Null; items]))
+            }
+            "window/workDoneProgress/create"
+            | "client/registerCapability"
+            | "client/unregisterCapability" => Ok(Value::Null),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("No method {}", method),
+            )),
+        }
+    }
+
+    /// Takes care of what the server sent since the last call, answering
+    /// its requests, and returns its notifications, the responses to
+    /// requests and the requests to edit files.
+    pub fn receive(&mut self) -> io::Result<Vec<RpcMessage>> {
+        let mut messages = Vec::new();
+        for message in self.connection.receive() {
+            match message {
+                RpcMessage::Response { id, result } if id == self.initialize => {
+                    let result = result.map_err(|e| io::Error::other(e.message))?;
+                    self.capabilities = result["capabilities"].clone();
+                    if self.capabilities["positionEncoding"] == "utf-8" {
+                        self.encoding = PositionEncoding::Utf8;
+                    }
+                    self.initialized = true;
+                    self.connection.notify("initialized", json!({}))?;
+                    for (method, params) in std::mem::take(&mut self.queued) {
+                        self.notify(&method, params)?;
+                    }
+                }
+                // Edits are made by the editor, which answers them in turn
+                RpcMessage::Request { id, method, params } if method != "workspace/applyEdit" => {
+                    let result = Self::answer(&method, &params);
+                    self.connection.respond(id, result)?;
+                }
+                message => messages.push(message),
+            }
+        }
+        Ok(messages)
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        self.connection.is_running()
+    }
+}
@@ -0,0 +1,713 @@
+//! `markdown.rs`
+//!
+//! Renders Markdown as styled terminal text for the preview pane: headings,
+//! emphasis, links, lists, block quotes, code blocks, tables and rules.
+//! Every rendered line remembers the row of the source it came from, so the
+//! pane can scroll along with the window editing the source.
+
+use crate::ports::editor_buffer::EditorBufferPort;
+use crate::ports::window::Rect;
+use crossterm::style;
+use std::{cmp, io};
+
+/// The colors of the preview, picked with `preview_theme`.
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewTheme {
+    pub heading: style::Color,
+    pub code: style::Color,
+    pub quote: style::Color,
+    pub link: style::Color,
+    pub rule: style::Color,
+}
+
+impl PreviewTheme {
+    /// Returns the theme called `name`, or the `github` one if there is no
+    /// such theme.
+    pub fn named(name: &str) -> Self {
+        match name {
+            "dracula" => Self {
+                heading: style::Color::Magenta,
+                code: style::Color::Green,
+                quote: style::Color::Yellow,
+                link: style::Color::Cyan,
+                rule: style::Color::DarkGrey,
+            },
+            "solarized" => Self {
+                heading: style::Color::DarkYellow,
+                code: style::Color::DarkCyan,
+                quote: style::Color::DarkGreen,
+                link: style::Color::DarkBlue,
+                rule: style::Color::DarkGrey,
+            },
+            "plain" => Self {
+                heading: style::Color::Reset,
+                code: style::Color::Reset,
+                quote: style::Color::Reset,
+                link: style::Color::Reset,
+                rule: style::Color::Reset,
+            },
+            _ => Self {
+                heading: style::Color::Blue,
+                code: style::Color::DarkRed,
+                quote: style::Color::DarkGrey,
+                link: style::Color::Blue,
+                rule: style::Color::DarkGrey,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SpanStyle {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strike: bool,
+    color: Option<style::Color>,
+}
+
+#[derive(Debug, Clone)]
+struct Span {
+    text: String,
+    style: SpanStyle,
+}
+
+impl Span {
+    fn new(text: impl Into<String>, style: SpanStyle) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+/// A line of the rendered preview.
+pub struct RenderedLine {
+    spans: Vec<Span>,
+    /// The row of the Markdown source the line was rendered from.
+    source_row: usize,
+}
+
+/// Turns Markdown into lines at most `width` columns wide.
+pub struct Renderer {
+    width: usize,
+    theme: PreviewTheme,
+    lines: Vec<RenderedLine>,
+}
+
+impl Renderer {
+    pub fn render(rows: &[&str], width: usize, theme: PreviewTheme) -> Vec<RenderedLine> {
+        let mut renderer = Self {
+            width: cmp::max(width, 1),
+            theme,
+            lines: Vec::new(),
+        };
+        renderer.render_rows(rows);
+        renderer.lines
+    }
+
+    fn push(&mut self, spans: Vec<Span>, source_row: usize) {
+        self.lines.push(RenderedLine { spans, source_row });
+    }
+
+    fn render_rows(&mut self, rows: &[&str]) {
+        let mut at = 0;
+        // The fence that opened the code block being rendered
+        let mut fence: Option<&str> = None;
+        while at < rows.len() {
+            let row = rows[at];
+            let trimmed = row.trim_start();
+
+            if let Some(open) = fence {
+                if trimmed.starts_with(open) {
+                    fence = None;
+                } else {
+                    self.render_code(row, at);
+                }
+                at += 1;
+                continue;
+            }
+            if let Some(open) = ["```", "~~~"]
+                .into_iter()
+                .find(|open| trimmed.starts_with(open))
+            {
+                fence = Some(open);
+                at += 1;
+                continue;
+            }
+
+            // A table goes on for as long as its rows start with a pipe
+            if trimmed.starts_with('|') {
+                let end = rows[at..]
+                    .iter()
+                    .position(|row| !row.trim_start().starts_with('|'))
+                    .map_or(rows.len(), |length| at + length);
+                self.render_table(&rows[at..end], at);
+                at = end;
+                continue;
+            }
+
+            self.render_row(row, at);
+            at += 1;
+        }
+    }
+
+    fn render_row(&mut self, row: &str, at: usize) {
+        let trimmed = row.trim_start();
+        if trimmed.is_empty() {
+            // Runs of blank rows show as one blank line
+            if self.lines.last().is_some_and(|line| !line.spans.is_empty()) {
+                self.push(Vec::new(), at);
+            }
+            return;
+        }
+
+        // Headings
+        let level = trimmed.chars().take_while(|ch| *ch == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            let style = SpanStyle {
+                bold: true,
+                color: Some(self.theme.heading),
+                ..SpanStyle::default()
+            };
+            let spans = self.inline(trimmed[level..].trim(), style);
+            let width = spans.iter().map(Span::width).sum::<usize>();
+            self.wrap(spans, Vec::new(), Vec::new(), at);
+            let underline = match level {
+                1 => "═",
+                2 => "─",
+                _ => return,
+            };
+            let rule = underline.repeat(cmp::min(width, self.width));
+            self.push(vec![Span::new(rule, style)], at);
+            return;
+        }
+
+        // Horizontal rules
+        let marks: String = trimmed.chars().filter(|ch| !ch.is_whitespace()).collect();
+        if marks.len() >= 3
+            && ["-", "*", "_"]
+                .iter()
+                .any(|mark| marks.chars().all(|ch| ch.to_string() == *mark))
+        {
+            let style = SpanStyle {
+                color: Some(self.theme.rule),
+                ..SpanStyle::default()
+            };
+            self.push(vec![Span::new("─".repeat(self.width), style)], at);
+            return;
+        }
+
+        // Block quotes, possibly nested
+        if trimmed.starts_with('>') {
+            let mut text = trimmed;
+            let mut depth = 0;
+            while let Some(rest) = text.strip_prefix('>') {
+                text = rest.trim_start();
+                depth += 1;
+            }
+            let style = SpanStyle {
+                italic: true,
+                color: Some(self.theme.quote),
+                ..SpanStyle::default()
+            };
+            let prefix = vec![Span::new("┃ ".repeat(depth), style)];
+            let spans = self.inline(text, style);
+            self.wrap(spans, prefix.clone(), prefix, at);
+            return;
+        }
+
+        // List items, indented by how deep they are nested
+        let indent = row.len() - trimmed.len();
+        if let Some((marker, text)) = Self::list_item(trimmed, indent) {
+            let pad = " ".repeat(indent);
+            let first = vec![Span::new(
+                format!("{}{}", pad, marker),
+                SpanStyle::default(),
+            )];
+            let rest = vec![Span::new(
+                " ".repeat(indent + marker.chars().count()),
+                SpanStyle::default(),
+            )];
+            let spans = self.inline(text, SpanStyle::default());
+            self.wrap(spans, first, rest, at);
+            return;
+        }
+
+        let spans = self.inline(trimmed, SpanStyle::default());
+        self.wrap(spans, Vec::new(), Vec::new(), at);
+    }
+
+    /// Splits a list item into the marker it is drawn with and its text.
+    fn list_item(trimmed: &str, indent: usize) -> Option<(String, &str)> {
+        let bullet = ["• ", "◦ ", "▪ "][(indent / 2) % 3];
+        let text = if let Some(text) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            text
+        } else {
+            // Ordered items keep their number
+            let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+            let rest = &trimmed[digits..];
+            if digits == 0 || !(rest.starts_with(". ") || rest.starts_with(") ")) {
+                return None;
+            }
+            return Some((format!("{}. ", &trimmed[..digits]), &rest[2..]));
+        };
+        // Task list items show a box instead of a bullet
+        if let Some(text) = text.strip_prefix("[ ] ") {
+            return Some(("☐ ".into(), text));
+        }
+        if let Some(text) = text
+            .strip_prefix("[x] ")
+            .or_else(|| text.strip_prefix("[X] "))
+        {
+            return Some(("☑ ".into(), text));
+        }
+        Some((bullet.into(), text))
+    }
+
+    fn render_code(&mut self, row: &str, at: usize) {
+        let style = SpanStyle {
+            color: Some(self.theme.code),
+            ..SpanStyle::default()
+        };
+        let bar = SpanStyle {
+            color: Some(self.theme.rule),
+            ..SpanStyle::default()
+        };
+        // Code is cut rather than wrapped, as its line breaks matter
+        let code: String = row
+            .replace('\t', "    ")
+            .chars()
+            .take(self.width.saturating_sub(2))
+            .collect();
+        self.push(vec![Span::new("▏ ", bar), Span::new(code, style)], at);
+    }
+
+    fn render_table(&mut self, rows: &[&str], first_row: usize) {
+        let cells = |row: &str| -> Vec<String> {
+            let row = row.trim();
+            let row = row.strip_prefix('|').unwrap_or(row);
+            let row = row.strip_suffix('|').unwrap_or(row);
+            row.split('|').map(|cell| cell.trim().to_string()).collect()
+        };
+        let is_separator = |cells: &[String]| {
+            cells.iter().all(|cell| {
+                let cell = cell.trim_matches(':');
+                !cell.is_empty() && cell.chars().all(|ch| ch == '-')
+            })
+        };
+
+        let table: Vec<(usize, Vec<Vec<Span>>)> = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| (first_row + index, cells(row)))
+            .filter(|(_, cells)| !is_separator(cells))
+            .map(|(at, cells)| {
+                let spans = cells
+                    .iter()
+                    .map(|cell| self.inline(cell, SpanStyle::default()))
+                    .collect();
+                (at, spans)
+            })
+            .collect();
+        let columns = table
+            .iter()
+            .map(|(_, cells)| cells.len())
+            .max()
+            .unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                table
+                    .iter()
+                    .filter_map(|(_, cells)| cells.get(column))
+                    .map(|spans| spans.iter().map(Span::width).sum::<usize>())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let border = SpanStyle {
+            color: Some(self.theme.rule),
+            ..SpanStyle::default()
+        };
+        for (index, (at, cells)) in table.into_iter().enumerate() {
+            let mut line = Vec::new();
+            for (column, width) in widths.iter().enumerate() {
+                if column > 0 {
+                    line.push(Span::new(" │ ", border));
+                }
+                let mut cell = cells.get(column).cloned().unwrap_or_default();
+                let used: usize = cell.iter().map(Span::width).sum();
+                // The first row is the header
+                if index == 0 {
+                    cell.iter_mut().for_each(|span| span.style.bold = true);
+                }
+                line.extend(cell);
+                line.push(Span::new(" ".repeat(width - used), SpanStyle::default()));
+            }
+            self.push(line, at);
+            if index == 0 {
+                let rule = widths
+                    .iter()
+                    .map(|width| "─".repeat(*width))
+                    .collect::<Vec<_>>()
+                    .join("─┼─");
+                self.push(vec![Span::new(rule, border)], at);
+            }
+        }
+    }
+
+    /// Splits `text` into spans styled by its emphasis, code and link
+    /// markers, on top of `base`.
+    fn inline(&self, text: &str, base: SpanStyle) -> Vec<Span> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut spans = Vec::new();
+        let mut current = String::new();
+        let mut style = base;
+        // The emphasis opened and not yet closed
+        let mut open: Vec<Emphasis> = Vec::new();
+        let rest = |at: usize| -> String { chars[at..].iter().collect() };
+        let flush = |current: &mut String, spans: &mut Vec<Span>, style: SpanStyle| {
+            if !current.is_empty() {
+                spans.push(Span::new(std::mem::take(current), style));
+            }
+        };
+
+        let mut at = 0;
+        while at < chars.len() {
+            let ch = chars[at];
+            let following = rest(at);
+
+            // A backslash makes the next char literal
+            if ch == '\\' && at + 1 < chars.len() {
+                current.push(chars[at + 1]);
+                at += 2;
+                continue;
+            }
+
+            // Code spans are taken as they are
+            if ch == '`' {
+                if let Some(length) = following[1..].find('`') {
+                    flush(&mut current, &mut spans, style);
+                    let code_style = SpanStyle {
+                        color: Some(self.theme.code),
+                        ..style
+                    };
+                    spans.push(Span::new(&following[1..1 + length], code_style));
+                    at += following[..1 + length + 1].chars().count();
+                    continue;
+                }
+            }
+
+            // Links and images show their text
+            if ch == '[' || (ch == '!' && following[1..].starts_with('[')) {
+                let start = if ch == '!' { 2 } else { 1 };
+                if let Some(close) = following.find("](") {
+                    if let Some(end) = following[close..].find(')') {
+                        flush(&mut current, &mut spans, style);
+                        let label = &following[start..close];
+                        let link_style = SpanStyle {
+                            underline: true,
+                            color: Some(self.theme.link),
+                            ..style
+                        };
+                        let label = if ch == '!' {
+                            format!("[image: {}]", label)
+                        } else {
+                            label.to_string()
+                        };
+                        spans.extend(self.inline(&label, link_style));
+                        at += following[..close + end + 1].chars().count();
+                        continue;
+                    }
+                }
+            }
+
+            // Emphasis markers turn their style on only if they are closed later
+            let marker = ["**", "__", "~~", "*", "_"]
+                .into_iter()
+                .find(|marker| following.starts_with(marker));
+            if let Some(marker) = marker {
+                let previous = at.checked_sub(1).map(|previous| chars[previous]);
+                // Underscores within words, as in snake_case, are not markers
+                let in_word = marker.starts_with('_')
+                    && previous.is_some_and(char::is_alphanumeric)
+                    && chars
+                        .get(at + marker.len())
+                        .is_some_and(|next| next.is_alphanumeric());
+                let emphasis = Emphasis::of(marker);
+                let closing = open.contains(&emphasis);
+                let opening = !closing && following[marker.len()..].contains(marker);
+                if !in_word && (closing || opening) {
+                    flush(&mut current, &mut spans, style);
+                    if closing {
+                        open.retain(|open| *open != emphasis);
+                    } else {
+                        open.push(emphasis);
+                    }
+                    // Emphasis the whole text already has stays on
+                    let on = open.contains(&emphasis) || emphasis.is_in(base);
+                    match emphasis {
+                        Emphasis::Bold => style.bold = on,
+                        Emphasis::Strike => style.strike = on,
+                        Emphasis::Italic => style.italic = on,
+                    }
+                    at += marker.len();
+                    continue;
+                }
+            }
+
+            current.push(ch);
+            at += 1;
+        }
+        flush(&mut current, &mut spans, style);
+        spans
+    }
+
+    /// Breaks `spans` into lines at spaces, starting the first line with
+    /// `first` and the others with `rest`.
+    fn wrap(&mut self, spans: Vec<Span>, first: Vec<Span>, rest: Vec<Span>, at: usize) {
+        let mut line = first;
+        let mut used: usize = line.iter().map(Span::width).sum();
+        let rest_width: usize = rest.iter().map(Span::width).sum();
+        let mut line_has_words = false;
+
+        for span in spans {
+            // Words keep the space after them, so they can be put back together
+            for word in span.text.split_inclusive(' ') {
+                let width = word.chars().count();
+                let visible = word.trim_end().chars().count();
+                if line_has_words && used + visible > self.width {
+                    self.push(std::mem::replace(&mut line, rest.clone()), at);
+                    used = rest_width;
+                    line_has_words = false;
+                }
+                let word = if line_has_words {
+                    word
+                } else {
+                    word.trim_start()
+                };
+                if word.is_empty() {
+                    continue;
+                }
+                line.push(Span::new(word, span.style));
+                used += width;
+                line_has_words = true;
+            }
+        }
+        self.push(line, at);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Emphasis {
+    Bold,
+    Italic,
+    Strike,
+}
+
+impl Emphasis {
+    fn of(marker: &str) -> Self {
+        match marker {
+            "**" | "__" => Emphasis::Bold,
+            "~~" => Emphasis::Strike,
+            _ => Emphasis::Italic,
+        }
+    }
+
+    fn is_in(self, style: SpanStyle) -> bool {
+        match self {
+            Emphasis::Bold => style.bold,
+            Emphasis::Italic => style.italic,
+            Emphasis::Strike => style.strike,
+        }
+    }
+}
+
+/// Returns the first rendered line showing source row `row` or one after it.
+pub fn line_of_row(lines: &[RenderedLine], row: usize) -> usize {
+    lines
+        .iter()
+        .position(|line| line.source_row >= row)
+        .unwrap_or(lines.len().saturating_sub(1))
+}
+
+/// Draws `lines` from line `offset` on into `rect`.
+pub fn draw(
+    out: &mut dyn EditorBufferPort,
+    lines: &[RenderedLine],
+    offset: usize,
+    rect: Rect,
+) -> io::Result<()> {
+    for (i, line) in lines.iter().skip(offset).take(rect.height).enumerate() {
+        out.move_cursor_to(rect.x as u16, (rect.y + i) as u16)?;
+        let mut left = rect.width;
+        for span in &line.spans {
+            if left == 0 {
+                break;
+            }
+            let text: String = span.text.chars().take(left).collect();
+            left -= text.chars().count();
+
+            let style = span.style;
+            if style.bold {
+                out.set_style(style::Attribute::Bold)?;
+            }
+            if style.italic {
+                out.set_style(style::Attribute::Italic)?;
+            }
+            if style.underline {
+                out.set_style(style::Attribute::Underlined)?;
+            }
+            if style.strike {
+                out.set_style(style::Attribute::CrossedOut)?;
+            }
+            if let Some(color) = style.color {
+                out.set_color(color)?;
+            }
+            out.append_str(&text);
+            if style != SpanStyle::default() {
+                out.set_style(style::Attribute::Reset)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, width: usize) -> Vec<RenderedLine> {
+        let rows: Vec<&str> = source.lines().collect();
+        Renderer::render(&rows, width, PreviewTheme::named("plain"))
+    }
+
+    /// Returns the text of each line.
+    fn texts(lines: &[RenderedLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.text.as_str()).collect())
+            .collect()
+    }
+
+    /// Returns the words of the only line `text` renders to, with their
+    /// style.
+    fn words(text: &str) -> Vec<(String, SpanStyle)> {
+        let lines = render(text, 80);
+        assert_eq!(lines.len(), 1);
+        lines[0]
+            .spans
+            .iter()
+            .filter(|span| !span.text.trim().is_empty())
+            .map(|span| (span.text.trim().to_string(), span.style))
+            .collect()
+    }
+
+    #[test]
+    fn renders_headings_rules_and_quotes() {
+        let lines = render("# Title\n\n## Part\n---\n> > quoted", 40);
+        assert_eq!(
+            texts(&lines),
+            [
+                "Title",
+                "═════",
+                "",
+                "Part",
+                "────",
+                "─".repeat(40).as_str(),
+                "┃ ┃ quoted"
+            ]
+        );
+        let rows: Vec<usize> = lines.iter().map(|line| line.source_row).collect();
+        assert_eq!(rows, [0, 0, 1, 2, 2, 3, 4]);
+    }
+
+    #[test]
+    fn renders_list_items_with_their_markers() {
+        let lines = render("- one\n  * two\n3. three\n- [ ] to do\n- [x] done", 40);
+        assert_eq!(
+            texts(&lines),
+            ["• one", "  ◦ two", "3. three", "☐ to do", "☑ done"]
+        );
+    }
+
+    #[test]
+    fn wraps_text_at_spaces_and_indents_list_items() {
+        let lines = render("one two three four\n- five six seven", 10);
+        assert_eq!(
+            texts(&lines),
+            ["one two ", "three four", "• five six ", "  seven"]
+        );
+    }
+
+    #[test]
+    fn cuts_code_blocks_rather_than_wrapping_them() {
+        let lines = render("```rust\nlet long_name = 1;\n```\ntext", 10);
+        assert_eq!(texts(&lines), ["▏ let long", "text"]);
+        assert_eq!(lines[0].source_row, 1);
+    }
+
+    #[test]
+    fn lines_up_table_columns() {
+        let lines = render("| a | long |\n|---|:-:|\n| wide | b |", 40);
+        assert_eq!(texts(&lines), ["a    │ long", "─────┼─────", "wide │ b   "]);
+    }
+
+    #[test]
+    fn styles_emphasis_code_and_links() {
+        let bold = SpanStyle {
+            bold: true,
+            ..SpanStyle::default()
+        };
+        let italic = SpanStyle {
+            italic: true,
+            ..SpanStyle::default()
+        };
+        let link = SpanStyle {
+            underline: true,
+            color: Some(style::Color::Reset),
+            ..SpanStyle::default()
+        };
+        let code = SpanStyle {
+            color: Some(style::Color::Reset),
+            ..SpanStyle::default()
+        };
+        assert_eq!(
+            words("**bold** _it_ `a*b` [site](http://x)"),
+            [
+                ("bold".into(), bold),
+                ("it".into(), italic),
+                ("a*b".into(), code),
+                ("site".into(), link),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_unclosed_and_escaped_markers_and_snake_case_alone() {
+        let plain = |text: &str| {
+            text.split(' ')
+                .map(|word| (word.to_string(), SpanStyle::default()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words("2 * 3 = 6"), plain("2 * 3 = 6"));
+        assert_eq!(words("snake_case_name"), plain("snake_case_name"));
+        assert_eq!(words(r"\*not\*"), plain("*not*"));
+    }
+
+    #[test]
+    fn finds_the_line_of_a_source_row() {
+        let lines = render("# Title\n\ntext", 40);
+        assert_eq!(line_of_row(&lines, 0), 0);
+        assert_eq!(line_of_row(&lines, 2), 3);
+        assert_eq!(line_of_row(&lines, 9), 3);
+    }
+}
@@ -1 +1,9 @@
+pub mod completion;
 pub mod editor;
+pub mod extensions;
+pub mod git_panel;
+pub mod language_client;
+pub mod markdown;
+pub mod picker;
+pub mod popup;
+pub mod workspace_edit;
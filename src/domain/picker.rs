@@ -0,0 +1,159 @@
+//! `picker.rs`
+//!
+//! A popup list narrowed down by typing a query, used to pick a file to
+//! open or a search result to jump to.
+
+use super::popup;
+use crate::ports::editor_buffer::EditorBufferPort;
+use crate::ports::window::Rect;
+use crossterm::style;
+use std::{cmp, io, path};
+
+/// An entry of a picker's list.
+#[derive(Clone)]
+pub struct PickerItem {
+    pub label: String,
+    /// The char indices of `label` drawn highlighted.
+    pub highlights: Vec<usize>,
+    /// The file opened when the item is picked, if it stands for one.
+    pub path: path::PathBuf,
+    /// Where the cursor goes in the file, as (byte offset, row).
+    pub position: Option<(usize, usize)>,
+    /// The position of the item in the list it was made from, if it stands
+    /// for an entry of one rather than for a file.
+    pub index: Option<usize>,
+}
+
+pub struct Picker {
+    pub title: String,
+    pub query: String,
+    /// Whether more items are still being looked for.
+    pub searching: bool,
+    items: Vec<PickerItem>,
+    selected: usize,
+    /// The first item shown, when the items do not fit the popup.
+    offset: usize,
+}
+
+impl Picker {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.into(),
+            query: String::new(),
+            searching: false,
+            items: Vec::new(),
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    /// Replaces the items, selecting the first one.
+    pub fn set_items(&mut self, items: Vec<PickerItem>) {
+        self.items = items;
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    /// Adds items at the end of the list, keeping the selection.
+    pub fn push_items(&mut self, items: Vec<PickerItem>) {
+        self.items.extend(items);
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn selected(&self) -> Option<&PickerItem> {
+        self.items.get(self.selected)
+    }
+
+    /// Returns the area of the popup, centered in `area`.
+    pub fn rect(area: Rect) -> Rect {
+        let width = cmp::min(area.width, cmp::max(area.width * 4 / 5, 20));
+        let height = cmp::min(area.height, cmp::max(area.height * 3 / 5, 6));
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the screen position of the end of the query.
+    pub fn cursor_position(&self, area: Rect) -> (usize, usize) {
+        let rect = Self::rect(area);
+        let x = rect.x + 3 + self.query.chars().count();
+        (
+            cmp::min(x, (rect.x + rect.width).saturating_sub(2)),
+            rect.y + 1,
+        )
+    }
+
+    /// Draws `text` padded or cut to exactly `width` columns, with the
+    /// chars at `highlights` in bold.
+    fn draw_text(
+        out: &mut dyn EditorBufferPort,
+        text: &str,
+        highlights: &[usize],
+        width: usize,
+    ) -> io::Result<()> {
+        let mut drawn = 0;
+        for (index, ch) in text.chars().take(width).enumerate() {
+            let highlighted = highlights.contains(&index);
+            if highlighted {
+                out.set_style(style::Attribute::Bold)?;
+            }
+            out.append_char(ch);
+            if highlighted {
+                out.set_style(style::Attribute::NormalIntensity)?;
+            }
+            drawn += 1;
+        }
+        out.append_str(&" ".repeat(width - drawn));
+        Ok(())
+    }
+
+    /// Draws the popup over `area`: a title, the query and as many items as
+    /// fit, inside a border. Returns where it was drawn, if there was room.
+    pub fn draw(&mut self, out: &mut dyn EditorBufferPort, area: Rect) -> io::Result<Option<Rect>> {
+        let rect = Self::rect(area);
+        if rect.width < 4 || rect.height < 4 {
+            return Ok(None);
+        }
+        let rows = rect.height - 3;
+
+        // Keep the selected item in view
+        self.offset = self
+            .offset
+            .clamp(self.selected.saturating_sub(rows - 1), self.selected);
+
+        let count = format!(
+            "{}{}",
+            self.items.len(),
+            if self.searching { "+" } else { "" }
+        );
+        let title = format!("{} ({})", self.title, count);
+        let inner = popup::draw_frame(out, rect, Some(&title))?;
+
+        out.move_cursor_to(inner.x as u16, inner.y as u16)?;
+        Self::draw_text(out, &format!("> {}", self.query), &[], inner.width)?;
+
+        for row in 0..rows {
+            let index = row + self.offset;
+            let Some(item) = self.items.get(index) else {
+                break;
+            };
+            out.move_cursor_to(inner.x as u16, (inner.y + 1 + row) as u16)?;
+            if index == self.selected {
+                out.set_style(style::Attribute::Reverse)?;
+            }
+            let highlights: Vec<usize> = item.highlights.iter().map(|i| i + 1).collect();
+            Self::draw_text(out, &format!(" {}", item.label), &highlights, inner.width)?;
+            out.set_style(style::Attribute::Reset)?;
+        }
+        Ok(Some(rect))
+    }
+}
@@ -0,0 +1,67 @@
+//! `popup.rs`
+//!
+//! Boxes drawn over the windows, such as the hover popup next to the
+//! cursor and the pickers in the middle of the screen.
+
+use crate::ports::editor_buffer::EditorBufferPort;
+use crate::ports::window::Rect;
+use std::{cmp, io};
+
+/// Returns where a box `width` by `height` goes in `area` next to the
+/// cursor at `(x, y)`: below it, or above it when there is more room there,
+/// shrunk to fit. `None` if there is no room for it.
+pub fn place(area: Rect, (x, y): (usize, usize), width: usize, height: usize) -> Option<Rect> {
+    let width = cmp::min(width, area.width);
+    let below = (area.y + area.height).saturating_sub(y + 1);
+    let above = y.saturating_sub(area.y);
+    let (top, height) = match height <= below || below >= above {
+        true => (y + 1, cmp::min(height, below)),
+        false => {
+            let height = cmp::min(height, above);
+            (y - height, height)
+        }
+    };
+    if width < 4 || height < 3 {
+        return None;
+    }
+    Some(Rect {
+        x: cmp::min(x, area.x + area.width - width),
+        y: top,
+        width,
+        height,
+    })
+}
+
+/// Draws a border around `rect` with `title` in its top edge, clearing
+/// what is inside, and returns the area inside.
+pub fn draw_frame(
+    out: &mut dyn EditorBufferPort,
+    rect: Rect,
+    title: Option<&str>,
+) -> io::Result<Rect> {
+    let inner = rect.width - 2;
+    let title: String = title
+        .map(|title| format!(" {} ", title))
+        .unwrap_or_default()
+        .chars()
+        .take(inner)
+        .collect();
+    out.move_cursor_to(rect.x as u16, rect.y as u16)?;
+    out.append_str(&format!(
+        "┌{}{}┐",
+        title,
+        "─".repeat(inner - title.chars().count())
+    ));
+    for row in rect.y + 1..rect.y + rect.height - 1 {
+        out.move_cursor_to(rect.x as u16, row as u16)?;
+        out.append_str(&format!("│{}│", " ".repeat(inner)));
+    }
+    out.move_cursor_to(rect.x as u16, (rect.y + rect.height - 1) as u16)?;
+    out.append_str(&format!("└{}┘", "─".repeat(inner)));
+    Ok(Rect {
+        x: rect.x + 1,
+        y: rect.y + 1,
+        width: inner,
+        height: rect.height - 2,
+    })
+}
@@ -0,0 +1,235 @@
+//! `workspace_edit.rs`
+//!
+//! Changes to the text of files that language servers ask for, such as
+//! renaming a symbol everywhere it is used or formatting a document.
+
+use crate::domain::language_client::PositionEncoding;
+use crate::toolshed::uri;
+use serde_json::Value;
+use std::{io, path};
+
+/// Text replacing a range of a file. Positions are rows and characters,
+/// counted in the encoding of the server.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn parse(value: &Value) -> Option<Self> {
+        let position = |at: &Value| {
+            Some((
+                at["line"].as_u64()? as usize,
+                at["character"].as_u64()? as usize,
+            ))
+        };
+        Some(Self {
+            start: position(&value["range"]["start"])?,
+            end: position(&value["range"]["end"])?,
+            text: value["newText"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// The edits of one file.
+#[derive(Debug, Clone)]
+pub struct FileEdit {
+    pub file: path::PathBuf,
+    pub edits: Vec<TextEdit>,
+}
+
+impl FileEdit {
+    /// Returns `rows` with the edits made, or an error if two of them
+    /// overlap.
+    pub fn apply(&self, rows: &[String], encoding: PositionEncoding) -> io::Result<Vec<String>> {
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(rows.len());
+        for row in rows {
+            starts.push(text.len());
+            text.push_str(row);
+            text.push('\n');
+        }
+        // Positions past the last row are at the end of the text
+        let offset = |(line, character): (usize, usize)| match rows.get(line) {
+            Some(row) => starts[line] + encoding.byte_offset(row, character),
+            None => text.len(),
+        };
+
+        let mut ranges: Vec<(usize, usize, &str)> = self
+            .edits
+            .iter()
+            .map(|edit| {
+                let start = offset(edit.start);
+                (start, offset(edit.end).max(start), edit.text.as_str())
+            })
+            .collect();
+        // Edits inserting at the same place go in in the order they came
+        ranges.sort_by_key(|&(start, end, _)| (start, end));
+        if ranges.windows(2).any(|pair| pair[1].0 < pair[0].1) {
+            return Err(io::Error::other(format!(
+                "Overlapping edits of {}",
+                self.file.display()
+            )));
+        }
+        for (start, end, replacement) in ranges.into_iter().rev() {
+            text.replace_range(start..end, replacement);
+        }
+        Ok(text.lines().map(String::from).collect())
+    }
+}
+
+/// Edits of any number of files, made all together or not at all.
+#[derive(Debug, Clone)]
+pub struct WorkspaceEdit {
+    pub files: Vec<FileEdit>,
+    pub encoding: PositionEncoding,
+}
+
+impl WorkspaceEdit {
+    /// Reads a `WorkspaceEdit` of the protocol. Creating, renaming and
+    /// deleting files is not supported, so edits doing it are refused as a
+    /// whole.
+    pub fn parse(value: &Value, encoding: PositionEncoding) -> io::Result<Self> {
+        let file = |uri: &Value| {
+            uri.as_str()
+                .and_then(uri::uri_path)
+                .ok_or_else(|| io::Error::other(format!("Not a file: {}", uri)))
+        };
+        let edits = |edits: &Value| -> Vec<TextEdit> {
+            edits
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(TextEdit::parse)
+                .collect()
+        };
+
+        let mut files = Vec::new();
+        if let Some(changes) = value["documentChanges"].as_array() {
+            for change in changes {
+                if change.get("kind").is_some() {
+                    return Err(io::Error::other(
+                        "Creating, renaming or deleting files is not supported",
+                    ));
+                }
+                files.push(FileEdit {
+                    file: file(&change["textDocument"]["uri"])?,
+                    edits: edits(&change["edits"]),
+                });
+            }
+        } else if let Some(changes) = value["changes"].as_object() {
+            for (uri, changes) in changes {
+                files.push(FileEdit {
+                    file: file(&Value::from(uri.as_str()))?,
+                    edits: edits(changes),
+                });
+            }
+        }
+        Ok(Self { files, encoding })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            text: text.into(),
+        }
+    }
+
+    fn apply(rows: &[&str], edits: Vec<TextEdit>) -> io::Result<Vec<String>> {
+        let rows: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+        let file_edit = FileEdit {
+            file: "main.rs".into(),
+            edits,
+        };
+        file_edit.apply(&rows, PositionEncoding::Utf8)
+    }
+
+    #[test]
+    fn makes_edits_given_in_any_order() {
+        let edits = vec![
+            edit((1, 0), (1, 3), "second"),
+            edit((0, 4), (0, 8), "first"),
+            edit((1, 3), (1, 3), "!"),
+        ];
+        assert_eq!(
+            apply(&["let name = 1;", "one"], edits).unwrap(),
+            ["let first = 1;", "second!"]
+        );
+    }
+
+    #[test]
+    fn joins_and_splits_rows() {
+        let joined = apply(&["a", "b", "c"], vec![edit((0, 1), (2, 0), " ")]).unwrap();
+        assert_eq!(joined, ["a c"]);
+        let split = apply(&["ab"], vec![edit((0, 1), (0, 1), "\n")]).unwrap();
+        assert_eq!(split, ["a", "b"]);
+        // Past the last row is the end of the text
+        let appended = apply(&["a"], vec![edit((5, 0), (5, 0), "b\n")]).unwrap();
+        assert_eq!(appended, ["a", "b"]);
+    }
+
+    #[test]
+    fn inserts_at_the_same_place_in_the_order_given() {
+        let edits = vec![edit((0, 1), (0, 1), "x"), edit((0, 1), (0, 1), "y")];
+        assert_eq!(apply(&["ab"], edits).unwrap(), ["axyb"]);
+    }
+
+    #[test]
+    fn rejects_overlapping_edits() {
+        let edits = vec![edit((0, 0), (0, 3), "x"), edit((0, 2), (0, 4), "y")];
+        assert!(apply(&["abcdef"], edits).is_err());
+        // Touching ranges do not overlap
+        let edits = vec![edit((0, 0), (0, 2), "x"), edit((0, 2), (0, 4), "y")];
+        assert_eq!(apply(&["abcdef"], edits).unwrap(), ["xyef"]);
+    }
+
+    #[test]
+    fn counts_characters_in_the_encoding_of_the_server() {
+        let rows = vec!["é😀x".to_string()];
+        let file_edit = FileEdit {
+            file: "main.rs".into(),
+            edits: vec![edit((0, 3), (0, 4), "y")],
+        };
+        assert_eq!(
+            file_edit.apply(&rows, PositionEncoding::Utf16).unwrap(),
+            ["é😀y"]
+        );
+    }
+
+    #[test]
+    fn parses_changes_and_document_changes() {
+        let uri = uri::file_uri(path::Path::new("/project/main.rs"));
+        let text_edit = json!({
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 0, "character": 1 },
+            },
+            "newText": "x",
+        });
+        let changes = json!({ "changes": { uri.clone(): [text_edit.clone()] } });
+        let document_changes = json!({
+            "documentChanges": [{ "textDocument": { "uri": uri }, "edits": [text_edit] }],
+        });
+        for value in [changes, document_changes] {
+            let edit = WorkspaceEdit::parse(&value, PositionEncoding::Utf8).unwrap();
+            assert_eq!(edit.files.len(), 1);
+            assert_eq!(edit.files[0].file, path::Path::new("/project/main.rs"));
+            assert_eq!(edit.files[0].edits[0].text, "x");
+        }
+    }
+
+    #[test]
+    fn refuses_edits_of_files_themselves() {
+        let value = json!({ "documentChanges": [{ "kind": "create", "uri": "file:///new.rs" }] });
+        assert!(WorkspaceEdit::parse(&value, PositionEncoding::Utf8).is_err());
+    }
+}
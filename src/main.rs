@@ -8,9 +8,10 @@ use crate::adapters::terminal_io::CrosstermEventReader;
 use crate::toolshed::logger;
 use adapters::terminal_io;
 use app::editor_app;
-use crossterm::{cursor, execute, terminal};
+use crossterm::{cursor, event, execute, terminal};
 use domain::editor;
 use ports::config::read_config;
+use std::env;
 use std::io::stdout;
 use std::io::Result as IoResult;
 
@@ -29,6 +30,7 @@ impl Drop for CleanUp {
     /// and disabling raw mode.
     fn drop(&mut self) {
         log_info!("Cleaning up application state");
+        execute!(stdout(), event::DisableMouseCapture).expect("Unable to disable mouse capture");
         terminal::disable_raw_mode().expect("Unable to disable raw mode");
         execute!(stdout(), terminal::Clear(terminal::ClearType::All))
             .expect("Unable to clear the screen");
@@ -60,17 +62,25 @@ fn main() -> IoResult<()> {
     let config = read_config("./config.toml").unwrap();
     log_info!("Config: {:?}", config);
 
+    // Mouse support is opt-in, as capturing the mouse disables the terminal's own selection
+    if config.general.mouse {
+        execute!(stdout(), event::EnableMouseCapture)?;
+    }
+
     // Initialize the terminal I/O adapters.
     let event_reader = CrosstermEventReader;
     let reader = terminal_io::ReaderAdapter::new(event_reader);
     let writer = terminal_io::WriterAdapter;
 
+    // Every command line argument is a file to open in its own buffer.
+    let files = env::args().skip(1).map(Into::into).collect();
+
     // Create an instance of the main editor application, passing the I/O adapters.
     let mut editor: editor_app::EditorApp<
         terminal_io::ReaderAdapter<CrosstermEventReader>,
         terminal_io::WriterAdapter,
         editor::EditorDomain,
-    > = editor_app::EditorApp::new(reader, writer, config);
+    > = editor_app::EditorApp::new(reader, writer, config, files);
 
     // Main execution loop of the editor. This loop continues running the editor
     // until an exit condition (like pressing 'Ctrl+Q') is met.
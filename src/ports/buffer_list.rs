@@ -0,0 +1,38 @@
+use crate::ports::cursor::CursorControllerPort;
+use crate::ports::editor_rows::EditorRowsPort;
+use crate::ports::undo::UndoHistoryPort;
+use std::path;
+
+/// A file (or scratch text) loaded into the editor, together with the
+/// per-buffer state that survives switching to another buffer. The cursor
+/// holds where the buffer was last left by a window.
+pub struct Buffer {
+    pub id: usize,
+    pub editor_rows: Box<dyn EditorRowsPort>,
+    pub cursor_controller: Box<dyn CursorControllerPort>,
+    pub undo_history: Box<dyn UndoHistoryPort>,
+}
+
+pub trait BufferListPort {
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// Adds a buffer, makes it the current one and returns its id.
+    fn add(
+        &mut self,
+        editor_rows: Box<dyn EditorRowsPort>,
+        cursor_controller: Box<dyn CursorControllerPort>,
+        undo_history: Box<dyn UndoHistoryPort>,
+    ) -> usize;
+    /// Removes the buffer with the given id. If it was the current buffer,
+    /// the following one (or the last one) becomes current.
+    fn remove(&mut self, id: usize) -> Option<Buffer>;
+    fn find_by_path(&self, path: &path::Path) -> Option<usize>;
+    fn switch_to(&mut self, id: usize) -> bool;
+    fn next(&mut self);
+    fn previous(&mut self);
+    fn get(&self, id: usize) -> Option<&Buffer>;
+    fn get_mut(&mut self, id: usize) -> Option<&mut Buffer>;
+    fn current(&self) -> &Buffer;
+    fn buffers(&self) -> &[Buffer];
+}
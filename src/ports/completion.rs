@@ -0,0 +1,41 @@
+use crate::ports::editor_rows::EditorRowsPort;
+use std::path;
+
+/// A suggestion for the text being typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    /// What the popup shows and what is typed is matched against.
+    pub label: String,
+    /// The text put in place of what is typed when the item is accepted.
+    pub insert: String,
+    /// The byte offset in the row where the text the item replaces starts.
+    pub start: usize,
+    /// A short description, such as the type of a symbol.
+    pub detail: Option<String>,
+    /// Markdown shown beside the popup while the item is selected.
+    pub documentation: Option<String>,
+    /// The name of the source the item came from, such as `buffer`.
+    pub source: String,
+}
+
+/// Where completion is asked for.
+pub struct CompletionRequest<'a> {
+    /// The row the cursor is in, up to the cursor.
+    pub line: &'a str,
+    /// The rows of every open buffer, the current one first.
+    pub buffers: Vec<&'a dyn EditorRowsPort>,
+    /// The directory relative paths are completed from.
+    pub directory: &'a path::Path,
+}
+
+/// A source of completion items, such as the words of the open buffers.
+/// Items are filtered against what is typed by the popup, so sources can
+/// return every item that fits where the cursor is.
+pub trait CompletionSourcePort {
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// The name `[completion] sources` refers to the source by.
+    fn name(&self) -> &str;
+    fn complete(&self, request: &CompletionRequest) -> Vec<CompletionItem>;
+}
@@ -1,19 +1,434 @@
+use crate::toolshed::schema;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use toml;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Config {
+    #[serde(default)]
+    pub general: GeneralConfig,
     pub cursor_keymaps: CursorKeyMapsConfig,
+    #[serde(default)]
+    pub editor_behavior: EditorBehaviorConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub completion: CompletionConfig,
+    #[serde(default)]
+    pub extensions: ExtensionsConfig,
 }
-#[derive(Deserialize, Debug)]
+
+/// The keys of normal mode, and of insert mode for `exit_insert_mode`.
+/// Keys are written as scripts bind them, such as `gg` or `<C-u>`, or as
+/// a single key spelled out, such as `ctrl-u` or `esc`. A value starting
+/// with `:` runs that command instead.
+#[derive(Deserialize, Debug, Clone)]
 pub struct CursorKeyMapsConfig {
     pub down: char,
     pub up: char,
     pub left: char,
     pub right: char,
+    /// Moves a page up.
+    #[serde(default = "default_page_up")]
+    pub page_up: String,
+    /// Moves a page down.
+    #[serde(default = "default_page_down")]
+    pub page_down: String,
+    /// Moves to the start of the line.
+    #[serde(default = "default_start_of_line")]
+    pub start_of_line: String,
+    /// Moves to the end of the line.
+    #[serde(default = "default_end_of_line")]
+    pub end_of_line: String,
+    /// Moves to the first line.
+    #[serde(default = "default_first_line")]
+    pub first_line: String,
+    /// Moves to the last line.
+    #[serde(default = "default_last_line")]
+    pub last_line: String,
+    /// Saves the buffer.
+    #[serde(default = "default_save")]
+    pub save: String,
+    /// Saves the buffer under a name typed after it.
+    #[serde(default = "default_save_as")]
+    pub save_as: String,
+    /// Closes the window, or quits.
+    #[serde(default = "default_quit")]
+    pub quit: String,
+    /// Enters insert mode.
+    #[serde(default = "default_insert_mode")]
+    pub insert_mode: String,
+    /// Leaves insert mode, typed in it.
+    #[serde(default = "default_exit_insert_mode")]
+    pub exit_insert_mode: String,
+    /// Copies the selection.
+    #[serde(default = "default_copy")]
+    pub copy: String,
+    /// Deletes the selection.
+    #[serde(default = "default_cut")]
+    pub cut: String,
+    /// Pastes what was copied last.
+    #[serde(default = "default_paste")]
+    pub paste: String,
+}
+
+fn default_page_up() -> String {
+    "<PageUp>".into()
+}
+
+fn default_page_down() -> String {
+    "<PageDown>".into()
+}
+
+fn default_start_of_line() -> String {
+    "<Home>".into()
+}
+
+fn default_end_of_line() -> String {
+    "<End>".into()
+}
+
+fn default_first_line() -> String {
+    "gg".into()
+}
+
+fn default_last_line() -> String {
+    "G".into()
+}
+
+fn default_save() -> String {
+    ":w".into()
+}
+
+fn default_save_as() -> String {
+    ":w ".into()
+}
+
+fn default_quit() -> String {
+    ":q".into()
+}
+
+fn default_insert_mode() -> String {
+    "i".into()
+}
+
+fn default_exit_insert_mode() -> String {
+    "<Esc>".into()
+}
+
+fn default_copy() -> String {
+    "y".into()
+}
+
+fn default_cut() -> String {
+    "d".into()
+}
+
+fn default_paste() -> String {
+    "p".into()
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct GeneralConfig {
+    /// Enables clicking, drag-selecting and wheel scrolling with the mouse.
+    #[serde(default)]
+    pub mouse: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EditorBehaviorConfig {
+    #[serde(default = "default_undo_limit")]
+    pub undo_limit: usize,
+}
+
+impl Default for EditorBehaviorConfig {
+    fn default() -> Self {
+        Self {
+            undo_limit: default_undo_limit(),
+        }
+    }
+}
+
+fn default_undo_limit() -> usize {
+    1000
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SearchConfig {
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Treats search patterns as regular expressions rather than plain text.
+    #[serde(default)]
+    pub regex_search: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompletionConfig {
+    /// Where items come from, in order: `buffer` for the words of the open
+    /// buffers, `path` for files and `lsp` for the language server.
+    #[serde(default = "default_completion_sources")]
+    pub sources: Vec<String>,
+    /// Opens the popup while typing, rather than only on `Ctrl-N`.
+    #[serde(default = "default_true")]
+    pub auto: bool,
+    /// How many characters of a word have to be typed before the popup
+    /// opens on its own.
+    #[serde(default = "default_min_prefix")]
+    pub min_prefix: usize,
+    /// The most items listed at once.
+    #[serde(default = "default_max_items")]
+    pub max_items: usize,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            sources: default_completion_sources(),
+            auto: true,
+            min_prefix: default_min_prefix(),
+            max_items: default_max_items(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ExtensionsConfig {
+    #[serde(default)]
+    pub file_explorer: FileExplorerConfig,
+    #[serde(default)]
+    pub markdown_preview: MarkdownPreviewConfig,
+    /// The other tables, by name, with whatever options they hold. The
+    /// extensions of the registry read their own, plugins included.
+    #[serde(skip)]
+    pub tables: toml::Table,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FileExplorerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// The directory listed when the explorer is opened without one. A
+    /// leading `~` stands for the home directory.
+    #[serde(default = "default_directory")]
+    pub default_directory: String,
+    #[serde(default)]
+    pub show_hidden_files: bool,
+    /// Shows the selected file in the focused window while browsing.
+    #[serde(default = "default_true")]
+    pub preview_files: bool,
+}
+
+impl Default for FileExplorerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_directory: default_directory(),
+            show_hidden_files: false,
+            preview_files: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitIntegrationConfig {
+    /// Commits a file every time it is saved.
+    #[serde(default)]
+    pub auto_commit: bool,
+    /// The message commits start with, where `{date}` stands for today's
+    /// date.
+    #[serde(default = "default_commit_message_template")]
+    pub commit_message_template: String,
+}
+
+impl Default for GitIntegrationConfig {
+    fn default() -> Self {
+        Self {
+            auto_commit: false,
+            commit_message_template: default_commit_message_template(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpellCheckerConfig {
+    /// The Hunspell dictionary to load, such as `en-US` for `en_US.dic` and
+    /// `en_US.aff`. They are looked for in the directories listed in
+    /// `DICPATH` and then in the usual system ones.
+    #[serde(default = "default_language")]
+    pub language: String,
+    /// Words accepted and suggested on top of the dictionary's.
+    #[serde(default)]
+    pub custom_dictionary: Vec<String>,
+    /// Words never reported as misspelled, but never suggested either.
+    #[serde(default)]
+    pub ignore_words: Vec<String>,
+    /// Underlines misspelled words.
+    #[serde(default = "default_true")]
+    pub highlight_mistakes: bool,
+    /// Replaces a misspelled word with the closest suggestion as soon as
+    /// the word is typed.
+    #[serde(default)]
+    pub auto_correct: bool,
+}
+
+impl Default for SpellCheckerConfig {
+    fn default() -> Self {
+        Self {
+            language: default_language(),
+            custom_dictionary: Vec::new(),
+            ignore_words: Vec::new(),
+            highlight_mistakes: true,
+            auto_correct: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MarkdownPreviewConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Renders the buffer as it is edited, rather than as it was last saved.
+    #[serde(default = "default_true")]
+    pub live_preview: bool,
+    /// The colors of the preview: `github`, `dracula`, `solarized` or
+    /// `plain`.
+    #[serde(default = "default_preview_theme")]
+    pub preview_theme: String,
+}
+
+impl Default for MarkdownPreviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            live_preview: true,
+            preview_theme: default_preview_theme(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CodeFormatterConfig {
+    /// Formats a buffer every time it is saved.
+    #[serde(default)]
+    pub format_on_save: bool,
+    /// The languages formatted, such as `rust`, `javascript` or `python`.
+    #[serde(default = "default_formatted_languages")]
+    pub languages: Vec<String>,
+    /// Formatter commands by language, replacing the built-in ones. The
+    /// buffer is written to the command's standard input and replaced with
+    /// its output; `{file}` stands for the buffer's file name.
+    #[serde(default)]
+    pub formatters: HashMap<String, String>,
+}
+
+impl Default for CodeFormatterConfig {
+    fn default() -> Self {
+        Self {
+            format_on_save: false,
+            languages: default_formatted_languages(),
+            formatters: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScriptingConfig {
+    /// The Rhai script run at startup, which defines commands, key bindings
+    /// and event handlers. Kept next to `config.toml` by default.
+    #[serde(default = "default_init_script")]
+    pub init_script: String,
+    /// How many operations a script may run for each call before it is
+    /// stopped, so that a runaway loop cannot hang the editor.
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+}
+
+impl Default for ScriptingConfig {
+    fn default() -> Self {
+        Self {
+            init_script: default_init_script(),
+            max_operations: default_max_operations(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LanguageServersConfig {
+    /// Servers by language, such as `rust`, added to the built-in ones or
+    /// replacing them.
+    #[serde(default)]
+    pub servers: HashMap<String, LanguageServerConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LanguageServerConfig {
+    /// The server's program, talking the protocol over its standard input
+    /// and output.
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The extensions of the files the server is for, such as `rs`.
+    #[serde(default)]
+    pub file_extensions: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_directory() -> String {
+    ".".into()
+}
+
+fn default_language() -> String {
+    "en-US".into()
+}
+
+fn default_formatted_languages() -> Vec<String> {
+    vec!["rust".into(), "javascript".into(), "python".into()]
+}
+
+fn default_preview_theme() -> String {
+    "github".into()
+}
+
+fn default_init_script() -> String {
+    "init.rhai".into()
+}
+
+fn default_max_operations() -> u64 {
+    1_000_000
+}
+
+fn default_completion_sources() -> Vec<String> {
+    vec!["lsp".into(), "buffer".into(), "path".into()]
+}
+
+fn default_min_prefix() -> usize {
+    2
+}
+
+fn default_max_items() -> usize {
+    50
+}
+
+fn default_commit_message_template() -> String {
+    "Updates made on {date}".into()
 }
 
 pub fn read_config(file_path: &str) -> Result<Config, Box<dyn std::error::Error>> {
-    toml::from_str(&fs::read_to_string(file_path)?).map_err(Into::into)
+    let text = fs::read_to_string(file_path)?;
+    let table: toml::Table = toml::from_str(&text)?;
+    let mut tables = match table.get("extensions") {
+        Some(toml::Value::Table(tables)) => tables.clone(),
+        _ => toml::Table::new(),
+    };
+    // The tables of the extensions typed above are not the registry's
+    for field in schema::fields::<ExtensionsConfig>() {
+        tables.remove(&field.path);
+    }
+    let mut config: Config = toml::Value::Table(table).try_into()?;
+    config.extensions.tables = tables;
+    Ok(config)
 }
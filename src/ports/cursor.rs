@@ -8,9 +8,14 @@ pub trait CursorControllerPort {
         Self: Sized;
     fn move_cursor(&mut self, direction: KeyCode, editor_rows: &dyn EditorRowsPort);
     fn get_cursor_position(&self) -> (usize, usize);
+    /// Returns the cursor as (byte offset in the row, row index in the file).
+    fn get_file_position(&self) -> (usize, usize);
     fn set_cursor_position(&mut self, x: usize, y: usize);
     fn get_row_offset(&self) -> usize;
     fn get_col_offset(&self) -> usize;
+    fn set_offsets(&mut self, row_offset: usize, col_offset: usize);
+    /// Changes the size of the viewport the cursor is kept inside of.
+    fn set_screen_size(&mut self, window_size: (usize, usize));
     fn scroll(&mut self, editor_rows: &dyn EditorRowsPort);
     fn get_render_x(&self, row: &dyn RowPort) -> usize;
 }
@@ -1,12 +1,19 @@
+use crate::ports::config::Config;
 use crate::ports::cursor::CursorControllerPort;
 use crate::ports::editor_buffer::EditorBufferPort;
 use crate::ports::editor_rows::EditorRowsPort;
+use crate::ports::extension::{ExtensionOptions, ExtensionSummary};
+use crate::ports::overlay::OverlayKind;
+use crate::ports::window::{FocusDirection, SplitDirection};
 use crossterm::event;
-use std::io;
+use std::{io, path};
 
 pub trait EditorDomainPort {
-    fn new(window_size: (usize, usize)) -> Self;
+    fn new(window_size: (usize, usize), config: &Config) -> Self;
     fn get_window_size(&self) -> io::Result<(usize, usize)>;
+    /// Adapts the layout to a new terminal size, given as the area left for
+    /// text once the tab line, status line and message bar are taken out.
+    fn set_window_size(&mut self, window_size: (usize, usize));
     fn draw_rows(&mut self) -> io::Result<()>;
     fn get_buffer(&mut self) -> &mut dyn EditorBufferPort;
     fn get_cursor_position(&self) -> (usize, usize);
@@ -17,4 +24,226 @@ pub trait EditorDomainPort {
     fn get_editor_rows(&mut self) -> &mut dyn EditorRowsPort;
     fn draw_status_bar(&mut self);
     fn draw_message_bar(&mut self);
+    /// Draws the list of tab pages on the first line of the screen.
+    fn draw_tab_line(&mut self);
+    fn set_message(&mut self, message: String);
+    /// Shows `line` in place of the message bar while a command is typed.
+    fn set_command_line(&mut self, line: Option<String>);
+
+    /// Opens `file` in a new buffer, or switches to it if it is already open,
+    /// returning the id of the buffer. `None` opens an empty, unnamed buffer.
+    fn open_file(&mut self, file: Option<path::PathBuf>) -> io::Result<usize>;
+    fn next_buffer(&mut self);
+    fn previous_buffer(&mut self);
+    fn switch_buffer(&mut self, id: usize) -> io::Result<()>;
+    /// Closes the current buffer, refusing to drop unsaved changes unless
+    /// `force` is set.
+    fn close_buffer(&mut self, force: bool) -> io::Result<()>;
+    /// Describes every open buffer, one entry per buffer.
+    fn list_buffers(&self) -> Vec<String>;
+    fn has_unsaved_changes(&self) -> bool;
+
+    /// Splits the focused window, optionally opening `file` in the new one.
+    fn split_window(
+        &mut self,
+        direction: SplitDirection,
+        file: Option<path::PathBuf>,
+    ) -> io::Result<()>;
+    /// Closes the focused window. Returns false if it is the last one.
+    fn close_window(&mut self) -> bool;
+    fn only_window(&mut self);
+    fn focus_window(&mut self, direction: FocusDirection);
+    fn focus_next_window(&mut self);
+    fn resize_window(&mut self, direction: SplitDirection, delta: isize);
+
+    /// Opens a tab page after the current one, optionally showing `file`.
+    fn new_tab(&mut self, file: Option<path::PathBuf>) -> io::Result<()>;
+    /// Closes the current tab page. Returns false if it is the last one.
+    fn close_tab(&mut self) -> bool;
+    fn next_tab(&mut self);
+    fn previous_tab(&mut self);
+    /// Switches to the tab page at `index`, counting from zero.
+    fn switch_tab(&mut self, index: usize) -> io::Result<()>;
+    /// Returns the index of the tab page whose label covers column `x` of
+    /// the tab line.
+    fn tab_at(&self, x: usize) -> Option<usize>;
+
+    /// Focuses the window under screen cell (`x`, `y`) and moves its cursor
+    /// to the clicked character.
+    fn click(&mut self, x: usize, y: usize);
+    /// Extends a selection from the last click to screen cell (`x`, `y`).
+    fn drag(&mut self, x: usize, y: usize);
+    /// Scrolls the window under screen cell (`x`, `y`) by `lines` rows.
+    fn scroll_view(&mut self, x: usize, y: usize, lines: isize);
+    fn has_selection(&self) -> bool;
+    fn clear_selection(&mut self);
+    /// Copies the selected text to the register and clears the selection.
+    fn yank_selection(&mut self);
+    /// Moves the selected text to the register.
+    fn delete_selection(&mut self);
+    /// Inserts the register's text at the cursor.
+    fn paste(&mut self);
+
+    /// Opens the file explorer on `directory`, or on the configured default
+    /// directory, and gives it focus.
+    fn open_explorer(&mut self, directory: Option<path::PathBuf>) -> io::Result<()>;
+    fn close_explorer(&mut self);
+    /// Opens the file explorer, focuses it if it is open, or closes it if it
+    /// already has focus.
+    fn toggle_explorer(&mut self) -> io::Result<()>;
+    fn explorer_focused(&self) -> bool;
+    /// Gives focus back to the focused window, leaving the explorer open.
+    fn leave_explorer(&mut self);
+    fn explorer_move(&mut self, delta: isize);
+    /// Opens the selected file in the focused window, or expands (or
+    /// collapses) the selected directory.
+    fn explorer_open(&mut self) -> io::Result<()>;
+    fn explorer_collapse(&mut self);
+    /// Returns the name of the selected explorer entry.
+    fn explorer_selection(&self) -> Option<String>;
+    fn explorer_create(&mut self, name: &str) -> io::Result<()>;
+    fn explorer_rename(&mut self, name: &str) -> io::Result<()>;
+    fn explorer_delete(&mut self) -> io::Result<()>;
+    fn explorer_refresh(&mut self) -> io::Result<()>;
+
+    /// Opens the fuzzy finder over the files of the working directory.
+    fn open_finder(&mut self);
+    /// Opens a picker listing the lines of the files in the working
+    /// directory that match `pattern`, searching as the pattern is edited.
+    fn open_grep(&mut self, pattern: &str);
+    /// Narrows down the open picker's list to the items matching `query`.
+    fn set_picker_query(&mut self, query: &str);
+    fn picker_move(&mut self, delta: isize);
+    /// Opens the selected item of the picker and closes it.
+    fn picker_accept(&mut self) -> io::Result<()>;
+    fn close_picker(&mut self);
+    /// Returns whether a picker is open, such as one the domain opened
+    /// itself to list what a language server answered.
+    fn picker_open(&self) -> bool;
+    /// Opens a picker of the actions of the command palette, labelled
+    /// `labels`.
+    fn open_palette(&mut self, labels: Vec<String>);
+    /// Returns the index of the action picked from the command palette
+    /// since the last call, to run it.
+    fn take_palette_choice(&mut self) -> Option<usize>;
+    /// Collects the results of work running in the background, returning
+    /// whether some of it is still running.
+    fn poll_tasks(&mut self) -> bool;
+    /// Draws the open popups over everything else, each over those opened
+    /// before it.
+    fn draw_popup(&mut self);
+    /// Returns the topmost popup taking keys, which keys go to first.
+    fn focused_overlay(&self) -> Option<OverlayKind>;
+
+    fn insert_char(&mut self, ch: char);
+    fn insert_newline(&mut self);
+    fn delete_char(&mut self);
+    /// Ends the current undo step, e.g. when leaving insert mode.
+    fn end_change(&mut self);
+    /// Opens the completion popup at the cursor, whatever was typed.
+    fn complete(&mut self) -> io::Result<()>;
+    /// Narrows the open completion popup down to what is typed after
+    /// `typed` was typed, or a char deleted if `None`. With `[completion]
+    /// auto`, opens the popup once enough of a word is typed.
+    fn update_completion(&mut self, typed: Option<char>);
+    fn completion_move(&mut self, delta: isize);
+    /// Puts the selected item in place of what is typed and closes the
+    /// popup. Returns false if nothing was selected.
+    fn completion_accept(&mut self) -> bool;
+    fn close_completion(&mut self);
+    fn undo(&mut self) -> bool;
+    fn redo(&mut self) -> bool;
+    /// Moves the cursor to the start of the next, or previous, block of rows
+    /// changed since the last commit. Returns false if there is none.
+    fn jump_to_hunk(&mut self, forward: bool) -> bool;
+    /// Shows the focused Markdown buffer rendered in a pane on the right,
+    /// or hides the pane.
+    fn toggle_markdown_preview(&mut self) -> io::Result<()>;
+    /// Shows or hides who last changed each row of the current buffer.
+    fn toggle_blame(&mut self) -> io::Result<()>;
+    /// Opens the current buffer's file as of `revision` in a read-only buffer.
+    fn open_revision(&mut self, revision: &str) -> io::Result<()>;
+    /// Shows `text` in a read-only buffer named `name`, such as a help
+    /// page, with the cursor on row `row`. A buffer by that name already
+    /// open is shown instead. Jumping back returns to where it was opened.
+    fn open_read_only(&mut self, name: path::PathBuf, text: &str, row: usize);
+
+    /// Opens a picker of corrections for the word under the cursor.
+    fn spell_suggest(&mut self) -> io::Result<()>;
+    /// Adds the word under the cursor to the dictionary, returning it.
+    fn spell_add_word(&mut self) -> io::Result<String>;
+    /// Moves the cursor to the next, or previous, misspelled word. Returns
+    /// false if there is none.
+    fn jump_to_misspelling(&mut self, forward: bool) -> bool;
+
+    /// Opens the popup listing the changes in the current file's repository.
+    fn open_git_panel(&mut self) -> io::Result<()>;
+    fn close_git_panel(&mut self);
+    fn git_panel_move(&mut self, delta: isize);
+    /// Stages the selected file or hunk, or unstages it if `stage` is false.
+    fn git_panel_stage(&mut self, stage: bool) -> io::Result<()>;
+    /// Lists the hunks of the selected file below it, or stops listing them.
+    fn git_panel_toggle_hunks(&mut self) -> io::Result<()>;
+    /// Closes the git panel and opens the selected file.
+    fn git_panel_open_file(&mut self) -> io::Result<()>;
+    fn git_panel_refresh(&mut self) -> io::Result<()>;
+    /// Opens a buffer to write the message of a commit of the staged
+    /// changes in, filled in from the configured template.
+    fn start_commit(&mut self) -> io::Result<()>;
+    /// Pipes the current buffer through the formatter of its language, or
+    /// has its language server format it or the selection, as one change
+    /// that can be undone.
+    fn format_buffer(&mut self) -> io::Result<()>;
+    /// Asks the language server of the current buffer where the symbol
+    /// under the cursor is defined, to jump there once it answers.
+    fn goto_definition(&mut self) -> io::Result<()>;
+    /// Asks the language server of the current buffer where the symbol
+    /// under the cursor is used, to list the places once it answers.
+    fn find_references(&mut self) -> io::Result<()>;
+    /// Asks the language server of the current buffer about the symbol
+    /// under the cursor, to show its type and docs in a popup.
+    fn show_hover(&mut self) -> io::Result<()>;
+    fn close_hover(&mut self);
+    /// Shows a popup of the keys that may follow `prefix`, each with the
+    /// name of the action it runs, until the next key.
+    fn show_key_hints(&mut self, prefix: String, hints: Vec<(String, String)>);
+    fn close_key_hints(&mut self);
+    /// Asks the language server of the current buffer to rename the symbol
+    /// under the cursor to `name` in every file using it.
+    fn rename_symbol(&mut self, name: &str) -> io::Result<()>;
+    /// Asks the language server of the current buffer for the code actions
+    /// of the selection, or of the cursor, to pick one to run.
+    fn code_actions(&mut self) -> io::Result<()>;
+    /// Goes back to where the cursor was before the last jump to a
+    /// definition. Returns false if there is nowhere to go back to.
+    fn jump_back(&mut self) -> bool;
+    /// Lets the extensions see a key typed in insert mode, before it is
+    /// handled.
+    fn run_key_hooks(&mut self, key: &event::KeyEvent);
+    /// Returns the names of the commands the extensions have.
+    fn extension_commands(&self) -> Vec<String>;
+    /// Returns the enabled extensions, with what they do and their commands.
+    fn extension_summaries(&self) -> Vec<ExtensionSummary>;
+    /// Returns the options of every extension barn comes with, enabled or
+    /// not, by the name of its table.
+    fn extension_options(&self) -> Vec<(String, ExtensionOptions)>;
+    /// Runs command `name` of the extension that has it, such as one a
+    /// plugin registered.
+    fn run_extension_command(&mut self, name: &str, argument: Option<&str>) -> io::Result<()>;
+    /// Returns the keys extensions, such as scripts, bound in `mode`
+    /// (`normal` or `insert`), named as in `<C-t>`.
+    fn key_bindings(&self, mode: &str) -> Vec<String>;
+    /// Runs what an extension, such as a script, bound to `key` in `mode`
+    /// (`normal` or `insert`). Returns whether there was anything bound.
+    fn run_key_binding(&mut self, mode: &str, key: &event::KeyEvent) -> bool;
+    /// Returns the command lines scripts ran since the last call, to be
+    /// carried out as if typed after `:`.
+    fn take_script_commands(&mut self) -> Vec<String>;
+    /// Lets the extensions change the current buffer before it is saved.
+    fn before_save(&mut self) -> io::Result<()>;
+    /// Lets the extensions know the current buffer was saved, committing it
+    /// if it holds a commit message. Returns a message to show, if any.
+    fn after_save(&mut self) -> io::Result<Option<String>>;
+    /// Saves the current buffer, optionally under a new file name.
+    fn save(&mut self, file_name: Option<path::PathBuf>) -> io::Result<usize>;
 }
@@ -12,4 +12,6 @@ pub trait EditorBufferPort: io::Write {
     fn move_cursor_to(&mut self, x: u16, y: u16) -> io::Result<()>;
     fn show_cursor(&mut self) -> io::Result<()>;
     fn set_style(&mut self, style: style::Attribute) -> io::Result<()>;
+    /// Sets the color of the text appended next, or the default with `Reset`.
+    fn set_color(&mut self, color: style::Color) -> io::Result<()>;
 }
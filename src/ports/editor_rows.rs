@@ -1,6 +1,5 @@
-use crate::adapters::editor_rows::Row;
 use std::any::Any;
-use std::path;
+use std::{io, path};
 
 pub trait RowPort: Any {
     fn new(row_content: Box<str>, render: String) -> Self
@@ -8,16 +7,50 @@ pub trait RowPort: Any {
         Self: Sized;
     fn get_content(&self) -> &str;
     fn get_render(&self) -> &String;
+    /// Returns the screen column at which the character at byte offset
+    /// `index` is drawn, with tabs expanded.
+    fn render_x(&self, index: usize) -> usize;
+    /// Returns the byte offset of the character drawn at screen column
+    /// `render_x`, or the row length if the column is past its end.
+    fn index_at_render_x(&self, render_x: usize) -> usize;
 }
 
 pub trait EditorRowsPort {
     fn new() -> Self
     where
         Self: Sized;
+    fn from_file(file: path::PathBuf) -> io::Result<Self>
+    where
+        Self: Sized;
+    /// Creates a buffer named `name` holding `text`, which cannot be edited
+    /// or saved.
+    fn read_only(name: path::PathBuf, text: &str) -> Self
+    where
+        Self: Sized;
+    fn is_read_only(&self) -> bool;
     fn number_of_rows(&self) -> usize;
     fn get_row(&self, at: usize) -> &str;
-    fn render_row(&self, row: &mut Row);
     fn get_render(&self, at: usize) -> &String;
     fn get_editor_row(&self, at: usize) -> &dyn RowPort;
     fn get_file_name(&self) -> Option<&path::PathBuf>;
+    fn set_file_name(&mut self, file_name: path::PathBuf);
+
+    /// Inserts `ch` into row `at_y` at byte offset `at_x`, creating the row
+    /// if `at_y` is one past the last row.
+    fn insert_char(&mut self, at_x: usize, at_y: usize, ch: char);
+    /// Splits row `at_y` at byte offset `at_x`, moving the tail to a new row.
+    fn insert_newline(&mut self, at_x: usize, at_y: usize);
+    /// Removes the character before byte offset `at_x` in row `at_y`. At the
+    /// start of a row, the row is joined onto the previous one instead.
+    fn delete_char(&mut self, at_x: usize, at_y: usize);
+
+    /// Returns a copy of every row's content, in order.
+    fn get_rows(&self) -> Vec<String>;
+    /// Replaces the whole content of the buffer.
+    fn set_rows(&mut self, rows: Vec<String>);
+
+    /// Writes the rows to the buffer's file, returning the number of bytes
+    /// written.
+    fn save(&mut self) -> io::Result<usize>;
+    fn is_dirty(&self) -> bool;
 }
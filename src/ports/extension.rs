@@ -0,0 +1,159 @@
+use crate::ports::editor_rows::EditorRowsPort;
+use crate::toolshed::schema::Field;
+use crossterm::{event, style};
+use std::any::Any;
+use std::ops::Range;
+use std::{fmt, io};
+
+/// What an extension is shown of a buffer.
+pub struct BufferView<'a> {
+    pub id: usize,
+    pub rows: &'a dyn EditorRowsPort,
+    /// The byte column and row of the cursor in the window showing the
+    /// buffer.
+    pub cursor: (usize, usize),
+}
+
+/// A change an extension makes to a buffer, undone like any other edit.
+pub enum BufferEdit {
+    /// Replaces byte range `range` of row `row` with `text`.
+    Replace {
+        row: usize,
+        range: Range<usize>,
+        text: String,
+    },
+    /// Replaces every row, keeping the cursor where it is.
+    ReplaceAll(Vec<String>),
+}
+
+/// Something an extension draws over the rows of a buffer.
+pub enum Decoration {
+    /// Underlines byte range `range` of row `row`.
+    Underline {
+        row: usize,
+        range: Range<usize>,
+        color: style::Color,
+    },
+    /// Writes `text` in the extension's gutter next to row `row`, from
+    /// `column` of the gutter on.
+    Gutter {
+        row: usize,
+        column: usize,
+        text: String,
+        color: style::Color,
+    },
+}
+
+/// What `:help extensions` tells of an enabled extension.
+pub struct ExtensionSummary {
+    pub name: String,
+    pub description: String,
+    pub commands: Vec<String>,
+}
+
+/// What `:help options` tells of the options an extension reads from its
+/// table.
+pub struct ExtensionOptions {
+    /// The fields of the table, with paths starting in it.
+    pub fields: Vec<Field>,
+    /// The options as they are by default, shown through their `Debug` form.
+    pub defaults: Option<Box<dyn fmt::Debug>>,
+}
+
+/// A feature enabled and configured by its table under `[extensions]`.
+/// Every hook but `init` does nothing unless the extension implements it.
+pub trait Extension {
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// The name of the extension's table, such as `spell_checker`.
+    fn name(&self) -> &str;
+    /// What the extension does, in a sentence.
+    fn description(&self) -> &str {
+        ""
+    }
+    /// Whether the extension runs when its table does not say, through
+    /// `enabled`.
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+    /// The options the extension reads from its table, if it has any.
+    fn options(&self) -> Option<ExtensionOptions> {
+        None
+    }
+    /// Sets the extension up with the options of its table. An extension
+    /// that fails to set up is left out.
+    fn init(&mut self, options: &toml::Table) -> io::Result<()>;
+    /// Called when a buffer is opened.
+    fn on_open(&mut self, _buffer: &BufferView) {}
+    /// Called after a buffer is edited, once for a run of edits.
+    fn on_change(&mut self, _buffer: &BufferView) {}
+    /// Called before a buffer is written, which is written with the edit
+    /// returned.
+    fn on_save(&mut self, _buffer: &BufferView) -> io::Result<Option<BufferEdit>> {
+        Ok(None)
+    }
+    /// Called after a buffer is written. Returns a message to show, if any.
+    fn after_save(&mut self, _buffer: &BufferView) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+    /// Called when a buffer is closed.
+    fn on_close(&mut self, _buffer: &BufferView) {}
+    /// Takes care of work done in the background, such as messages from
+    /// other processes. Returns whether more is expected, for the editor to
+    /// call again soon, or why the work failed.
+    fn poll(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+    /// Called on each key typed in insert mode, before the key is handled.
+    fn on_key(&mut self, _buffer: &BufferView, _key: &event::KeyEvent) -> Option<BufferEdit> {
+        None
+    }
+    /// The number of columns the extension draws in front of the rows of
+    /// the buffer.
+    fn gutter_width(&self, _buffer: &BufferView) -> usize {
+        0
+    }
+    /// Returns what to draw over rows `rows` of the buffer.
+    fn decorations(&self, _buffer: &BufferView, _rows: Range<usize>) -> Vec<Decoration> {
+        Vec::new()
+    }
+    /// Returns what to show on the message bar, when there is no message,
+    /// about where the cursor is in the buffer.
+    fn status_text(&self, _buffer: &BufferView) -> Option<String> {
+        None
+    }
+    /// Returns the names of the commands the extension has, to list them.
+    fn commands(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Runs command `name`, entered on the command line with `buffer`
+    /// focused, if the extension has one by that name.
+    fn run_command(
+        &mut self,
+        _buffer: &BufferView,
+        _name: &str,
+        _argument: Option<&str>,
+    ) -> Option<io::Result<()>> {
+        None
+    }
+    /// Returns the keys the extension binds in `mode` (`normal` or
+    /// `insert`), named as in `<C-t>`, to list them.
+    fn key_bindings(&self, _mode: &str) -> Vec<String> {
+        Vec::new()
+    }
+    /// Runs what the extension bound to `key` in `mode` (`normal` or
+    /// `insert`), if anything, in place of what the key does.
+    fn run_key_binding(
+        &mut self,
+        _buffer: &BufferView,
+        _mode: &str,
+        _key: &event::KeyEvent,
+    ) -> Option<io::Result<()>> {
+        None
+    }
+    /// Gives access to the extension's own type, for the commands of the
+    /// editor that are specific to it.
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
@@ -0,0 +1,50 @@
+use std::{io, path};
+
+/// A file or directory listed in the file explorer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerEntry {
+    pub path: path::PathBuf,
+    /// How many directories deep the entry is below the explorer's root.
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+impl ExplorerEntry {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+}
+
+/// A tree of the files below a directory. Directories are only read when
+/// they are expanded.
+pub trait FileExplorerPort {
+    fn new(root: path::PathBuf, show_hidden: bool) -> io::Result<Self>
+    where
+        Self: Sized;
+    fn root(&self) -> &path::Path;
+    /// Returns the visible entries in the order they are listed: the
+    /// contents of a directory follow it while it is expanded.
+    fn entries(&self) -> Vec<ExplorerEntry>;
+    /// Returns the index of the selected entry in `entries`.
+    fn selected(&self) -> usize;
+    fn select(&mut self, index: usize);
+    fn selected_entry(&self) -> Option<ExplorerEntry>;
+    /// Expands the selected directory, or collapses it if it is expanded.
+    fn toggle(&mut self) -> io::Result<()>;
+    /// Collapses the selected directory, or selects the directory holding
+    /// the selected entry.
+    fn collapse(&mut self);
+    /// Reads the expanded directories again to pick up changes on disk.
+    fn refresh(&mut self) -> io::Result<()>;
+    /// Creates `name` in the selected directory (or next to the selected
+    /// file) and selects it. A name ending in `/` creates a directory.
+    fn create(&mut self, name: &str) -> io::Result<path::PathBuf>;
+    /// Renames the selected entry, returning its new path.
+    fn rename(&mut self, name: &str) -> io::Result<path::PathBuf>;
+    /// Deletes the selected entry, with everything in it for a directory.
+    fn delete(&mut self) -> io::Result<()>;
+}
@@ -0,0 +1,24 @@
+use std::path;
+
+/// A file matching the fuzzy finder's query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinderMatch {
+    pub path: path::PathBuf,
+    /// The path relative to the finder's root, as shown in the results.
+    pub label: String,
+    pub score: i64,
+    /// The char indices of `label` matched by the query.
+    pub positions: Vec<usize>,
+}
+
+/// An index of the files below a directory, searched by fuzzy matching
+/// their paths.
+pub trait FileFinderPort {
+    /// Indexes the files below `root`, skipping those ignored by git and,
+    /// unless `show_hidden` is set, hidden ones.
+    fn new(root: path::PathBuf, show_hidden: bool) -> Self
+    where
+        Self: Sized;
+    /// Returns the files matching `query`, best match first.
+    fn find(&self, query: &str) -> Vec<FinderMatch>;
+}
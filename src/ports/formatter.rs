@@ -0,0 +1,14 @@
+use crate::ports::config::CodeFormatterConfig;
+use std::{io, path};
+
+/// Reformats source files with the formatter of their language.
+pub trait FormatterPort {
+    /// Sets up the formatters of the configured languages.
+    fn new(config: &CodeFormatterConfig) -> Self
+    where
+        Self: Sized;
+    /// Returns whether there is a formatter for the language of `file`.
+    fn formats(&self, file: &path::Path) -> bool;
+    /// Returns `text`, the content of `file`, formatted.
+    fn format(&self, file: &path::Path, text: &str) -> io::Result<String>;
+}
@@ -0,0 +1,81 @@
+use std::{io, path};
+
+/// Who last changed a line of a file, and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    /// The abbreviated hash of the commit, or `None` if the line is not
+    /// committed yet.
+    pub commit: Option<String>,
+    pub author: String,
+    /// The day the line was authored, as `YYYY-MM-DD`.
+    pub date: String,
+}
+
+/// A file with changes, as listed by `git status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStatus {
+    /// The path of the file relative to the repository root.
+    pub path: path::PathBuf,
+    /// How the file differs between `HEAD` and the index, as a letter such
+    /// as `M`, `A` or `D`, or a space if it does not.
+    pub staged: char,
+    /// How the file differs between the index and the working tree, or `?`
+    /// if it is untracked.
+    pub unstaged: char,
+}
+
+/// One hunk of a file's diff, which can be staged or unstaged by itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchHunk {
+    /// The lines of the diff naming the file, which the hunk needs to be
+    /// applied.
+    pub header: String,
+    /// The `@@` line and the lines of the hunk, each ending in a newline.
+    pub text: String,
+}
+
+impl PatchHunk {
+    /// Returns the `@@` line of the hunk.
+    pub fn title(&self) -> &str {
+        self.text.lines().next().unwrap_or_default()
+    }
+}
+
+/// Access to the git repository holding the files being edited.
+pub trait GitPort {
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// Returns the content of `file` as of the `HEAD` commit, or `None` if
+    /// the file is not tracked in a git repository.
+    fn head_content(&self, file: &path::Path) -> Option<String>;
+    /// Returns the content of `file` as of `revision`, which is anything
+    /// git accepts as a commit, such as a hash, a branch or `HEAD~2`.
+    fn show(&self, file: &path::Path, revision: &str) -> io::Result<String>;
+    /// Returns who last changed each line of `file`, as it is saved on disk.
+    fn blame(&self, file: &path::Path) -> io::Result<Vec<BlameLine>>;
+    /// Returns the root of the repository `directory` is in.
+    fn repository_root(&self, directory: &path::Path) -> io::Result<path::PathBuf>;
+    /// Returns where git keeps the message of the commit being made.
+    fn commit_message_file(&self, root: &path::Path) -> io::Result<path::PathBuf>;
+    /// Lists the files that differ from `HEAD` or are untracked.
+    fn status(&self, root: &path::Path) -> io::Result<Vec<FileStatus>>;
+    /// Returns the hunks of `file`'s staged changes, or of its unstaged
+    /// ones.
+    fn diff_hunks(
+        &self,
+        root: &path::Path,
+        file: &path::Path,
+        staged: bool,
+    ) -> io::Result<Vec<PatchHunk>>;
+    /// Adds the changes to `file` to the index.
+    fn stage(&self, root: &path::Path, file: &path::Path) -> io::Result<()>;
+    /// Takes the changes to `file` out of the index.
+    fn unstage(&self, root: &path::Path, file: &path::Path) -> io::Result<()>;
+    /// Applies `hunk` to the index, or takes it out of the index when
+    /// `reverse` is set.
+    fn apply_to_index(&self, root: &path::Path, hunk: &PatchHunk, reverse: bool) -> io::Result<()>;
+    /// Commits the index with `message`, returning the new commit's
+    /// abbreviated hash.
+    fn commit(&self, root: &path::Path, message: &str) -> io::Result<String>;
+}
@@ -1,7 +1,21 @@
+pub mod buffer_list;
+pub mod completion;
 pub mod config;
 pub mod cursor;
 pub mod editor;
 pub mod editor_buffer;
 pub mod editor_rows;
+pub mod extension;
+pub mod file_explorer;
+pub mod file_finder;
+pub mod formatter;
+pub mod git;
+pub mod overlay;
+pub mod project_search;
+pub mod rpc;
+pub mod script_engine;
+pub mod spell_checker;
 pub mod status_message;
 pub mod terminal_io;
+pub mod undo;
+pub mod window;
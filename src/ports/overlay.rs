@@ -0,0 +1,49 @@
+use crate::ports::window::Rect;
+
+/// The floating windows drawn over the editor windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayKind {
+    /// What the language server knows of the symbol under the cursor.
+    Hover,
+    /// The completion items at the cursor.
+    Completion,
+    /// The keys that may follow a pending prefix key, with what they do.
+    KeyHints,
+    GitPanel,
+    Picker,
+}
+
+impl OverlayKind {
+    /// Returns whether the overlay takes the keys while it is the topmost
+    /// one that does, rather than leaving them to the mode.
+    pub fn takes_keys(self) -> bool {
+        !matches!(self, OverlayKind::Hover | OverlayKind::KeyHints)
+    }
+}
+
+/// A floating window, and where it was last drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlay {
+    pub kind: OverlayKind,
+    /// The cells it covers, border included. Empty until it is drawn, or
+    /// when there was no room for it.
+    pub rect: Rect,
+}
+
+/// The floating windows open, in z-order. Those higher up are drawn over
+/// those below, and get the keys and the mouse first.
+pub trait OverlayStackPort {
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// Puts `kind` on top of the others, unless it is open already.
+    fn open(&mut self, kind: OverlayKind);
+    fn close(&mut self, kind: OverlayKind);
+    /// Returns the open overlays, the bottom one first.
+    fn overlays(&self) -> &[Overlay];
+    fn set_rect(&mut self, kind: OverlayKind, rect: Rect);
+    /// Returns the topmost overlay taking keys.
+    fn focused(&self) -> Option<OverlayKind>;
+    /// Returns the topmost overlay covering screen cell `(x, y)`.
+    fn at(&self, x: usize, y: usize) -> Option<OverlayKind>;
+}
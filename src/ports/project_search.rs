@@ -0,0 +1,37 @@
+use std::{io, path};
+
+/// A line of a file matching a project search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub path: path::PathBuf,
+    /// The path relative to the search's root.
+    pub label: String,
+    /// The row of the line in the file, counting from zero.
+    pub line: usize,
+    /// The byte range of the first match in the line.
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// How the pattern of a project search is matched.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    pub ignore_case: bool,
+    /// Treats the pattern as a regular expression rather than plain text.
+    pub regex: bool,
+    pub show_hidden: bool,
+}
+
+/// A search through the files below a directory, running in the background.
+/// Dropping it stops the search.
+pub trait ProjectSearchPort {
+    /// Starts searching for `pattern` in the files below `root`, skipping
+    /// those ignored by git.
+    fn start(root: path::PathBuf, pattern: &str, options: SearchOptions) -> io::Result<Self>
+    where
+        Self: Sized;
+    /// Returns the matches found since the last call.
+    fn poll(&mut self) -> Vec<SearchMatch>;
+    fn is_done(&self) -> bool;
+}
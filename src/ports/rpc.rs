@@ -0,0 +1,61 @@
+use serde_json::Value;
+use std::{io, path};
+
+/// The error codes of JSON-RPC that barn answers with.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// The error a request is answered with when it fails.
+#[derive(Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A message received from the other end of a connection.
+#[derive(Debug, Clone)]
+pub enum RpcMessage {
+    /// A call that has to be answered, with the same `id`.
+    Request {
+        id: Value,
+        method: String,
+        params: Value,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
+    /// The answer to one of our requests, by the id `request` returned.
+    Response {
+        id: u64,
+        result: Result<Value, RpcError>,
+    },
+}
+
+/// A JSON-RPC connection to a program running next to the editor, over
+/// its standard input and output.
+pub trait RpcPort {
+    /// Starts `program` with `args` in `directory`.
+    fn spawn(program: &str, args: &[String], directory: &path::Path) -> io::Result<Self>
+    where
+        Self: Sized;
+    /// Sends a request, returning the id its response comes with.
+    fn request(&mut self, method: &str, params: Value) -> io::Result<u64>;
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()>;
+    /// Answers the request of the program with id `id`.
+    fn respond(&mut self, id: Value, result: Result<Value, RpcError>) -> io::Result<()>;
+    /// Returns the messages received since the last call, without waiting
+    /// for more.
+    fn receive(&mut self) -> Vec<RpcMessage>;
+    /// Returns whether the program is still running.
+    fn is_running(&mut self) -> bool;
+}
@@ -0,0 +1,53 @@
+use crate::ports::config::ScriptingConfig;
+use std::{io, path};
+
+/// What a script sees of the editor while it runs, and what it changes.
+/// Scripts work on a copy of the buffer; the editor applies their changes
+/// once they are done.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptContext {
+    pub buffer_id: usize,
+    pub file_name: Option<String>,
+    pub rows: Vec<String>,
+    /// The byte column and row of the cursor.
+    pub cursor: (usize, usize),
+    /// Whether the script changed `rows`.
+    pub edited: bool,
+    /// Whether the script moved `cursor`.
+    pub moved: bool,
+    /// Messages to show, in order.
+    pub messages: Vec<String>,
+    /// Command lines to run, as if typed after `:`.
+    pub commands: Vec<String>,
+}
+
+/// Runs the user's scripts, which define commands, key bindings and event
+/// handlers.
+pub trait ScriptEnginePort {
+    fn new(config: &ScriptingConfig) -> Self
+    where
+        Self: Sized;
+    /// Runs the init script `file`, keeping what it defines.
+    fn load(&mut self, file: &path::Path) -> io::Result<()>;
+    /// Returns the names of the commands the scripts defined.
+    fn commands(&self) -> Vec<String>;
+    /// Runs command `name`, if a script defined it.
+    fn run_command(
+        &mut self,
+        context: &mut ScriptContext,
+        name: &str,
+        argument: Option<&str>,
+    ) -> Option<io::Result<()>>;
+    /// Returns the keys functions are bound to in `mode`, named as in `<C-t>`.
+    fn key_bindings(&self, mode: &str) -> Vec<String>;
+    /// Runs the function bound to `key`, named as in `<C-t>`, in `mode`
+    /// (`normal` or `insert`), if there is one.
+    fn run_key_binding(
+        &mut self,
+        context: &mut ScriptContext,
+        mode: &str,
+        key: &str,
+    ) -> Option<io::Result<()>>;
+    /// Runs the handlers of `event`: `open`, `change` or `save`.
+    fn emit(&mut self, context: &mut ScriptContext, event: &str) -> io::Result<()>;
+}
@@ -0,0 +1,17 @@
+use crate::ports::config::SpellCheckerConfig;
+use std::io;
+
+/// A dictionary to check the spelling of words against.
+pub trait SpellCheckerPort {
+    /// Loads the dictionary of the configured language, along with the
+    /// configured extra words and the words added by the user before.
+    fn new(config: &SpellCheckerConfig) -> io::Result<Self>
+    where
+        Self: Sized;
+    /// Returns whether `word` is spelled correctly.
+    fn check(&self, word: &str) -> bool;
+    /// Returns up to `limit` known words close to `word`, closest first.
+    fn suggest(&self, word: &str, limit: usize) -> Vec<String>;
+    /// Accepts `word` from now on, remembering it for the next sessions.
+    fn add_word(&mut self, word: &str) -> io::Result<()>;
+}
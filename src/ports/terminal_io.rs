@@ -3,6 +3,7 @@ use crossterm::{event, terminal};
 use std::io;
 use std::time;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum EventReaderError {
     PollError(io::Error),
@@ -20,6 +21,15 @@ impl std::fmt::Display for EventReaderError {
 
 impl std::error::Error for EventReaderError {}
 
+impl From<EventReaderError> for io::Error {
+    fn from(err: EventReaderError) -> Self {
+        match err {
+            EventReaderError::PollError(e) | EventReaderError::ReadError(e) => e,
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum WriterError {
     CursorOperationError(io::Error),
@@ -54,7 +64,11 @@ pub trait EventReader {
 }
 
 pub trait ReaderPort {
-    fn read_key(&self) -> Result<event::KeyEvent, EventReaderError>;
+    /// Waits for the next key, mouse or resize event.
+    fn read_event(&self) -> Result<event::Event, EventReaderError>;
+    /// Waits at most `timeout` for the next key, mouse or resize event.
+    fn poll_event(&self, timeout: time::Duration)
+        -> Result<Option<event::Event>, EventReaderError>;
 }
 
 pub trait WriterPort {
@@ -0,0 +1,22 @@
+/// The state of a buffer at a point in time, as stored in its undo history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub rows: Vec<String>,
+    pub cursor: (usize, usize),
+}
+
+pub trait UndoHistoryPort {
+    fn new(limit: usize) -> Self
+    where
+        Self: Sized;
+    /// Stores the state from before a change and opens a change group. Edits
+    /// made while the group is open are undone together.
+    fn record(&mut self, snapshot: Snapshot);
+    fn in_change(&self) -> bool;
+    /// Closes the current change group, so the next edit starts a new one.
+    fn end_change(&mut self);
+    /// Steps back one change, returning the state to restore. `current` is
+    /// kept so the change can be redone.
+    fn undo(&mut self, current: Snapshot) -> Option<Snapshot>;
+    fn redo(&mut self, current: Snapshot) -> Option<Snapshot>;
+}
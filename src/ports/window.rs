@@ -0,0 +1,92 @@
+use crate::ports::cursor::CursorControllerPort;
+
+/// A rectangular area of the screen, in terminal cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// How a window is divided when it is split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// The windows are stacked on top of each other (`:split`).
+    Horizontal,
+    /// The windows are placed side by side (`:vsplit`).
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusDirection {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+/// A viewport into a buffer. Several windows may show the same buffer, each
+/// with its own cursor and scroll offsets.
+pub struct Window {
+    pub id: usize,
+    pub buffer_id: usize,
+    pub cursor_controller: Box<dyn CursorControllerPort>,
+    /// The other end of the selection, which extends from here to the
+    /// cursor (both included), as (byte offset, row) in the buffer.
+    pub selection_anchor: Option<(usize, usize)>,
+}
+
+impl Window {
+    /// Returns the selected range as its first and last position, in order.
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_controller.get_file_position();
+        // Positions are ordered by row first, so compare them as (y, x)
+        if (anchor.1, anchor.0) <= (cursor.1, cursor.0) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+}
+
+/// The result of laying out the window tree on the screen.
+pub struct WindowPlacement {
+    /// Every window id with the area it covers, including its status line.
+    pub windows: Vec<(usize, Rect)>,
+    /// The columns drawn between side by side windows.
+    pub separators: Vec<Rect>,
+}
+
+pub trait WindowLayoutPort {
+    fn new(root: Window) -> Self
+    where
+        Self: Sized;
+    /// Splits the focused window in two. The new window takes the top (or
+    /// left) half and receives focus.
+    fn split(&mut self, direction: SplitDirection, window: Window);
+    /// Closes the focused window, unless it is the last one.
+    fn close(&mut self) -> Option<Window>;
+    /// Closes every window except the focused one.
+    fn only(&mut self) -> Vec<Window>;
+    /// Moves focus to the nearest window in `direction` within `area`.
+    fn focus(&mut self, direction: FocusDirection, area: Rect) -> bool;
+    fn focus_next(&mut self);
+    /// Focuses the window with the given id, if it is in the layout.
+    fn focus_id(&mut self, id: usize) -> bool;
+    /// Grows (or shrinks, for negative `delta`) the focused window by `delta`
+    /// cells along `direction`.
+    fn resize(&mut self, direction: SplitDirection, delta: isize, area: Rect);
+    fn place(&self, area: Rect) -> WindowPlacement;
+    fn focused(&self) -> &Window;
+    fn focused_mut(&mut self) -> &mut Window;
+    fn windows(&self) -> Vec<&Window>;
+    fn windows_mut(&mut self) -> Vec<&mut Window>;
+}
@@ -0,0 +1,42 @@
+//! `date.rs`
+//!
+//! Calendar dates from Unix timestamps, for showing when commits were made
+//! without pulling in a date library.
+
+use std::time;
+
+/// Returns the (year, month, day) of the day `days` after 1970-01-01, in
+/// the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Count from 0000-03-01 so the leap day falls at the end of each year
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns today's date in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let now = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    format_date(now, 0)
+}
+
+/// Formats the date of `timestamp`, in seconds since the Unix epoch, as
+/// `YYYY-MM-DD`. `offset` is the timezone's distance from UTC in seconds.
+pub fn format_date(timestamp: i64, offset: i64) -> String {
+    let (year, month, day) = civil_from_days((timestamp + offset).div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,208 @@
+//! `diff.rs`
+//!
+//! Line based diffing, used to compare a buffer with the version of its
+//! file in git. The differences are found with Myers' algorithm and grouped
+//! into hunks of consecutive changed lines.
+
+use std::cmp;
+
+/// Above this many edits the remaining lines are reported as one modified
+/// hunk, as the algorithm's memory use grows with the square of the edits.
+const MAX_EDITS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkKind {
+    Added,
+    Modified,
+    /// Lines removed before row `start` of the new text.
+    Deleted,
+}
+
+/// A run of changed lines, given as rows of the new text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    pub kind: HunkKind,
+    pub start: usize,
+    /// The number of rows of the new text in the hunk; zero when deleted.
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Finds the shortest edit script turning `old` into `new`, or `None` if it
+/// takes more than `MAX_EDITS` edits.
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = cmp::min((n + m) as usize, MAX_EDITS) as isize;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // The furthest reaching paths before each round, for walking back
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        // `v` holds diagonals -d..=d, shifted to start at zero
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = if d == 0 { 0 } else { at(previous_k) };
+        let previous_y = previous_x - previous_k;
+
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == previous_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    edits.reverse();
+    edits
+}
+
+/// Compares the lines of `old` and `new`, returning the hunks of `new` that
+/// differ from `old`.
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    // Leave out the lines both ends have in common, which is most of them
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    let edits = match myers(old, new) {
+        Some(edits) => edits,
+        None => {
+            // Too different to diff line by line
+            let mut edits = vec![Edit::Delete; old.len()];
+            edits.extend(vec![Edit::Insert; new.len()]);
+            edits
+        }
+    };
+
+    let mut hunks = Vec::new();
+    let mut row = prefix;
+    let mut edits = edits.into_iter().peekable();
+    while let Some(edit) = edits.next() {
+        if edit == Edit::Equal {
+            row += 1;
+            continue;
+        }
+        // Gather the run of changes this edit starts
+        let (mut deleted, mut inserted) = (0, 0);
+        let mut next = Some(edit);
+        while let Some(edit @ (Edit::Delete | Edit::Insert)) = next {
+            if edit == Edit::Delete {
+                deleted += 1;
+            } else {
+                inserted += 1;
+            }
+            next = edits.next_if(|edit| *edit != Edit::Equal);
+        }
+        let kind = match (deleted, inserted) {
+            (0, _) => HunkKind::Added,
+            (_, 0) => HunkKind::Deleted,
+            _ => HunkKind::Modified,
+        };
+        hunks.push(Hunk {
+            kind,
+            start: row,
+            count: inserted,
+        });
+        row += inserted;
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(kind: HunkKind, start: usize, count: usize) -> Hunk {
+        Hunk { kind, start, count }
+    }
+
+    #[test]
+    fn finds_nothing_in_equal_lines() {
+        assert_eq!(diff_lines(&["a", "b"], &["a", "b"]), []);
+        assert_eq!(diff_lines(&[], &[]), []);
+    }
+
+    #[test]
+    fn finds_added_modified_and_deleted_lines() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "x", "c", "e", "f", "g"];
+        assert_eq!(
+            diff_lines(&old, &new),
+            [
+                hunk(HunkKind::Modified, 1, 1),
+                hunk(HunkKind::Deleted, 3, 0),
+                hunk(HunkKind::Added, 4, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_lines_added_to_or_removed_from_an_empty_text() {
+        assert_eq!(diff_lines(&[], &["a", "b"]), [hunk(HunkKind::Added, 0, 2)]);
+        assert_eq!(
+            diff_lines(&["a", "b"], &[]),
+            [hunk(HunkKind::Deleted, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn reports_texts_too_different_as_one_hunk() {
+        let old: Vec<String> = (0..2000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..2000).map(|i| format!("new {}", i)).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        assert_eq!(diff_lines(&old, &new), [hunk(HunkKind::Modified, 0, 2000)]);
+    }
+}
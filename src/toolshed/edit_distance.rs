@@ -0,0 +1,62 @@
+//! `edit_distance.rs`
+//!
+//! How far apart two words are, for suggesting corrections to misspelled
+//! ones.
+
+/// Returns the number of single char insertions, deletions, substitutions
+/// and swaps of neighbouring chars it takes to turn `a` into `b` (the
+/// optimal string alignment distance).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows of the distance table are enough: swaps look two rows back
+    let mut two_back = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(two_back[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut two_back, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_insertions_deletions_and_substitutions() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("word", "word"), 0);
+        assert_eq!(edit_distance("", "word"), 4);
+        assert_eq!(edit_distance("word", "wrd"), 1);
+        assert_eq!(edit_distance("word", "sword"), 1);
+        assert_eq!(edit_distance("word", "ward"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn counts_a_swap_of_neighbours_as_one_edit() {
+        assert_eq!(edit_distance("teh", "the"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        // Chars swapped are not edited again
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn counts_chars_rather_than_bytes() {
+        assert_eq!(edit_distance("café", "cafe"), 1);
+        assert_eq!(edit_distance("naïve", "naive"), 1);
+    }
+}
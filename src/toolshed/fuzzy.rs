@@ -0,0 +1,150 @@
+//! `fuzzy.rs`
+//!
+//! Subsequence matching for the fuzzy finder. Every character of the
+//! pattern has to appear in the candidate, in order and ignoring case.
+//! Matches score higher when they are consecutive, start a word or fall in
+//! the file name, and lower for every character skipped between them.
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 24;
+/// A match right after a separator such as `/`, `_` or `.`.
+const BONUS_WORD_START: i64 = 20;
+/// A match on an uppercase letter following a lowercase one.
+const BONUS_CAMEL_CASE: i64 = 12;
+/// A match in the last component of a path.
+const BONUS_FILE_NAME: i64 = 4;
+const PENALTY_GAP: i64 = 1;
+
+const NONE: i64 = i64::MIN / 2;
+
+fn bonus(candidate: &[char], index: usize, file_name_start: usize) -> i64 {
+    let position = match index.checked_sub(1).map(|previous| candidate[previous]) {
+        None => BONUS_WORD_START,
+        Some('/' | '\\' | '_' | '-' | '.' | ' ') => BONUS_WORD_START,
+        Some(previous) if previous.is_lowercase() && candidate[index].is_uppercase() => {
+            BONUS_CAMEL_CASE
+        }
+        Some(_) => 0,
+    };
+    let file_name = if index >= file_name_start {
+        BONUS_FILE_NAME
+    } else {
+        0
+    };
+    position + file_name
+}
+
+fn same(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Scores `candidate` against `pattern`, returning the score and the char
+/// indices of the matched characters, or `None` if it does not match.
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    let (n, m) = (pattern.len(), candidate.len());
+    if n == 0 {
+        return Some((0, Vec::new()));
+    }
+
+    // Rule out candidates that do not contain the pattern before scoring
+    let mut rest = candidate.iter();
+    if !pattern.iter().all(|&p| rest.any(|&c| same(p, c))) {
+        return None;
+    }
+
+    let file_name_start = candidate
+        .iter()
+        .rposition(|&c| c == '/' || c == '\\')
+        .map_or(0, |index| index + 1);
+    let bonuses: Vec<i64> = (0..m)
+        .map(|j| bonus(&candidate, j, file_name_start))
+        .collect();
+
+    // ends[i][j]: best score for pattern[..=i] with pattern[i] matched at j.
+    // best[i][j]: best score for pattern[..=i] within candidate[..=j].
+    let mut ends = vec![vec![NONE; m]; n];
+    let mut best = vec![vec![NONE; m]; n];
+    for i in 0..n {
+        for j in i..m {
+            if same(pattern[i], candidate[j]) {
+                ends[i][j] = if i == 0 {
+                    SCORE_MATCH + bonuses[j]
+                } else if j > 0 {
+                    let after_gap = best[i - 1][j - 1] + SCORE_MATCH + bonuses[j];
+                    let consecutive =
+                        ends[i - 1][j - 1] + SCORE_MATCH + bonuses[j].max(BONUS_CONSECUTIVE);
+                    after_gap.max(consecutive)
+                } else {
+                    NONE
+                };
+            }
+            let skipped = if j > 0 {
+                best[i][j - 1] - PENALTY_GAP
+            } else {
+                NONE
+            };
+            best[i][j] = ends[i][j].max(skipped);
+        }
+    }
+
+    // Trailing characters are not penalized, so pick the best place for the
+    // last match and walk back from there
+    let (mut j, score) = (0..m)
+        .map(|j| (j, ends[n - 1][j]))
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+    if score <= NONE / 2 {
+        return None;
+    }
+    let mut positions = vec![0; n];
+    for i in (0..n).rev() {
+        positions[i] = j;
+        if i == 0 {
+            break;
+        }
+        let consecutive = ends[i - 1][j - 1] + SCORE_MATCH + bonuses[j].max(BONUS_CONSECUTIVE);
+        if ends[i][j] == consecutive {
+            j -= 1;
+            continue;
+        }
+        // Find where the previous character matched before the gap
+        let target = best[i - 1][j - 1];
+        j = (i - 1..j)
+            .rev()
+            .find(|&k| ends[i - 1][k] - PENALTY_GAP * (j - 1 - k) as i64 == target)?;
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, candidate: &str) -> i64 {
+        fuzzy_match(pattern, candidate).unwrap().0
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert_eq!(fuzzy_match("", "main.rs"), Some((0, Vec::new())));
+        assert_eq!(fuzzy_match("MRS", "main.rs").unwrap().1, [0, 5, 6]);
+        assert_eq!(fuzzy_match("sr", "rs"), None);
+        assert_eq!(fuzzy_match("mainx", "main.rs"), None);
+    }
+
+    #[test]
+    fn prefers_consecutive_matches() {
+        assert!(score("edit", "src/editor.rs") > score("edit", "src/e_d_i_t.rs"));
+        assert_eq!(
+            fuzzy_match("edit", "src/editor.rs").unwrap().1,
+            [4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn prefers_matches_in_the_file_name() {
+        assert!(score("app", "src/app.rs") > score("app", "app/src/lib.rs"));
+        assert!(score("fb", "src/FooBar.rs") > score("fb", "src/foobar.rs"));
+    }
+}
@@ -0,0 +1,147 @@
+//! `keys.rs`
+//!
+//! Vim-style names of keys, such as `x`, `<C-t>`, `<A-Up>` or `<F5>`, as
+//! scripts bind them. Modifiers come first, as `C-` (control), `A-` (alt)
+//! and `S-` (shift), which is only written for keys that are not chars.
+//! `config.toml` may spell a key out instead, such as `ctrl-t`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// The keys that go by a name rather than by the char they type.
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("CR", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+];
+
+/// Returns the name of `key`, or `None` for keys that have none.
+pub fn key_name(key: &KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::F(number) => format!("F{}", number),
+        KeyCode::Char(ch) if !matches!(ch, ' ' | '<') => {
+            // Control chars are named in lowercase, as terminals do not tell them apart
+            match key.modifiers.contains(KeyModifiers::CONTROL) {
+                true => ch.to_ascii_lowercase().to_string(),
+                false => ch.to_string(),
+            }
+        }
+        code => NAMED_KEYS
+            .iter()
+            .find(|(_, named)| *named == code)?
+            .0
+            .to_string(),
+    };
+
+    let mut modifiers = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        modifiers.push_str("C-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        modifiers.push_str("A-");
+    }
+    // Shift is part of the char typed
+    if key.modifiers.contains(KeyModifiers::SHIFT) && !matches!(key.code, KeyCode::Char(_)) {
+        modifiers.push_str("S-");
+    }
+    Some(match (modifiers.is_empty(), key.code) {
+        (true, KeyCode::Char(_)) if name.chars().count() == 1 => name,
+        _ => format!("<{}{}>", modifiers, name),
+    })
+}
+
+/// Reads a sequence of keys written one after the other, such as `gd` or
+/// `<C-w>s`.
+pub fn parse_keys(sequence: &str) -> Option<Vec<KeyEvent>> {
+    let mut keys = Vec::new();
+    let mut rest = sequence;
+    while let Some(ch) = rest.chars().next() {
+        // A `<` that does not start a name stands for itself
+        let name = match ch == '<' {
+            true => match rest.find('>') {
+                Some(end) if end > 1 => &rest[..=end],
+                _ => "<",
+            },
+            false => &rest[..ch.len_utf8()],
+        };
+        keys.push(parse_key(name)?);
+        rest = &rest[name.len()..];
+    }
+    Some(keys)
+}
+
+/// Reads keys as `config.toml` writes them: like `parse_keys`, or as a
+/// single key spelled out, such as `ctrl-u`, `alt-left` or `esc`.
+pub fn parse_config_keys(text: &str) -> Option<Vec<KeyEvent>> {
+    let mut words: Vec<&str> = text.split('-').collect();
+    let name = words.pop().unwrap_or_default();
+    let modifiers: Option<String> = words
+        .iter()
+        .map(|word| match word.to_ascii_lowercase().as_str() {
+            "ctrl" | "c" => Some("C-"),
+            "alt" | "a" | "m" => Some("A-"),
+            "shift" | "s" => Some("S-"),
+            _ => None,
+        })
+        .collect();
+    // A single char with no modifier is read the same either way
+    let spelled = match (modifiers, name.chars().count()) {
+        (_, 0) => None,
+        (Some(modifiers), count) if count > 1 || !modifiers.is_empty() => {
+            parse_key(&format!("<{}{}>", modifiers, name))
+        }
+        _ => None,
+    };
+    match spelled {
+        Some(key) => Some(vec![key]),
+        None => parse_keys(text),
+    }
+}
+
+/// Reads a key name, which may be written in any case but for chars.
+pub fn parse_key(name: &str) -> Option<KeyEvent> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+    }
+
+    let mut rest = name.strip_prefix('<')?.strip_suffix('>')?;
+    let mut modifiers = KeyModifiers::NONE;
+    while let Some((modifier, after)) = rest.split_once('-').filter(|(_, after)| !after.is_empty())
+    {
+        modifiers |= match modifier.to_ascii_uppercase().as_str() {
+            "C" => KeyModifiers::CONTROL,
+            "A" | "M" => KeyModifiers::ALT,
+            "S" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        rest = after;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(ch), None) => KeyCode::Char(ch),
+        _ => match NAMED_KEYS
+            .iter()
+            .find(|(named, _)| named.eq_ignore_ascii_case(rest))
+        {
+            Some((_, code)) => *code,
+            None => {
+                let number = rest.strip_prefix(['F', 'f'])?.parse().ok()?;
+                KeyCode::F(number)
+            }
+        },
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
@@ -59,7 +59,6 @@ impl Logger {
 
         let file = OpenOptions::new()
             .create(true)
-            .write(true)
             .append(true)
             .open(file_path)?;
 
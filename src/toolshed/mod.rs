@@ -1 +1,9 @@
+pub mod date;
+pub mod diff;
+pub mod edit_distance;
+pub mod fuzzy;
+pub mod keys;
 pub mod logger;
+pub mod prose;
+pub mod schema;
+pub mod uri;
@@ -0,0 +1,189 @@
+//! `prose.rs`
+//!
+//! Finds the words written for people in a file, which are the ones worth
+//! spell checking: all of a text file, but only the comments and string
+//! literals of source code.
+
+use std::ops::Range;
+use std::path;
+
+/// How comments are written in a programming language.
+#[derive(Debug, Clone, Copy)]
+pub struct CommentSyntax {
+    pub line: &'static [&'static str],
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+/// What kind of text a file holds.
+#[derive(Debug, Clone, Copy)]
+pub enum TextKind {
+    /// Text meant to be read as a whole, such as Markdown or a commit message.
+    Prose,
+    Code(CommentSyntax),
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: Some(("/*", "*/")),
+};
+const HASH: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: None,
+};
+const DASHES: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: None,
+};
+const SEMICOLON: CommentSyntax = CommentSyntax {
+    line: &[";"],
+    block: None,
+};
+const MARKUP: CommentSyntax = CommentSyntax {
+    line: &[],
+    block: Some(("<!--", "-->")),
+};
+
+/// Guesses what kind of text `file` holds from its name, or returns `None`
+/// if it is not known to hold any prose.
+pub fn text_kind(file: Option<&path::Path>) -> Option<TextKind> {
+    let Some(file) = file else {
+        // New buffers are usually notes
+        return Some(TextKind::Prose);
+    };
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let kind = match extension {
+        "" | "md" | "markdown" | "txt" | "text" | "rst" | "adoc" | "tex" => TextKind::Prose,
+        "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "js" | "jsx" | "ts" | "tsx" | "go"
+        | "swift" | "kt" | "scala" | "cs" | "css" | "scss" | "dart" | "zig" => {
+            TextKind::Code(C_LIKE)
+        }
+        "py" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "r" | "toml" | "yaml" | "yml"
+        | "conf" | "cfg" | "ini" | "mk" | "cmake" => TextKind::Code(HASH),
+        "lua" | "sql" | "hs" | "elm" => TextKind::Code(DASHES),
+        "el" | "lisp" | "clj" | "scm" | "asm" => TextKind::Code(SEMICOLON),
+        "html" | "htm" | "xml" | "svg" | "vue" => TextKind::Code(MARKUP),
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Walks through a file row by row, remembering whether a block comment is
+/// still open at the end of a row.
+pub struct ProseScanner {
+    kind: TextKind,
+    in_block_comment: bool,
+}
+
+impl ProseScanner {
+    pub fn new(kind: TextKind) -> Self {
+        Self {
+            kind,
+            in_block_comment: false,
+        }
+    }
+
+    /// Returns the byte ranges of `row` that hold prose. Rows have to be
+    /// scanned in order, from the first one.
+    pub fn scan_row(&mut self, row: &str) -> Vec<Range<usize>> {
+        let TextKind::Code(syntax) = self.kind else {
+            return std::iter::once(0..row.len()).collect();
+        };
+
+        let mut ranges = Vec::new();
+        let mut at = 0;
+        while at < row.len() {
+            let rest = &row[at..];
+            if self.in_block_comment {
+                let (_, end) = syntax.block.unwrap_or_default();
+                match rest.find(end) {
+                    Some(found) => {
+                        ranges.push(at..at + found);
+                        at += found + end.len();
+                        self.in_block_comment = false;
+                    }
+                    None => {
+                        ranges.push(at..row.len());
+                        break;
+                    }
+                }
+            } else if let Some(start) = syntax.line.iter().find(|start| rest.starts_with(**start)) {
+                ranges.push(at + start.len()..row.len());
+                break;
+            } else if let Some((start, _)) =
+                syntax.block.filter(|(start, _)| rest.starts_with(start))
+            {
+                at += start.len();
+                self.in_block_comment = true;
+            } else if let Some(string) = rest.strip_prefix('"') {
+                // A string ends at the next quote that is not escaped
+                let mut escaped = false;
+                let end = string.find(|ch| {
+                    let closes = ch == '"' && !escaped;
+                    escaped = ch == '\\' && !escaped;
+                    closes
+                });
+                match end {
+                    Some(end) => {
+                        ranges.push(at + 1..at + 1 + end);
+                        at += end + 2;
+                    }
+                    None => {
+                        ranges.push(at + 1..row.len());
+                        break;
+                    }
+                }
+            } else {
+                at += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        ranges
+    }
+}
+
+/// Returns the byte ranges of the words in `range` of `text` worth
+/// checking. Identifiers, acronyms, numbers, paths and escape sequences are
+/// left out.
+pub fn words(text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let is_word_char = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '\'';
+    let mut words = Vec::new();
+    let mut chars = text[range.clone()].char_indices().peekable();
+    while let Some((offset, ch)) = chars.next() {
+        // Skip `\n` and the like as a whole
+        if ch == '\\' {
+            chars.next();
+            continue;
+        }
+        if !is_word_char(ch) {
+            continue;
+        }
+        let start = range.start + offset;
+        let mut end = start + ch.len_utf8();
+        while let Some((offset, ch)) = chars.next_if(|(_, ch)| is_word_char(*ch)) {
+            end = range.start + offset + ch.len_utf8();
+        }
+
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        let word = &text[start..end];
+        let trimmed = word.trim_matches('\'');
+        let start = start + (word.len() - word.trim_start_matches('\'').len());
+        let end = start + trimmed.len();
+
+        let in_path = matches!(before, Some('/' | '.' | ':' | '@'))
+            || matches!(after, Some('/' | '@' | ':' | '('))
+            || (after == Some('.')
+                && text[end..]
+                    .chars()
+                    .nth(1)
+                    .is_some_and(char::is_alphanumeric));
+        let is_identifier = trimmed.chars().any(|ch| ch.is_numeric() || ch == '_')
+            || trimmed.chars().skip(1).any(char::is_uppercase);
+        if trimmed.chars().count() > 1 && !in_path && !is_identifier {
+            words.push(start..end);
+        }
+    }
+    words
+}
@@ -0,0 +1,375 @@
+//! `schema.rs`
+//!
+//! The fields a type reads through serde, found by deserializing it from a
+//! deserializer that makes up a value for every field it is asked for.
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+
+/// A field read by a type, or by a struct it holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// The names leading to the field, joined with `.`, such as `sources`
+    /// or `general.mouse`.
+    pub path: String,
+    /// The struct the field belongs to, such as `CompletionConfig`.
+    pub owner: &'static str,
+    /// What the field holds: `boolean`, `integer`, `float`, `character`,
+    /// `string`, `list`, `table` or `value`.
+    pub kind: &'static str,
+    /// Whether the field has no default, and has to be given.
+    pub required: bool,
+}
+
+/// Returns the fields `T` reads, in the order it reads them, those of the
+/// structs it holds following their own.
+pub fn fields<'de, T: Deserialize<'de>>() -> Vec<Field> {
+    let mut fields = Vec::new();
+    // The value made up is thrown away, only the fields asked for matter
+    let _ = T::deserialize(Probe {
+        fields: &mut fields,
+        path: String::new(),
+        owner: "",
+        given: None,
+    });
+    let required = required::<T>();
+    for field in &mut fields {
+        field.required = required.contains(&field.path);
+    }
+    fields
+}
+
+/// Returns `T` as read when every field with a default is left out, the
+/// others made up, or `None` if it cannot be read at all.
+pub fn defaults<'de, T: Deserialize<'de>>() -> Option<T> {
+    T::deserialize(Probe {
+        fields: &mut Vec::new(),
+        path: String::new(),
+        owner: "",
+        given: Some(&required::<T>()),
+    })
+    .ok()
+}
+
+/// Returns the paths of the fields of `T` that have no default.
+fn required<'de, T: Deserialize<'de>>() -> HashSet<String> {
+    let mut given = HashSet::new();
+    // Every pass leaves out the fields not known to be required, the first
+    // of the others missed being the next one found
+    loop {
+        let read = T::deserialize(Probe {
+            fields: &mut Vec::new(),
+            path: String::new(),
+            owner: "",
+            given: Some(&given),
+        });
+        match read {
+            Err(Stop::MissingAt(path)) if given.insert(path.clone()) => {}
+            _ => return given,
+        }
+    }
+}
+
+/// Why the made-up value could not be read.
+#[derive(Debug)]
+enum Stop {
+    Failed(String),
+    /// A field left out has no default, named by the struct missing it.
+    Missing(&'static str),
+    /// The same, once the path of the struct is added.
+    MissingAt(String),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Failed(message) => f.write_str(message),
+            Stop::Missing(name) => write!(f, "missing field `{}`", name),
+            Stop::MissingAt(path) => write!(f, "missing field `{}`", path),
+        }
+    }
+}
+
+impl std::error::Error for Stop {}
+
+impl de::Error for Stop {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Stop::Failed(message.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Stop::Missing(field)
+    }
+}
+
+/// Returns the path of field `name` of the struct at `path`.
+fn join(path: &str, name: &str) -> String {
+    match path.is_empty() {
+        true => name.to_string(),
+        false => format!("{}.{}", path, name),
+    }
+}
+
+/// Deserializes the field at `path` of struct `owner`, recording it. When
+/// `given` is set, structs are only handed the fields it holds the path of.
+struct Probe<'a> {
+    fields: &'a mut Vec<Field>,
+    path: String,
+    owner: &'static str,
+    given: Option<&'a HashSet<String>>,
+}
+
+impl Probe<'_> {
+    fn record(&mut self, kind: &'static str) {
+        // The type itself is not a field
+        if !self.path.is_empty() {
+            self.fields.push(Field {
+                path: self.path.clone(),
+                owner: self.owner,
+                kind,
+                required: false,
+            });
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Probe<'_> {
+    type Error = Stop;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("value");
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("boolean");
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("integer");
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("integer");
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("float");
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("character");
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("string");
+        visitor.visit_str("")
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Stop> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Stop> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("list");
+        visitor.visit_seq(Empty)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Stop> {
+        self.record("table");
+        visitor.visit_map(Empty)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Stop> {
+        self.record("table");
+        let path = self.path.clone();
+        let read = visitor.visit_map(StructFields {
+            fields: self.fields,
+            path: self.path,
+            owner: name,
+            names: fields.iter(),
+            current: "",
+            given: self.given,
+        });
+        read.map_err(|stop| match stop {
+            Stop::Missing(name) => Stop::MissingAt(join(&path, name)),
+            stop => stop,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+/// Hands a struct every field it has, each with a made-up value.
+struct StructFields<'a> {
+    fields: &'a mut Vec<Field>,
+    path: String,
+    owner: &'static str,
+    names: std::slice::Iter<'static, &'static str>,
+    current: &'static str,
+    given: Option<&'a HashSet<String>>,
+}
+
+impl<'de> MapAccess<'de> for StructFields<'_> {
+    type Error = Stop;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Stop> {
+        let is_given = |name: &&&str| {
+            self.given
+                .is_none_or(|given| given.contains(&join(&self.path, name)))
+        };
+        let Some(name) = self.names.find(is_given) else {
+            return Ok(None);
+        };
+        self.current = name;
+        seed.deserialize(IntoDeserializer::<Stop>::into_deserializer(*name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Stop> {
+        seed.deserialize(Probe {
+            fields: self.fields,
+            path: join(&self.path, self.current),
+            owner: self.owner,
+            given: self.given,
+        })
+    }
+}
+
+/// An empty list or table.
+struct Empty;
+
+impl<'de> SeqAccess<'de> for Empty {
+    type Error = Stop;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        _seed: T,
+    ) -> Result<Option<T::Value>, Stop> {
+        Ok(None)
+    }
+}
+
+impl<'de> MapAccess<'de> for Empty {
+    type Error = Stop;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        _seed: K,
+    ) -> Result<Option<K::Value>, Stop> {
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, _seed: V) -> Result<V::Value, Stop> {
+        Err(Stop::Failed("no value".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Inner {
+        name: String,
+        #[serde(default)]
+        sizes: Vec<u8>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Outer {
+        #[serde(default = "default_limit")]
+        limit: u32,
+        inner: Inner,
+        #[serde(default)]
+        letter: Option<char>,
+    }
+
+    fn default_limit() -> u32 {
+        10
+    }
+
+    #[test]
+    fn finds_the_fields_of_nested_structs() {
+        let fields: Vec<(String, &str, &str, bool)> = fields::<Outer>()
+            .into_iter()
+            .map(|field| (field.path, field.owner, field.kind, field.required))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("limit".into(), "Outer", "integer", false),
+                ("inner".into(), "Outer", "table", true),
+                ("inner.name".into(), "Inner", "string", true),
+                ("inner.sizes".into(), "Inner", "list", false),
+                ("letter".into(), "Outer", "character", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_defaults_making_up_the_fields_required() {
+        let outer = defaults::<Outer>().unwrap();
+        assert_eq!(outer.limit, 10);
+        assert_eq!(outer.inner.name, "");
+        assert!(outer.inner.sizes.is_empty());
+        assert_eq!(outer.letter, None);
+    }
+}
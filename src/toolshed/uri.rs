@@ -0,0 +1,49 @@
+//! `uri.rs`
+//!
+//! Conversion between paths and the `file://` URIs language servers name
+//! documents with.
+
+use std::path;
+
+/// The bytes left as they are in a URI path; every other byte is written
+/// as `%` and its hex value.
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte)
+}
+
+/// Returns the URI of `file`, which has to be absolute.
+pub fn file_uri(file: &path::Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in file.to_string_lossy().bytes() {
+        if is_unreserved(byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Returns the path a `file://` URI names, or `None` for other URIs.
+pub fn uri_path(uri: &str) -> Option<path::PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut at = 0;
+    while at < encoded.len() {
+        let decoded = (encoded[at] == b'%')
+            .then(|| encoded.get(at + 1..at + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+                at += 3;
+            }
+            None => {
+                bytes.push(encoded[at]);
+                at += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned().into())
+}